@@ -0,0 +1,235 @@
+//! Shared-secret authentication for the plugin HTTP API.
+//!
+//! The primary instance holds a bearer token that the Studio plugin and any
+//! proxy instances must send as `Authorization: Bearer <token>`. The token is
+//! either given with `--token` or generated once and kept in
+//! `~/.studiolink/token`, so restarts reuse it and proxy instances started
+//! later can read it from there.
+//!
+//! Rotating replaces the token but keeps the previous one valid for a short
+//! grace period. Requests that still use the previous token get the new one
+//! back in the `X-StudioLink-Token` response header, so a connected plugin
+//! picks up the rotation without anyone pasting the token again. A plugin on
+//! the WebSocket is sent the new token as a frame instead, and its socket is
+//! closed once the token it holds stops being accepted.
+
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Response header carrying the current token to a caller that authenticated
+/// with the previous one.
+pub const ROTATED_TOKEN_HEADER: &str = "x-studiolink-token";
+
+/// How long the previous token keeps working after a rotation by default.
+pub const DEFAULT_ROTATION_GRACE: Duration = Duration::from_secs(60);
+
+/// Longest grace period a rotation may ask for.
+pub const MAX_ROTATION_GRACE: Duration = Duration::from_secs(3600);
+
+/// Shortest token accepted from `--token` or the token file.
+pub const MIN_TOKEN_LEN: usize = 16;
+
+/// Which token a request presented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenMatch {
+    Current,
+    /// The token from before the last rotation, still inside its grace period
+    Previous,
+}
+
+/// The tokens the HTTP API currently accepts.
+#[derive(Debug)]
+pub struct AuthTokens {
+    current: String,
+    previous: Option<(String, Instant)>,
+    /// Where rotations are written so proxy instances see them. None keeps
+    /// the token in memory only.
+    path: Option<PathBuf>,
+}
+
+/// Tokens shared between the listeners that check them, behind their own
+/// lock so a check never waits on AppState.
+pub type SharedTokens = std::sync::Arc<std::sync::RwLock<AuthTokens>>;
+
+impl AuthTokens {
+    pub fn new(token: String, path: Option<PathBuf>) -> Self {
+        Self {
+            current: token,
+            previous: None,
+            path,
+        }
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Check a presented token in constant time against the current token
+    /// and, until its grace period ends, the previous one.
+    pub fn check(&self, presented: &str) -> Option<TokenMatch> {
+        if constant_time_eq(presented.as_bytes(), self.current.as_bytes()) {
+            return Some(TokenMatch::Current);
+        }
+        match &self.previous {
+            Some((previous, expires))
+                if Instant::now() < *expires
+                    && constant_time_eq(presented.as_bytes(), previous.as_bytes()) =>
+            {
+                Some(TokenMatch::Previous)
+            }
+            _ => None,
+        }
+    }
+
+    /// Replace the token with a fresh one, keeping the old one valid for
+    /// `grace` (zero revokes it immediately). The new token is written to the
+    /// token file; a failed write is logged, since the rotation still applies.
+    pub fn rotate(&mut self, grace: Duration) -> &str {
+        let old = std::mem::replace(&mut self.current, generate_token());
+        self.previous = (!grace.is_zero()).then(|| (old, Instant::now() + grace));
+        if let Some(path) = &self.path {
+            if let Err(e) = write_token_file(path, &self.current) {
+                tracing::warn!("Failed to save rotated token to {}: {}", path.display(), e);
+            }
+        }
+        &self.current
+    }
+}
+
+/// A new random token: 32 bytes from the system RNG, base64url without padding.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Default token file: ~/.studiolink/token
+pub fn default_token_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".studiolink").join("token"))
+}
+
+/// Read a token file, ignoring surrounding whitespace. None if the file is
+/// missing, unreadable or holds something too short to be a token.
+pub fn read_token_file(path: &Path) -> Option<String> {
+    let token = std::fs::read_to_string(path).ok()?.trim().to_string();
+    (token.len() >= MIN_TOKEN_LEN).then_some(token)
+}
+
+/// Write the token file, readable by the current user only on Unix. A new
+/// file is created 0600 and an existing one is tightened before the token is
+/// written, so the secret is never readable by other users.
+pub fn write_token_file(path: &Path, token: &str) -> io::Result<()> {
+    use std::io::Write;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(format!("{}\n", token).as_bytes())
+}
+
+/// The token from an `Authorization: Bearer <token>` header value.
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|t| !t.is_empty())
+}
+
+/// Validate a token given on the command line.
+pub fn parse_token(value: &str) -> std::result::Result<String, String> {
+    let token = value.trim();
+    if token.len() < MIN_TOKEN_LEN {
+        return Err(format!(
+            "token must be at least {} characters",
+            MIN_TOKEN_LEN
+        ));
+    }
+    if token.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("token must not contain whitespace".into());
+    }
+    Ok(token.to_string())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_keeps_previous_token_for_grace_period() {
+        let mut tokens = AuthTokens::new("a".repeat(32), None);
+        let old = tokens.current().to_string();
+        let new = tokens.rotate(Duration::from_secs(60)).to_string();
+
+        assert_ne!(old, new);
+        assert_eq!(tokens.check(&new), Some(TokenMatch::Current));
+        assert_eq!(tokens.check(&old), Some(TokenMatch::Previous));
+        assert_eq!(tokens.check("b".repeat(32).as_str()), None);
+
+        tokens.rotate(Duration::ZERO);
+        assert_eq!(tokens.check(&new), None);
+        assert_eq!(tokens.check(&old), None);
+    }
+
+    #[test]
+    fn bearer_header_parsing() {
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("bearer  abc "), Some("abc"));
+        assert_eq!(bearer_token("Basic abc"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert!(parse_token("short").is_err());
+        assert!(parse_token("has a space in the middle").is_err());
+        assert_eq!(generate_token().len(), 43);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn token_file_is_private_even_when_it_existed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("studiolink-auth-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("token");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "old contents that are longer than the token\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let token = generate_token();
+        write_token_file(&path, &token).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(read_token_file(&path).as_deref(), Some(token.as_str()));
+
+        std::fs::remove_file(&path).unwrap();
+        write_token_file(&path, &token).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
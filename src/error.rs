@@ -6,11 +6,19 @@ pub enum StudioLinkError {
     /// Plugin is not connected or not responding
     PluginNotConnected,
     /// Request timed out waiting for plugin response
-    RequestTimeout(String),
+    RequestTimeout {
+        tool: String,
+        /// How long the call actually waited before giving up
+        waited_secs: f64,
+        /// The timeout that was in effect for this call
+        timeout_secs: u64,
+    },
     /// Plugin returned an error
     PluginError(String),
     /// Invalid tool arguments
     InvalidArguments(String),
+    /// A mutating tool was called while the server runs with --read-only
+    ReadOnly { tool: String },
     /// HTTP server error
     ServerError(String),
     /// MCP protocol error
@@ -25,9 +33,22 @@ impl fmt::Display for StudioLinkError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::PluginNotConnected => write!(f, "Studio plugin is not connected"),
-            Self::RequestTimeout(id) => write!(f, "Request {} timed out", id),
+            Self::RequestTimeout {
+                tool,
+                waited_secs,
+                timeout_secs,
+            } => write!(
+                f,
+                "Request {} timed out after {:.1}s (timeout {}s)",
+                tool, waited_secs, timeout_secs
+            ),
             Self::PluginError(msg) => write!(f, "Plugin error: {}", msg),
             Self::InvalidArguments(msg) => write!(f, "Invalid arguments: {}", msg),
+            Self::ReadOnly { tool } => write!(
+                f,
+                "{} is disabled: StudioLink is running with --read-only, which refuses tools that modify the place, DataStores or play state",
+                tool
+            ),
             Self::ServerError(msg) => write!(f, "Server error: {}", msg),
             Self::McpError(msg) => write!(f, "MCP error: {}", msg),
             Self::SerializationError(msg) => write!(f, "Serialization error: {}", msg),
@@ -38,6 +59,31 @@ impl fmt::Display for StudioLinkError {
 
 impl std::error::Error for StudioLinkError {}
 
+impl StudioLinkError {
+    /// Structured details an agent can act on without parsing the message —
+    /// e.g. whether a timeout was a fast failure or a genuinely long wait.
+    pub fn metadata(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::RequestTimeout {
+                tool,
+                waited_secs,
+                timeout_secs,
+            } => Some(serde_json::json!({
+                "kind": "timeout",
+                "tool": tool,
+                "waited_secs": (waited_secs * 10.0).round() / 10.0,
+                "timeout_secs": timeout_secs,
+            })),
+            Self::ReadOnly { tool } => Some(serde_json::json!({
+                "kind": "read_only",
+                "tool": tool,
+                "refused": true,
+            })),
+            _ => None,
+        }
+    }
+}
+
 impl From<std::io::Error> for StudioLinkError {
     fn from(e: std::io::Error) -> Self {
         Self::IoError(e)
@@ -51,3 +97,23 @@ impl From<serde_json::Error> for StudioLinkError {
 }
 
 pub type Result<T> = std::result::Result<T, StudioLinkError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_metadata_carries_wait_and_tool() {
+        let err = StudioLinkError::RequestTimeout {
+            tool: "run_code".into(),
+            waited_secs: 30.04,
+            timeout_secs: 30,
+        };
+        let meta = err.metadata().unwrap();
+        assert_eq!(meta["tool"], "run_code");
+        assert_eq!(meta["waited_secs"], 30.0);
+        assert_eq!(meta["timeout_secs"], 30);
+        assert!(err.to_string().contains("after 30.0s"));
+        assert!(StudioLinkError::PluginNotConnected.metadata().is_none());
+    }
+}
@@ -1,13 +1,36 @@
+mod auth;
 mod error;
 mod mcp;
+mod mcp_http;
+mod opencloud;
 mod server;
+mod snapshots;
 mod state;
 mod tools;
 
 use clap::Parser;
 use rmcp::ServiceExt;
+use std::sync::Arc;
 use tracing_subscriber::EnvFilter;
 
+/// Where the datastore_* tools read and write.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DataStoreBackend {
+    /// Through the Studio plugin's DataStoreService (needs a session)
+    Plugin,
+    /// Through the Open Cloud REST API with the key in STUDIOLINK_OPENCLOUD_KEY
+    Opencloud,
+}
+
+/// How MCP clients reach this server.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Transport {
+    /// MCP over stdin/stdout of the process the client spawned
+    Stdio,
+    /// MCP over HTTP at --mcp-bind (POST /mcp, responses streamed as SSE)
+    StreamableHttp,
+}
+
 /// StudioLink — Advanced Roblox Studio MCP Server
 /// 49 tools for professional game development with AI assistance
 #[derive(Parser, Debug)]
@@ -20,6 +43,63 @@ struct Args {
     /// Enable verbose logging
     #[arg(short, long)]
     verbose: bool,
+
+    /// Restrict which modules run_code may `require`: comma-separated
+    /// instance paths (e.g. "ReplicatedStorage.Shared,ServerStorage.Utils")
+    /// or asset IDs. A path also allows everything beneath it.
+    #[arg(long, value_delimiter = ',')]
+    require_allowlist: Option<Vec<String>>,
+
+    /// Reject new requests for a session once this many are queued and not
+    /// yet picked up by its plugin
+    #[arg(long, default_value_t = state::DEFAULT_MAX_QUEUE_DEPTH)]
+    max_queue_depth: usize,
+
+    /// Retry read tools after throttling errors: comma-separated
+    /// tool=attempts (e.g. "get_file_tree=3,datastore_get=1"; 1 disables).
+    /// DataStore reads default to 3 attempts.
+    #[arg(long, value_delimiter = ',', value_parser = tools::parse_retry_override)]
+    retry: Vec<(String, u32)>,
+
+    /// MCP transport: stdio for a client that spawns this process, or
+    /// streamable-http so clients can connect over HTTP
+    #[arg(long, value_enum, default_value_t = Transport::Stdio)]
+    transport: Transport,
+
+    /// Address the streamable-http transport listens on
+    #[arg(long, default_value = mcp_http::DEFAULT_MCP_BIND)]
+    mcp_bind: String,
+
+    /// Bearer token the plugin must send to the HTTP API. Defaults to the
+    /// one in ~/.studiolink/token, generated on first start.
+    #[arg(long, value_parser = auth::parse_token, conflicts_with = "no_auth")]
+    token: Option<String>,
+
+    /// Leave the plugin HTTP API open to any local process (no token)
+    #[arg(long)]
+    no_auth: bool,
+
+    /// Refuse every tool that modifies the place, DataStores or play state,
+    /// including run_code, so agents can only look
+    #[arg(long)]
+    read_only: bool,
+
+    /// Register only these tools: comma-separated names, or prefixes ending
+    /// in `*` (e.g. "get_*,datastore_get,datastore_scan")
+    #[arg(long, value_delimiter = ',')]
+    tools_allow: Option<Vec<String>>,
+
+    /// Don't register these tools (same syntax as --tools-allow; wins over it)
+    #[arg(long, value_delimiter = ',')]
+    tools_deny: Vec<String>,
+
+    /// Backend for the datastore_* tools
+    #[arg(long, value_enum, default_value_t = DataStoreBackend::Plugin)]
+    datastore_backend: DataStoreBackend,
+
+    /// Universe (experience) id for --datastore-backend opencloud
+    #[arg(long)]
+    opencloud_universe_id: Option<u64>,
 }
 
 #[tokio::main]
@@ -46,25 +126,103 @@ async fn main() -> color_eyre::Result<()> {
     );
     tracing::info!("49 tools for professional game development");
 
+    let tool_filter = tools::ToolFilter {
+        allow: args.tools_allow.clone(),
+        deny: args.tools_deny.clone(),
+    };
+    let known_tools = mcp::StudioLinkMcp::all_tool_names();
+    let known: Vec<&str> = known_tools.iter().map(String::as_str).collect();
+    let unmatched = tool_filter.unmatched(&known);
+    if !unmatched.is_empty() {
+        return Err(color_eyre::eyre::eyre!(
+            "--tools-allow/--tools-deny name no known tool: {}",
+            unmatched.join(", ")
+        ));
+    }
+
     // Create shared state
     let (state, notify_rx) = state::AppState::new();
+    if let Some(allowlist) = args.require_allowlist {
+        tracing::info!("require allowlist: {:?}", allowlist);
+        state.lock().await.require_allowlist = Some(allowlist);
+    }
+    state.lock().await.max_queue_depth = args.max_queue_depth.max(1);
+    if args.read_only {
+        tracing::info!("Read-only mode: mutating tools are refused");
+        state.lock().await.read_only = true;
+    }
+    state.lock().await.tool_filter = tool_filter.clone();
+    if args.datastore_backend == DataStoreBackend::Opencloud {
+        let universe_id = args.opencloud_universe_id.ok_or_else(|| {
+            color_eyre::eyre::eyre!("--datastore-backend opencloud needs --opencloud-universe-id")
+        })?;
+        let api_key = opencloud::api_key_from_env().ok_or_else(|| {
+            color_eyre::eyre::eyre!(
+                "--datastore-backend opencloud needs an API key in {}",
+                opencloud::API_KEY_ENV
+            )
+        })?;
+        tracing::info!(
+            "DataStore tools use Open Cloud for universe {}",
+            universe_id
+        );
+        state.lock().await.open_cloud = Some(std::sync::Arc::new(
+            opencloud::OpenCloudDataStores::new(opencloud::DEFAULT_BASE_URL, universe_id, api_key),
+        ));
+    }
+    for (tool, attempts) in args.retry {
+        let mut s = state.lock().await;
+        let policy = s.retry_policies.entry(tool).or_insert(tools::RetryPolicy {
+            max_attempts: 1,
+            base_delay: std::time::Duration::from_secs(1),
+        });
+        policy.max_attempts = attempts;
+    }
 
     // Try to start HTTP server — if port is taken, switch to proxy mode
     let port = args.port;
     let proxy_url = format!("http://127.0.0.1:{}", port);
 
+    // Fired when the MCP loop ends so the HTTP server stops with it
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let mut http_task = None;
+
     // Check if port is available by trying to bind
     match tokio::net::TcpListener::bind(format!("127.0.0.1:{}", port)).await {
         Ok(listener) => {
             // Port available — we are the primary instance
             tracing::info!("Primary mode: starting HTTP server on port {}", port);
+            {
+                let mut s = state.lock().await;
+                s.http_base_url = listener
+                    .local_addr()
+                    .ok()
+                    .map(|addr| format!("http://{}", addr));
+                if let Some(path) = state::AppState::default_display_names_path() {
+                    s.load_display_names(path);
+                }
+                s.snapshot_store =
+                    snapshots::SnapshotStore::default_root().map(snapshots::SnapshotStore::new);
+                if args.no_auth {
+                    tracing::warn!(
+                        "--no-auth: the plugin HTTP API accepts requests without a token"
+                    );
+                } else {
+                    s.auth = Some(Arc::new(std::sync::RwLock::new(load_auth_tokens(
+                        args.token.clone(),
+                    ))));
+                }
+            }
             let http_state = state.clone();
-            tokio::spawn(async move {
-                let router = server::create_router(http_state, notify_rx);
-                if let Err(e) = axum::serve(listener, router).await {
+            http_task = Some(tokio::spawn(async move {
+                let router = server::create_router(http_state, notify_rx).await;
+                let shutdown = async {
+                    let _ = shutdown_rx.await;
+                };
+                if let Err(e) = server::serve(listener, router, shutdown).await {
                     tracing::error!("HTTP server error: {}", e);
                 }
-            });
+            }));
         }
         Err(_) => {
             // Port taken — verify it's actually a StudioLink instance before entering proxy mode
@@ -89,24 +247,109 @@ async fn main() -> color_eyre::Result<()> {
             let mut s = state.lock().await;
             s.proxy_mode = true;
             s.proxy_url = proxy_url;
-            s.proxy_client = Some(client);
+            s.proxy_token = args.token.clone().or_else(|| {
+                auth::default_token_path().and_then(|path| auth::read_token_file(&path))
+            });
             drop(s);
         }
     }
 
-    // Start MCP server on stdio
-    tracing::info!("Starting MCP server on stdio...");
-    let mcp_handler = mcp::StudioLinkMcp::new(state);
+    // The MCP endpoint takes the plugin API token: the primary's own tokens
+    // (so rotations apply), or in proxy mode the one used for the primary
+    let mcp_auth = if args.no_auth {
+        None
+    } else {
+        let s = state.lock().await;
+        Some(s.auth.clone().unwrap_or_else(|| {
+            let tokens = match s.proxy_token.clone() {
+                Some(token) => auth::AuthTokens::new(token, None),
+                None => load_auth_tokens(args.token.clone()),
+            };
+            Arc::new(std::sync::RwLock::new(tokens))
+        }))
+    };
+    let mcp_handler = mcp::StudioLinkMcp::new(state).filtered(&tool_filter);
+    if mcp_handler.tool_count() < known_tools.len() {
+        tracing::info!(
+            "Tool filter: {} of {} tools registered",
+            mcp_handler.tool_count(),
+            known_tools.len()
+        );
+    }
+
+    match args.transport {
+        Transport::Stdio => {
+            // Start MCP server on stdio
+            tracing::info!("Starting MCP server on stdio...");
 
-    // Run MCP server via stdio transport — this is the main loop
-    let transport = rmcp::transport::stdio();
-    let mcp_server = mcp_handler.serve(transport).await?;
+            // Run MCP server via stdio transport — this is the main loop
+            let transport = rmcp::transport::stdio();
+            let mcp_server = mcp_handler.serve(transport).await?;
 
-    // Wait for MCP server to finish (HTTP server runs independently in background)
-    match mcp_server.waiting().await {
-        Ok(_) => tracing::info!("MCP server stopped gracefully"),
-        Err(e) => tracing::error!("MCP server error: {}", e),
+            // Wait for MCP server to finish, then take the HTTP server down with it
+            match mcp_server.waiting().await {
+                Ok(_) => tracing::info!("MCP server stopped gracefully"),
+                Err(e) => tracing::error!("MCP server error: {}", e),
+            }
+        }
+        Transport::StreamableHttp => {
+            let listener = tokio::net::TcpListener::bind(&args.mcp_bind).await?;
+            let addr = listener.local_addr()?;
+            if !addr.ip().is_loopback() {
+                tracing::warn!(
+                    "MCP endpoint is reachable from other machines at {}; anyone who can connect can run code in Studio",
+                    addr
+                );
+            }
+            tracing::info!(
+                "Serving MCP over streamable HTTP at http://{}{} (Ctrl+C to stop)",
+                addr,
+                mcp_http::MCP_PATH
+            );
+            let ctrl_c = async {
+                let _ = tokio::signal::ctrl_c().await;
+            };
+            if mcp_auth.is_none() {
+                tracing::warn!("--no-auth: the MCP endpoint accepts requests without a token");
+            }
+            if let Err(e) = mcp_http::serve(mcp_handler, listener, mcp_auth, ctrl_c).await {
+                tracing::error!("MCP HTTP server error: {}", e);
+            }
+            tracing::info!("MCP server stopped");
+        }
+    }
+    let _ = shutdown_tx.send(());
+    if let Some(task) = http_task {
+        let _ = task.await;
+        tracing::info!("HTTP server stopped");
     }
 
     Ok(())
 }
+
+/// Tokens for the plugin HTTP API: `--token` if given, else the saved token
+/// file, else a freshly generated token. Whatever is used is written back to
+/// the file so proxy instances and the next start find it.
+fn load_auth_tokens(explicit: Option<String>) -> auth::AuthTokens {
+    let path = auth::default_token_path();
+    let saved = path.as_deref().and_then(auth::read_token_file);
+    let token = explicit
+        .or_else(|| saved.clone())
+        .unwrap_or_else(auth::generate_token);
+    match &path {
+        Some(path) if saved.as_deref() != Some(token.as_str()) => {
+            match auth::write_token_file(path, &token) {
+                Ok(()) => tracing::info!(
+                    "Plugin API token saved to {}; paste it into the plugin's Set Token dialog",
+                    path.display()
+                ),
+                Err(e) => tracing::warn!("Failed to save token to {}: {}", path.display(), e),
+            }
+        }
+        Some(path) => tracing::info!("Plugin API requires the token in {}", path.display()),
+        None => tracing::warn!(
+            "No home directory to save the plugin API token in; proxy instances need --token"
+        ),
+    }
+    auth::AuthTokens::new(token, path)
+}
@@ -1,13 +1,14 @@
 use rmcp::handler::server::tool::ToolRouter;
 use rmcp::handler::server::wrapper::Parameters;
 use rmcp::model::*;
-use rmcp::{tool, tool_handler, tool_router, ServerHandler};
+use rmcp::{tool, tool_router, ServerHandler};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use crate::error::StudioLinkError;
 use crate::state::AppState;
 use crate::tools;
 
@@ -21,17 +22,61 @@ use crate::tools;
 pub struct RunCodeParams {
     /// Luau code to execute in Roblox Studio
     pub command: String,
-    /// (v0.6 EXPERIMENTAL) Optional session_id to route this single call to a
-    /// specific Studio session, overriding active_session. Pass-through is
-    /// observable at GET http://127.0.0.1:34872/debug/routing — used to verify
-    /// whether the MCP client is shipping the field.
+    /// Optional, per call: run this one call on a specific Studio session
+    /// (from list_sessions) without changing active_session. The session must
+    /// exist and be heartbeating. Routing is visible at
+    /// GET http://127.0.0.1:34872/debug/routing.
+    #[serde(alias = "sessionId")]
     pub session_id: Option<String>,
+    /// Stop capturing print/warn output after this many bytes and return what was captured with truncated=true (default: 1048576, max: 16777216)
+    #[serde(rename = "maxOutputBytes")]
+    pub max_output_bytes: Option<u64>,
+    /// Also abort the script once maxOutputBytes is exceeded (default: false — the script keeps running, output is dropped)
+    #[serde(rename = "stopOnOverflow")]
+    pub stop_on_overflow: Option<bool>,
+    /// Run one at a time with other run_code calls on the same session (default: true). Pass false for read-only code to skip the queue; a call that waited reports it with a [LOCK] line / lockWaitedMs
+    pub exclusive: Option<bool>,
+    /// Statically check the code first (compile errors, deprecated APIs, undefined globals) and return the findings without running it (default: false)
+    pub preflight: Option<bool>,
+    /// With preflight: run the code after all, but only if the check found no errors or warnings
+    #[serde(rename = "abortOnWarning")]
+    pub abort_on_warning: Option<bool>,
+    /// Return {output, stats} with the run's wall-clock time and instances added/removed while it ran (default: false; instance counts are best-effort)
+    #[serde(rename = "captureBudget")]
+    pub capture_budget: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct InsertModelParams {
-    /// Search query for the Roblox Creator Store
-    pub query: String,
+    /// Search query for the Roblox Creator Store; the top match is inserted
+    pub query: Option<String>,
+    /// Creator Store asset ID to insert instead of searching
+    #[serde(rename = "assetId")]
+    pub asset_id: Option<u64>,
+    /// Dot-separated path of the instance to insert into (default: Workspace)
+    pub parent: Option<String>,
+    /// Check the model's scripts for require-by-ID backdoors and getfenv/setfenv before inserting; a model with findings is discarded and the findings returned (default: false)
+    pub scan: Option<bool>,
+    /// Return the top matches (name, creator, asset ID, thumbnail) without inserting anything
+    pub preview: Option<bool>,
+    /// Number of matches to return in preview mode (1-10, default 5)
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct InsertAssetParams {
+    /// Creator Store asset ID to insert (e.g. from an insert_model preview)
+    #[serde(rename = "assetId")]
+    pub asset_id: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GetConsoleOutputParams {
+    /// Collapse identical consecutive lines into one line with an "(xN)" count (default: false)
+    #[serde(rename = "collapseRepeats")]
+    pub collapse_repeats: Option<bool>,
+    /// Return {entries: [{seq, time, severity, message}], count, firstSeq, lastSeq} instead of a text blob (default: false). time is Unix ms; seq keeps increasing across reads
+    pub structured: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -40,6 +85,15 @@ pub struct StartStopPlayParams {
     pub mode: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct WaitForStudioModeParams {
+    /// Mode to wait for: 'start_play', 'stop', or 'run_server'
+    pub mode: String,
+    /// Give up after this many seconds (default 30, max 120)
+    #[serde(rename = "timeoutSecs")]
+    pub timeout_secs: Option<f64>,
+}
+
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct RunScriptInPlayModeParams {
     /// Luau code to run in play mode
@@ -48,16 +102,46 @@ pub struct RunScriptInPlayModeParams {
     pub mode: String,
     /// Timeout in seconds (default: 100)
     pub timeout: Option<u64>,
+    /// Sample frame time, heartbeat step time and memory during the run (default: false)
+    #[serde(rename = "captureStats")]
+    pub capture_stats: Option<bool>,
+    /// Seed for math.random and unseeded Random.new() so the run can be reproduced (within ±2^53). A seed is generated when omitted; either way the result reports it
+    #[serde(rename = "randomSeed")]
+    pub random_seed: Option<i64>,
 }
 
 // --- DataStore ---
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct DataStoreListParams {
+    /// Fetch from Roblox even if a cached list is available (default: false)
+    pub refresh: Option<bool>,
+}
+
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct DataStoreGetParams {
     /// Name of the DataStore
     pub store_name: String,
     /// Key to read
     pub key: String,
+    /// If the stored value is a JSONEncode'd string, return the decoded object/array instead (default: false)
+    #[serde(rename = "decodeJson")]
+    pub decode_json: Option<bool>,
+    /// Read from the OrderedDataStore of this name (values are integers)
+    pub ordered: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct DataStoreGetManyParams {
+    /// Name of the DataStore
+    pub store_name: String,
+    /// Keys to read (1-50, no duplicates)
+    pub keys: Vec<String>,
+    /// If a stored value is a JSONEncode'd string, return the decoded object/array instead (default: false)
+    #[serde(rename = "decodeJson")]
+    pub decode_json: Option<bool>,
+    /// Read from the OrderedDataStore of this name (values are integers)
+    pub ordered: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -66,8 +150,10 @@ pub struct DataStoreSetParams {
     pub store_name: String,
     /// Key to write
     pub key: String,
-    /// Value to set (any JSON value)
+    /// Value to set (any JSON value; must be an integer with ordered=true)
     pub value: Value,
+    /// Write to the OrderedDataStore of this name, e.g. a leaderboard (integer values only)
+    pub ordered: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -86,6 +172,16 @@ pub struct DataStoreScanParams {
     pub page_size: Option<u32>,
     /// Maximum number of pages to scan (default: 1)
     pub max_pages: Option<u32>,
+    /// Scan an OrderedDataStore with GetSortedAsync, returning {key, value} entries sorted by value (page_size max 100)
+    pub ordered: Option<bool>,
+    /// Ordered scans only: sort ascending instead of descending (default: false)
+    pub ascending: Option<bool>,
+    /// Ordered scans only: smallest value to include
+    #[serde(rename = "minValue")]
+    pub min_value: Option<i64>,
+    /// Ordered scans only: largest value to include
+    #[serde(rename = "maxValue")]
+    pub max_value: Option<i64>,
 }
 
 // --- Profiler ---
@@ -96,6 +192,26 @@ pub struct ProfileStartParams {
     pub frequency: Option<u32>,
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ProfileSessionParams {
+    /// Optional Luau setup code run in the play server before profiling starts (e.g. spawn NPCs)
+    pub code: Option<String>,
+    /// Mode: 'start_play' (default) or 'run_server'
+    pub mode: Option<String>,
+    /// Seconds to profile for (1-300)
+    #[serde(rename = "durationSecs")]
+    pub duration_secs: u64,
+    /// Sampling frequency in Hz (default: 1000)
+    pub frequency: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ProfileMemoryStartParams {
+    /// Milliseconds between memory samples (default: 100, min: 10)
+    #[serde(rename = "intervalMs")]
+    pub interval_ms: Option<u32>,
+}
+
 // --- Diffing ---
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -110,6 +226,104 @@ pub struct SnapshotCompareParams {
     pub snapshot_a: String,
     /// ID or name of the second snapshot
     pub snapshot_b: String,
+    /// Also return a unified diff of each changed script's source (stored snapshots only; large or rewritten scripts are summarized)
+    #[serde(rename = "scriptDiffs")]
+    pub script_diffs: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SnapshotExportParams {
+    /// Name of a snapshot saved in the snapshot store
+    pub id: String,
+    /// File to write the exported snapshot to; with format "rojo", a new or empty directory for the project
+    pub path: String,
+    /// "file" (default): one portable snapshot file for snapshot_import. "rojo": a Rojo project (default.project.json, .lua scripts, .model.json and meta.json files)
+    pub format: Option<String>,
+    /// Replace an existing file at path (default false: an existing file is refused). Rojo exports always need a new or empty directory
+    pub overwrite: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SnapshotImportParams {
+    /// Snapshot file written by snapshot_export
+    pub path: String,
+    /// Store it under this name instead of the exported one
+    pub name: Option<String>,
+    /// Import even if the snapshot's place_id differs from the active session's
+    pub force: Option<bool>,
+}
+
+// --- Filesystem Sync ---
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SyncExportParams {
+    /// Directory to write into: absent, empty, or an earlier sync_export
+    pub dir: String,
+    /// Only export scripts under this dotted path (e.g. "ServerScriptService"). Default: the whole place.
+    pub root: Option<String>,
+    /// File extension: "luau" (default) or "lua"
+    pub extension: Option<String>,
+    /// Overwrite files edited since the last export. Default: false.
+    pub force: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SyncStatusParams {
+    /// Directory written by sync_export
+    pub dir: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ScriptCommitParams {
+    /// History repository. Default: ~/.studiolink/history/place-<placeId>
+    pub repo: Option<String>,
+    /// Commit message, e.g. what the session changed. Default: place name and script count.
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ScriptHistoryParams {
+    /// History repository. Default: ~/.studiolink/history/place-<placeId>
+    pub repo: Option<String>,
+    /// Dotted script path (e.g. ServerScriptService.Main), or a file path in the repository for
+    /// scripts deleted since. Default: every commit.
+    pub path: Option<String>,
+    /// Commits to return, newest first (1-200). Default: 20.
+    pub limit: Option<usize>,
+    /// Include each commit's diff of the script (needs path). Default: false.
+    pub diff: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SourcemapGenerateParams {
+    /// sync_export directory; its files fill in each script's filePaths
+    pub dir: Option<String>,
+    /// Keep instances with no script below them. Default: false (like `rojo sourcemap`).
+    #[serde(rename = "includeNonScripts")]
+    pub include_non_scripts: Option<bool>,
+    /// Write the sourcemap here (e.g. sourcemap.json) and return counts instead of the sourcemap
+    #[serde(rename = "outputPath")]
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SyncWatchStartParams {
+    /// Directory written by sync_export
+    pub dir: String,
+    /// Push even if the export came from a different place than the active session's. Default: false.
+    pub force: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SyncWatchPollParams {
+    /// Return only events after this cursor (from the previous poll or from sync_watch_start)
+    pub cursor: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SyncWatchStopParams {
+    /// Directory to stop watching. Default: every watched directory.
+    pub dir: Option<String>,
 }
 
 // --- Testing ---
@@ -118,12 +332,29 @@ pub struct SnapshotCompareParams {
 pub struct TestRunParams {
     /// Optional path to run tests for a specific module
     pub path: Option<String>,
+    /// Collect line coverage for the place's ModuleScripts (default false; slows the run)
+    pub coverage: Option<bool>,
+    /// With coverage, also write an lcov tracefile to this path
+    #[serde(rename = "outputPath")]
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct TestCreateParams {
     /// Path to the script or ModuleScript to generate tests for
     pub target_path: String,
+    /// Parent path to create the test ModuleScript in (named "<Target>.spec"); omit to only return the template
+    #[serde(rename = "createIn")]
+    pub create_in: Option<String>,
+}
+
+// --- Security ---
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SecurityScanParams {
+    /// Project-specific rules: Luau function expressions called with each script/remote target (max 20, 4 KB each)
+    #[serde(rename = "customRules")]
+    pub custom_rules: Option<Vec<String>>,
 }
 
 // --- Linter ---
@@ -134,6 +365,21 @@ pub struct LintScriptsParams {
     pub path: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct LintWatchStartParams {
+    /// Optional path to limit which scripts are watched
+    pub path: Option<String>,
+    /// Quiet period after an edit before the script is re-linted (default 750)
+    #[serde(rename = "debounceMs")]
+    pub debounce_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct LintWatchPollParams {
+    /// Cursor from the previous poll; omit or 0 for all results so far
+    pub cursor: Option<u64>,
+}
+
 // --- Animation ---
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -156,6 +402,17 @@ pub struct DocsGenerateParams {
 pub struct WorkspaceAnalyzeParams {
     /// Optional path to limit analysis scope (e.g. "ServerScriptService")
     pub path: Option<String>,
+    /// Return only a compact, versioned set of numeric metrics (script counts, avg function length, issue/security counts, circular deps, ...) for storing and diffing over time
+    pub fingerprint: Option<bool>,
+}
+
+// --- Network ---
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NetworkMonitorStopParams {
+    /// Also break RemoteEvent traffic down by the player that fired it (default: false)
+    #[serde(rename = "byPlayer")]
+    pub by_player: Option<bool>,
 }
 
 // --- Instance Management ---
@@ -166,12 +423,72 @@ pub struct GetFileTreeParams {
     pub path: Option<String>,
     /// Maximum depth to traverse (default: 10)
     pub depth: Option<u32>,
+    /// Add each node's CollectionService tags (default: false)
+    #[serde(rename = "includeTags")]
+    pub include_tags: Option<bool>,
+    /// Add each node's attribute names, without values (default: false)
+    #[serde(rename = "includeAttributes")]
+    pub include_attributes: Option<bool>,
+    /// Child order: "default" (Studio's order), "name" or "class". Use "name" for reproducible trees to diff
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GetFileTreeStreamParams {
+    /// Optional root path (e.g. "Workspace.Map"). If empty, streams all services.
+    pub path: Option<String>,
+    /// Maximum depth to traverse (default: 1000)
+    pub depth: Option<u32>,
+    /// File to write the nodes to as NDJSON (one {id, parentId, name, className, depth} per line). If omitted, only a summary is returned.
+    #[serde(rename = "outputPath")]
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct GetInstancePropertiesParams {
     /// Dot-separated path to the instance (e.g. "Workspace.Part")
     pub path: String,
+    /// Return the whole subtree serialized as base64 rbxm instead of a property summary
+    pub serialize: Option<bool>,
+    /// With serialize, write the .rbxm file here instead of returning base64
+    #[serde(rename = "outputPath")]
+    pub output_path: Option<String>,
+    /// Round numeric values in the result to this many decimal places (display only)
+    pub round: Option<u8>,
+    /// Return each class property as {value, isDefault}, flagging whether it still matches the class default (default: false)
+    #[serde(rename = "markDefaults")]
+    pub mark_defaults: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GetDebugIdParams {
+    /// Dot-separated path to the instance
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ResolveDebugIdParams {
+    /// DebugId returned by get_debug_id (e.g. "0_1234")
+    #[serde(rename = "debugId")]
+    pub debug_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ImportInstanceParams {
+    /// Path on disk to an .rbxm or .rbxmx file (e.g. one written by get_instance_properties with serialize)
+    pub path: String,
+    /// Dot-separated path of the instance to insert under (e.g. "Workspace")
+    #[serde(rename = "parentPath")]
+    pub parent_path: String,
+    /// Also return each inserted root's DebugId, a handle that survives renames and reparenting (default: false)
+    #[serde(rename = "includeDebugId")]
+    pub include_debug_id: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GetSelectionParams {
+    /// Also summarize the selection as a unit: classes present, combined bounding box, and properties shared by every selected instance
+    pub summary: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -182,9 +499,16 @@ pub struct SetPropertyParams {
     pub property: String,
     /// Value to set
     pub value: Value,
-    /// Optional value type hint: "string", "number", "boolean", "Vector3", "Color3", "UDim2", "BrickColor", "Enum"
+    /// Optional value type hint: "string", "number", "boolean", "Vector2", "Vector3", "Color3", "UDim2", "Rect", "BrickColor", "Enum", "PhysicalProperties".
+    /// Vector2 takes [x, y]; Rect takes {"min": [x, y], "max": [x, y]} or [minX, minY, maxX, maxY].
+    /// Color3 takes "#RRGGBB", [r, g, b] or {r, g, b}, with components all 0-1 or all 0-255.
+    /// PhysicalProperties takes {"density", "friction", "elasticity", "frictionWeight"?, "elasticityWeight"?}
+    /// (density 0.0001-100, friction 0-2, elasticity 0-1, weights 0-100); null turns custom physics off.
     #[serde(rename = "valueType")]
     pub value_type: Option<String>,
+    /// "set" (default) replaces the value; "add" and "multiply" apply value to the current one
+    /// (numbers, Vector2, Vector3 only), e.g. add [0, 5, 0] to Position or 0.1 to Transparency
+    pub operation: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -193,11 +517,16 @@ pub struct MassSetPropertyParams {
     pub paths: Vec<String>,
     /// Property name to set
     pub property: String,
-    /// Value to set
-    pub value: Value,
-    /// Optional value type hint
+    /// Value to set on every instance (give this or valueTemplate)
+    pub value: Option<Value>,
+    /// Per-instance value with {index} (1-based position in paths), {i} (0-based) and {name} (current Name) substituted, e.g. "Part_{index}" (give this or value)
+    #[serde(rename = "valueTemplate")]
+    pub value_template: Option<String>,
+    /// Optional value type hint (same set as set_property, including Vector2, Rect and Color3)
     #[serde(rename = "valueType")]
     pub value_type: Option<String>,
+    /// Report "N of M applied" progress while running and yield between batches so Studio stays responsive (default: false). Frames are returned in the result's `progress` array.
+    pub progress: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -208,14 +537,31 @@ pub struct CreateInstanceParams {
     /// Dot-separated path to the parent instance (default: Workspace)
     #[serde(rename = "parentPath")]
     pub parent_path: Option<String>,
-    /// Optional properties to set on the new instance
+    /// Optional properties to set on the new instance. Color / *Color3 values may be "#RRGGBB", [r, g, b] or {r, g, b}, 0-1 or 0-255
     pub properties: Option<Value>,
+    /// Check every property exists and accepts its value before creating anything (default true)
+    pub validate: Option<bool>,
+    /// Also return the new instance's DebugId, a handle that survives renames and reparenting (default: false)
+    #[serde(rename = "includeDebugId")]
+    pub include_debug_id: Option<bool>,
+    /// Appearance preset to apply (e.g. "glass", "neon", "metal"); see list_appearance_presets
+    pub preset: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct SetAppearancePresetParams {
+    /// Dot-separated path to the part to restyle
+    pub path: String,
+    /// Preset name from list_appearance_presets (e.g. "glass", "neon", "metal")
+    pub preset: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct DeleteInstanceParams {
     /// Dot-separated path to the instance to delete
     pub path: String,
+    /// Delete even if the plugin flags the instance as a singleton that is normally kept (e.g. the current camera). Services, the DataModel and Terrain are never deletable
+    pub force: Option<bool>,
 }
 
 // --- Script Tools ---
@@ -224,6 +570,14 @@ pub struct DeleteInstanceParams {
 pub struct GetScriptSourceParams {
     /// Dot-separated path to the script (e.g. "ServerScriptService.MyScript")
     pub path: String,
+    /// First line to return (1-based). Use with endLine to read large scripts in parts
+    #[serde(rename = "startLine")]
+    pub start_line: Option<u32>,
+    /// Last line to return (inclusive)
+    #[serde(rename = "endLine")]
+    pub end_line: Option<u32>,
+    /// "plain" (default), "ansi" for terminal colours or "html" for syntax-highlighted markup; line numbers are kept
+    pub render: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -232,6 +586,17 @@ pub struct SetScriptSourceParams {
     pub path: String,
     /// New source code for the script
     pub source: String,
+    /// Re-indent the source to StyLua layout before storing it (default: false).
+    /// The write is rejected if the source has unbalanced blocks or strings.
+    pub format: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct DiffScriptSourceParams {
+    /// Dot-separated path to the script
+    pub path: String,
+    /// Candidate source to compare against the script's current source
+    pub source: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -250,6 +615,43 @@ pub struct SearchObjectsParams {
     /// Search mode: "name", "class", or "both" (default: "name")
     #[serde(rename = "searchBy")]
     pub search_by: Option<String>,
+    /// "place" (default) or "selection" to search only inside the instances selected in Studio
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct FindByPropertyParams {
+    /// Property name to compare (e.g. "Transparency", "Anchored", "Material")
+    pub property: String,
+    /// Value to compare against: a number, string or boolean
+    pub value: serde_json::Value,
+    /// "eq" (default), "ne", "gt", "lt", "gte" or "lte"; ordering operators need a numeric value
+    pub operator: Option<String>,
+    /// Allowed difference for eq/ne on numeric values (default: exact)
+    pub tolerance: Option<f64>,
+    /// Only consider instances that IsA this class
+    #[serde(rename = "className")]
+    pub class_name: Option<String>,
+    /// "place" (default) or "selection" to search only inside the instances selected in Studio
+    pub scope: Option<String>,
+}
+
+// --- Tool History ---
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ExportHistoryParams {
+    /// File to write the history to
+    pub path: String,
+    /// "json" (default): array of {tool, args}. "curl": shell script of curl calls against the HTTP API
+    pub format: Option<String>,
+}
+
+// --- Undo Grouping ---
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct BeginMacroParams {
+    /// Name shown for the grouped entry in Studio's undo history
+    pub name: String,
 }
 
 // --- Session ---
@@ -260,6 +662,86 @@ pub struct SwitchSessionParams {
     pub session_id: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GetActiveSessionParams {
+    /// Also resolve the place icon asset ID, creator and universe ID via the plugin (default: false — costs an extra round-trip and a MarketplaceService lookup)
+    pub enrich: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct PollChangesParams {
+    /// Cursor returned by the previous poll_changes call (default: 0, everything still in the log)
+    pub cursor: Option<u64>,
+    /// Session to read changes for (default: the bound or active session)
+    #[serde(alias = "sessionId")]
+    pub session_id: Option<String>,
+    /// Wait up to this many milliseconds for a change if there is none yet (default: 0, max: 30000)
+    #[serde(rename = "waitMs")]
+    pub wait_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GetResultPageParams {
+    /// Continuation token from a paginated result
+    pub token: String,
+    /// Zero-based page number (page 0 came back with the original call)
+    pub page: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct RenameSessionParams {
+    /// Session ID to rename
+    pub session_id: String,
+    /// Display name to show in listings (e.g. "Combat Test"), max 64 characters
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct CreateSessionGroupParams {
+    /// Group name (e.g. "my-experience"). Re-using a name replaces the group
+    pub name: String,
+    /// Session IDs to include; all must be currently connected
+    #[serde(rename = "sessionIds")]
+    pub session_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct BroadcastToGroupParams {
+    /// Group name from create_session_group
+    pub group: String,
+    /// Plugin tool to run on every session (e.g. "run_code", "get_studio_mode")
+    pub tool: String,
+    /// Arguments passed to the tool as-is (e.g. {"command": "print(game.PlaceId)"} for run_code)
+    pub args: Option<Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct BroadcastToolParams {
+    /// Plugin tool to run on every session (e.g. "lint_scripts", "get_studio_mode")
+    pub tool: String,
+    /// Arguments passed to the tool as-is on every session
+    pub args: Option<Value>,
+    /// Only these sessions instead of every connected one; all must be connected
+    #[serde(rename = "sessionIds")]
+    pub session_ids: Option<Vec<String>>,
+    /// Per-session timeout in seconds (default 30, max 120)
+    #[serde(rename = "timeoutSecs")]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct CancelRequestParams {
+    /// Request to cancel, from pending_requests in server_diagnostics or a
+    /// previous cancel_request listing
+    #[serde(rename = "requestId")]
+    pub request_id: Option<String>,
+    /// Cancel every pending call of this tool instead (e.g. "workspace_analyze")
+    pub tool: Option<String>,
+    /// Only consider requests sent to this session
+    pub session_id: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct SetMySessionParams {
     /// Session ID to bind to this MCP instance. Pass null to clear and fall back to active_session.
@@ -278,6 +760,46 @@ pub struct PlaceVersionHistoryParams {
 pub struct PublishPlaceParams {
     /// Version type: "Saved" (default) or "Published".
     pub version_type: Option<String>,
+    /// Local .rbxl or .rbxlx to upload through Open Cloud instead of opening
+    /// Studio's publish dialog.
+    #[serde(rename = "filePath")]
+    pub file_path: Option<String>,
+    /// Universe to publish to. Default: the active session's game.
+    #[serde(rename = "universeId")]
+    pub universe_id: Option<u64>,
+    /// Place to publish to. Default: the active session's place.
+    #[serde(rename = "placeId")]
+    pub place_id: Option<u64>,
+    /// Notes for this version, kept in ~/.studiolink/publish_log.jsonl with the version number.
+    #[serde(rename = "versionNotes")]
+    pub version_notes: Option<String>,
+    /// Validate the file, IDs and API key without uploading. Default: false.
+    #[serde(rename = "dryRun")]
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct AssetUploadParams {
+    /// Local file to upload: image (.png, .jpg, .bmp, .tga), audio (.mp3, .ogg, .wav, .flac),
+    /// mesh or model (.fbx, .obj, .gltf, .glb, .rbxm, .rbxmx) or video (.mp4, .mov).
+    #[serde(rename = "filePath")]
+    pub file_path: String,
+    /// Decal, Audio, Model or Video. Default: inferred from the extension.
+    #[serde(rename = "assetType")]
+    pub asset_type: Option<String>,
+    /// Asset name on Roblox. Default: the file name without its extension.
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    /// User that will own the asset. Give this or groupId.
+    #[serde(rename = "userId")]
+    pub user_id: Option<u64>,
+    /// Group that will own the asset. Give this or userId.
+    #[serde(rename = "groupId")]
+    pub group_id: Option<u64>,
+    /// How long to wait for processing and moderation (max 600). Default: 120.
+    #[serde(rename = "waitSecs")]
+    pub wait_secs: Option<u64>,
 }
 
 // --- Multi-Client Testing ---
@@ -428,6 +950,9 @@ pub struct ErrorHistoryParams {
     pub pattern: Option<String>,
     /// Max entries to return (newest first). Default: 100.
     pub limit: Option<u32>,
+    /// Add a structured `error` ({message, frames: [{script, line, function}]}) to each error entry. Default: false.
+    #[serde(rename = "parseStacks")]
+    pub parse_stacks: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema)]
@@ -464,14 +989,67 @@ pub struct MicroprofilerCaptureParams {
 #[derive(Clone)]
 pub struct StudioLinkMcp {
     pub state: Arc<Mutex<AppState>>,
-    #[allow(dead_code)]
     tool_router: ToolRouter<Self>,
+    subscriptions: Arc<std::sync::Mutex<tools::resources::Subscriptions>>,
+    connection: crate::state::SharedConnection,
 }
 
 impl StudioLinkMcp {
     pub fn new(state: Arc<Mutex<AppState>>) -> Self {
         let tool_router = Self::tool_router();
-        Self { state, tool_router }
+        Self {
+            state,
+            tool_router,
+            subscriptions: Default::default(),
+            connection: Default::default(),
+        }
+    }
+
+    /// A copy for another MCP connection: same state and tools, but its own
+    /// resource subscriptions, bound session, open macro and switch history.
+    pub fn for_connection(&self) -> Self {
+        Self {
+            subscriptions: Default::default(),
+            connection: Default::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Names of every tool this server implements, before filtering.
+    pub fn all_tool_names() -> Vec<String> {
+        Self::tool_router()
+            .list_all()
+            .into_iter()
+            .map(|tool| tool.name.into_owned())
+            .collect()
+    }
+
+    /// Unregister the tools `filter` doesn't permit; clients neither see
+    /// nor can call them.
+    pub fn filtered(mut self, filter: &tools::ToolFilter) -> Self {
+        for name in Self::all_tool_names() {
+            if !filter.permits(&name) {
+                self.tool_router.remove_route(&name);
+            }
+        }
+        self
+    }
+
+    /// How many tools are registered after filtering.
+    pub fn tool_count(&self) -> usize {
+        self.tool_router.list_all().len()
+    }
+
+    /// A resource may not be read through a tool that --tools-allow/--tools-deny
+    /// removed from this server.
+    fn check_filtered(&self, tool: &str) -> Result<(), StudioLinkError> {
+        if !self.tool_router.has_route(tool) && Self::all_tool_names().iter().any(|t| t == tool) {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "{} is not available on this server (--tools-allow/--tools-deny)",
+                tool
+            )));
+        }
+        Ok(())
     }
 }
 
@@ -480,9 +1058,119 @@ fn ok_text(result: serde_json::Value) -> String {
     result.to_string()
 }
 
-/// Helper: format tool result as error text
-fn err_text(e: impl std::fmt::Display) -> String {
-    format!("Error: {}", e)
+/// The envelope for one page of a paginated result. Concatenating `data`
+/// from pages 0..pages gives back the original result text.
+fn page_envelope(token: &str, page: usize, pages: usize, data: String) -> String {
+    let continuation = (page + 1 < pages).then_some(token);
+    serde_json::json!({
+        "paginated": true,
+        "page": page,
+        "pages": pages,
+        "continuation": continuation,
+        "data": data,
+    })
+    .to_string()
+}
+
+/// A progress sink whose frames are sent to `peer` as notifications/progress
+/// for `token`. The forwarding task ends once every sender is dropped, i.e.
+/// when the tool call and its plugin requests are done. Frames that don't
+/// advance are skipped, since MCP progress must keep increasing even when
+/// one call makes several plugin requests.
+fn progress_forwarder(
+    peer: rmcp::Peer<rmcp::RoleServer>,
+    token: rmcp::model::ProgressToken,
+) -> crate::state::ProgressSink {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut last = f64::NEG_INFINITY;
+        while let Some(param) = rx.recv().await {
+            let param: rmcp::model::ProgressNotificationParam = param;
+            if param.progress <= last {
+                continue;
+            }
+            last = param.progress;
+            if let Err(e) = peer.notify_progress(param).await {
+                tracing::debug!("Dropping progress notification: {}", e);
+                break;
+            }
+        }
+    });
+    crate::state::ProgressSink { token, tx }
+}
+
+/// Watch the change log for as long as `subscriptions` exists, sending
+/// resources/updated for each subscribed resource a change touches and
+/// resources/list_changed when instances come or go. Starts from the latest
+/// change, so nothing from before the subscription is reported.
+fn resource_watcher(
+    peer: rmcp::Peer<rmcp::RoleServer>,
+    state: Arc<Mutex<AppState>>,
+    subscriptions: std::sync::Weak<std::sync::Mutex<tools::resources::Subscriptions>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut cursor = None;
+        while !peer.is_transport_closed() {
+            let wait_ms = cursor.map(|_| tools::session::MAX_CHANGE_WAIT_MS);
+            let polled = match tools::session::poll_changes(&state, None, cursor, wait_ms).await {
+                Ok(polled) => polled,
+                Err(e) => {
+                    // Usually no session yet; look again shortly
+                    tracing::debug!("Resource watcher: {}", e);
+                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            let first = cursor.is_none();
+            cursor = polled["cursor"].as_u64().or(cursor);
+            if first {
+                continue;
+            }
+            let changes: Vec<crate::state::ChangeEntry> =
+                serde_json::from_value(polled["changes"].clone()).unwrap_or_default();
+            let missed = polled["missed"] == true;
+            if changes.is_empty() && !missed {
+                continue;
+            }
+            let Some(subscriptions) = subscriptions.upgrade() else {
+                break;
+            };
+            let updated = subscriptions
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .updated(&changes, missed);
+            drop(subscriptions);
+            for uri in updated {
+                let param = ResourceUpdatedNotificationParam { uri };
+                if let Err(e) = peer.notify_resource_updated(param).await {
+                    tracing::debug!("Dropping resource notification: {}", e);
+                }
+            }
+            if missed || changes.iter().any(tools::resources::changes_list) {
+                let _ = peer.notify_resource_list_changed().await;
+            }
+        }
+    })
+}
+
+/// An MCP error for a failed resource request.
+fn resource_error(e: StudioLinkError) -> rmcp::ErrorData {
+    match e {
+        StudioLinkError::InvalidArguments(msg) => rmcp::ErrorData::invalid_params(msg, None),
+        StudioLinkError::PluginError(msg) if msg.contains("not found") => {
+            rmcp::ErrorData::resource_not_found(msg, None)
+        }
+        e => rmcp::ErrorData::internal_error(e.to_string(), None),
+    }
+}
+
+/// Helper: format tool result as error text. Errors with structured details
+/// (timeouts) append them as a JSON line after the message.
+fn err_text(e: StudioLinkError) -> String {
+    match e.metadata() {
+        Some(meta) => format!("Error: {}\n{}", e, meta),
+        None => format!("Error: {}", e),
+    }
 }
 
 #[tool_router]
@@ -492,29 +1180,73 @@ impl StudioLinkMcp {
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "Execute Luau code in Roblox Studio and return the printed output. Can be used to both make changes and retrieve information."
+        description = "Execute Luau code in Roblox Studio and return the printed output. Can be used to both make changes and retrieve information. Output is capped at maxOutputBytes (default 1 MiB); past the cap the result is {output, truncated: true, capturedBytes, droppedBytes, stopped}. The cap is separate from the 30s request timeout — set stopOnOverflow=true to abort runaway print loops instead of waiting them out. preflight=true only analyzes the code (compile errors, deprecated APIs, undefined globals) and returns {executed: false, preflight: {passed, errors, warnings, issues}}; add abortOnWarning=true to run it when the check passes. captureBudget=true returns {output, stats: {wallMs, instancesAdded, instancesRemoved}} to gauge what a snippet cost; the instance counts are best-effort (DataModel-wide, other scripts included, property writes not counted)."
     )]
     async fn run_code(&self, params: Parameters<RunCodeParams>) -> String {
         let p = params.0;
-        match tools::core::run_code(&self.state, p.session_id.as_deref(), &p.command).await {
+        let preflight = match tools::core::Preflight::from_flags(p.preflight, p.abort_on_warning) {
+            Ok(preflight) => preflight,
+            Err(e) => return err_text(e),
+        };
+        match tools::core::run_code(
+            &self.state,
+            p.session_id.as_deref(),
+            &p.command,
+            tools::core::OutputCap {
+                max_bytes: p.max_output_bytes,
+                stop_on_overflow: p.stop_on_overflow,
+            },
+            p.exclusive,
+            preflight,
+            p.capture_budget,
+        )
+        .await
+        {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
     }
 
     #[tool(
-        description = "Search and insert a model from the Roblox Creator Store into the workspace."
+        description = "Search and insert a model from the Roblox Creator Store, or insert one by assetId. Inserts into Workspace unless parent names another instance. With preview=true nothing is inserted: returns the top matches (name, creator, assetId, thumbnail) so you can pick one. scan=true checks the model's scripts for require(<assetId>) backdoors and getfenv/setfenv before inserting: a clean model is inserted with the scan attached, a model with findings is discarded and the findings (script, line, rule, snippet) returned."
     )]
     async fn insert_model(&self, params: Parameters<InsertModelParams>) -> String {
-        match tools::core::insert_model(&self.state, &params.0.query).await {
+        let p = params.0;
+        let options = tools::core::InsertModelOptions {
+            query: p.query,
+            asset_id: p.asset_id,
+            parent: p.parent,
+            scan: p.scan.unwrap_or(false),
+            preview: p.preview.unwrap_or(false),
+            limit: p.limit,
+        };
+        match tools::core::insert_model(&self.state, options).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
     }
 
-    #[tool(description = "Get the console output from Roblox Studio.")]
-    async fn get_console_output(&self) -> String {
-        match tools::core::get_console_output(&self.state).await {
+    #[tool(
+        description = "Insert a specific Creator Store asset into the workspace by asset ID, e.g. one chosen from an insert_model preview."
+    )]
+    async fn insert_asset(&self, params: Parameters<InsertAssetParams>) -> String {
+        match tools::core::insert_asset(&self.state, params.0.asset_id).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Get the console output from Roblox Studio. Set collapseRepeats=true to fold identical consecutive lines (e.g. an error spammed every frame) into one line with an (xN) count. structured=true returns typed entries {seq, time, severity, message} (time in Unix ms, seq monotonic across reads) for correlating logs with tool calls; collapsed runs get a repeats count."
+    )]
+    async fn get_console_output(&self, params: Parameters<GetConsoleOutputParams>) -> String {
+        match tools::core::get_console_output(
+            &self.state,
+            params.0.collapse_repeats,
+            params.0.structured,
+        )
+        .await
+        {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -531,7 +1263,7 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Run a Luau script in play mode with automatic stop after completion or timeout. Returns structured output with logs, errors, and duration."
+        description = "Run a Luau script in play mode with automatic stop after completion or timeout. Returns structured output with logs, errors, and duration. captureStats=true adds `stats`: frame time avg/p95/max, heartbeat step time and memory start/end/peak, for a quick before/after performance check (confidence \"low\" on short runs). Runs are seeded for reproducibility: pass randomSeed to replay a run; the result's randomSeed {value, generated, applied} reports the seed used."
     )]
     async fn run_script_in_play_mode(
         &self,
@@ -542,6 +1274,8 @@ impl StudioLinkMcp {
             &params.0.code,
             &params.0.mode,
             params.0.timeout,
+            params.0.capture_stats,
+            params.0.random_seed,
         )
         .await
         {
@@ -560,24 +1294,44 @@ impl StudioLinkMcp {
         }
     }
 
+    #[tool(
+        description = "Wait until Studio reports the given mode ('start_play', 'run_server' or 'stop'), e.g. right after start_stop_play, instead of sleeping and guessing. Polls get_studio_mode every 250ms, riding out polls that fail during the transition. Returns waitedMs once the mode is reached; errors after timeoutSecs (default 30, max 120) with the last mode seen."
+    )]
+    async fn wait_for_studio_mode(&self, params: Parameters<WaitForStudioModeParams>) -> String {
+        let p = params.0;
+        match tools::core::wait_for_studio_mode(&self.state, &p.mode, p.timeout_secs).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
     // ═══════════════════════════════════════════
     // FAZ 2: DATASTORE & PROFILING
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "List all DataStore names in the current experience. Requires 'Allow Studio Access to API Services' enabled in game settings."
+        description = "List all DataStore names in the current experience. Requires 'Allow Studio Access to API Services' enabled in game settings. The list is cached per session for 5 minutes (cleared on session switch and after datastore_set/datastore_delete) to save DataStore request budget; `cached` and `cacheAgeSecs` show staleness, refresh=true re-fetches."
     )]
-    async fn datastore_list(&self) -> String {
-        match tools::datastore::datastore_list(&self.state).await {
+    async fn datastore_list(&self, params: Parameters<DataStoreListParams>) -> String {
+        match tools::datastore::datastore_list(&self.state, params.0.refresh).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
     }
 
-    #[tool(description = "Read a specific key's value from a DataStore.")]
+    #[tool(
+        description = "Read a specific key's value from a DataStore. Set decodeJson=true to decode values stored as HttpService:JSONEncode strings; if decoding fails the raw string is returned with a decodeNote. Set ordered=true to read an OrderedDataStore (e.g. a leaderboard)."
+    )]
     async fn datastore_get(&self, params: Parameters<DataStoreGetParams>) -> String {
-        match tools::datastore::datastore_get(&self.state, &params.0.store_name, &params.0.key)
-            .await
+        let p = params.0;
+        match tools::datastore::datastore_get(
+            &self.state,
+            &p.store_name,
+            &p.key,
+            p.decode_json,
+            p.ordered,
+        )
+        .await
         {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
@@ -585,7 +1339,26 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Write a value to a DataStore key. WARNING: This modifies live production data."
+        description = "Read up to 50 keys from one DataStore in a single request. Returns results mapping each key to {value} or {error}, so one bad key doesn't fail the batch. Each key still costs one GetAsync from the server's per-minute request budget; the plugin waits for budget rather than throttling, so large batches can take a while and keys left over when it stops waiting come back as errors. decodeJson and ordered work as in datastore_get."
+    )]
+    async fn datastore_get_many(&self, params: Parameters<DataStoreGetManyParams>) -> String {
+        let p = params.0;
+        match tools::datastore::datastore_get_many(
+            &self.state,
+            &p.store_name,
+            &p.keys,
+            p.decode_json,
+            p.ordered,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Write a value to a DataStore key. Set ordered=true to write to an OrderedDataStore (e.g. a leaderboard); its values must be integers and anything else is rejected before sending. WARNING: This modifies live production data."
     )]
     async fn datastore_set(&self, params: Parameters<DataStoreSetParams>) -> String {
         match tools::datastore::datastore_set(
@@ -593,6 +1366,7 @@ impl StudioLinkMcp {
             &params.0.store_name,
             &params.0.key,
             params.0.value,
+            params.0.ordered,
         )
         .await
         {
@@ -613,13 +1387,21 @@ impl StudioLinkMcp {
         }
     }
 
-    #[tool(description = "Scan and list all keys in a DataStore with pagination support.")]
+    #[tool(
+        description = "Scan and list all keys in a DataStore with pagination support. With ordered=true, scans the OrderedDataStore of that name (e.g. a leaderboard) via GetSortedAsync and returns {key, value} entries sorted by value; use ascending, minValue and maxValue to control order and range."
+    )]
     async fn datastore_scan(&self, params: Parameters<DataStoreScanParams>) -> String {
         match tools::datastore::datastore_scan(
             &self.state,
             &params.0.store_name,
             params.0.page_size,
             params.0.max_pages,
+            params.0.ordered,
+            tools::datastore::OrderedScan {
+                ascending: params.0.ascending,
+                min_value: params.0.min_value,
+                max_value: params.0.max_value,
+            },
         )
         .await
         {
@@ -647,7 +1429,7 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Analyze profiling data: slowest functions, CPU hotspots, and optimization suggestions."
+        description = "Analyze profiling data: slowest functions, CPU hotspots, and optimization suggestions. hotspots[] entries are {function, scriptPath, line, selfTimeMs, suggestion}; scriptPath/line are null for C functions or anonymous code."
     )]
     async fn profile_analyze(&self) -> String {
         match tools::profiler::profile_analyze(&self.state).await {
@@ -656,12 +1438,59 @@ impl StudioLinkMcp {
         }
     }
 
+    #[tool(
+        description = "One-call CPU profile of play mode: enters play mode (mode 'start_play' or 'run_server'), optionally runs setup code in the play server, profiles for durationSecs, stops the profiler, exits play mode and returns the profile_analyze result plus a `session` summary. Play mode is always stopped, even if a step fails; `stopError` is set if stopping itself failed."
+    )]
+    async fn profile_session(&self, params: Parameters<ProfileSessionParams>) -> String {
+        let p = params.0;
+        match tools::profiler::profile_session(
+            &self.state,
+            p.code.as_deref(),
+            p.mode.as_deref(),
+            p.duration_secs,
+            p.frequency,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Start sampling memory (Luau heap and per-category engine memory) to find allocation hotspots and GC stalls. Optional intervalMs between samples (default: 100)."
+    )]
+    async fn profile_memory_start(&self, params: Parameters<ProfileMemoryStartParams>) -> String {
+        match tools::profiler::profile_memory_start(&self.state, params.0.interval_ms).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(description = "Stop memory sampling and return heap totals for the run.")]
+    async fn profile_memory_stop(&self) -> String {
+        match tools::profiler::profile_memory_stop(&self.state).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Analyze the last memory profile: hotspots[] entries are {category, allocatedMb, netMb, peakMb, suggestion}, ranked by memory allocated during the run, plus gcPressure {level, allocatedKb, reclaimedKb, allocationRateKbPerSec, collections, collectionsPerMin} from the Luau heap. Allocation is attributed per memory category (Roblox exposes no per-function allocation data)."
+    )]
+    async fn profile_memory_analyze(&self) -> String {
+        match tools::profiler::profile_memory_analyze(&self.state).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
     // ═══════════════════════════════════════════
     // FAZ 3: DIFFING & TESTING
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "Take a snapshot of the current place state (all instances, properties, scripts). Optional name for the snapshot."
+        description = "Take a snapshot of the current place state (all instances, properties, scripts). Optional name for the snapshot. Snapshots are also stored on disk content-addressed, so unchanged instances are shared between snapshots; the result's storage field reports bytes written vs. saved."
     )]
     async fn snapshot_take(&self, params: Parameters<SnapshotTakeParams>) -> String {
         match tools::diffing::snapshot_take(&self.state, params.0.name.as_deref()).await {
@@ -671,13 +1500,14 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Compare two snapshots and list all differences (added/removed/changed instances and properties)."
+        description = "Compare two snapshots and list all differences (added/removed/changed instances and properties). Stored snapshots are compared by content hash and work across Studio restarts. scriptDiffs=true adds `scriptDiffs`: a unified diff per changed script (bounded in size; scripts that were essentially rewritten get a summary instead)."
     )]
     async fn snapshot_compare(&self, params: Parameters<SnapshotCompareParams>) -> String {
         match tools::diffing::snapshot_compare(
             &self.state,
             &params.0.snapshot_a,
             &params.0.snapshot_b,
+            params.0.script_diffs,
         )
         .await
         {
@@ -695,18 +1525,169 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Run TestEZ test suites. Optionally specify a path to run tests for a specific module."
+        description = "Export a stored snapshot to a single self-contained file (place id, scope, timestamp and all instance data) so it can be shared with another machine; an existing file at path is refused unless overwrite=true. format='rojo' instead writes a Rojo project directory (scripts as .server.lua/.client.lua/.lua, other instances as .model.json with the properties the snapshot recorded) to bring Studio state into filesystem-based source control."
+    )]
+    async fn snapshot_export(&self, params: Parameters<SnapshotExportParams>) -> String {
+        let p = params.0;
+        match tools::diffing::snapshot_export(
+            &self.state,
+            &p.id,
+            &p.path,
+            p.format.as_deref(),
+            p.overwrite,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Import a snapshot file written by snapshot_export into the snapshot store, so snapshot_compare can diff it against a live snapshot_take. Refused if the snapshot's place differs from the active session's unless force=true; never overwrites an existing snapshot (pass name to rename)."
+    )]
+    async fn snapshot_import(&self, params: Parameters<SnapshotImportParams>) -> String {
+        let p = params.0;
+        match tools::diffing::snapshot_import(&self.state, &p.path, p.name.as_deref(), p.force)
+            .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Write every script (or those under root) into a local directory mirroring the DataModel, e.g. ServerScriptService/Combat/Damage.server.luau, for editing in an external editor. studiolink-sync.json maps each file back to its instance path and records its source hash. Re-exporting into the same directory replaces the export and removes files for deleted scripts, but refuses if files were edited locally since (force=true overrides)."
+    )]
+    async fn sync_export(&self, params: Parameters<SyncExportParams>) -> String {
+        let p = params.0;
+        match tools::sync::sync_export(
+            &self.state,
+            &p.dir,
+            p.root.as_deref(),
+            p.extension.as_deref(),
+            p.force,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Compare a sync_export directory with its manifest: files edited or deleted locally since the export (with their instance paths), and .lua/.luau files the export didn't write. Reads only local files."
+    )]
+    async fn sync_status(&self, params: Parameters<SyncStatusParams>) -> String {
+        match tools::sync::sync_status(&params.0.dir) {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Commit the current source of every script into a local git repository (default ~/.studiolink/history/place-<placeId>), laid out like sync_export, for a durable audit trail of edits. Only commits when a script changed since the last commit; returns the commit hash and each changed file with its instance path. Needs git on PATH."
+    )]
+    async fn script_commit(&self, params: Parameters<ScriptCommitParams>) -> String {
+        let p = params.0;
+        match tools::git::script_commit(&self.state, p.repo.as_deref(), p.message.as_deref()).await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Read script history recorded by script_commit: the commits that changed one script (path), newest first, with unified diffs when diff=true; without path, every commit with the files it changed."
+    )]
+    async fn script_history(&self, params: Parameters<ScriptHistoryParams>) -> String {
+        let p = params.0;
+        match tools::git::script_history(
+            &self.state,
+            p.repo.as_deref(),
+            p.path.as_deref(),
+            p.limit,
+            p.diff,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Generate a Rojo-style sourcemap.json of the live place ({name, className, filePaths?, children?}, rooted at DataModel) so luau-lsp and other tools can resolve requires. Pass dir (a sync_export directory) to fill in filePaths, and outputPath to write the file instead of returning it. Also served at GET /sourcemap on the HTTP API."
+    )]
+    async fn sourcemap_generate(&self, params: Parameters<SourcemapGenerateParams>) -> String {
+        let p = params.0;
+        match tools::sourcemap::sourcemap_generate(
+            &self.state,
+            p.dir.as_deref(),
+            p.include_non_scripts,
+            p.output_path.as_deref(),
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Watch a sync_export directory and push .lua/.luau edits into Studio with set_script_source as they are saved, to the session active now. A file whose script also changed in Studio since the export is not pushed and is reported as a conflict. Read outcomes (pushed, conflict, untracked, error) with sync_watch_poll; stop with sync_watch_stop."
+    )]
+    async fn sync_watch_start(&self, params: Parameters<SyncWatchStartParams>) -> String {
+        let p = params.0;
+        match tools::sync::sync_watch_start(&self.state, &p.dir, p.force).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Events from running sync watches since cursor: each saved file's outcome (pushed, in_sync, conflict, untracked, error) with its instance path. Returns the cursor for the next poll and the directories being watched."
+    )]
+    async fn sync_watch_poll(&self, params: Parameters<SyncWatchPollParams>) -> String {
+        match tools::sync::sync_watch_poll(&self.state, params.0.cursor).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(description = "Stop a sync watch started with sync_watch_start, or all of them.")]
+    async fn sync_watch_stop(&self, params: Parameters<SyncWatchStopParams>) -> String {
+        match tools::sync::sync_watch_stop(&self.state, params.0.dir.as_deref()).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Run TestEZ test suites. Optionally specify a path to run tests for a specific module. Set coverage=true to get per-module line coverage ({module, coveredLines, totalLines, percent}) and outputPath to also write an lcov file. Coverage instruments every ModuleScript for the run, so it is noticeably slower; it is off by default."
     )]
     async fn test_run(&self, params: Parameters<TestRunParams>) -> String {
-        match tools::testing::test_run(&self.state, params.0.path.as_deref()).await {
+        let p = params.0;
+        match tools::testing::test_run(
+            &self.state,
+            p.path.as_deref(),
+            p.coverage,
+            p.output_path.as_deref(),
+        )
+        .await
+        {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
     }
 
-    #[tool(description = "Generate a TestEZ test template for a given script or ModuleScript.")]
+    #[tool(
+        description = "Generate a TestEZ test template for a given script or ModuleScript. With createIn (a parent path, e.g. the target's folder), also creates it as a '<Target>.spec' ModuleScript there as one undo step and returns its path in `created`."
+    )]
     async fn test_create(&self, params: Parameters<TestCreateParams>) -> String {
-        match tools::testing::test_create(&self.state, &params.0.target_path).await {
+        let p = params.0;
+        match tools::testing::test_create(&self.state, &p.target_path, p.create_in.as_deref()).await
+        {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -725,10 +1706,10 @@ impl StudioLinkMcp {
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "Scan the entire place for security vulnerabilities: unvalidated RemoteEvents, client trust issues, exposed data, missing rate limiting."
+        description = "Scan the entire place for security vulnerabilities: unvalidated RemoteEvents, client trust issues, exposed data, missing rate limiting, and RemoteEvent/RemoteFunction handlers whose call chain reaches DataStore SetAsync/UpdateAsync without validating client input (category \"Unvalidated Persistence\", with script, line and call chain). Includes byCategory counts. customRules adds project rules as Luau predicates, e.g. \"function(t) return t.kind == 'remote' and t.handlerSource:find('typeof') == nil end\"; t is {kind='script', path, className, source} or {kind='remote', path, className, name, handlerSource}. A truthy (or string) return is a \"Custom Rule\" finding tagged with ruleIndex; broken rules are listed in ruleErrors."
     )]
-    async fn security_scan(&self) -> String {
-        match tools::security::security_scan(&self.state).await {
+    async fn security_scan(&self, params: Parameters<SecurityScanParams>) -> String {
+        match tools::security::security_scan(&self.state, params.0.custom_rules).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -767,8 +1748,44 @@ impl StudioLinkMcp {
     #[tool(
         description = "Analyze scripts for code quality: deprecated APIs, anti-patterns, naming issues, unused variables, missing type annotations."
     )]
-    async fn lint_scripts(&self, params: Parameters<LintScriptsParams>) -> String {
-        match tools::linter::lint_scripts(&self.state, params.0.path.as_deref()).await {
+    async fn lint_scripts(&self, params: Parameters<LintScriptsParams>) -> String {
+        match tools::linter::lint_scripts(&self.state, params.0.path.as_deref()).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Start re-linting scripts as they are edited. Each script is linted once edits to it pause for debounceMs, so typing doesn't trigger a lint per keystroke. Poll results with lint_watch_poll."
+    )]
+    async fn lint_watch_start(&self, params: Parameters<LintWatchStartParams>) -> String {
+        match tools::linter::lint_watch_start(
+            &self.state,
+            params.0.path.as_deref(),
+            params.0.debounce_ms,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Get lint results for scripts re-linted since cursor (latest result per script). Pass the returned cursor on the next call to see only new changes."
+    )]
+    async fn lint_watch_poll(&self, params: Parameters<LintWatchPollParams>) -> String {
+        match tools::linter::lint_watch_poll(&self.state, params.0.cursor).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Stop the lint watch. Returns results changed since cursor that had not been polled yet."
+    )]
+    async fn lint_watch_stop(&self, params: Parameters<LintWatchPollParams>) -> String {
+        match tools::linter::lint_watch_stop(&self.state, params.0.cursor).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -817,10 +1834,10 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Stop network monitoring and return a detailed traffic report with per-Remote statistics and bandwidth estimates."
+        description = "Stop network monitoring and return a detailed traffic report with per-Remote statistics and bandwidth estimates. byPlayer=true adds byPlayer: RemoteEvent traffic per firing player, busiest first, with players over 10 calls/sec marked suspicious."
     )]
-    async fn network_monitor_stop(&self) -> String {
-        match tools::network::network_monitor_stop(&self.state).await {
+    async fn network_monitor_stop(&self, params: Parameters<NetworkMonitorStopParams>) -> String {
+        match tools::network::network_monitor_stop(&self.state, params.0.by_player).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -859,10 +1876,16 @@ impl StudioLinkMcp {
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "Comprehensive workspace analysis: coding style (naming, indent, strict mode, type annotations), architecture (framework, services, folder structure), script statistics, issues (deprecated APIs, security, memory leaks, optimization), dependencies (circular, dead modules), and detected patterns/libraries. Run this first on any new workspace."
+        description = "Comprehensive workspace analysis: coding style (naming, indent, strict mode, type annotations), architecture (framework, services, folder structure), script statistics, issues (deprecated APIs, security, memory leaks, optimization), dependencies (circular, dead modules), and detected patterns/libraries with framework-idiomatic recommendations (Knit, Roact/React, Fusion, Matter, Rodux, ProfileService; \"unknown/custom\" when none match). Run this first on any new workspace. fingerprint=true returns just a versioned numeric metrics object suitable for trend tracking in CI."
     )]
     async fn workspace_analyze(&self, params: Parameters<WorkspaceAnalyzeParams>) -> String {
-        match tools::workspace::workspace_analyze(&self.state, params.0.path.as_deref()).await {
+        match tools::workspace::workspace_analyze(
+            &self.state,
+            params.0.path.as_deref(),
+            params.0.fingerprint,
+        )
+        .await
+        {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -873,11 +1896,37 @@ impl StudioLinkMcp {
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "Get a hierarchical tree of all instances in the place. Optionally specify a path to focus on a subtree and depth to limit traversal."
+        description = "Get a hierarchical tree of all instances in the place. Optionally specify a path to focus on a subtree and depth to limit traversal. includeTags / includeAttributes add each node's CollectionService tags and attribute names (keys only) for inspecting tag-driven games; both are off by default to keep the tree lean. Children follow Studio's internal order, which can change between calls; sort='name' (recommended for diffing) or 'class' makes the output reproducible. Every node carries its debugId; an instance reached a second time appears as a ref node (ref: true) that is not expanded again."
     )]
     async fn get_file_tree(&self, params: Parameters<GetFileTreeParams>) -> String {
-        match tools::instance::get_file_tree(&self.state, params.0.path.as_deref(), params.0.depth)
-            .await
+        let p = params.0;
+        match tools::instance::get_file_tree(
+            &self.state,
+            p.path.as_deref(),
+            p.depth,
+            p.include_tags,
+            p.include_attributes,
+            p.sort.as_deref(),
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Stream a very large instance tree (hundreds of thousands of instances) without building it in memory. The plugin sends flat nodes in batches that are relayed as NDJSON: with outputPath they are written to that file, otherwise only a summary (node count, max depth, top classes) is returned. Not available in proxy mode."
+    )]
+    async fn get_file_tree_stream(&self, params: Parameters<GetFileTreeStreamParams>) -> String {
+        let p = params.0;
+        match tools::instance::get_file_tree_stream(
+            &self.state,
+            p.path.as_deref(),
+            p.depth,
+            p.output_path.as_deref(),
+        )
+        .await
         {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
@@ -885,28 +1934,40 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Get all properties of an instance at the given path, including class-specific properties (BasePart, GuiObject, Light, etc.), attributes, and tags."
+        description = "Get all properties of an instance at the given path, including class-specific properties (BasePart, GuiObject, Light, etc.), attributes, and tags. Set serialize=true to instead get the full subtree as base64 rbxm for an exact round-trip copy (10 MB cap; large subtrees get a warning), and outputPath to write it to a .rbxm file. round=N rounds numbers (CFrame, Vector3, etc.) to N decimal places for less noisy comparisons; display only, the place keeps full precision. markDefaults=true returns each class property as {value, isDefault} so you can tell what was customized from what is still the class default."
     )]
     async fn get_instance_properties(
         &self,
         params: Parameters<GetInstancePropertiesParams>,
     ) -> String {
-        match tools::instance::get_instance_properties(&self.state, &params.0.path).await {
+        let p = params.0;
+        match tools::instance::get_instance_properties(
+            &self.state,
+            &p.path,
+            p.serialize,
+            p.output_path.as_deref(),
+            p.round,
+            p.mark_defaults,
+        )
+        .await
+        {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
     }
 
     #[tool(
-        description = "Set a single property on an instance. Supports type hints for Vector3, Color3, UDim2, BrickColor, Enum values."
+        description = "Set a single property on an instance. Supports type hints for Vector2, Vector3, Color3, UDim2, Rect, BrickColor, Enum and PhysicalProperties values. Vector2 (e.g. AnchorPoint) takes [x, y]; Rect (e.g. SliceCenter) takes {min: [x, y], max: [x, y]} or [minX, minY, maxX, maxY]. Color3 takes \"#RRGGBB\", [r, g, b] or {r, g, b}; components above 1 mean the 0-255 scale, so [1, 1, 1] is white. For PhysicalProperties pass value {density, friction, elasticity, frictionWeight?, elasticityWeight?} on CustomPhysicalProperties (this enables custom physics); pass null to disable. operation=\"add\" or \"multiply\" changes a number/Vector2/Vector3 relative to its current value in one step (e.g. move up 5 studs: property Position, value [0, 5, 0], operation add) and returns oldValue/newValue."
     )]
     async fn set_property(&self, params: Parameters<SetPropertyParams>) -> String {
+        let p = params.0;
         match tools::instance::set_property(
             &self.state,
-            &params.0.path,
-            &params.0.property,
-            params.0.value,
-            params.0.value_type.as_deref(),
+            &p.path,
+            &p.property,
+            p.value,
+            p.value_type.as_deref(),
+            p.operation.as_deref(),
         )
         .await
         {
@@ -916,7 +1977,7 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Set the same property on multiple instances at once. Provide an array of paths."
+        description = "Set a property on multiple instances at once. Provide an array of paths and either a value shared by all, or a valueTemplate expanded per instance in path order ({index} 1-based, {i} 0-based, {name} current Name; e.g. \"Part_{index}\" for bulk renaming). Template runs return the computed `values`. For thousands of instances set progress=true: the plugin reports \"N of M applied\" periodically (returned as `progress` frames), yields between batches and gets the extended 120s timeout."
     )]
     async fn mass_set_property(&self, params: Parameters<MassSetPropertyParams>) -> String {
         match tools::instance::mass_set_property(
@@ -924,7 +1985,9 @@ impl StudioLinkMcp {
             params.0.paths,
             &params.0.property,
             params.0.value,
+            params.0.value_template.as_deref(),
             params.0.value_type.as_deref(),
+            params.0.progress,
         )
         .await
         {
@@ -934,14 +1997,18 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Create a new instance with the given class name under a parent path. Optionally set initial properties."
+        description = "Create a new instance with the given class name under a parent path. Optionally set initial properties. By default (validate=true) the instance is only created if every property exists on the class and accepts its value; otherwise nothing is created and the invalid properties are listed. validate=false applies what it can and skips the rest. includeDebugId=true also returns debugId for use with resolve_debug_id. preset applies a named appearance bundle (e.g. \"glass\", \"neon\", \"metal\"; see list_appearance_presets) after properties. If the parent is an unusual container for the class (e.g. a LocalScript under Workspace, a Frame outside a ScreenGui) the instance is still created and `warnings` suggests the conventional parent."
     )]
     async fn create_instance(&self, params: Parameters<CreateInstanceParams>) -> String {
+        let p = params.0;
         match tools::instance::create_instance(
             &self.state,
-            &params.0.class_name,
-            params.0.parent_path.as_deref(),
-            params.0.properties,
+            &p.class_name,
+            p.parent_path.as_deref(),
+            p.properties,
+            p.validate,
+            p.include_debug_id,
+            p.preset.as_deref(),
         )
         .await
         {
@@ -950,9 +2017,77 @@ impl StudioLinkMcp {
         }
     }
 
-    #[tool(description = "Delete an instance and all its descendants at the given path.")]
+    #[tool(
+        description = "Apply a named appearance preset (Material, Color, Transparency, Reflectance) to an existing part as one undo step. Presets: glass, neon, metal, gold, wood, plastic, concrete, ice, forcefield; see list_appearance_presets for exact values."
+    )]
+    async fn set_appearance_preset(&self, params: Parameters<SetAppearancePresetParams>) -> String {
+        let p = params.0;
+        match tools::appearance::set_appearance_preset(&self.state, &p.path, &p.preset).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "List the appearance presets usable with create_instance preset and set_appearance_preset, with the properties each one sets."
+    )]
+    async fn list_appearance_presets(&self) -> String {
+        ok_text(tools::appearance::list_appearance_presets())
+    }
+
+    #[tool(
+        description = "Delete an instance and all its descendants at the given path. Services, the DataModel and other non-deletable instances are refused with the reason; force=true overrides only soft refusals such as the current camera."
+    )]
     async fn delete_instance(&self, params: Parameters<DeleteInstanceParams>) -> String {
-        match tools::instance::delete_instance(&self.state, &params.0.path).await {
+        match tools::instance::delete_instance(&self.state, &params.0.path, params.0.force).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Get the Studio DebugId of the instance at a path. The DebugId stays the same when the instance is renamed or moved, so hold onto it instead of the path across edits and turn it back into a path with resolve_debug_id."
+    )]
+    async fn get_debug_id(&self, params: Parameters<GetDebugIdParams>) -> String {
+        match tools::instance::get_debug_id(&self.state, &params.0.path).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Find the current path of the instance with a given DebugId (from get_debug_id). Fails with a clear error if the instance has since been destroyed."
+    )]
+    async fn resolve_debug_id(&self, params: Parameters<ResolveDebugIdParams>) -> String {
+        match tools::instance::resolve_debug_id(&self.state, &params.0.debug_id).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Insert the contents of an .rbxm/.rbxmx model file under parentPath, as one undo step. Pair with get_instance_properties serialize=true to copy instances between places. Returns the inserted root paths; asset references (meshes, textures, sounds) that fail to load are listed in unresolvedAssets but do not stop the import. includeDebugId=true adds debugIds [{path, debugId}] for use with resolve_debug_id."
+    )]
+    async fn import_instance(&self, params: Parameters<ImportInstanceParams>) -> String {
+        let p = params.0;
+        match tools::instance::import_instance(
+            &self.state,
+            &p.path,
+            &p.parent_path,
+            p.include_debug_id,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Get the instances currently selected in Studio. With summary=true, also returns the classes present, the combined world bounding box (parts and models), commonProperties (identical on every selected instance) and differingProperties (present on all but with different values). For mixed-class selections only properties every instance has are reported."
+    )]
+    async fn get_selection(&self, params: Parameters<GetSelectionParams>) -> String {
+        match tools::instance::get_selection(&self.state, params.0.summary).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -963,21 +2098,40 @@ impl StudioLinkMcp {
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "Get the source code of a script with line numbers. Works with Script, LocalScript, and ModuleScript."
+        description = "Get the source code of a script with line numbers. Works with Script, LocalScript, and ModuleScript. Scripts over 512 KiB fail with 'too large: N bytes' — read them in parts with startLine/endLine. Non-UTF-8 content fails with 'non-text content'. render='ansi' or 'html' returns the source syntax-highlighted for showing directly to a developer (default 'plain'; line numbers kept in every mode)."
     )]
     async fn get_script_source(&self, params: Parameters<GetScriptSourceParams>) -> String {
-        match tools::scripts::get_script_source(&self.state, &params.0.path).await {
+        match tools::scripts::get_script_source(
+            &self.state,
+            &params.0.path,
+            params.0.start_line,
+            params.0.end_line,
+            params.0.render.as_deref(),
+        )
+        .await
+        {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
     }
 
     #[tool(
-        description = "Replace the entire source code of a script. Records a waypoint for undo support."
+        description = "Replace the entire source code of a script. Records a waypoint for undo support. With format=true the source is re-indented (StyLua layout) first and the formatted source is returned; a source that fails to format is not written."
     )]
     async fn set_script_source(&self, params: Parameters<SetScriptSourceParams>) -> String {
-        match tools::scripts::set_script_source(&self.state, &params.0.path, &params.0.source).await
-        {
+        let p = params.0;
+        match tools::scripts::set_script_source(&self.state, &p.path, &p.source, p.format).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Preview a source change: unified diff from the script's current source in Studio to the given candidate source, with added/removed line counts. Changes nothing; use before set_script_source or script_patch. Diffs over 1000 lines are cut off with truncated=true."
+    )]
+    async fn diff_script_source(&self, params: Parameters<DiffScriptSourceParams>) -> String {
+        let p = params.0;
+        match tools::scripts::diff_script_source(&self.state, &p.path, &p.source).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -996,13 +2150,35 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Search for instances by name or class across the entire place. Use searchBy: 'name', 'class', or 'both'."
+        description = "Search for instances by name or class across the entire place. Use searchBy: 'name', 'class', or 'both'. scope: 'selection' restricts the search to descendants of the instances currently selected in Studio (errors if nothing is selected)."
     )]
     async fn search_objects(&self, params: Parameters<SearchObjectsParams>) -> String {
         match tools::scripts::search_objects(
             &self.state,
             &params.0.query,
             params.0.search_by.as_deref(),
+            params.0.scope.as_deref(),
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Find instances whose property compares to a value, e.g. Transparency gt 0.5 or Anchored eq false. operator: eq (default), ne, gt, lt, gte, lte; gt/lt/gte/lte need a numeric value. tolerance makes numeric eq/ne approximate. Enums and other non-primitive properties match by string form (\"Enum.Material.Neon\"). Optional className (IsA filter) and scope ('place' or 'selection'). Matches include the actual property value; returns at most 100."
+    )]
+    async fn find_by_property(&self, params: Parameters<FindByPropertyParams>) -> String {
+        let p = params.0;
+        match tools::scripts::find_by_property(
+            &self.state,
+            &p.property,
+            p.value,
+            p.operator.as_deref(),
+            p.tolerance,
+            p.class_name.as_deref(),
+            p.scope.as_deref(),
         )
         .await
         {
@@ -1033,12 +2209,43 @@ impl StudioLinkMcp {
         }
     }
 
+    #[tool(
+        description = "Start grouping every following mutation (create_instance, set_property, set_script_source, delete_instance, ...) into a single named undo step, so the whole feature can be undone at once. Close it with end_macro. Only one macro can be open; nesting is rejected. An open macro is committed automatically if you switch to a different session."
+    )]
+    async fn begin_macro(&self, params: Parameters<BeginMacroParams>) -> String {
+        match tools::history::begin_macro(&self.state, &params.0.name).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Commit the macro opened by begin_macro as one undo entry in Studio's history."
+    )]
+    async fn end_macro(&self) -> String {
+        match tools::history::end_macro(&self.state).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Export the recent plugin calls made by this MCP instance (last 200) as a replayable artifact for bug repros. format=\"json\" writes an array of {tool, args}; format=\"curl\" writes a shell script of curl calls against POST /proxy/tool_call (set STUDIOLINK_URL to override the server address). Sensitive args (tokens, passwords, API keys, cookies) are redacted."
+    )]
+    async fn export_history(&self, params: Parameters<ExportHistoryParams>) -> String {
+        let p = params.0;
+        match tools::history::export_history(&self.state, &p.path, p.format.as_deref()).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
     // ═══════════════════════════════════════════
     // SESSION MANAGEMENT (Multi-Place Support)
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "List all connected Roblox Studio sessions. CALL THIS FIRST in every conversation that touches Studio. Each open Studio window is a separate session with its own session_id. If more than one session exists, pick the one this chat should drive and pass session_id on every subsequent tool call (run_code, character_*, ui_*, start_stop_play, etc.) — do not rely on active_session in multi-chat / multi-place setups."
+        description = "List all connected Roblox Studio sessions. CALL THIS FIRST in every conversation that touches Studio. Each open Studio window is a separate session with its own session_id. If more than one session exists, pick the one this chat should drive and pass session_id on every subsequent tool call (run_code, character_*, ui_*, start_stop_play, etc.) — do not rely on active_session in multi-chat / multi-place setups. Each session also carries an `endpoint` (HTTP base URL, poll URL, tool-call URL and the target_session body to route to it) for external automation; served_by says whether this server is the primary or a proxy."
     )]
     async fn list_sessions(&self) -> String {
         match tools::session::list_sessions(&self.state).await {
@@ -1058,10 +2265,90 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Get information about the currently active Studio session (PlaceId, name, connection status)."
+        description = "Switch back to the session that was active before the last switch_session. Repeated calls toggle between the two most recent windows; sessions that have since disconnected are skipped."
+    )]
+    async fn switch_back(&self) -> String {
+        match tools::session::switch_back(&self.state).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Give a session a display name (e.g. \"Combat Test\") shown as display_name in list_sessions and get_active_session. Separate from place_name. Remembered per Studio window, so it survives plugin reconnects."
+    )]
+    async fn rename_session(&self, params: Parameters<RenameSessionParams>) -> String {
+        let p = params.0;
+        match tools::session::rename_session(&self.state, &p.session_id, &p.display_name).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Create a named group of connected sessions (e.g. the Studio windows of one multi-place experience) that broadcast_to_group can target at once. Every session ID must be connected. Re-using a name replaces the group."
+    )]
+    async fn create_session_group(&self, params: Parameters<CreateSessionGroupParams>) -> String {
+        let p = params.0;
+        match tools::session::create_session_group(&self.state, &p.name, &p.session_ids).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Run one plugin tool on every session in a group concurrently and aggregate the results. args are the tool's raw plugin arguments. Each session's outcome is listed under results with success/error; one failing session does not fail the others."
+    )]
+    async fn broadcast_to_group(&self, params: Parameters<BroadcastToGroupParams>) -> String {
+        let p = params.0;
+        match tools::session::broadcast_to_group(&self.state, &p.group, &p.tool, p.args).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Abort a plugin request that is still running or queued, e.g. a long workspace_analyze another chat started. Pass requestId, or tool to cancel every pending call of that tool (optionally only on session_id). Queued requests are dropped; running ones are told to stop, and the waiting call fails at once with a 'cancelled' error. With no arguments, just lists pending requests {request_id, session_id, tool, state, age_secs}."
+    )]
+    async fn cancel_request(&self, params: Parameters<CancelRequestParams>) -> String {
+        let p = params.0;
+        match tools::session::cancel_request(
+            &self.state,
+            p.request_id.as_deref(),
+            p.tool.as_deref(),
+            p.session_id.as_deref(),
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Run one plugin tool on every connected session at once (or only sessionIds) and aggregate the results, e.g. lint_scripts across all open places. args are the tool's raw plugin arguments. Returns {tool, sessions, succeeded, failed, results}; each result has session_id and success plus result or error, so one failing session does not fail the others."
+    )]
+    async fn broadcast_tool(&self, params: Parameters<BroadcastToolParams>) -> String {
+        let p = params.0;
+        match tools::session::broadcast_tool(
+            &self.state,
+            &p.tool,
+            p.args,
+            p.session_ids,
+            p.timeout_secs,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Get information about the currently active Studio session (PlaceId, name, connection status). Set enrich=true to also get the place icon asset ID, creator and universe ID; fields that can't be resolved are listed in enrichErrors instead of failing the call."
     )]
-    async fn get_active_session(&self) -> String {
-        match tools::session::get_active_session(&self.state).await {
+    async fn get_active_session(&self, params: Parameters<GetActiveSessionParams>) -> String {
+        match tools::session::get_active_session(&self.state, params.0.enrich).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -1077,6 +2364,49 @@ impl StudioLinkMcp {
         }
     }
 
+    #[tool(
+        description = "Changes other agents made to a session's instance tree since your last poll, so you can invalidate cached trees. Returns {cursor, changes: [{seq, tool, path, kind}], missed}; pass cursor back next time. kind is created/deleted/modified/source, or unknown with path=null after run_code/undo/redo (drop everything cached). missed=true means older changes were evicted. waitMs blocks until the next change."
+    )]
+    async fn poll_changes(&self, params: Parameters<PollChangesParams>) -> String {
+        let p = params.0;
+        match tools::session::poll_changes(
+            &self.state,
+            p.session_id.as_deref(),
+            p.cursor,
+            p.wait_ms,
+        )
+        .await
+        {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Fetch another page of a result that was too large to return at once. Large results come back as {paginated: true, page: 0, pages, continuation, data}; call this with the continuation token and page=1, 2, ... and concatenate every page's data in order to get the full result. Tokens expire after 10 minutes."
+    )]
+    async fn get_result_page(&self, params: Parameters<GetResultPageParams>) -> String {
+        let p = params.0;
+        let page = self.state.lock().await.result_pages.page(&p.token, p.page);
+        match page {
+            Some((data, pages)) => page_envelope(&p.token, p.page, pages, data),
+            None => err_text(StudioLinkError::InvalidArguments(format!(
+                "no page {} for token '{}': the token is unknown or expired, or the page is out of range",
+                p.page, p.token
+            ))),
+        }
+    }
+
+    #[tool(
+        description = "One-shot server report for bug reports: version, primary/proxy mode, uptime, connected sessions with queue depths and heartbeat age, proxy target and enabled features. Reads server state only — works with no Studio session connected."
+    )]
+    async fn server_diagnostics(&self) -> String {
+        match tools::debug::server_diagnostics(&self.state).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
     #[tool(
         description = "Bind this Claude/Cursor chat to a specific Studio session for the rest of the conversation. After calling set_my_session(session_id), every subsequent tool call WITHOUT an explicit session_id will automatically route to the bound session — no more passing session_id on every call. Pass null/none to clear and fall back to active_session. RECOMMENDED FLOW: list_sessions → ask user (or infer) which place this chat owns → set_my_session(<that_id>) once → forget about session_id for the rest."
     )]
@@ -1088,7 +2418,7 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Read the bound_session_id for this MCP instance (set via set_my_session) along with the global active_session. Returns null when nothing is bound."
+        description = "Read the bound_session_id for this MCP connection (set via set_my_session) along with the global active_session. Returns null when nothing is bound."
     )]
     async fn get_my_session(&self) -> String {
         match tools::affinity::get_my_session(&self.state).await {
@@ -1112,10 +2442,46 @@ impl StudioLinkMcp {
     }
 
     #[tool(
-        description = "Open Studio's publish dialog for the active place. version_type is 'Saved' (default) or 'Published'. The user must complete the dialog manually — true headless publish requires RobloxScriptSecurity which plugins don't have. Returns immediately with dialog_opened=true."
+        description = "Publish a place. version_type is 'Saved' (default) or 'Published'. With filePath, uploads that .rbxl/.rbxlx as a new version through Open Cloud (API key in STUDIOLINK_OPENCLOUD_KEY; universeId/placeId default to the active session's) and returns versionNumber; versionNotes go to a local publish log, and dryRun=true validates everything without uploading. Without filePath, opens Studio's publish dialog for the active place, which the user completes manually (returns dialog_opened=true)."
     )]
     async fn publish_place(&self, params: Parameters<PublishPlaceParams>) -> String {
-        match tools::publish::publish_place(&self.state, params.0.version_type).await {
+        let p = params.0;
+        let file = tools::publish::FilePublish {
+            file_path: p.file_path,
+            universe_id: p.universe_id,
+            place_id: p.place_id,
+            version_notes: p.version_notes,
+            dry_run: p.dry_run.unwrap_or(false),
+        };
+        match tools::publish::publish_place(&self.state, p.version_type, file).await {
+            Ok(result) => ok_text(result),
+            Err(e) => err_text(e),
+        }
+    }
+
+    #[tool(
+        description = "Upload a local image, audio, mesh/model or video file as a Roblox asset through the Open Cloud Assets API (API key with asset:write in STUDIOLINK_OPENCLOUD_KEY). Owner is userId or groupId. Waits for processing and moderation (waitSecs, default 120) and returns assetId and contentId (rbxassetid://...) ready for set_property, e.g. a Decal's Texture or a Sound's SoundId. Moderation rejection is an error."
+    )]
+    async fn asset_upload(&self, params: Parameters<AssetUploadParams>) -> String {
+        let p = params.0;
+        let creator = match (p.user_id, p.group_id) {
+            (Some(id), None) => crate::opencloud::AssetCreator::User(id),
+            (None, Some(id)) => crate::opencloud::AssetCreator::Group(id),
+            _ => {
+                return err_text(StudioLinkError::InvalidArguments(
+                    "asset_upload needs exactly one of userId or groupId to own the asset".into(),
+                ))
+            }
+        };
+        let options = tools::asset_upload::AssetUploadOptions {
+            file_path: p.file_path,
+            asset_type: p.asset_type,
+            display_name: p.display_name,
+            description: p.description,
+            creator,
+            wait_secs: p.wait_secs,
+        };
+        match tools::asset_upload::asset_upload(options).await {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -1372,11 +2738,19 @@ impl StudioLinkMcp {
     // ═══════════════════════════════════════════
 
     #[tool(
-        description = "Read LogService:GetLogHistory() entries with optional filtering by message_type (Output/Info/Warning/Error) and substring pattern. Returns up to `limit` newest matches (default 100)."
+        description = "Read LogService:GetLogHistory() entries with optional filtering by message_type (Output/Info/Warning/Error) and substring pattern. Returns up to `limit` newest matches (default 100). With parseStacks=true, error entries also carry error: {message, frames: [{script, line, function}]} for jumping to get_script_source at the failing line; traces that don't parse are left as raw text with stackParsed=false."
     )]
     async fn error_history(&self, params: Parameters<ErrorHistoryParams>) -> String {
         let p = params.0;
-        match tools::logs::error_history(&self.state, p.message_type, p.pattern, p.limit).await {
+        match tools::logs::error_history(
+            &self.state,
+            p.message_type,
+            p.pattern,
+            p.limit,
+            p.parse_stacks,
+        )
+        .await
+        {
             Ok(result) => ok_text(result),
             Err(e) => err_text(e),
         }
@@ -1426,8 +2800,192 @@ impl StudioLinkMcp {
     }
 }
 
-#[tool_handler]
 impl ServerHandler for StudioLinkMcp {
+    /// Dispatch to the tool router, then page any result over
+    /// `RESULT_PAGE_BYTES` so no tool needs its own size handling.
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParams,
+        context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let read_only = self.state.lock().await.read_only;
+        if let Some(refusal) = tools::read_only_refusal(read_only, &request.name) {
+            tracing::info!("Refused {} (read-only mode)", request.name);
+            return Ok(CallToolResult::error(vec![Content::text(err_text(
+                refusal,
+            ))]));
+        }
+        let paged = request.name != "get_result_page";
+        let sink = context
+            .meta
+            .get_progress_token()
+            .map(|token| progress_forwarder(context.peer.clone(), token));
+        let tcc = rmcp::handler::server::tool::ToolCallContext::new(self, request, context);
+        let call = tools::CONNECTION.scope(self.connection.clone(), self.tool_router.call(tcc));
+        let mut result = match sink {
+            Some(sink) => tools::PROGRESS_SINK.scope(sink, call).await?,
+            None => call.await?,
+        };
+        if !paged || result.is_error == Some(true) || result.content.len() != 1 {
+            return Ok(result);
+        }
+        let text = match result.content[0].as_text() {
+            Some(t) if t.text.len() > crate::state::RESULT_PAGE_BYTES => t.text.clone(),
+            _ => return Ok(result),
+        };
+        let (token, pages, first) = {
+            let mut s = self.state.lock().await;
+            let (token, pages) = s.result_pages.store(&text);
+            let first = s.result_pages.page(&token, 0).map(|(data, _)| data);
+            (token, pages, first.unwrap_or_default())
+        };
+        result.content = vec![Content::text(page_envelope(&token, 0, pages, first))];
+        Ok(result)
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<ListToolsResult, rmcp::ErrorData> {
+        Ok(ListToolsResult {
+            tools: self.tool_router.list_all(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
+
+    fn get_tool(&self, name: &str) -> Option<Tool> {
+        self.tool_router.get(name).cloned()
+    }
+
+    /// Every service and every script, paged by RESOURCE_PAGE_SIZE; the
+    /// cursor is the index of the next resource.
+    async fn list_resources(
+        &self,
+        request: Option<PaginatedRequestParams>,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<ListResourcesResult, rmcp::ErrorData> {
+        self.check_filtered("get_file_tree")
+            .map_err(resource_error)?;
+        let start = match request.and_then(|r| r.cursor) {
+            Some(cursor) => cursor
+                .parse::<usize>()
+                .map_err(|_| rmcp::ErrorData::invalid_params("invalid cursor", None))?,
+            None => 0,
+        };
+        let all = tools::CONNECTION
+            .scope(self.connection.clone(), tools::resources::list(&self.state))
+            .await
+            .map_err(resource_error)?;
+        let end = (start + tools::resources::RESOURCE_PAGE_SIZE).min(all.len());
+        Ok(ListResourcesResult {
+            resources: all.get(start..end).unwrap_or_default().to_vec(),
+            next_cursor: (end < all.len()).then(|| end.to_string()),
+            meta: None,
+        })
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<ListPromptsResult, rmcp::ErrorData> {
+        Ok(ListPromptsResult::with_all_items(tools::prompts::list(
+            |tool| self.tool_router.has_route(tool),
+        )))
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParams,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<GetPromptResult, rmcp::ErrorData> {
+        let read_only = self.state.lock().await.read_only;
+        tools::prompts::get(
+            &request.name,
+            request.arguments.as_ref(),
+            read_only,
+            |tool| self.tool_router.has_route(tool),
+        )
+        .map_err(|e| rmcp::ErrorData::invalid_params(e.to_string(), None))
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, rmcp::ErrorData> {
+        Ok(ListResourceTemplatesResult::with_all_items(
+            tools::resources::templates(),
+        ))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParams,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<ReadResourceResult, rmcp::ErrorData> {
+        let resource =
+            tools::resources::PlaceResource::parse(&request.uri).map_err(resource_error)?;
+        let backing = match resource {
+            tools::resources::PlaceResource::Script(_) => "get_script_source",
+            tools::resources::PlaceResource::Instance(_) => "get_file_tree",
+        };
+        self.check_filtered(backing).map_err(resource_error)?;
+        let (text, mime) = tools::CONNECTION
+            .scope(
+                self.connection.clone(),
+                tools::resources::read(&self.state, &resource),
+            )
+            .await
+            .map_err(resource_error)?;
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri: request.uri,
+                mime_type: Some(mime.into()),
+                text,
+                meta: None,
+            }],
+        })
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParams,
+        context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<(), rmcp::ErrorData> {
+        let resource =
+            tools::resources::PlaceResource::parse(&request.uri).map_err(resource_error)?;
+        let mut subs = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        subs.resources.insert(resource);
+        if subs.watcher.as_ref().is_none_or(|w| w.is_finished()) {
+            subs.watcher = Some(resource_watcher(
+                context.peer,
+                self.state.clone(),
+                Arc::downgrade(&self.subscriptions),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParams,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<(), rmcp::ErrorData> {
+        let resource =
+            tools::resources::PlaceResource::parse(&request.uri).map_err(resource_error)?;
+        let mut subs = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        subs.resources.remove(&resource);
+        if subs.resources.is_empty() {
+            if let Some(watcher) = subs.watcher.take() {
+                watcher.abort();
+            }
+        }
+        Ok(())
+    }
+
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             instructions: Some(
@@ -1465,12 +3023,19 @@ impl ServerHandler for StudioLinkMcp {
                 Unbind: call set_my_session(session_id=null) or set_my_session()\n\
                 with no argument to fall back to active_session.\n\
                 \n\
-                Each Claude/Cursor chat has its own studiolink process with its\n\
-                own bound_session_id, so chats do not clobber each other.\n\
+                Each Claude/Cursor chat has its own studiolink process (or its\n\
+                own streamable-HTTP MCP session) with its own bound_session_id,\n\
+                so chats do not clobber each other.\n\
                 ═══════════════════════════════════════════════════════════════════"
                     .into(),
             ),
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_prompts()
+                .enable_resources()
+                .enable_resources_subscribe()
+                .enable_resources_list_changed()
+                .build(),
             server_info: Implementation {
                 name: "StudioLink".into(),
                 version: env!("CARGO_PKG_VERSION").into(),
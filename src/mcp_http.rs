@@ -0,0 +1,357 @@
+//! Streamable HTTP transport for the MCP side (`--transport streamable-http`).
+//!
+//! Lets clients that did not spawn this process (remote agents, several
+//! editors at once) talk MCP over HTTP. Every MCP session gets its own
+//! copy of the handler, all sharing one `AppState` and tool set but each
+//! with its own resource subscriptions, bound session, open macro and
+//! switch history.
+//!
+//! The endpoint takes the same bearer token as the plugin API, and refuses
+//! requests whose Host or Origin isn't loopback or the bind address, so a
+//! web page can't reach it through DNS rebinding.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use rmcp::transport::streamable_http_server::{
+    session::local::LocalSessionManager, StreamableHttpServerConfig, StreamableHttpService,
+};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::auth::SharedTokens;
+use crate::mcp::StudioLinkMcp;
+
+/// Where the MCP endpoint listens unless --mcp-bind says otherwise: one
+/// above the plugin port, loopback only.
+pub const DEFAULT_MCP_BIND: &str = "127.0.0.1:34873";
+
+/// Path of the MCP endpoint (POST for messages, GET for the SSE stream).
+pub const MCP_PATH: &str = "/mcp";
+
+/// Serve MCP on `listener` until `shutdown` resolves; open MCP sessions are
+/// cancelled then. `auth` is None only with --no-auth.
+pub async fn serve(
+    handler: StudioLinkMcp,
+    listener: tokio::net::TcpListener,
+    auth: Option<SharedTokens>,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let bind = listener.local_addr()?;
+    let config = StreamableHttpServerConfig::default();
+    let cancel = config.cancellation_token.clone();
+    let service = StreamableHttpService::new(
+        move || Ok(handler.for_connection()),
+        Arc::new(LocalSessionManager::default()),
+        config,
+    );
+    let router = Router::new()
+        .nest_service(MCP_PATH, service)
+        .layer(middleware::from_fn_with_state(auth, require_token))
+        .layer(middleware::from_fn_with_state(bind, check_origin));
+    crate::server::serve(listener, router, async move {
+        shutdown.await;
+        cancel.cancel();
+    })
+    .await
+}
+
+async fn require_token(
+    State(auth): State<Option<SharedTokens>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    crate::server::authorize(auth.as_ref(), request, next).await
+}
+
+/// Refuse requests whose Host or Origin names anything other than loopback
+/// or the address the endpoint is bound to.
+async fn check_origin(State(bind): State<SocketAddr>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let allowed = [header::HOST, header::ORIGIN].into_iter().all(|name| {
+        headers
+            .get(name)
+            .is_none_or(|v| v.to_str().is_ok_and(|v| authority_allowed(v, &bind)))
+    });
+    if !allowed {
+        tracing::warn!(
+            "Rejected MCP request from host {:?}, origin {:?}",
+            headers.get(header::HOST),
+            headers.get(header::ORIGIN)
+        );
+        return (StatusCode::FORBIDDEN, "Host or Origin not allowed").into_response();
+    }
+    next.run(request).await
+}
+
+/// Whether a Host value ("localhost:34873") or Origin ("http://[::1]:3000")
+/// names loopback or `bind`'s address. With an unspecified bind (0.0.0.0)
+/// any IP literal is accepted: rebinding needs a DNS name.
+fn authority_allowed(value: &str, bind: &SocketAddr) -> bool {
+    let authority = value.split_once("://").map_or(value, |(_, rest)| rest);
+    let authority = authority.split('/').next().unwrap_or_default();
+    let host = match authority.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => authority
+            .rsplit_once(':')
+            .map_or(authority, |(host, _)| host),
+    };
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback() || ip == bind.ip() || bind.ip().is_unspecified(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+
+    #[tokio::test]
+    async fn initialize_over_http() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), MCP_PATH);
+        let (state, _rx) = AppState::new();
+        let token = "t".repeat(32);
+        let auth = Arc::new(std::sync::RwLock::new(crate::auth::AuthTokens::new(
+            token.clone(),
+            None,
+        )));
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(serve(
+            StudioLinkMcp::new(state),
+            listener,
+            Some(auth),
+            async move {
+                let _ = shutdown_rx.await;
+            },
+        ));
+
+        let client = reqwest::Client::new();
+        let initialize = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "0" },
+            },
+        });
+        let missing = client.post(&url).json(&initialize).send().await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let foreign = client
+            .post(&url)
+            .bearer_auth(&token)
+            .header("Origin", "http://evil.example")
+            .json(&initialize)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(foreign.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let response = client
+            .post(&url)
+            .bearer_auth(&token)
+            .header("Origin", "http://localhost:5173")
+            .header("Accept", "application/json, text/event-stream")
+            .json(&initialize)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        assert!(response.headers().contains_key("mcp-session-id"));
+        let body = response.text().await.unwrap();
+        assert!(body.contains("serverInfo"), "{}", body);
+
+        shutdown_tx.send(()).unwrap();
+        let _ = task.await;
+    }
+
+    /// POST one JSON-RPC message on `session` (None to initialize) and
+    /// return the session id and the response, read out of the SSE body.
+    async fn post(
+        client: &reqwest::Client,
+        url: &str,
+        token: &str,
+        session: Option<&str>,
+        message: serde_json::Value,
+    ) -> (String, Option<serde_json::Value>) {
+        let mut request = client
+            .post(url)
+            .bearer_auth(token)
+            .header("Accept", "application/json, text/event-stream")
+            .json(&message);
+        if let Some(session) = session {
+            request = request.header("mcp-session-id", session);
+        }
+        let response = request.send().await.unwrap();
+        assert!(response.status().is_success(), "{}", response.status());
+        let session = response
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .or(session.map(str::to_string))
+            .unwrap();
+        let body = response.text().await.unwrap();
+        let reply = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(data.trim()).ok())
+            .find(|v| v.get("id").is_some());
+        (session, reply)
+    }
+
+    async fn open_session(client: &reqwest::Client, url: &str, token: &str) -> String {
+        let (session, _) = post(
+            client,
+            url,
+            token,
+            None,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": "2025-03-26",
+                    "capabilities": {},
+                    "clientInfo": { "name": "test", "version": "0" },
+                },
+            }),
+        )
+        .await;
+        post(
+            client,
+            url,
+            token,
+            Some(&session),
+            serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        )
+        .await;
+        session
+    }
+
+    async fn call(
+        client: &reqwest::Client,
+        url: &str,
+        token: &str,
+        session: &str,
+        tool: &str,
+        arguments: serde_json::Value,
+    ) -> serde_json::Value {
+        let (_, reply) = post(
+            client,
+            url,
+            token,
+            Some(session),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/call",
+                "params": { "name": tool, "arguments": arguments },
+            }),
+        )
+        .await;
+        let reply = reply.expect("tools/call reply");
+        let text = reply["result"]["content"][0]["text"].as_str().unwrap();
+        serde_json::from_str(text).unwrap()
+    }
+
+    #[tokio::test]
+    async fn connections_keep_their_own_bound_session() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), MCP_PATH);
+        let (state, _rx) = AppState::new();
+        state
+            .lock()
+            .await
+            .register_session(crate::state::SessionRegistration {
+                session_id: "studio-a".into(),
+                place_id: 1,
+                place_name: "Place".into(),
+                game_id: 0,
+                client_id: None,
+                supported_tools: None,
+                plugin_version: None,
+            });
+        let token = "t".repeat(32);
+        let auth = Arc::new(std::sync::RwLock::new(crate::auth::AuthTokens::new(
+            token.clone(),
+            None,
+        )));
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(serve(
+            StudioLinkMcp::new(state),
+            listener,
+            Some(auth),
+            async move {
+                let _ = shutdown_rx.await;
+            },
+        ));
+
+        let client = reqwest::Client::new();
+        let a = open_session(&client, &url, &token).await;
+        let b = open_session(&client, &url, &token).await;
+        assert_ne!(a, b);
+
+        let bound = call(
+            &client,
+            &url,
+            &token,
+            &a,
+            "set_my_session",
+            serde_json::json!({ "session_id": "studio-a" }),
+        )
+        .await;
+        assert_eq!(bound["bound_session_id"], "studio-a");
+        let mine = call(
+            &client,
+            &url,
+            &token,
+            &a,
+            "get_my_session",
+            serde_json::json!({}),
+        )
+        .await;
+        assert_eq!(mine["bound_session_id"], "studio-a");
+        let other = call(
+            &client,
+            &url,
+            &token,
+            &b,
+            "get_my_session",
+            serde_json::json!({}),
+        )
+        .await;
+        assert!(other["bound_session_id"].is_null(), "{}", other);
+
+        shutdown_tx.send(()).unwrap();
+        let _ = task.await;
+    }
+
+    #[test]
+    fn only_loopback_or_bind_hosts_are_allowed() {
+        let loopback: SocketAddr = "127.0.0.1:34873".parse().unwrap();
+        assert!(authority_allowed("127.0.0.1:34873", &loopback));
+        assert!(authority_allowed("localhost:34873", &loopback));
+        assert!(authority_allowed("http://[::1]:3000", &loopback));
+        assert!(!authority_allowed("attacker.example:34873", &loopback));
+        assert!(!authority_allowed("http://attacker.example", &loopback));
+        assert!(!authority_allowed("192.168.1.5:34873", &loopback));
+
+        let lan: SocketAddr = "192.168.1.5:34873".parse().unwrap();
+        assert!(authority_allowed("192.168.1.5:34873", &lan));
+        assert!(!authority_allowed("192.168.1.6:34873", &lan));
+        let any: SocketAddr = "0.0.0.0:34873".parse().unwrap();
+        assert!(authority_allowed("10.0.0.7:34873", &any));
+        assert!(!authority_allowed("studio.example:34873", &any));
+    }
+}
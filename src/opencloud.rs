@@ -0,0 +1,605 @@
+//! Roblox Open Cloud REST API access.
+//!
+//! `OpenCloudDataStores` backs the datastore_* tools with
+//! `--datastore-backend opencloud`: with an API key that has DataStore
+//! permissions for the universe, they read and write live data without any
+//! Studio session connected. Results have the same shape the plugin returns,
+//! plus `backend: "opencloud"`. `publish_place` uploads a place file as a new
+//! version, and `create_asset`/`get_asset_operation` drive the Assets API for
+//! asset_upload.
+//!
+//! The key is read from `STUDIOLINK_OPENCLOUD_KEY` rather than the command
+//! line so it stays out of process listings.
+
+use reqwest::{Method, StatusCode, Url};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::error::{Result, StudioLinkError};
+use crate::tools::datastore::OrderedScan;
+
+/// Environment variable holding the Open Cloud API key.
+pub const API_KEY_ENV: &str = "STUDIOLINK_OPENCLOUD_KEY";
+
+pub const DEFAULT_BASE_URL: &str = "https://apis.roblox.com";
+
+/// Per-request timeout; Open Cloud answers quickly or not at all.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Pages datastore_list follows before returning what it has.
+const MAX_LIST_PAGES: u32 = 20;
+
+/// Open Cloud's largest page for listing entries.
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Place uploads can take a while for large places.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The API key from `STUDIOLINK_OPENCLOUD_KEY`, if set.
+pub fn api_key_from_env() -> Option<String> {
+    std::env::var(API_KEY_ENV)
+        .ok()
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+}
+
+/// Upload a place file as a new version of `place_id` and return the version
+/// number. `version_type` is "Saved" or "Published".
+pub async fn publish_place(
+    base_url: &str,
+    api_key: &str,
+    universe_id: u64,
+    place_id: u64,
+    place: Vec<u8>,
+    xml: bool,
+    version_type: &str,
+) -> Result<u64> {
+    let mut url = Url::parse(base_url)
+        .map_err(|e| StudioLinkError::ServerError(format!("bad Open Cloud URL: {}", e)))?;
+    url.path_segments_mut()
+        .expect("Open Cloud base URL can have a path")
+        .pop_if_empty()
+        .extend(["universes", "v1", &universe_id.to_string()])
+        .extend(["places", &place_id.to_string(), "versions"]);
+    url.query_pairs_mut()
+        .append_pair("versionType", version_type);
+    let response = reqwest::Client::new()
+        .post(url)
+        .header("x-api-key", api_key)
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            if xml {
+                "application/xml"
+            } else {
+                "application/octet-stream"
+            },
+        )
+        .body(place)
+        .timeout(PUBLISH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| StudioLinkError::ServerError(format!("Open Cloud request failed: {}", e)))?;
+    let body = read_response(response).await?.ok_or_else(|| {
+        StudioLinkError::InvalidArguments(format!(
+            "Open Cloud found no place {} in universe {}",
+            place_id, universe_id
+        ))
+    })?;
+    body["versionNumber"].as_u64().ok_or_else(|| {
+        StudioLinkError::ServerError(format!(
+            "Open Cloud publish response has no versionNumber: {}",
+            body
+        ))
+    })
+}
+
+/// Who owns an uploaded asset.
+#[derive(Debug, Clone, Copy)]
+pub enum AssetCreator {
+    User(u64),
+    Group(u64),
+}
+
+/// One file for `create_asset`.
+pub struct NewAsset {
+    /// Open Cloud asset type: Audio, Decal, Model or Video
+    pub asset_type: &'static str,
+    pub display_name: String,
+    pub description: String,
+    pub creator: AssetCreator,
+    pub file_name: String,
+    pub content_type: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+/// Start an asset upload. Returns the long-running operation, whose `path`
+/// `get_asset_operation` polls.
+pub async fn create_asset(base_url: &str, api_key: &str, asset: NewAsset) -> Result<Value> {
+    let creator = match asset.creator {
+        AssetCreator::User(id) => json!({ "userId": id.to_string() }),
+        AssetCreator::Group(id) => json!({ "groupId": id.to_string() }),
+    };
+    let request = json!({
+        "assetType": asset.asset_type,
+        "displayName": asset.display_name,
+        "description": asset.description,
+        "creationContext": { "creator": creator },
+    });
+    let file = reqwest::multipart::Part::bytes(asset.bytes)
+        .file_name(asset.file_name)
+        .mime_str(asset.content_type)
+        .map_err(|e| StudioLinkError::ServerError(format!("bad content type: {}", e)))?;
+    let form = reqwest::multipart::Form::new()
+        .text("request", request.to_string())
+        .part("fileContent", file);
+    let response = reqwest::Client::new()
+        .post(assets_url(base_url, &["assets"])?)
+        .header("x-api-key", api_key)
+        .multipart(form)
+        .timeout(PUBLISH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| StudioLinkError::ServerError(format!("Open Cloud request failed: {}", e)))?;
+    read_response(response)
+        .await?
+        .ok_or_else(|| StudioLinkError::ServerError("Open Cloud Assets API not found".into()))
+}
+
+/// Fetch an asset operation by the `path` `create_asset` returned
+/// ("operations/<id>").
+pub async fn get_asset_operation(base_url: &str, api_key: &str, path: &str) -> Result<Value> {
+    let segments: Vec<&str> = path.split('/').collect();
+    get_assets_resource(base_url, api_key, &segments, path).await
+}
+
+/// Fetch an asset's current state, including its moderation result.
+pub async fn get_asset(base_url: &str, api_key: &str, asset_id: &str) -> Result<Value> {
+    get_assets_resource(base_url, api_key, &["assets", asset_id], asset_id).await
+}
+
+async fn get_assets_resource(
+    base_url: &str,
+    api_key: &str,
+    segments: &[&str],
+    what: &str,
+) -> Result<Value> {
+    let response = reqwest::Client::new()
+        .get(assets_url(base_url, segments)?)
+        .header("x-api-key", api_key)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| StudioLinkError::ServerError(format!("Open Cloud request failed: {}", e)))?;
+    read_response(response)
+        .await?
+        .ok_or_else(|| StudioLinkError::ServerError(format!("Open Cloud has no {}", what)))
+}
+
+/// `assets/v1/` followed by `segments`, each escaped.
+fn assets_url(base_url: &str, segments: &[&str]) -> Result<Url> {
+    let mut url = Url::parse(base_url)
+        .map_err(|e| StudioLinkError::ServerError(format!("bad Open Cloud URL: {}", e)))?;
+    url.path_segments_mut()
+        .expect("Open Cloud base URL can have a path")
+        .pop_if_empty()
+        .extend(["assets", "v1"])
+        .extend(segments);
+    Ok(url)
+}
+
+/// A response's JSON body: Ok(None) for 404, an error naming Open Cloud's
+/// message for any other failure.
+async fn read_response(response: reqwest::Response) -> Result<Option<Value>> {
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let text = response.text().await.unwrap_or_default();
+    let body: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+    if !status.is_success() {
+        let message = body["message"].as_str().unwrap_or(text.trim());
+        let hint = match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                " (check the API key's permissions for this universe)"
+            }
+            _ => "",
+        };
+        return Err(StudioLinkError::ServerError(format!(
+            "Open Cloud returned {}: {}{}",
+            status, message, hint
+        )));
+    }
+    Ok(Some(body))
+}
+
+pub struct OpenCloudDataStores {
+    base_url: String,
+    universe_id: u64,
+    api_key: String,
+    client: reqwest::Client,
+}
+
+impl OpenCloudDataStores {
+    pub fn new(base_url: &str, universe_id: u64, api_key: String) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            universe_id,
+            api_key,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// `cloud/v2/universes/<id>/` followed by `segments`, each escaped.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = Url::parse(&self.base_url).expect("Open Cloud base URL is valid");
+        url.path_segments_mut()
+            .expect("Open Cloud base URL can have a path")
+            .pop_if_empty()
+            .extend(["cloud", "v2", "universes", &self.universe_id.to_string()])
+            .extend(segments);
+        url
+    }
+
+    /// The entries collection of a store; ordered stores live under a scope.
+    fn entries_url(&self, store: &str, ordered: bool, key: Option<&str>) -> Url {
+        let mut segments = if ordered {
+            vec!["ordered-data-stores", store, "scopes", "global", "entries"]
+        } else {
+            vec!["data-stores", store, "entries"]
+        };
+        segments.extend(key);
+        self.url(&segments)
+    }
+
+    /// Send a request and read the response with `read_response`.
+    async fn send(&self, method: Method, url: Url, body: Option<Value>) -> Result<Option<Value>> {
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-api-key", &self.api_key)
+            .timeout(REQUEST_TIMEOUT);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.map_err(|e| {
+            StudioLinkError::ServerError(format!("Open Cloud request failed: {}", e))
+        })?;
+        read_response(response).await
+    }
+
+    /// datastore_list: every standard DataStore name in the universe.
+    pub async fn list_stores(&self) -> Result<Value> {
+        let mut stores = Vec::new();
+        let mut token: Option<String> = None;
+        let mut pages = 0;
+        loop {
+            let mut url = self.url(&["data-stores"]);
+            url.query_pairs_mut()
+                .append_pair("maxPageSize", &MAX_PAGE_SIZE.to_string());
+            if let Some(token) = &token {
+                url.query_pairs_mut().append_pair("pageToken", token);
+            }
+            let page = self.send(Method::GET, url, None).await?.unwrap_or_default();
+            pages += 1;
+            stores.extend(
+                page["dataStores"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|s| s["id"].as_str().map(str::to_string)),
+            );
+            token = next_page_token(&page);
+            if token.is_none() || pages >= MAX_LIST_PAGES {
+                break;
+            }
+        }
+        Ok(json!({
+            "stores": stores,
+            "count": stores.len(),
+            "hasMore": token.is_some(),
+            "backend": "opencloud",
+        }))
+    }
+
+    pub async fn get(&self, store: &str, key: &str, ordered: bool) -> Result<Value> {
+        let entry = self
+            .send(
+                Method::GET,
+                self.entries_url(store, ordered, Some(key)),
+                None,
+            )
+            .await?;
+        Ok(json!({
+            "storeName": store,
+            "key": key,
+            "value": entry.map(|e| e["value"].clone()).unwrap_or(Value::Null),
+            "ordered": ordered,
+            "backend": "opencloud",
+        }))
+    }
+
+    /// datastore_get_many: the keys one by one, each to `{value, found}` or
+    /// `{error}`.
+    pub async fn get_many(&self, store: &str, keys: &[String], ordered: bool) -> Result<Value> {
+        let mut results = serde_json::Map::new();
+        let mut errors = 0;
+        for key in keys {
+            let url = self.entries_url(store, ordered, Some(key));
+            let result = match self.send(Method::GET, url, None).await {
+                Ok(Some(entry)) => json!({ "value": entry["value"], "found": true }),
+                Ok(None) => json!({ "value": null, "found": false }),
+                Err(e) => {
+                    errors += 1;
+                    json!({ "error": e.to_string() })
+                }
+            };
+            results.insert(key.clone(), result);
+        }
+        Ok(json!({
+            "storeName": store,
+            "ordered": ordered,
+            "results": results,
+            "keyCount": keys.len(),
+            "errorCount": errors,
+            "backend": "opencloud",
+        }))
+    }
+
+    /// datastore_set: create or replace the entry.
+    pub async fn set(&self, store: &str, key: &str, value: Value, ordered: bool) -> Result<Value> {
+        let mut url = self.entries_url(store, ordered, Some(key));
+        url.query_pairs_mut().append_pair("allowMissing", "true");
+        self.send(Method::PATCH, url, Some(json!({ "value": value })))
+            .await?;
+        Ok(json!({
+            "message": format!("Key '{}' updated in '{}'", key, store),
+            "backend": "opencloud",
+        }))
+    }
+
+    /// datastore_delete: like RemoveAsync, deleting a missing key succeeds.
+    pub async fn delete(&self, store: &str, key: &str) -> Result<Value> {
+        self.send(
+            Method::DELETE,
+            self.entries_url(store, false, Some(key)),
+            None,
+        )
+        .await?;
+        Ok(json!({
+            "message": format!("Key '{}' deleted from '{}'", key, store),
+            "backend": "opencloud",
+        }))
+    }
+
+    /// datastore_scan: keys of a standard store, or `{key, value}` entries of
+    /// an ordered one sorted by value.
+    pub async fn scan(
+        &self,
+        store: &str,
+        page_size: u32,
+        max_pages: u32,
+        ordered: bool,
+        scan: OrderedScan,
+    ) -> Result<Value> {
+        let ascending = scan.ascending.unwrap_or(false);
+        let mut items = Vec::new();
+        let mut token: Option<String> = None;
+        let mut pages = 0;
+        loop {
+            let mut url = self.entries_url(store, ordered, None);
+            {
+                let mut query = url.query_pairs_mut();
+                query.append_pair(
+                    "maxPageSize",
+                    &page_size.clamp(1, MAX_PAGE_SIZE).to_string(),
+                );
+                if ordered {
+                    query.append_pair("orderBy", if ascending { "value" } else { "value desc" });
+                    if let Some(filter) = value_filter(scan.min_value, scan.max_value) {
+                        query.append_pair("filter", &filter);
+                    }
+                }
+                if let Some(token) = &token {
+                    query.append_pair("pageToken", token);
+                }
+            }
+            let Some(page) = self.send(Method::GET, url, None).await? else {
+                return Err(StudioLinkError::InvalidArguments(format!(
+                    "no {} named '{}' in universe {}",
+                    if ordered {
+                        "OrderedDataStore"
+                    } else {
+                        "DataStore"
+                    },
+                    store,
+                    self.universe_id
+                )));
+            };
+            pages += 1;
+            let list = if ordered {
+                &page["orderedDataStoreEntries"]
+            } else {
+                &page["dataStoreEntries"]
+            };
+            for entry in list.as_array().into_iter().flatten() {
+                let key = entry["id"].clone();
+                items.push(if ordered {
+                    json!({ "key": key, "value": entry["value"] })
+                } else {
+                    json!({ "key": key })
+                });
+            }
+            token = next_page_token(&page);
+            if token.is_none() || pages >= max_pages {
+                break;
+            }
+        }
+        let mut result = json!({
+            "count": items.len(),
+            "hasMore": token.is_some(),
+            "pagesScanned": pages,
+            "backend": "opencloud",
+        });
+        if ordered {
+            result["entries"] = json!(items);
+            result["order"] = json!(if ascending { "ascending" } else { "descending" });
+        } else {
+            result["keys"] = json!(items);
+        }
+        Ok(result)
+    }
+}
+
+fn next_page_token(page: &Value) -> Option<String> {
+    page["nextPageToken"]
+        .as_str()
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+}
+
+/// Open Cloud's filter syntax for an ordered scan's value range.
+fn value_filter(min: Option<i64>, max: Option<i64>) -> Option<String> {
+    match (min, max) {
+        (Some(min), Some(max)) => Some(format!("entry >= {} && entry <= {}", min, max)),
+        (Some(min), None) => Some(format!("entry >= {}", min)),
+        (None, Some(max)) => Some(format!("entry <= {}", max)),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, RawQuery, State};
+    use axum::http::{HeaderMap, Method as HttpMethod};
+    use axum::routing::any;
+    use axum::{Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    type Seen = Arc<Mutex<Vec<String>>>;
+
+    /// A fake Open Cloud that records each request as "METHOD path?query"
+    /// and answers from a tiny fixed data set.
+    async fn fake_open_cloud(
+        State(seen): State<Seen>,
+        method: HttpMethod,
+        headers: HeaderMap,
+        Path(path): Path<String>,
+        RawQuery(query): RawQuery,
+    ) -> (axum::http::StatusCode, Json<Value>) {
+        use axum::http::StatusCode as S;
+        if headers.get("x-api-key").and_then(|v| v.to_str().ok()) != Some("test-key") {
+            return (
+                S::UNAUTHORIZED,
+                Json(json!({ "message": "Invalid API key" })),
+            );
+        }
+        let line = format!("{} /{}?{}", method, path, query.clone().unwrap_or_default());
+        seen.lock().unwrap().push(line);
+        let body = match (method.as_str(), path.as_str()) {
+            ("GET", "cloud/v2/universes/7/data-stores/Players/entries/user_1") => {
+                json!({ "id": "user_1", "value": { "coins": 5 } })
+            }
+            ("GET", "cloud/v2/universes/7/data-stores/Players/entries") => {
+                if query.unwrap_or_default().contains("pageToken=next") {
+                    json!({ "dataStoreEntries": [{ "id": "user_2" }] })
+                } else {
+                    json!({ "dataStoreEntries": [{ "id": "user_1" }], "nextPageToken": "next" })
+                }
+            }
+            ("GET", "cloud/v2/universes/7/ordered-data-stores/Wins/scopes/global/entries") => {
+                json!({ "orderedDataStoreEntries": [{ "id": "user_1", "value": 12 }] })
+            }
+            ("PATCH", _) => json!({ "value": 1 }),
+            ("POST", "universes/v1/7/places/99/versions") => json!({ "versionNumber": 42 }),
+            _ => return (S::NOT_FOUND, Json(json!({ "message": "Not found" }))),
+        };
+        (S::OK, Json(body))
+    }
+
+    async fn start() -> (String, Seen) {
+        let seen: Seen = Arc::default();
+        let router = Router::new()
+            .route("/{*path}", any(fake_open_cloud))
+            .with_state(seen.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (format!("http://{}", addr), seen)
+    }
+
+    #[tokio::test]
+    async fn datastore_calls_map_to_open_cloud_requests() {
+        let (base, seen) = start().await;
+        let cloud = OpenCloudDataStores::new(&base, 7, "test-key".into());
+
+        let got = cloud.get("Players", "user_1", false).await.unwrap();
+        assert_eq!(got["value"]["coins"], 5);
+        let missing = cloud.get("Players", "nobody", false).await.unwrap();
+        assert!(missing["value"].is_null());
+
+        let scanned = cloud
+            .scan("Players", 50, 5, false, OrderedScan::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            scanned["keys"],
+            json!([{ "key": "user_1" }, { "key": "user_2" }])
+        );
+        assert_eq!(scanned["pagesScanned"], 2);
+        assert_eq!(scanned["hasMore"], false);
+
+        let scan = OrderedScan {
+            ascending: Some(true),
+            min_value: Some(10),
+            max_value: None,
+        };
+        let wins = cloud.scan("Wins", 10, 1, true, scan).await.unwrap();
+        assert_eq!(wins["entries"], json!([{ "key": "user_1", "value": 12 }]));
+
+        cloud
+            .set("Players", "user 3", json!({ "coins": 1 }), false)
+            .await
+            .unwrap();
+
+        let seen = seen.lock().unwrap().clone();
+        assert!(seen.contains(
+            &"GET /cloud/v2/universes/7/ordered-data-stores/Wins/scopes/global/entries?maxPageSize=10&orderBy=value&filter=entry+%3E%3D+10".to_string()
+        ));
+        assert!(seen.contains(
+            &"PATCH /cloud/v2/universes/7/data-stores/Players/entries/user 3?allowMissing=true"
+                .to_string()
+        ));
+
+        let bad_key = OpenCloudDataStores::new(&base, 7, "wrong".into());
+        let err = bad_key.get("Players", "user_1", false).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid API key"));
+        assert!(err.to_string().contains("permissions"));
+    }
+
+    #[tokio::test]
+    async fn publish_uploads_a_place_version() {
+        let (base, seen) = start().await;
+        let version = publish_place(
+            &base,
+            "test-key",
+            7,
+            99,
+            b"<roblox!".to_vec(),
+            false,
+            "Published",
+        )
+        .await
+        .unwrap();
+        assert_eq!(version, 42);
+        assert_eq!(
+            seen.lock().unwrap().clone(),
+            ["POST /universes/v1/7/places/99/versions?versionType=Published"]
+        );
+
+        let err = publish_place(&base, "test-key", 7, 5, Vec::new(), true, "Saved")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+}
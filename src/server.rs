@@ -1,19 +1,82 @@
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::Json,
+    body::Body,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, Request, State,
+    },
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
+use flate2::read::GzDecoder;
 use serde::Deserialize;
+use std::io::Read;
 use std::sync::Arc;
 use tokio::sync::{watch, Mutex};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::CorsLayer;
 
-use crate::state::{AppState, PluginRequest, PluginResponse, SessionRegistration};
+use crate::auth::{self, TokenMatch};
+use crate::error::StudioLinkError;
+use crate::state::{
+    AppState, ChunkProgress, PluginRequest, PluginResponse, ProgressFrame, ResponseChunk,
+    SessionRegistration, Sessions,
+};
+
+/// Shared state for Axum handlers. Plugin traffic (polls, responses,
+/// progress, the WebSocket) only touches `sessions` and `auth`, so it keeps
+/// flowing while a tool call holds the AppState lock.
+#[derive(Clone)]
+struct SharedState {
+    app: Arc<Mutex<AppState>>,
+    sessions: Arc<Sessions>,
+    auth: Option<auth::SharedTokens>,
+}
+
+impl SharedState {
+    async fn lock(&self) -> tokio::sync::MutexGuard<'_, AppState> {
+        self.app.lock().await
+    }
+}
+
+/// How long in-flight requests get to finish once shutdown is triggered.
+/// Plugin long-polls would otherwise keep a graceful shutdown waiting.
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
 
-/// Shared state type for Axum handlers
-type SharedState = Arc<Mutex<AppState>>;
+/// Largest request body accepted once gunzipped (16 MiB), so a small
+/// compressed body can't expand without bound.
+const MAX_DECODED_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Serve `router` on `listener` until `shutdown` resolves, then stop
+/// accepting connections and give in-flight requests `SHUTDOWN_GRACE` to
+/// complete before returning anyway.
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    router: Router,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let (triggered_tx, triggered_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = axum::serve(listener, router).with_graceful_shutdown(async move {
+        shutdown.await;
+        let _ = triggered_tx.send(());
+    });
+    let grace_expired = async move {
+        match triggered_rx.await {
+            Ok(()) => tokio::time::sleep(SHUTDOWN_GRACE).await,
+            // Server ended on its own; let that branch report it
+            Err(_) => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        result = server => result,
+        _ = grace_expired => {
+            tracing::warn!("HTTP server shutdown grace expired; dropping open connections");
+            Ok(())
+        }
+    }
+}
 
 /// Query params for session-aware polling
 #[derive(Deserialize)]
@@ -21,9 +84,59 @@ struct SessionQuery {
     session_id: Option<String>,
 }
 
+/// Query params for GET /changes
+#[derive(Deserialize)]
+struct ChangesQuery {
+    session_id: Option<String>,
+    #[serde(default)]
+    cursor: u64,
+    #[serde(default)]
+    wait_ms: u64,
+}
+
+#[derive(Deserialize)]
+struct SourcemapQuery {
+    dir: Option<String>,
+    #[serde(default)]
+    include_non_scripts: bool,
+}
+
+/// Query params carrying the token on /ws, for WebSocket clients that
+/// can't set an Authorization header
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Body of POST /cancel
+#[derive(Deserialize)]
+struct CancelRequest {
+    request_id: Option<String>,
+    tool: Option<String>,
+    session_id: Option<String>,
+}
+
+/// Body of POST /token/rotate
+#[derive(Deserialize, Default)]
+struct RotateTokenRequest {
+    /// How long the old token keeps working; 0 revokes it at once
+    grace_secs: Option<u64>,
+}
+
 /// Create the Axum HTTP server router
-pub fn create_router(state: SharedState, _global_notify_rx: watch::Receiver<bool>) -> Router {
-    Router::new()
+pub async fn create_router(
+    app: Arc<Mutex<AppState>>,
+    _global_notify_rx: watch::Receiver<bool>,
+) -> Router {
+    let state = {
+        let s = app.lock().await;
+        SharedState {
+            sessions: s.sessions.clone(),
+            auth: s.auth.clone(),
+            app: app.clone(),
+        }
+    };
+    let protected = Router::new()
         // Session management
         .route("/register", post(handle_register))
         .route("/unregister", post(handle_unregister))
@@ -31,18 +144,209 @@ pub fn create_router(state: SharedState, _global_notify_rx: watch::Receiver<bool
         // Tool request/response (session-aware)
         .route("/request", get(handle_poll_request))
         .route("/response", post(handle_plugin_response))
+        .route("/response/chunk", post(handle_response_chunk))
+        .route("/progress", post(handle_progress))
+        // Push transport: requests delivered over a WebSocket instead of long-polls
+        .route("/ws", get(handle_ws))
         // Proxy support (for secondary MCP instances)
         .route("/proxy/tool_call", post(handle_proxy_tool_call))
         .route("/switch_session", post(handle_switch_session))
-        // Health
-        .route("/health", get(handle_health))
+        .route("/rename_session", post(handle_rename_session))
         // v0.6 diagnostic: last 50 tool dispatches with target_session value.
         // Lets us verify whether the MCP client is shipping session_id.
         .route("/debug/routing", get(handle_debug_routing))
+        // Per-session mutation log for poll_changes from proxy instances
+        .route("/changes", get(handle_changes))
+        // Rojo-style sourcemap of the live place for luau-lsp and similar
+        .route("/sourcemap", get(handle_sourcemap))
+        // cancel_request from proxy instances
+        .route("/cancel", post(handle_cancel))
+        // NDJSON relay for streaming tools (get_file_tree_stream)
+        .route("/stream/{stream_id}", post(handle_stream_chunk))
+        .route("/token/rotate", post(handle_rotate_token))
+        .route_layer(middleware::from_fn(decode_gzip_body))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token));
+
+    Router::new()
+        .merge(protected)
+        // Health stays open so a new instance can tell StudioLink is running
+        .route("/health", get(handle_health))
+        // Machine-readable description of this HTTP API for alternative clients.
+        .route("/openapi.json", get(handle_openapi))
+        // Gzip responses for clients that send Accept-Encoding: gzip; long-poll
+        // requests carrying script sources shrink several times over
+        .layer(CompressionLayer::new().gzip(true))
         .layer(CorsLayer::permissive())
         .with_state(state)
 }
 
+/// Gunzip request bodies sent with `Content-Encoding: gzip` (the plugin
+/// compresses large /response bodies), so handlers always see plain JSON.
+async fn decode_gzip_body(request: Request, next: Next) -> Response {
+    let gzipped = request
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("gzip"));
+    if !gzipped {
+        return next.run(request).await;
+    }
+    let (mut parts, body) = request.into_parts();
+    let decoded = match axum::body::to_bytes(body, MAX_DECODED_BODY_BYTES).await {
+        Ok(compressed) => gunzip(&compressed),
+        Err(e) => Err(std::io::Error::other(e)),
+    };
+    match decoded {
+        Ok(decoded) => {
+            parts.headers.remove(header::CONTENT_ENCODING);
+            parts.headers.remove(header::CONTENT_LENGTH);
+            next.run(Request::from_parts(parts, Body::from(decoded)))
+                .await
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            format!("gzip request body didn't decode: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+fn gunzip(compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    GzDecoder::new(compressed)
+        .take(MAX_DECODED_BODY_BYTES as u64 + 1)
+        .read_to_end(&mut decoded)?;
+    if decoded.len() > MAX_DECODED_BODY_BYTES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "over {} MiB once decompressed",
+                MAX_DECODED_BODY_BYTES / (1024 * 1024)
+            ),
+        ));
+    }
+    Ok(decoded)
+}
+
+async fn require_token(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    authorize(state.auth.as_ref(), request, next).await
+}
+
+/// The token a request presents: its bearer header, or on /ws the `token`
+/// query parameter.
+fn presented_token(headers: &header::HeaderMap, uri: &axum::http::Uri) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(auth::bearer_token)
+        .map(str::to_string)
+        .or_else(|| {
+            (uri.path() == "/ws")
+                .then(|| Query::<TokenQuery>::try_from_uri(uri).ok())
+                .flatten()
+                .and_then(|q| q.0.token)
+        })
+}
+
+/// Reject requests without a valid bearer token when authentication is on
+/// (`auth` is Some). A request made with the token from before the last
+/// rotation still goes through during the grace period, and its response
+/// carries the new token.
+pub(crate) async fn authorize(
+    auth: Option<&auth::SharedTokens>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let presented = presented_token(request.headers(), request.uri());
+
+    let Some(auth) = auth else {
+        return next.run(request).await;
+    };
+    let checked = {
+        let tokens = auth.read().unwrap_or_else(|e| e.into_inner());
+        presented
+            .as_deref()
+            .and_then(|t| tokens.check(t))
+            .map(|m| (m, tokens.current().to_string()))
+    };
+
+    match checked {
+        Some((TokenMatch::Current, _)) => next.run(request).await,
+        Some((TokenMatch::Previous, current)) => {
+            let mut response = next.run(request).await;
+            if let Ok(value) = HeaderValue::from_str(&current) {
+                response
+                    .headers_mut()
+                    .insert(auth::ROTATED_TOKEN_HEADER, value);
+            }
+            response
+        }
+        None => {
+            let error = if presented.is_some() {
+                "Invalid token"
+            } else {
+                "Missing bearer token"
+            };
+            tracing::warn!(
+                "Rejected {} {}: {}",
+                request.method(),
+                request.uri().path(),
+                error
+            );
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                Json(serde_json::json!({
+                    "error": format!("{}. Send Authorization: Bearer <token> (see ~/.studiolink/token or --token).", error),
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// POST /token/rotate — Replace the bearer token. The old token keeps working
+/// for `grace_secs` (default 60) so connected plugins can pick up the new one.
+async fn handle_rotate_token(
+    State(state): State<SharedState>,
+    body: Option<Json<RotateTokenRequest>>,
+) -> std::result::Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let request = body.map(|b| b.0).unwrap_or_default();
+    let grace = request
+        .grace_secs
+        .map(std::time::Duration::from_secs)
+        .unwrap_or(auth::DEFAULT_ROTATION_GRACE);
+    if grace > auth::MAX_ROTATION_GRACE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!("grace_secs must be at most {}", auth::MAX_ROTATION_GRACE.as_secs()),
+            })),
+        ));
+    }
+    let Some(auth) = &state.auth else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Authentication is disabled (--no-auth)" })),
+        ));
+    };
+    let token = auth
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .rotate(grace)
+        .to_string();
+    tracing::info!(
+        "Bearer token rotated; the previous token stays valid for {}s",
+        grace.as_secs()
+    );
+    // Open WebSockets check their token as they wake
+    state.sessions.wake_all();
+    Ok(Json(serde_json::json!({
+        "token": token,
+        "previous_valid_secs": grace.as_secs(),
+    })))
+}
+
 /// POST /register — Plugin registers itself as a new session
 async fn handle_register(
     State(state): State<SharedState>,
@@ -75,7 +379,8 @@ async fn handle_unregister(
 async fn handle_list_sessions(State(state): State<SharedState>) -> Json<serde_json::Value> {
     let s = state.lock().await;
     let sessions: Vec<serde_json::Value> = s
-        .list_sessions()
+        .sessions
+        .list()
         .iter()
         .map(|info| {
             serde_json::json!({
@@ -84,6 +389,8 @@ async fn handle_list_sessions(State(state): State<SharedState>) -> Json<serde_js
                 "place_name": info.place_name,
                 "game_id": info.game_id,
                 "connected_at": info.connected_at,
+                "display_name": info.display_name,
+                "endpoint": s.session_endpoint(&info.session_id),
             })
         })
         .collect();
@@ -101,46 +408,215 @@ async fn handle_list_sessions(State(state): State<SharedState>) -> Json<serde_js
 async fn handle_poll_request(
     State(state): State<SharedState>,
     Query(params): Query<SessionQuery>,
-) -> Result<Json<PluginRequest>, StatusCode> {
+) -> Result<(QueueDepthHeader, Json<PluginRequest>), (StatusCode, QueueDepthHeader)> {
     let session_id = match params.session_id {
         Some(id) => id,
-        None => return Err(StatusCode::BAD_REQUEST),
+        None => return Err((StatusCode::BAD_REQUEST, queue_depth_header(0))),
     };
 
-    // Update heartbeat and check for immediate request
-    {
-        let mut s = state.lock().await;
-        s.heartbeat(&session_id);
+    let sessions = &state.sessions;
 
-        if let Some(request) = s.get_pending_request_for_session(&session_id) {
-            return Ok(Json(request));
-        }
+    // Update heartbeat and check for immediate request
+    sessions.heartbeat(&session_id);
+    if let Some(request) = sessions.next_request(&session_id) {
+        return Ok((
+            queue_depth_header(sessions.queue_depth(&session_id)),
+            Json(request),
+        ));
     }
 
     // Long poll: get the session's notify channel and wait
-    let notify_rx = {
-        let s = state.lock().await;
-        s.get_session_notify_rx(&session_id)
-    };
+    let notify_rx = sessions.notify_rx(&session_id);
 
     let Some(mut notify_rx) = notify_rx else {
-        return Err(StatusCode::NOT_FOUND);
+        return Err((StatusCode::NOT_FOUND, queue_depth_header(0)));
     };
 
     let timeout =
         tokio::time::timeout(std::time::Duration::from_secs(30), notify_rx.changed()).await;
 
-    match timeout {
-        Ok(Ok(())) => {
-            let mut s = state.lock().await;
-            if let Some(request) = s.get_pending_request_for_session(&session_id) {
-                Ok(Json(request))
-            } else {
-                Err(StatusCode::NO_CONTENT)
+    if let (Ok(Ok(())), Some(request)) = (timeout, sessions.next_request(&session_id)) {
+        return Ok((
+            queue_depth_header(sessions.queue_depth(&session_id)),
+            Json(request),
+        ));
+    }
+    Err((
+        StatusCode::NO_CONTENT,
+        queue_depth_header(sessions.queue_depth(&session_id)),
+    ))
+}
+
+/// How often an idle /ws connection is pinged, which also lets a plugin that
+/// answers pings count as heartbeating.
+const WS_PING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Messages a plugin sends over /ws, tagged by `type`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum WsMessage {
+    Response(PluginResponse),
+    Progress(ProgressFrame),
+    Heartbeat,
+}
+
+/// GET /ws?session_id=xxx — Plugin opens a WebSocket for push delivery
+///
+/// Each queued request is sent as a text frame holding the same JSON that
+/// GET /request returns; the plugin replies with `{"type": "response", ...}`
+/// (the POST /response body), `{"type": "progress", ...}` or
+/// `{"type": "heartbeat"}`. Long-polling keeps working alongside it, and a
+/// plugin whose socket drops simply falls back to it.
+///
+/// The token is checked again on every rotation and ping: while the old
+/// token is in its grace period the server pushes `{"type": "token", "token":
+/// ...}` for the plugin to adopt, and once the token the socket holds is no
+/// longer accepted the socket is closed.
+async fn handle_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<SharedState>,
+    Query(params): Query<SessionQuery>,
+    request_headers: header::HeaderMap,
+    uri: axum::http::Uri,
+) -> Response {
+    let Some(session_id) = params.session_id else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let Some(notify_rx) = state.sessions.notify_rx(&session_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let token = presented_token(&request_headers, &uri);
+    ws.on_upgrade(move |socket| ws_session(socket, state, session_id, notify_rx, token))
+}
+
+/// What a socket's token is worth after a rotation.
+enum WsTokenCheck {
+    Valid,
+    /// Still accepted, but no longer the current token; holds the new one
+    Rotated(String),
+    Revoked,
+}
+
+fn check_ws_token(auth: &auth::SharedTokens, token: &str) -> WsTokenCheck {
+    let tokens = auth.read().unwrap_or_else(|e| e.into_inner());
+    match tokens.check(token) {
+        Some(TokenMatch::Current) => WsTokenCheck::Valid,
+        Some(TokenMatch::Previous) => WsTokenCheck::Rotated(tokens.current().to_string()),
+        None => WsTokenCheck::Revoked,
+    }
+}
+
+/// Serve one plugin WebSocket until either side closes it, the session is
+/// unregistered (which drops its notify channel) or the token it
+/// authenticated with stops being accepted.
+async fn ws_session(
+    mut socket: WebSocket,
+    state: SharedState,
+    session_id: String,
+    mut notify_rx: watch::Receiver<bool>,
+    mut token: Option<String>,
+) {
+    set_websocket(&state.sessions, &session_id, true);
+    tracing::info!("Session {} connected over WebSocket", session_id);
+    let mut ping = tokio::time::interval(WS_PING_INTERVAL);
+    'session: loop {
+        // Rotations wake the socket; an expiring grace period is caught by the ping
+        if let (Some(auth), Some(current)) = (&state.auth, token.as_mut()) {
+            match check_ws_token(auth, current) {
+                WsTokenCheck::Valid => {}
+                WsTokenCheck::Rotated(new) => {
+                    let frame = serde_json::json!({ "type": "token", "token": new });
+                    if socket
+                        .send(Message::Text(frame.to_string().into()))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                    *current = new;
+                }
+                WsTokenCheck::Revoked => {
+                    tracing::warn!(
+                        "Closing WebSocket of session {}: its token was revoked",
+                        session_id
+                    );
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+        }
+        // Push everything queued before waiting again
+        while let Some(request) = state.sessions.next_request(&session_id) {
+            let text = serde_json::to_string(&request).unwrap_or_default();
+            if socket.send(Message::Text(text.into())).await.is_err() {
+                // Leave it for the long-poll fallback
+                state
+                    .sessions
+                    .with(&session_id, |s| s.request_queue.push_front(request));
+                break 'session;
+            }
+        }
+        tokio::select! {
+            changed = notify_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    handle_ws_message(&state.sessions, &session_id, text.as_str());
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => state.sessions.heartbeat(&session_id),
+            },
+            _ = ping.tick() => {
+                if socket.send(Message::Ping(Default::default())).await.is_err() {
+                    break;
+                }
             }
         }
-        _ => Err(StatusCode::NO_CONTENT),
     }
+    set_websocket(&state.sessions, &session_id, false);
+    tracing::info!("Session {} WebSocket closed", session_id);
+}
+
+fn set_websocket(sessions: &Sessions, session_id: &str, connected: bool) {
+    sessions.with(session_id, |s| {
+        s.websocket = connected;
+        if connected {
+            s.last_heartbeat = std::time::Instant::now();
+        }
+    });
+}
+
+/// Apply one text frame from a plugin. Every frame counts as a heartbeat.
+fn handle_ws_message(sessions: &Sessions, session_id: &str, text: &str) {
+    sessions.heartbeat(session_id);
+    match serde_json::from_str::<WsMessage>(text) {
+        Ok(WsMessage::Response(response)) => {
+            let id = response.id.clone();
+            if !sessions.deliver_response(response) {
+                tracing::debug!("WebSocket response for unknown request {}", id);
+            }
+        }
+        Ok(WsMessage::Progress(frame)) => {
+            sessions.record_progress(frame);
+        }
+        Ok(WsMessage::Heartbeat) => {}
+        Err(e) => tracing::warn!(
+            "Ignoring malformed WebSocket message from {}: {}",
+            session_id,
+            e
+        ),
+    }
+}
+
+/// `X-Queue-Depth` on every /request response: requests still waiting for
+/// this session, so a slow plugin can pace itself.
+type QueueDepthHeader = [(&'static str, String); 1];
+
+fn queue_depth_header(depth: usize) -> QueueDepthHeader {
+    [("x-queue-depth", depth.to_string())]
 }
 
 /// POST /response — Plugin sends back command results
@@ -148,9 +624,50 @@ async fn handle_plugin_response(
     State(state): State<SharedState>,
     Json(response): Json<PluginResponse>,
 ) -> StatusCode {
-    let mut s = state.lock().await;
+    if state.sessions.deliver_response(response) {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
 
-    if s.deliver_response(response) {
+/// POST /response/chunk — Plugin sends one part of a response too large for
+/// a single /response body. The last part to arrive delivers the whole
+/// response to the waiting tool call.
+async fn handle_response_chunk(
+    State(state): State<SharedState>,
+    Json(chunk): Json<ResponseChunk>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    match state.sessions.deliver_response_chunk(chunk) {
+        Some(Ok(ChunkProgress::Waiting { received, total })) => Ok(Json(
+            serde_json::json!({ "received": received, "total": total, "complete": false }),
+        )),
+        Some(Ok(ChunkProgress::Complete(_))) => Ok(Json(serde_json::json!({ "complete": true }))),
+        Some(Err(e)) => Err((StatusCode::BAD_REQUEST, e)),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            "no tool call is waiting on this request id".into(),
+        )),
+    }
+}
+
+/// POST /progress — Plugin reports progress on a request it is still working
+/// on. Frames are attached to the eventual result as `progress`.
+async fn handle_progress(
+    State(state): State<SharedState>,
+    Json(frame): Json<ProgressFrame>,
+) -> StatusCode {
+    tracing::info!(
+        "Progress {}: {}/{} {}",
+        frame.request_id,
+        frame.current,
+        frame
+            .total
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| "?".into()),
+        frame.message.as_deref().unwrap_or("")
+    );
+    if state.sessions.record_progress(frame) {
         StatusCode::OK
     } else {
         StatusCode::NOT_FOUND
@@ -163,7 +680,68 @@ async fn handle_proxy_tool_call(
     State(state): State<SharedState>,
     Json(request): Json<PluginRequest>,
 ) -> Result<Json<PluginResponse>, StatusCode> {
-    let mut rx = {
+    let limit = std::time::Duration::from_secs(60);
+
+    if request.hops > crate::tools::MAX_PROXY_HOPS {
+        return Ok(Json(PluginResponse {
+            id: request.id,
+            success: false,
+            result: serde_json::Value::Null,
+            error: Some(format!(
+                "proxy loop detected: '{}' was forwarded {} times (max {}). \
+                 A StudioLink instance is proxying to itself or to an instance that proxies back; \
+                 check proxy_target in each instance's /health.",
+                request.tool,
+                request.hops,
+                crate::tools::MAX_PROXY_HOPS
+            )),
+        }));
+    }
+
+    let (relay_to, read_only) = {
+        let s = state.lock().await;
+        (s.proxy_mode.then(|| s.proxy_url.clone()), s.read_only)
+    };
+    // A read-only primary also refuses mutations forwarded by other instances
+    if let Some(refusal) = crate::tools::plugin_read_only_refusal(read_only, &request.tool) {
+        return Ok(Json(PluginResponse {
+            id: request.id,
+            success: false,
+            result: serde_json::Value::Null,
+            error: Some(refusal.to_string()),
+        }));
+    }
+    if let Some(proxy_url) = relay_to {
+        // This instance is itself a secondary: pass the call along
+        let relayed = crate::tools::send_via_proxy(
+            &state.app,
+            &proxy_url,
+            request.target_session.as_deref(),
+            &request.tool,
+            request.args,
+            limit,
+            request.hops,
+        )
+        .await;
+        return match relayed {
+            Ok(result) => Ok(Json(PluginResponse {
+                id: request.id,
+                success: true,
+                result,
+                error: None,
+            })),
+            Err(StudioLinkError::PluginNotConnected) => Err(StatusCode::SERVICE_UNAVAILABLE),
+            Err(StudioLinkError::RequestTimeout { .. }) => Err(StatusCode::GATEWAY_TIMEOUT),
+            Err(e) => Ok(Json(PluginResponse {
+                id: request.id,
+                success: false,
+                result: serde_json::Value::Null,
+                error: Some(e.to_string()),
+            })),
+        };
+    }
+
+    let resolved = {
         let mut s = state.lock().await;
 
         // Log routing on the primary side too so /debug/routing shows the
@@ -174,9 +752,17 @@ async fn handle_proxy_tool_call(
         // wins over active_session.
         let resolved: String = match request.target_session.as_deref() {
             Some(sid) => {
-                if !s.sessions.contains_key(sid) {
+                if !s.sessions.contains(sid) {
                     return Err(StatusCode::NOT_FOUND);
                 }
+                if let Some(msg) = s.sessions.unresponsive_error(sid) {
+                    return Ok(Json(PluginResponse {
+                        id: request.id,
+                        success: false,
+                        result: serde_json::Value::Null,
+                        error: Some(msg),
+                    }));
+                }
                 sid.to_string()
             }
             None => match s.active_session.clone() {
@@ -185,18 +771,48 @@ async fn handle_proxy_tool_call(
             },
         };
 
-        match s.queue_request_to_session(&resolved, &request.tool, request.args) {
-            Some((_id, rx)) => rx,
-            None => return Err(StatusCode::SERVICE_UNAVAILABLE),
+        if let Some(msg) = s
+            .sessions
+            .with(&resolved, |sess| sess.unsupported_tool_error(&request.tool))
+            .flatten()
+            .or_else(|| s.queue_full_error(&resolved))
+        {
+            return Ok(Json(PluginResponse {
+                id: request.id,
+                success: false,
+                result: serde_json::Value::Null,
+                error: Some(msg),
+            }));
         }
+        resolved
     };
 
-    // Wait for the plugin to respond (timeout: 60 seconds)
-    let timeout = tokio::time::timeout(std::time::Duration::from_secs(60), rx.recv()).await;
+    // Serialize exclusive run_code with calls from this server's own MCP client
+    let started = std::time::Instant::now();
+    let exec =
+        crate::tools::acquire_exec_lock(&state.app, &resolved, &request.tool, &request.args, limit)
+            .await
+            .map_err(|_| StatusCode::GATEWAY_TIMEOUT)?;
+
+    // Wait for the plugin to respond, within what the lock wait left of 60s
+    let remaining = limit.saturating_sub(started.elapsed());
+    let (id, mut rx) = state
+        .sessions
+        .queue_request(&resolved, &request.tool, request.args, remaining)
+        .ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let timeout = tokio::time::timeout(remaining, rx.recv()).await;
 
     match timeout {
-        Ok(Some(response)) => Ok(Json(response)),
-        _ => Err(StatusCode::GATEWAY_TIMEOUT),
+        Ok(Some(mut response)) => {
+            if let Some(waited) = exec.and_then(|(_, waited)| waited) {
+                response.result = crate::tools::annotate_lock_wait(response.result, waited);
+            }
+            Ok(Json(response))
+        }
+        _ => {
+            state.sessions.finish_in_flight(&id);
+            Err(StatusCode::GATEWAY_TIMEOUT)
+        }
     }
 }
 
@@ -211,8 +827,9 @@ async fn handle_switch_session(
         .unwrap_or("");
 
     let mut s = state.lock().await;
-    if s.switch_session(session_id) {
-        let info = s.get_active_session_info().cloned();
+    let conn = s.connection.clone();
+    if s.switch_session(&mut conn.lock(), session_id) {
+        let info = s.get_active_session_info();
         Json(serde_json::json!({
             "success": true,
             "message": format!("Switched to session: {}", session_id),
@@ -226,10 +843,47 @@ async fn handle_switch_session(
     }
 }
 
+/// POST /rename_session — Set a session's display name (used by proxy mode).
+/// The name is validated by the tool before it gets here.
+async fn handle_rename_session(
+    State(state): State<SharedState>,
+    Json(payload): Json<serde_json::Value>,
+) -> Json<serde_json::Value> {
+    let session_id = payload
+        .get("session_id")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let display_name = payload
+        .get("display_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .trim();
+
+    let mut s = state.lock().await;
+    if display_name.is_empty() {
+        return Json(serde_json::json!({
+            "success": false,
+            "message": "display_name must be a non-empty string",
+        }));
+    }
+    if s.rename_session(session_id, display_name) {
+        Json(serde_json::json!({
+            "success": true,
+            "session_id": session_id,
+            "display_name": display_name,
+        }))
+    } else {
+        Json(serde_json::json!({
+            "success": false,
+            "message": format!("Session '{}' not found.", session_id),
+        }))
+    }
+}
+
 /// GET /health — Check server and all session statuses
 async fn handle_health(State(state): State<SharedState>) -> Json<serde_json::Value> {
     let s = state.lock().await;
-    let session_count = s.sessions.len();
+    let session_count = state.sessions.len();
     let active = s.get_active_session().map(|s| s.to_string());
 
     Json(serde_json::json!({
@@ -238,6 +892,7 @@ async fn handle_health(State(state): State<SharedState>) -> Json<serde_json::Val
         "active_session": active,
         "connected_sessions": session_count,
         "plugin_connected": s.is_plugin_connected(),
+        "proxy_target": s.proxy_mode.then(|| s.proxy_url.clone()),
     }))
 }
 
@@ -252,3 +907,975 @@ async fn handle_debug_routing(State(state): State<SharedState>) -> Json<serde_js
         "note": "target_session=null means the call routed to active_session (default behavior). target_session=string means the MCP client passed an explicit session_id.",
     }))
 }
+
+/// GET /changes — Mutations on a session since a cursor (poll_changes).
+async fn handle_changes(
+    State(state): State<SharedState>,
+    Query(query): Query<ChangesQuery>,
+) -> Json<serde_json::Value> {
+    let wait_ms = query.wait_ms.min(crate::tools::session::MAX_CHANGE_WAIT_MS);
+    match crate::tools::session::changes_since(
+        &state.app,
+        query.session_id.as_deref(),
+        query.cursor,
+        wait_ms,
+    )
+    .await
+    {
+        Ok(result) => Json(result),
+        Err(e) => Json(serde_json::json!({ "error": e.to_string() })),
+    }
+}
+
+/// GET /sourcemap — Rojo-style sourcemap.json of the active session's place
+async fn handle_sourcemap(
+    State(state): State<SharedState>,
+    Query(query): Query<SourcemapQuery>,
+) -> Json<serde_json::Value> {
+    match crate::tools::sourcemap::generate(
+        &state.app,
+        query.dir.as_deref(),
+        query.include_non_scripts,
+    )
+    .await
+    {
+        Ok(sourcemap) => Json(sourcemap),
+        Err(e) => Json(serde_json::json!({ "error": e.to_string() })),
+    }
+}
+
+/// POST /cancel — Cancel pending requests (cancel_request from a proxy)
+async fn handle_cancel(
+    State(state): State<SharedState>,
+    Json(body): Json<CancelRequest>,
+) -> Json<serde_json::Value> {
+    match crate::tools::session::cancel_pending(
+        &state.app,
+        body.request_id.as_deref(),
+        body.tool.as_deref(),
+        body.session_id.as_deref(),
+    )
+    .await
+    {
+        Ok(result) => Json(result),
+        Err(e) => Json(serde_json::json!({ "error": e.to_string() })),
+    }
+}
+
+/// POST /stream/{stream_id} — Plugin pushes an NDJSON chunk (one JSON value
+/// per line) to a stream opened by a tool call. Lines are relayed as they
+/// arrive so the whole result never has to be assembled in memory.
+async fn handle_stream_chunk(
+    State(state): State<SharedState>,
+    Path(stream_id): Path<String>,
+    body: String,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    match state.sessions.push_stream_lines(&stream_id, &body) {
+        Some(lines) => Ok(Json(serde_json::json!({ "relayed": lines }))),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// GET /openapi.json — OpenAPI 3.1 document describing every HTTP route.
+async fn handle_openapi() -> Json<serde_json::Value> {
+    Json(openapi_document())
+}
+
+/// JSON Schema for a wire type, derived from its serde definition so the spec
+/// can't drift from what the handlers actually (de)serialize.
+fn component_schema<T: schemars::JsonSchema>() -> serde_json::Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(T)).unwrap_or_default();
+    if let Some(obj) = schema.as_object_mut() {
+        obj.remove("$schema");
+    }
+    schema
+}
+
+/// Build the OpenAPI document for the plugin/proxy HTTP API. Request and
+/// response bodies for register/request/response/progress/proxy reference the
+/// schemas generated from `SessionRegistration`, `PluginRequest`,
+/// `PluginResponse`, `ResponseChunk` and `ProgressFrame`.
+fn openapi_document() -> serde_json::Value {
+    let session_id_query = serde_json::json!({
+        "name": "session_id",
+        "in": "query",
+        "required": true,
+        "schema": { "type": "string" },
+        "description": "Session to poll for, as returned by /register",
+    });
+    let session_id_body = serde_json::json!({
+        "required": true,
+        "content": { "application/json": { "schema": {
+            "type": "object",
+            "required": ["session_id"],
+            "properties": { "session_id": { "type": "string" } },
+        }}},
+    });
+    let json_object = serde_json::json!({
+        "content": { "application/json": { "schema": { "type": "object" } } },
+    });
+    let body_ref = |name: &str| {
+        serde_json::json!({
+            "required": true,
+            "content": { "application/json": { "schema": {
+                "$ref": format!("#/components/schemas/{}", name)
+            }}},
+        })
+    };
+    let response_ref = |description: &str, name: &str| {
+        serde_json::json!({
+            "description": description,
+            "content": { "application/json": { "schema": {
+                "$ref": format!("#/components/schemas/{}", name)
+            }}},
+        })
+    };
+    let with_description = |description: &str| {
+        let mut r = json_object.clone();
+        r["description"] = description.into();
+        r
+    };
+
+    serde_json::json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "StudioLink plugin HTTP API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Routes used by the Roblox Studio plugin (register/poll/respond) and by secondary StudioLink instances in proxy mode. Unless the server runs with --no-auth, every route except /health and /openapi.json needs Authorization: Bearer <token> and answers 401 without it.",
+        },
+        "servers": [{ "url": "http://127.0.0.1:34872" }],
+        "security": [{ "bearerAuth": [] }],
+        "paths": {
+            "/register": { "post": {
+                "summary": "Register a Studio session",
+                "requestBody": body_ref("SessionRegistration"),
+                "responses": {
+                    "200": with_description("Registered; body is {status, session_id}"),
+                },
+            }},
+            "/unregister": { "post": {
+                "summary": "Unregister a Studio session",
+                "requestBody": session_id_body.clone(),
+                "responses": { "200": { "description": "Session removed (no-op if unknown)" } },
+            }},
+            "/sessions": { "get": {
+                "summary": "List connected sessions",
+                "responses": { "200": with_description("{sessions, active_session, count}; each session has an endpoint {base_url, server_role, session_id, poll_url, tool_call_url, tool_call_body}") },
+            }},
+            "/request": { "get": {
+                "summary": "Long-poll for the next tool request (30s)",
+                "description": "Every response carries an X-Queue-Depth header with the requests still queued for the session (also queue_depth in the body). Once a session has --max-queue-depth requests queued, new tool calls for it are rejected with a retry hint.",
+                "parameters": [session_id_query],
+                "responses": {
+                    "200": response_ref("Next queued request", "PluginRequest"),
+                    "204": { "description": "Long poll timed out with nothing queued" },
+                    "400": { "description": "Missing session_id" },
+                    "404": { "description": "Unknown session; the plugin should re-register" },
+                },
+            }},
+            "/response": { "post": {
+                "summary": "Deliver a tool result",
+                "requestBody": body_ref("PluginResponse"),
+                "responses": {
+                    "200": { "description": "Delivered to the waiting tool call" },
+                    "404": { "description": "No tool call is waiting on this id (timed out or unknown)" },
+                },
+            }},
+            "/response/chunk": { "post": {
+                "summary": "Deliver one part of a large tool result",
+                "description": "For results whose encoded PluginResponse is too large for one /response body. The plugin splits the JSON text into `total` parts and posts each with its 0-based `index`, in any order; resent parts replace earlier copies. When the last part arrives the text is decoded and delivered like a /response body. A response that doesn't decode fails the waiting call.",
+                "requestBody": body_ref("ResponseChunk"),
+                "responses": {
+                    "200": with_description("{received, total, complete: false} while parts are missing, {complete: true} once delivered"),
+                    "400": { "description": "Part doesn't fit the response (index out of range, total changed, over 256 MiB); the partial response is dropped" },
+                    "404": { "description": "No tool call is waiting on this id (timed out or unknown)" },
+                },
+            }},
+            "/progress": { "post": {
+                "summary": "Report progress on an in-flight request",
+                "requestBody": body_ref("ProgressFrame"),
+                "responses": {
+                    "200": { "description": "Recorded; attached to the result as `progress`" },
+                    "404": { "description": "No tool call is waiting on this request id" },
+                },
+            }},
+            "/ws": { "get": {
+                "summary": "WebSocket push transport (alternative to /request long-polling)",
+                "description": "Upgrade to a WebSocket. The server sends each queued request as a text frame holding a PluginRequest; the plugin sends back text frames tagged by type: {\"type\": \"response\", ...PluginResponse}, {\"type\": \"progress\", ...ProgressFrame} or {\"type\": \"heartbeat\"}. Every frame counts as a heartbeat. After a token rotation the server sends {\"type\": \"token\", \"token\": ...} with the new token, and it closes the socket once the token it was opened with (or last sent) is revoked. If the socket drops, /request and /response keep working.",
+                "parameters": [session_id_query, {
+                    "name": "token",
+                    "in": "query",
+                    "schema": { "type": "string" },
+                    "description": "Bearer token, for WebSocket clients that can't send an Authorization header",
+                }],
+                "responses": {
+                    "101": { "description": "Switching protocols" },
+                    "400": { "description": "Missing session_id" },
+                    "404": { "description": "Unknown session; the plugin should re-register" },
+                },
+            }},
+            "/proxy/tool_call": { "post": {
+                "summary": "Forward a tool call from a secondary StudioLink instance",
+                "requestBody": body_ref("PluginRequest"),
+                "responses": {
+                    "200": response_ref("Plugin result", "PluginResponse"),
+                    "404": { "description": "target_session not found" },
+                    "503": { "description": "No active session or queueing failed" },
+                    "504": { "description": "Plugin did not respond within 60s" },
+                },
+            }},
+            "/switch_session": { "post": {
+                "summary": "Switch the active session",
+                "requestBody": session_id_body,
+                "responses": { "200": with_description("{success, message, place_name?}") },
+            }},
+            "/rename_session": { "post": {
+                "summary": "Set a session's display name (persisted by client_id)",
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "required": ["session_id", "display_name"],
+                        "properties": {
+                            "session_id": { "type": "string" },
+                            "display_name": { "type": "string" },
+                        },
+                    }}},
+                },
+                "responses": { "200": with_description("{success, session_id?, display_name?, message?}") },
+            }},
+            "/token/rotate": { "post": {
+                "summary": "Replace the bearer token",
+                "description": "The previous token keeps working for grace_secs; responses to requests made with it carry the new token in an X-StudioLink-Token header. The new token is also written to ~/.studiolink/token.",
+                "requestBody": {
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "properties": {
+                            "grace_secs": { "type": "integer", "description": "Default 60, max 3600; 0 revokes the old token immediately" },
+                        },
+                    }}},
+                },
+                "responses": {
+                    "200": with_description("{token, previous_valid_secs}"),
+                    "400": { "description": "grace_secs too large" },
+                    "404": { "description": "Authentication is disabled" },
+                },
+            }},
+            "/health": { "get": {
+                "summary": "Server and session status",
+                "security": [],
+                "responses": { "200": with_description("{server, version, active_session, connected_sessions, plugin_connected}") },
+            }},
+            "/debug/routing": { "get": {
+                "summary": "Last 50 tool dispatches with their target_session",
+                "responses": { "200": with_description("{count, entries, note}") },
+            }},
+            "/changes": { "get": {
+                "summary": "Mutations on a session since a cursor (poll_changes)",
+                "parameters": [
+                    { "name": "session_id", "in": "query", "schema": { "type": "string" }, "description": "Defaults to the active session" },
+                    { "name": "cursor", "in": "query", "schema": { "type": "integer" }, "description": "Cursor from the previous call (0 the first time)" },
+                    { "name": "wait_ms", "in": "query", "schema": { "type": "integer" }, "description": "Wait up to this long for a change (max 30000)" },
+                ],
+                "responses": { "200": with_description("{session_id, cursor, changes, missed} or {error}") },
+            }},
+            "/sourcemap": { "get": {
+                "summary": "Rojo-style sourcemap.json of the active session's place (sourcemap_generate)",
+                "description": "Scripts and their ancestors as {name, className, filePaths?, children?} nodes, rooted at a DataModel node, for luau-lsp and other tooling that resolves requires.",
+                "parameters": [
+                    { "name": "dir", "in": "query", "schema": { "type": "string" }, "description": "sync_export directory whose files fill in filePaths" },
+                    { "name": "include_non_scripts", "in": "query", "schema": { "type": "boolean" }, "description": "Keep instances with no script below them" },
+                ],
+                "responses": { "200": with_description("The sourcemap, or {error}") },
+            }},
+            "/cancel": { "post": {
+                "summary": "Cancel pending tool requests (cancel_request)",
+                "description": "Cancels one request by request_id, or every pending request for tool (optionally on session_id). Queued requests are dropped; running ones get a cancel_request message ({\"tool\": \"cancel_request\", \"args\": {\"requestId\": ...}}) ahead of the queue, which the plugin never answers. With neither field, only lists pending requests.",
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "properties": {
+                            "request_id": { "type": "string" },
+                            "tool": { "type": "string" },
+                            "session_id": { "type": "string" },
+                        },
+                    }}},
+                },
+                "responses": { "200": with_description("{cancelled, count, pending} or {error}") },
+            }},
+            "/stream/{stream_id}": { "post": {
+                "summary": "Push an NDJSON chunk to an open tool stream",
+                "parameters": [{
+                    "name": "stream_id",
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                    "description": "Stream id passed to the plugin in the tool args",
+                }],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/x-ndjson": { "schema": { "type": "string" } } },
+                },
+                "responses": {
+                    "200": with_description("{relayed}: number of lines forwarded"),
+                    "404": { "description": "Unknown or already-closed stream" },
+                },
+            }},
+            "/openapi.json": { "get": {
+                "summary": "This document",
+                "security": [],
+                "responses": { "200": with_description("OpenAPI 3.1 document") },
+            }},
+        },
+        "components": {
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer" },
+            },
+            "schemas": {
+                "SessionRegistration": component_schema::<SessionRegistration>(),
+                "PluginRequest": component_schema::<PluginRequest>(),
+                "PluginResponse": component_schema::<PluginResponse>(),
+                "ProgressFrame": component_schema::<ProgressFrame>(),
+                "ResponseChunk": component_schema::<ResponseChunk>(),
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openapi_schemas_follow_serde_types() {
+        let doc = openapi_document();
+        let reg = &doc["components"]["schemas"]["SessionRegistration"];
+        let required: Vec<&str> = reg["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        for field in ["session_id", "place_id", "place_name", "game_id"] {
+            assert!(required.contains(&field), "missing required {}", field);
+        }
+        assert!(reg.get("$schema").is_none());
+        assert!(doc["components"]["schemas"]["PluginRequest"]["properties"]
+            .get("target_session")
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn server_stops_only_after_shutdown_trigger() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (state, notify_rx) = AppState::new();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(serve(
+            listener,
+            create_router(state, notify_rx).await,
+            async move {
+                let _ = shutdown_rx.await;
+            },
+        ));
+
+        let client = reqwest::Client::new();
+        let health = format!("http://{}/health", addr);
+        assert!(client
+            .get(&health)
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .is_success());
+        assert!(!task.is_finished());
+
+        shutdown_tx.send(()).unwrap();
+        tokio::time::timeout(SHUTDOWN_GRACE * 2, task)
+            .await
+            .expect("server did not stop after shutdown")
+            .unwrap()
+            .unwrap();
+        assert!(client.get(&health).send().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn self_referential_proxy_fails_fast() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (state, notify_rx) = AppState::new();
+        {
+            let mut s = state.lock().await;
+            s.proxy_mode = true;
+            s.proxy_url = url.clone();
+        }
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(serve(
+            listener,
+            create_router(state.clone(), notify_rx).await,
+            async move {
+                let _ = shutdown_rx.await;
+            },
+        ));
+
+        let health: serde_json::Value = reqwest::get(format!("{}/health", url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(health["proxy_target"], url.as_str());
+
+        let started = std::time::Instant::now();
+        let err = crate::tools::send_to_plugin(
+            &state,
+            None,
+            "get_studio_mode",
+            serde_json::json!({}),
+            std::time::Duration::from_secs(30),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("proxy loop detected"), "{}", err);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        shutdown_tx.send(()).unwrap();
+        let _ = task.await;
+    }
+
+    #[tokio::test]
+    async fn bearer_token_guards_plugin_routes() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (state, notify_rx) = AppState::new();
+        let token = "t".repeat(32);
+        state.lock().await.auth = Some(Arc::new(std::sync::RwLock::new(auth::AuthTokens::new(
+            token.clone(),
+            None,
+        ))));
+        tokio::spawn(serve(
+            listener,
+            create_router(state.clone(), notify_rx).await,
+            std::future::pending(),
+        ));
+
+        let client = reqwest::Client::new();
+        let sessions = format!("{}/sessions", url);
+        let missing = client.get(&sessions).send().await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let wrong = client.get(&sessions).bearer_auth("x".repeat(32)).send();
+        assert_eq!(
+            wrong.await.unwrap().status(),
+            reqwest::StatusCode::UNAUTHORIZED
+        );
+        let ok = client
+            .get(&sessions)
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert!(ok.status().is_success());
+        // Health stays open for instance detection
+        let health = client.get(format!("{}/health", url)).send().await.unwrap();
+        assert!(health.status().is_success());
+
+        let rotated: serde_json::Value = client
+            .post(format!("{}/token/rotate", url))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "grace_secs": 30 }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let new_token = rotated["token"].as_str().unwrap();
+        assert_ne!(new_token, token);
+
+        // The old token still works during the grace period and learns the new one
+        let old = client
+            .get(&sessions)
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert!(old.status().is_success());
+        assert_eq!(
+            old.headers()[auth::ROTATED_TOKEN_HEADER].to_str().unwrap(),
+            new_token
+        );
+        let new = client
+            .get(&sessions)
+            .bearer_auth(new_token)
+            .send()
+            .await
+            .unwrap();
+        assert!(new.status().is_success());
+        assert!(!new.headers().contains_key(auth::ROTATED_TOKEN_HEADER));
+    }
+
+    #[tokio::test]
+    async fn stream_chunks_are_accepted_with_the_token() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (state, notify_rx) = AppState::new();
+        let token = "t".repeat(32);
+        let (stream_id, mut lines) = {
+            let mut s = state.lock().await;
+            s.auth = Some(Arc::new(std::sync::RwLock::new(auth::AuthTokens::new(
+                token.clone(),
+                None,
+            ))));
+            s.sessions.open_stream()
+        };
+        tokio::spawn(serve(
+            listener,
+            create_router(state.clone(), notify_rx).await,
+            std::future::pending(),
+        ));
+
+        let client = reqwest::Client::new();
+        let stream_url = format!("{}/stream/{}", url, stream_id);
+        let body = "{\"id\":1}\n{\"id\":2}";
+        let missing = client.post(&stream_url).body(body).send().await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let relayed: serde_json::Value = client
+            .post(&stream_url)
+            .bearer_auth(&token)
+            .header("Content-Type", "application/x-ndjson")
+            .body(body)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(relayed["relayed"], 2);
+        assert_eq!(lines.recv().await.unwrap(), "{\"id\":1}");
+        assert_eq!(lines.recv().await.unwrap(), "{\"id\":2}");
+    }
+
+    #[tokio::test]
+    async fn gzip_in_both_directions() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (state, notify_rx) = AppState::new();
+        tokio::spawn(serve(
+            listener,
+            create_router(state.clone(), notify_rx).await,
+            std::future::pending(),
+        ));
+        let mut rx = {
+            let mut s = state.lock().await;
+            s.register_session(SessionRegistration {
+                session_id: "a".into(),
+                place_id: 1,
+                place_name: "Place".into(),
+                game_id: 1,
+                client_id: None,
+                supported_tools: None,
+                plugin_version: None,
+            });
+            let source = "print('hello')\n".repeat(2000);
+            s.sessions
+                .queue_request(
+                    "a",
+                    "set_script_source",
+                    serde_json::json!({ "source": source }),
+                    std::time::Duration::from_secs(30),
+                )
+                .unwrap()
+                .1
+        };
+
+        let client = reqwest::Client::new();
+        let polled = client
+            .get(format!("{}/request?session_id=a", url))
+            .header("accept-encoding", "gzip")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(polled.headers()["content-encoding"], "gzip");
+        let compressed = polled.bytes().await.unwrap();
+        assert!(compressed.len() < 2000);
+        let request: PluginRequest = serde_json::from_slice(&gunzip(&compressed).unwrap()).unwrap();
+
+        let response =
+            serde_json::json!({ "id": request.id, "success": true, "result": { "ok": true } });
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(response.to_string().as_bytes()).unwrap();
+        let sent = client
+            .post(format!("{}/response", url))
+            .header("content-type", "application/json")
+            .header("content-encoding", "gzip")
+            .body(encoder.finish().unwrap())
+            .send()
+            .await
+            .unwrap();
+        assert!(sent.status().is_success());
+        assert_eq!(rx.recv().await.unwrap().result["ok"], true);
+
+        let garbage = client
+            .post(format!("{}/response", url))
+            .header("content-encoding", "gzip")
+            .body("not gzip")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(garbage.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn openapi_refs_resolve() {
+        fn collect_refs<'a>(value: &'a serde_json::Value, refs: &mut Vec<&'a str>) {
+            match value {
+                serde_json::Value::Object(map) => {
+                    for (key, v) in map {
+                        match (key.as_str(), v.as_str()) {
+                            ("$ref", Some(r)) => refs.push(r),
+                            _ => collect_refs(v, refs),
+                        }
+                    }
+                }
+                serde_json::Value::Array(items) => {
+                    items.iter().for_each(|v| collect_refs(v, refs));
+                }
+                _ => {}
+            }
+        }
+
+        let doc = openapi_document();
+        let mut refs = Vec::new();
+        collect_refs(&doc["paths"], &mut refs);
+        for name in [
+            "SessionRegistration",
+            "PluginRequest",
+            "PluginResponse",
+            "ProgressFrame",
+            "ResponseChunk",
+        ] {
+            let target = format!("#/components/schemas/{}", name);
+            assert!(refs.contains(&target.as_str()), "no $ref to {}", name);
+        }
+        for r in refs {
+            let name = r
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("{} is not under components/schemas", r));
+            assert!(
+                doc["components"]["schemas"].get(name).is_some(),
+                "{} doesn't resolve",
+                r
+            );
+        }
+        assert!(doc["paths"]["/request"]["get"]["responses"]
+            .get("204")
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn websocket_pushes_requests_and_takes_responses() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as WsFrame;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (state, notify_rx) = AppState::new();
+        state.lock().await.register_session(SessionRegistration {
+            session_id: "ws1".into(),
+            place_id: 1,
+            place_name: "Place".into(),
+            game_id: 0,
+            client_id: None,
+            supported_tools: None,
+            plugin_version: None,
+        });
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(serve(
+            listener,
+            create_router(state.clone(), notify_rx).await,
+            async move {
+                let _ = shutdown_rx.await;
+            },
+        ));
+
+        let unknown =
+            tokio_tungstenite::connect_async(format!("ws://{}/ws?session_id=nope", addr)).await;
+        assert!(unknown.is_err());
+
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/ws?session_id=ws1", addr))
+                .await
+                .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(state
+            .lock()
+            .await
+            .sessions
+            .with("ws1", |s| s.websocket)
+            .unwrap());
+
+        let call = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                crate::tools::send_to_plugin(
+                    &state,
+                    Some("ws1"),
+                    "get_studio_mode",
+                    serde_json::json!({}),
+                    std::time::Duration::from_secs(5),
+                )
+                .await
+            })
+        };
+        let request: PluginRequest = loop {
+            match socket.next().await.unwrap().unwrap() {
+                WsFrame::Text(text) => break serde_json::from_str(text.as_str()).unwrap(),
+                _ => continue,
+            }
+        };
+        assert_eq!(request.tool, "get_studio_mode");
+
+        let reply = serde_json::json!({
+            "type": "response",
+            "id": request.id,
+            "success": true,
+            "result": "stop",
+        });
+        socket
+            .send(WsFrame::Text(reply.to_string().into()))
+            .await
+            .unwrap();
+        assert_eq!(call.await.unwrap().unwrap(), "stop");
+
+        socket.close(None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!state
+            .lock()
+            .await
+            .sessions
+            .with("ws1", |s| s.websocket)
+            .unwrap());
+
+        shutdown_tx.send(()).unwrap();
+        let _ = task.await;
+    }
+
+    #[tokio::test]
+    async fn plugin_traffic_bypasses_the_app_lock() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (state, notify_rx) = AppState::new();
+        let sessions = {
+            let mut s = state.lock().await;
+            s.register_session(SessionRegistration {
+                session_id: "s1".into(),
+                place_id: 1,
+                place_name: "Place".into(),
+                game_id: 0,
+                client_id: None,
+                supported_tools: None,
+                plugin_version: None,
+            });
+            s.sessions.clone()
+        };
+        tokio::spawn(serve(
+            listener,
+            create_router(state.clone(), notify_rx).await,
+            std::future::pending(),
+        ));
+        let (id, mut rx) = sessions
+            .queue_request(
+                "s1",
+                "get_studio_mode",
+                serde_json::json!({}),
+                std::time::Duration::from_secs(30),
+            )
+            .unwrap();
+
+        // A tool call holding AppState must not stall the plugin's poll and reply
+        let _held = state.lock().await;
+        let client = reqwest::Client::new();
+        let request: PluginRequest = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            client.get(format!("{}/request?session_id=s1", url)).send(),
+        )
+        .await
+        .expect("poll waited on the AppState lock")
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+        assert_eq!(request.id, id);
+        let reply = client
+            .post(format!("{}/response", url))
+            .json(&PluginResponse {
+                id: id.clone(),
+                success: true,
+                result: serde_json::json!("stop"),
+                error: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert!(reply.status().is_success());
+        assert_eq!(rx.recv().await.unwrap().result, "stop");
+    }
+
+    #[tokio::test]
+    async fn proxied_requests_can_be_cancelled() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (state, notify_rx) = AppState::new();
+        state.lock().await.register_session(SessionRegistration {
+            session_id: "s1".into(),
+            place_id: 1,
+            place_name: "Place".into(),
+            game_id: 0,
+            client_id: None,
+            supported_tools: None,
+            plugin_version: None,
+        });
+        tokio::spawn(serve(
+            listener,
+            create_router(state.clone(), notify_rx).await,
+            std::future::pending(),
+        ));
+
+        let client = reqwest::Client::new();
+        let call = tokio::spawn(
+            client
+                .post(format!("{}/proxy/tool_call", url))
+                .json(&serde_json::json!({
+                    "id": "secondary-1",
+                    "tool": "workspace_analyze",
+                    "args": {},
+                    "target_session": "s1",
+                }))
+                .send(),
+        );
+
+        // The forwarded call shows up in the pending list like a local one
+        let mut pending = serde_json::Value::Null;
+        for _ in 0..50 {
+            let listed: serde_json::Value = client
+                .post(format!("{}/cancel", url))
+                .json(&serde_json::json!({}))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            if listed["pending"].as_array().is_some_and(|p| !p.is_empty()) {
+                pending = listed["pending"][0].clone();
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(pending["tool"], "workspace_analyze");
+        assert_eq!(pending["session_id"], "s1");
+
+        let cancelled: serde_json::Value = client
+            .post(format!("{}/cancel", url))
+            .json(&serde_json::json!({ "request_id": pending["request_id"] }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(cancelled["count"], 1, "{}", cancelled);
+
+        let response: PluginResponse = call.await.unwrap().unwrap().json().await.unwrap();
+        assert!(!response.success);
+        assert!(response.error.unwrap().contains("cancelled"));
+    }
+
+    #[tokio::test]
+    async fn websocket_follows_token_rotation_and_revocation() {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::Message as WsFrame;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (state, notify_rx) = AppState::new();
+        let token = "t".repeat(32);
+        {
+            let mut s = state.lock().await;
+            s.auth = Some(Arc::new(std::sync::RwLock::new(auth::AuthTokens::new(
+                token.clone(),
+                None,
+            ))));
+            s.register_session(SessionRegistration {
+                session_id: "ws1".into(),
+                place_id: 1,
+                place_name: "Place".into(),
+                game_id: 0,
+                client_id: None,
+                supported_tools: None,
+                plugin_version: None,
+            });
+        }
+        tokio::spawn(serve(
+            listener,
+            create_router(state.clone(), notify_rx).await,
+            std::future::pending(),
+        ));
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!(
+            "ws://{}/ws?session_id=ws1&token={}",
+            addr, token
+        ))
+        .await
+        .unwrap();
+
+        let client = reqwest::Client::new();
+        let rotate = |bearer: String, grace_secs: u64| {
+            let request = client
+                .post(format!("http://{}/token/rotate", addr))
+                .bearer_auth(bearer)
+                .json(&serde_json::json!({ "grace_secs": grace_secs }));
+            async move {
+                let body: serde_json::Value = request.send().await.unwrap().json().await.unwrap();
+                body["token"].as_str().unwrap().to_string()
+            }
+        };
+        let wait = std::time::Duration::from_secs(5);
+
+        // A rotation with a grace period hands the socket the new token
+        let rotated = rotate(token.clone(), 60).await;
+        let pushed: serde_json::Value = loop {
+            match tokio::time::timeout(wait, socket.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap()
+            {
+                WsFrame::Text(text) => break serde_json::from_str(text.as_str()).unwrap(),
+                _ => continue,
+            }
+        };
+        assert_eq!(pushed["type"], "token");
+        assert_eq!(pushed["token"], rotated.as_str());
+
+        // Revoking the token the socket now holds closes it
+        rotate(rotated, 0).await;
+        loop {
+            match tokio::time::timeout(wait, socket.next()).await.unwrap() {
+                Some(Ok(WsFrame::Close(_))) | None | Some(Err(_)) => break,
+                Some(Ok(_)) => continue,
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!state
+            .lock()
+            .await
+            .sessions
+            .with("ws1", |s| s.websocket)
+            .unwrap());
+    }
+}
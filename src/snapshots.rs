@@ -0,0 +1,494 @@
+//! On-disk snapshot store.
+//!
+//! Snapshots are stored content-addressed: every node body (class, properties,
+//! script source, attributes) is written once as a blob named by its SHA-256,
+//! and a snapshot is just a manifest mapping instance paths to blob hashes.
+//! Repeated snapshots of a mostly-unchanged place therefore share nearly all
+//! blobs, and two snapshots can be diffed by comparing hashes alone.
+//!
+//! Layout under the store root:
+//!   blobs/<first 2 hex chars>/<sha256>.json.gz
+//!   manifests/<sanitized name>.json.gz
+//!
+//! Files are gzip-compressed JSON; hashes are taken over the uncompressed
+//! JSON. Stores written before compression hold plain `.json` files, which
+//! are still read, and new saves share blobs with them.
+//!
+//! To move a snapshot between machines it is exported as one self-contained
+//! JSON file (`SnapshotExport`) with every node body inlined, and imported
+//! back through `save`, which re-deduplicates against the local blobs.
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Bump when the manifest layout changes incompatibly.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// A stored snapshot: instance path → blob hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub name: String,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub place_id: Option<u64>,
+    pub entries: BTreeMap<String, String>,
+}
+
+/// Bump when the export file layout changes incompatibly.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Marker identifying a snapshot export file.
+const EXPORT_FORMAT: &str = "studiolink-snapshot";
+
+/// A snapshot as a single portable file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotExport {
+    pub format: String,
+    pub version: u32,
+    pub name: String,
+    pub timestamp: u64,
+    pub place_id: Option<u64>,
+    /// Root the snapshot covers; snapshot_take always captures the DataModel.
+    pub scope: String,
+    pub nodes: serde_json::Map<String, serde_json::Value>,
+}
+
+impl SnapshotExport {
+    /// Parse an export file, rejecting other files and newer versions.
+    pub fn parse(bytes: &[u8]) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let header: serde_json::Value = serde_json::from_slice(bytes)
+            .map_err(|e| invalid(format!("not a snapshot export: {}", e)))?;
+        if header["format"] != EXPORT_FORMAT {
+            return Err(invalid(
+                "not a snapshot export (missing format marker)".into(),
+            ));
+        }
+        let version = header["version"].as_u64().unwrap_or(0);
+        if version > u64::from(EXPORT_VERSION) {
+            return Err(invalid(format!(
+                "snapshot export version {} is newer than this build reads ({})",
+                version, EXPORT_VERSION
+            )));
+        }
+        serde_json::from_value(header)
+            .map_err(|e| invalid(format!("malformed snapshot export: {}", e)))
+    }
+}
+
+/// What a save cost on disk versus what deduplication avoided writing.
+/// `bytes_written` is compressed size on disk; `bytes_saved` counts the
+/// uncompressed JSON of shared blobs.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveStats {
+    pub nodes: usize,
+    pub blobs_written: usize,
+    pub blobs_shared: usize,
+    pub bytes_written: u64,
+    pub bytes_saved: u64,
+    /// Uncompressed size of what was written
+    pub bytes_uncompressed: u64,
+}
+
+/// Paths that differ between two manifests, found by hash comparison.
+#[derive(Debug, Default, Serialize)]
+pub struct ManifestDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Default location: ~/.studiolink/snapshots
+    pub fn default_root() -> Option<PathBuf> {
+        std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(".studiolink").join("snapshots"))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.root
+            .join("blobs")
+            .join(&hash[..2])
+            .join(format!("{}.json.gz", hash))
+    }
+
+    fn manifest_path(&self, name: &str) -> PathBuf {
+        self.root
+            .join("manifests")
+            .join(format!("{}.json.gz", sanitize_name(name)))
+    }
+
+    /// The compressed file if present, else an uncompressed one from before
+    /// compression.
+    fn existing(path: PathBuf) -> Option<PathBuf> {
+        if path.exists() {
+            return Some(path);
+        }
+        let legacy = path.with_extension("");
+        legacy.exists().then_some(legacy)
+    }
+
+    /// Store a snapshot. `nodes` maps instance path → node body; each body is
+    /// written as a blob unless an identical one already exists.
+    pub fn save(
+        &self,
+        name: &str,
+        timestamp: u64,
+        place_id: Option<u64>,
+        nodes: &serde_json::Map<String, serde_json::Value>,
+    ) -> io::Result<SaveStats> {
+        let mut stats = SaveStats {
+            nodes: nodes.len(),
+            ..Default::default()
+        };
+        let mut entries = BTreeMap::new();
+
+        for (path, body) in nodes {
+            // serde_json maps are key-sorted, so equal bodies serialize equally
+            let bytes = serde_json::to_vec(body)?;
+            let hash = hash_blob(&bytes);
+            let blob_path = self.blob_path(&hash);
+            if Self::existing(blob_path.clone()).is_some() {
+                stats.blobs_shared += 1;
+                stats.bytes_saved += bytes.len() as u64;
+            } else {
+                stats.bytes_written += write_compressed(&blob_path, &bytes)?;
+                stats.bytes_uncompressed += bytes.len() as u64;
+                stats.blobs_written += 1;
+            }
+            entries.insert(path.clone(), hash);
+        }
+
+        let manifest = Manifest {
+            version: MANIFEST_VERSION,
+            name: name.to_string(),
+            timestamp,
+            place_id,
+            entries,
+        };
+        let manifest_path = self.manifest_path(name);
+        let bytes = serde_json::to_vec(&manifest)?;
+        stats.bytes_written += write_compressed(&manifest_path, &bytes)?;
+        stats.bytes_uncompressed += bytes.len() as u64;
+        // A same-named snapshot from before compression would shadow nothing
+        // but still show up in list(); replace it
+        let legacy = manifest_path.with_extension("");
+        if legacy.exists() {
+            std::fs::remove_file(legacy)?;
+        }
+        Ok(stats)
+    }
+
+    /// Load a manifest by snapshot name; Ok(None) if it was never stored.
+    pub fn load_manifest(&self, name: &str) -> io::Result<Option<Manifest>> {
+        let Some(path) = Self::existing(self.manifest_path(name)) else {
+            return Ok(None);
+        };
+        let manifest: Manifest = serde_json::from_slice(&read_json_file(&path)?)?;
+        if manifest.version > MANIFEST_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "snapshot '{}' uses manifest version {} (this build reads up to {})",
+                    name, manifest.version, MANIFEST_VERSION
+                ),
+            ));
+        }
+        Ok(Some(manifest))
+    }
+
+    pub fn load_blob(&self, hash: &str) -> io::Result<serde_json::Value> {
+        let path = Self::existing(self.blob_path(hash)).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("missing blob {}", hash))
+        })?;
+        Ok(serde_json::from_slice(&read_json_file(&path)?)?)
+    }
+
+    /// A stored snapshot with its node bodies inlined; Ok(None) if unknown.
+    pub fn export(&self, name: &str) -> io::Result<Option<SnapshotExport>> {
+        let Some(manifest) = self.load_manifest(name)? else {
+            return Ok(None);
+        };
+        let mut nodes = serde_json::Map::new();
+        for (path, hash) in &manifest.entries {
+            nodes.insert(path.clone(), self.load_blob(hash)?);
+        }
+        Ok(Some(SnapshotExport {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            name: manifest.name,
+            timestamp: manifest.timestamp,
+            place_id: manifest.place_id,
+            scope: "game".to_string(),
+            nodes,
+        }))
+    }
+
+    /// All stored manifests, oldest first. Unreadable files are skipped.
+    pub fn list(&self) -> io::Result<Vec<Manifest>> {
+        let dir = self.root.join("manifests");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut manifests: Vec<Manifest> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| read_json_file(&entry.path()).ok())
+            .filter_map(|bytes| serde_json::from_slice(&bytes).ok())
+            .collect();
+        manifests.sort_by_key(|m| m.timestamp);
+        Ok(manifests)
+    }
+}
+
+/// Write `bytes` gzip-compressed, creating parent directories; returns the
+/// compressed size. Goes through a temporary file so an interrupted write
+/// never leaves a truncated blob behind under its final name.
+fn write_compressed(path: &Path, bytes: &[u8]) -> io::Result<u64> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    let compressed = encoder.finish()?;
+    let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp, &compressed)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(compressed.len() as u64)
+}
+
+/// Read a store file, decompressing it if it is gzip.
+fn read_json_file(path: &Path) -> io::Result<Vec<u8>> {
+    let bytes = std::fs::read(path)?;
+    if !bytes.starts_with(&[0x1f, 0x8b]) {
+        return Ok(bytes);
+    }
+    let mut json = Vec::new();
+    GzDecoder::new(bytes.as_slice()).read_to_end(&mut json)?;
+    Ok(json)
+}
+
+/// Hex SHA-256 of a blob's bytes.
+pub fn hash_blob(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Diff two manifests without reading any blobs.
+pub fn diff_manifests(a: &Manifest, b: &Manifest) -> ManifestDiff {
+    let mut diff = ManifestDiff::default();
+    for (path, hash_b) in &b.entries {
+        match a.entries.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(hash_a) if hash_a != hash_b => diff.changed.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    for path in a.entries.keys() {
+        if !b.entries.contains_key(path) {
+            diff.removed.push(path.clone());
+        }
+    }
+    diff
+}
+
+/// Snapshot names become file names; keep them to a safe character set.
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_store() -> SnapshotStore {
+        SnapshotStore::new(
+            std::env::temp_dir().join(format!("studiolink-snapshots-{}", uuid::Uuid::new_v4())),
+        )
+    }
+
+    fn nodes(value: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn repeated_snapshots_share_blobs() {
+        let store = temp_store();
+        let a = nodes(json!({
+            "/Workspace": { "ClassName": "Workspace" },
+            "/Workspace/Part": { "ClassName": "Part", "Size": "4, 1, 2" },
+        }));
+        let first = store.save("a", 1, Some(1), &a).unwrap();
+        assert_eq!(first.blobs_written, 2);
+        assert_eq!(first.blobs_shared, 0);
+
+        let second = store.save("b", 2, Some(1), &a).unwrap();
+        assert_eq!(second.blobs_written, 0);
+        assert_eq!(second.blobs_shared, 2);
+        assert!(second.bytes_saved > 0);
+
+        let _ = std::fs::remove_dir_all(store.root());
+    }
+
+    #[test]
+    fn diff_by_hash_finds_added_removed_changed() {
+        let store = temp_store();
+        store
+            .save(
+                "a",
+                1,
+                None,
+                &nodes(json!({
+                    "/W/Keep": { "ClassName": "Part" },
+                    "/W/Edit": { "ClassName": "Script", "Source": "print(1)" },
+                    "/W/Gone": { "ClassName": "Part" },
+                })),
+            )
+            .unwrap();
+        store
+            .save(
+                "b",
+                2,
+                None,
+                &nodes(json!({
+                    "/W/Keep": { "ClassName": "Part" },
+                    "/W/Edit": { "ClassName": "Script", "Source": "print(2)" },
+                    "/W/New": { "ClassName": "Part" },
+                })),
+            )
+            .unwrap();
+
+        let a = store.load_manifest("a").unwrap().unwrap();
+        let b = store.load_manifest("b").unwrap().unwrap();
+        let diff = diff_manifests(&a, &b);
+        assert_eq!(diff.added, vec!["/W/New"]);
+        assert_eq!(diff.removed, vec!["/W/Gone"]);
+        assert_eq!(diff.changed, vec!["/W/Edit"]);
+        assert_eq!(store.list().unwrap().len(), 2);
+        assert!(store.load_manifest("missing").unwrap().is_none());
+
+        let _ = std::fs::remove_dir_all(store.root());
+    }
+
+    #[test]
+    fn export_round_trips_through_another_store() {
+        let source = temp_store();
+        let body = nodes(json!({ "/W/Part": { "ClassName": "Part", "Size": "1, 1, 1" } }));
+        source.save("shared", 7, Some(42), &body).unwrap();
+
+        let export = source.export("shared").unwrap().unwrap();
+        let bytes = serde_json::to_vec(&export).unwrap();
+        let parsed = SnapshotExport::parse(&bytes).unwrap();
+        assert_eq!(parsed.place_id, Some(42));
+        assert_eq!(parsed.scope, "game");
+
+        let target = temp_store();
+        target
+            .save(
+                &parsed.name,
+                parsed.timestamp,
+                parsed.place_id,
+                &parsed.nodes,
+            )
+            .unwrap();
+        let manifest = target.load_manifest("shared").unwrap().unwrap();
+        assert_eq!(
+            manifest.entries,
+            source.load_manifest("shared").unwrap().unwrap().entries
+        );
+        assert!(source.export("missing").unwrap().is_none());
+
+        let _ = std::fs::remove_dir_all(source.root());
+        let _ = std::fs::remove_dir_all(target.root());
+    }
+
+    #[test]
+    fn parse_rejects_foreign_and_newer_files() {
+        assert!(SnapshotExport::parse(b"{\"entries\": {}}").is_err());
+        assert!(SnapshotExport::parse(b"not json").is_err());
+        let newer = json!({
+            "format": EXPORT_FORMAT, "version": EXPORT_VERSION + 1, "name": "x",
+            "timestamp": 0, "placeId": null, "scope": "game", "nodes": {},
+        });
+        let err = SnapshotExport::parse(newer.to_string().as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("newer"));
+    }
+
+    #[test]
+    fn files_are_compressed_and_uncompressed_stores_still_read() {
+        let store = temp_store();
+        let source = "print('hello')\n".repeat(200);
+        let body = nodes(json!({ "/W/Script": { "ClassName": "Script", "Source": source } }));
+        let stats = store.save("new", 1, None, &body).unwrap();
+        assert!(stats.bytes_written * 4 < stats.bytes_uncompressed);
+        let hash = hash_blob(&serde_json::to_vec(&body["/W/Script"]).unwrap());
+        let on_disk = std::fs::read(store.blob_path(&hash)).unwrap();
+        assert!(on_disk.starts_with(&[0x1f, 0x8b]));
+        assert_eq!(store.load_blob(&hash).unwrap()["Source"], source);
+
+        // A store written before compression: plain JSON files
+        let manifest = store.load_manifest("new").unwrap().unwrap();
+        let old = Manifest {
+            name: "old".into(),
+            ..manifest
+        };
+        let old_path = store.root().join("manifests").join("old.json");
+        std::fs::write(&old_path, serde_json::to_vec(&old).unwrap()).unwrap();
+        let old_blob = store.blob_path(&hash).with_extension("");
+        std::fs::write(&old_blob, serde_json::to_vec(&body["/W/Script"]).unwrap()).unwrap();
+        std::fs::remove_file(store.blob_path(&hash)).unwrap();
+
+        let loaded = store.load_manifest("old").unwrap().unwrap();
+        assert_eq!(
+            store.load_blob(&loaded.entries["/W/Script"]).unwrap()["Source"],
+            source
+        );
+        assert_eq!(store.list().unwrap().len(), 2);
+        assert_eq!(store.save("again", 2, None, &body).unwrap().blobs_shared, 1);
+
+        store.save("old", 3, None, &body).unwrap();
+        assert!(!old_path.exists());
+        assert_eq!(store.list().unwrap().len(), 3);
+
+        let _ = std::fs::remove_dir_all(store.root());
+    }
+
+    #[test]
+    fn names_are_sanitized_for_the_filesystem() {
+        assert_eq!(sanitize_name("../evil/name"), ".._evil_name");
+        assert_eq!(sanitize_name("before-fix_1.0"), "before-fix_1.0");
+    }
+}
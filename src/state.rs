@@ -1,11 +1,13 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{mpsc, watch, Mutex};
 use uuid::Uuid;
 
 /// A request queued for the Studio plugin to process
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PluginRequest {
     pub id: String,
     pub tool: String,
@@ -16,10 +18,22 @@ pub struct PluginRequest {
     /// primary's active_session.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub target_session: Option<String>,
+    /// How many proxy forwards this request has been through. Guards against
+    /// an instance proxying to itself or a chain of instances looping.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hops: u32,
+    /// Requests still queued for the session behind this one. Set only on
+    /// /request responses so a plugin that is falling behind can see it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_depth: Option<usize>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
 }
 
 /// A response from the Studio plugin
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PluginResponse {
     pub id: String,
     pub success: bool,
@@ -30,12 +44,71 @@ pub struct PluginResponse {
 }
 
 /// Registration payload sent by a Studio plugin when it connects
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SessionRegistration {
     pub session_id: String,
     pub place_id: u64,
     pub place_name: String,
     pub game_id: u64,
+    /// Stable per-Studio-install id kept in plugin settings. Unlike
+    /// session_id (new on every plugin load) it survives reconnects, so
+    /// per-window data such as display names can be keyed on it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Tools this plugin build implements. Omitted by plugins that predate
+    /// capability negotiation, in which case every tool is assumed supported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supported_tools: Option<Vec<String>>,
+    /// Plugin version string (e.g. "0.7.0"), for error messages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_version: Option<String>,
+}
+
+/// Intermediate progress for an in-flight request, POSTed by the plugin to
+/// /progress while a long tool (e.g. mass_set_property) is still running.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProgressFrame {
+    pub request_id: String,
+    /// Units of work done so far
+    pub current: u64,
+    /// Total units of work, if known
+    #[serde(default)]
+    pub total: Option<u64>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Progress frames kept per request; older frames are dropped first.
+const MAX_PROGRESS_FRAMES: usize = 100;
+
+/// One part of a response too large for a single POST /response. The plugin
+/// JSON-encodes the whole PluginResponse, splits the text into `total` parts
+/// and POSTs each to /response/chunk; once all have arrived the server
+/// decodes the text and delivers it like any other response.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ResponseChunk {
+    pub request_id: String,
+    /// 0-based position of this part
+    pub index: u32,
+    /// Number of parts; the same in every part of a response
+    pub total: u32,
+    /// This part of the encoded response text
+    pub data: String,
+}
+
+/// Parts accepted for one chunked response.
+const MAX_RESPONSE_CHUNKS: u32 = 4096;
+
+/// Largest chunked response reassembled (256 MiB).
+const MAX_CHUNKED_RESPONSE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Where progress for one MCP tool call goes: the progress token the client
+/// sent with the call, and a channel the MCP layer turns into
+/// notifications/progress.
+#[derive(Debug, Clone)]
+pub struct ProgressSink {
+    pub token: rmcp::model::ProgressToken,
+    pub tx: mpsc::UnboundedSender<rmcp::model::ProgressNotificationParam>,
 }
 
 /// Information about a connected Studio session (serializable for API responses)
@@ -46,6 +119,10 @@ pub struct SessionInfo {
     pub place_name: String,
     pub game_id: u64,
     pub connected_at: u64,
+    pub client_id: Option<String>,
+    /// User-chosen name set via rename_session (e.g. "Combat Test").
+    /// Independent of place_name, which always comes from the place itself.
+    pub display_name: Option<String>,
 }
 
 /// Response channel for delivering plugin results back to tool handlers
@@ -59,6 +136,993 @@ pub(crate) struct SessionState {
     pub request_queue: VecDeque<PluginRequest>,
     pub notify_tx: watch::Sender<bool>,
     pub notify_rx: watch::Receiver<bool>,
+    /// Tools reported at /register; None = plugin didn't report (allow all)
+    pub supported_tools: Option<std::collections::HashSet<String>>,
+    pub plugin_version: Option<String>,
+    /// Outstanding requests by id. A plugin busy with a long tool can miss
+    /// heartbeats, so the session counts as alive until these expire.
+    pub in_flight: HashMap<String, InFlight>,
+    /// The plugin is connected on /ws, so requests are pushed to it instead
+    /// of waiting for a long-poll.
+    pub websocket: bool,
+    /// Response channels of tool calls waiting on this session, keyed by
+    /// request id.
+    pub responses: HashMap<String, ResponseSender>,
+    /// Progress frames for in-flight requests, keyed by request id. Attached
+    /// to the result as `progress` when the response is delivered.
+    pub progress: HashMap<String, VecDeque<ProgressFrame>>,
+    /// MCP progress sinks of in-flight requests whose client asked for
+    /// progress, keyed by request id. Frames are forwarded as they arrive.
+    pub progress_sinks: HashMap<String, ProgressSink>,
+    /// Responses arriving through POST /response/chunk.
+    pub chunks: ResponseChunks,
+}
+
+/// A request sent to a session and not yet answered.
+#[derive(Debug, Clone)]
+pub struct InFlight {
+    pub tool: String,
+    pub started: std::time::Instant,
+    pub deadline: std::time::Instant,
+}
+
+/// Tool name of the control message that tells the plugin to abort a request
+/// it is already running. It has no response channel; the plugin never
+/// answers it.
+pub const CANCEL_TOOL: &str = "cancel_request";
+
+/// A request still waiting for its plugin response, as listed in
+/// server_diagnostics and matched by cancel_request.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingRequest {
+    pub request_id: String,
+    pub session_id: String,
+    pub tool: String,
+    /// "queued" (the plugin hasn't taken it yet) or "dispatched"
+    pub state: &'static str,
+    pub age_secs: u64,
+}
+
+impl SessionState {
+    /// Whether a request sent to this session is still within its timeout.
+    pub fn has_request_in_flight(&self) -> bool {
+        let now = std::time::Instant::now();
+        self.in_flight.values().any(|f| f.deadline > now)
+    }
+
+    /// Error message if this session's plugin is known not to implement
+    /// `tool`, so callers get a clear upgrade hint instead of "Unknown tool".
+    pub fn unsupported_tool_error(&self, tool: &str) -> Option<String> {
+        let supported = self.supported_tools.as_ref()?;
+        if supported.contains(tool) {
+            return None;
+        }
+        Some(format!(
+            "tool '{}' is not supported by this plugin version ({}); update the StudioLink plugin",
+            tool,
+            self.plugin_version
+                .as_deref()
+                .map(|v| format!("v{}", v))
+                .unwrap_or_else(|| "unknown version".into())
+        ))
+    }
+}
+
+/// One session behind its own lock. Held only for short synchronous
+/// sections, never across an await.
+pub(crate) type SessionHandle = Arc<std::sync::Mutex<SessionState>>;
+
+fn lock_session(handle: &SessionHandle) -> std::sync::MutexGuard<'_, SessionState> {
+    handle.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Connected sessions and the requests in flight to them.
+///
+/// Each session has its own lock, and the map itself is only written when a
+/// session registers or goes away, so polls, responses and progress for one
+/// Studio window never wait on another window or on AppState. The plugin
+/// endpoints use this without taking the AppState lock at all.
+///
+/// At most one session lock is held at a time. The change log may be locked
+/// while holding a session lock, never the other way round. The stream map
+/// is never held together with any other lock.
+#[derive(Default)]
+pub struct Sessions {
+    map: std::sync::RwLock<HashMap<String, SessionHandle>>,
+    /// Successful mutations per session, for poll_changes.
+    changes: std::sync::Mutex<ChangeLog>,
+    /// Open NDJSON relay streams keyed by stream id. The plugin POSTs node
+    /// batches to /stream/{id}; each line is forwarded to the tool call that
+    /// opened the stream.
+    streams: std::sync::Mutex<HashMap<String, mpsc::UnboundedSender<String>>>,
+}
+
+impl Sessions {
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, SessionHandle>> {
+        self.map.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Every session handle. The map lock is released before the caller
+    /// locks any of them.
+    fn handles(&self) -> Vec<SessionHandle> {
+        self.read().values().cloned().collect()
+    }
+
+    /// The handle for `session_id`, if it is registered.
+    pub(crate) fn get(&self, session_id: &str) -> Option<SessionHandle> {
+        self.read().get(session_id).cloned()
+    }
+
+    /// Run `f` on a session under its lock. None if it isn't registered.
+    pub(crate) fn with<R>(
+        &self,
+        session_id: &str,
+        f: impl FnOnce(&mut SessionState) -> R,
+    ) -> Option<R> {
+        let handle = self.get(session_id)?;
+        let mut session = lock_session(&handle);
+        Some(f(&mut session))
+    }
+
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.read().contains_key(session_id)
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.read().keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Info about all connected sessions
+    pub fn list(&self) -> Vec<SessionInfo> {
+        self.handles()
+            .iter()
+            .map(|h| lock_session(h).info.clone())
+            .collect()
+    }
+
+    /// The change log, locked.
+    pub fn changes(&self) -> std::sync::MutexGuard<'_, ChangeLog> {
+        self.changes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn streams(&self) -> std::sync::MutexGuard<'_, HashMap<String, mpsc::UnboundedSender<String>>> {
+        self.streams.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Open a relay stream; lines pushed to `stream_id` arrive on the receiver
+    /// until `close_stream` drops the sender.
+    pub fn open_stream(&self) -> (String, mpsc::UnboundedReceiver<String>) {
+        let id = Uuid::new_v4().to_string();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.streams().insert(id.clone(), tx);
+        (id, rx)
+    }
+
+    /// Forward each non-empty line of an NDJSON chunk. Returns the number of
+    /// lines relayed, or None if the stream is unknown or already closed.
+    pub fn push_stream_lines(&self, stream_id: &str, chunk: &str) -> Option<usize> {
+        let mut streams = self.streams();
+        let tx = streams.get(stream_id)?;
+        let mut count = 0;
+        for line in chunk.lines().filter(|l| !l.trim().is_empty()) {
+            if tx.send(line.to_string()).is_err() {
+                streams.remove(stream_id);
+                return None;
+            }
+            count += 1;
+        }
+        Some(count)
+    }
+
+    /// Close a relay stream so its receiver sees end-of-stream.
+    pub fn close_stream(&self, stream_id: &str) {
+        self.streams().remove(stream_id);
+    }
+
+    /// Relay streams currently open.
+    pub fn open_streams(&self) -> usize {
+        self.streams().len()
+    }
+
+    fn insert(&self, session: SessionState) {
+        let id = session.info.session_id.clone();
+        self.map
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, Arc::new(std::sync::Mutex::new(session)));
+    }
+
+    /// Remove a session. Tool calls still waiting on it fail at once instead
+    /// of running into their timeout.
+    fn remove(&self, session_id: &str) -> bool {
+        let removed = self
+            .map
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(session_id);
+        let Some(handle) = removed else {
+            return false;
+        };
+        let waiting: Vec<(String, ResponseSender)> =
+            lock_session(&handle).responses.drain().collect();
+        for (id, tx) in waiting {
+            let response = PluginResponse {
+                id,
+                success: false,
+                result: serde_json::Value::Null,
+                error: Some(format!(
+                    "session {} disconnected before answering",
+                    session_id
+                )),
+            };
+            self.changes().settle(&response);
+            let _ = tx.send(response);
+        }
+        true
+    }
+
+    /// The session with a tool call waiting on `request_id`.
+    fn owner(&self, request_id: &str) -> Option<SessionHandle> {
+        self.handles().into_iter().find(|h| {
+            let session = lock_session(h);
+            session.responses.contains_key(request_id) || session.in_flight.contains_key(request_id)
+        })
+    }
+
+    /// Queue a request to a specific session. It is tracked as in flight,
+    /// keeping the session counted as connected, until it is answered or
+    /// `timeout` runs out.
+    pub fn queue_request(
+        &self,
+        session_id: &str,
+        tool: &str,
+        args: serde_json::Value,
+        timeout: std::time::Duration,
+    ) -> Option<(String, ResponseReceiver)> {
+        let handle = self.get(session_id)?;
+        let mut session = lock_session(&handle);
+
+        let id = Uuid::new_v4().to_string();
+        let request = PluginRequest {
+            id: id.clone(),
+            tool: tool.to_string(),
+            args,
+            target_session: None,
+            hops: 0,
+            queue_depth: None,
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        session.responses.insert(id.clone(), tx);
+        let now = std::time::Instant::now();
+        session.in_flight.insert(
+            id.clone(),
+            InFlight {
+                tool: request.tool.clone(),
+                started: now,
+                deadline: now + timeout,
+            },
+        );
+        self.changes()
+            .expect(&id, session_id, &request.tool, &request.args);
+        session.request_queue.push_back(request);
+
+        // Notify this session's plugin
+        let _ = session.notify_tx.send(true);
+
+        Some((id, rx))
+    }
+
+    /// Get the next pending request for a specific session (called by plugin polling)
+    pub fn next_request(&self, session_id: &str) -> Option<PluginRequest> {
+        self.with(session_id, |session| {
+            let mut request = session.request_queue.pop_front()?;
+            request.queue_depth = Some(session.request_queue.len());
+            Some(request)
+        })?
+    }
+
+    /// Requests waiting in a session's queue (0 for unknown sessions).
+    pub fn queue_depth(&self, session_id: &str) -> usize {
+        self.with(session_id, |s| s.request_queue.len())
+            .unwrap_or(0)
+    }
+
+    /// Record a progress frame. Returns false (and drops it) if no tool call
+    /// is waiting on that request, so late frames can't accumulate.
+    pub fn record_progress(&self, frame: ProgressFrame) -> bool {
+        let Some(handle) = self.owner(&frame.request_id) else {
+            return false;
+        };
+        let mut session = lock_session(&handle);
+        if !session.responses.contains_key(&frame.request_id) {
+            return false;
+        }
+        if let Some(sink) = session.progress_sinks.get(&frame.request_id) {
+            let _ = sink.tx.send(rmcp::model::ProgressNotificationParam {
+                progress_token: sink.token.clone(),
+                progress: frame.current as f64,
+                total: frame.total.map(|t| t as f64),
+                message: frame.message.clone(),
+            });
+        }
+        let frames = session
+            .progress
+            .entry(frame.request_id.clone())
+            .or_default();
+        if frames.len() >= MAX_PROGRESS_FRAMES {
+            frames.pop_front();
+        }
+        frames.push_back(frame);
+        true
+    }
+
+    /// Forward progress for `request_id` to an MCP client as it arrives.
+    pub fn attach_progress_sink(&self, session_id: &str, request_id: &str, sink: ProgressSink) {
+        self.with(session_id, |s| {
+            s.progress_sinks.insert(request_id.to_string(), sink);
+        });
+    }
+
+    /// Requests still waiting for a response, oldest first, optionally only
+    /// those of one session.
+    pub fn pending_requests(&self, session_id: Option<&str>) -> Vec<PendingRequest> {
+        let handles = match session_id {
+            Some(id) => self.get(id).into_iter().collect(),
+            None => self.handles(),
+        };
+        let mut pending: Vec<(std::time::Instant, PendingRequest)> = Vec::new();
+        for handle in handles {
+            let session = lock_session(&handle);
+            for (request_id, f) in &session.in_flight {
+                let queued = session.request_queue.iter().any(|r| &r.id == request_id);
+                let request = PendingRequest {
+                    request_id: request_id.clone(),
+                    session_id: session.info.session_id.clone(),
+                    tool: f.tool.clone(),
+                    state: if queued { "queued" } else { "dispatched" },
+                    age_secs: f.started.elapsed().as_secs(),
+                };
+                pending.push((f.started, request));
+            }
+        }
+        pending.sort_by_key(|(started, _)| *started);
+        pending.into_iter().map(|(_, r)| r).collect()
+    }
+
+    /// Cancel a pending request. If its plugin hasn't taken it yet it is just
+    /// removed from the queue; otherwise a cancel message is queued ahead of
+    /// everything else so the plugin aborts it. Either way the waiting tool
+    /// call fails at once. None if no session has the request pending.
+    pub fn cancel_request(&self, request_id: &str) -> Option<PendingRequest> {
+        let handle = self.owner(request_id)?;
+        let cancelled = {
+            let mut session = lock_session(&handle);
+            let f = session.in_flight.get(request_id)?;
+            let mut cancelled = PendingRequest {
+                request_id: request_id.to_string(),
+                session_id: session.info.session_id.clone(),
+                tool: f.tool.clone(),
+                state: "dispatched",
+                age_secs: f.started.elapsed().as_secs(),
+            };
+            let queued_at = session
+                .request_queue
+                .iter()
+                .position(|r| r.id == request_id);
+            match queued_at {
+                Some(index) => {
+                    session.request_queue.remove(index);
+                    cancelled.state = "queued";
+                }
+                None => {
+                    session.request_queue.push_front(PluginRequest {
+                        id: Uuid::new_v4().to_string(),
+                        tool: CANCEL_TOOL.to_string(),
+                        args: serde_json::json!({ "requestId": request_id }),
+                        target_session: None,
+                        hops: 0,
+                        queue_depth: None,
+                    });
+                    let _ = session.notify_tx.send(true);
+                }
+            }
+            cancelled
+        };
+        self.deliver_response(PluginResponse {
+            id: request_id.to_string(),
+            success: false,
+            result: serde_json::Value::Null,
+            error: Some(format!(
+                "{} was cancelled by cancel_request after {}s",
+                cancelled.tool, cancelled.age_secs
+            )),
+        });
+        Some(cancelled)
+    }
+
+    /// Stop tracking a request, whether it was answered or abandoned.
+    pub fn finish_in_flight(&self, request_id: &str) {
+        self.changes().forget(request_id);
+        if let Some(handle) = self.owner(request_id) {
+            let mut session = lock_session(&handle);
+            session.progress_sinks.remove(request_id);
+            session.in_flight.remove(request_id);
+        }
+    }
+
+    /// Deliver a response from the plugin to the waiting tool handler
+    pub fn deliver_response(&self, mut response: PluginResponse) -> bool {
+        self.changes().settle(&response);
+        let tx = self.owner(&response.id).and_then(|handle| {
+            let mut session = lock_session(&handle);
+            session.chunks.discard(&response.id);
+            session.progress_sinks.remove(&response.id);
+            session.in_flight.remove(&response.id);
+            if let Some(frames) = session.progress.remove(&response.id) {
+                if let Some(obj) = response.result.as_object_mut() {
+                    obj.insert(
+                        "progress".to_string(),
+                        serde_json::to_value(frames).unwrap_or_default(),
+                    );
+                }
+            }
+            session.responses.remove(&response.id)
+        });
+        if let Some(tx) = tx {
+            tx.send(response).is_ok()
+        } else {
+            tracing::warn!("No response channel found for request {}", response.id);
+            false
+        }
+    }
+
+    /// Add one part of a chunked response, delivering the response once every
+    /// part is in. None if no tool call is waiting on the request. A complete
+    /// response that doesn't decode fails the call instead of leaving it to
+    /// time out.
+    pub fn deliver_response_chunk(
+        &self,
+        chunk: ResponseChunk,
+    ) -> Option<Result<ChunkProgress, String>> {
+        let request_id = chunk.request_id.clone();
+        let handle = self.owner(&request_id)?;
+        let progress = {
+            let mut session = lock_session(&handle);
+            if !session.responses.contains_key(&request_id) {
+                session.chunks.discard(&request_id);
+                return None;
+            }
+            session.chunks.add(chunk)
+        };
+        if let Ok(ChunkProgress::Complete(text)) = &progress {
+            let response = match serde_json::from_str::<PluginResponse>(text) {
+                Ok(response) if response.id == request_id => response,
+                Ok(response) => PluginResponse {
+                    id: request_id.clone(),
+                    success: false,
+                    result: serde_json::Value::Null,
+                    error: Some(format!(
+                        "chunked response for {} carried the id {}",
+                        request_id, response.id
+                    )),
+                },
+                Err(e) => PluginResponse {
+                    id: request_id.clone(),
+                    success: false,
+                    result: serde_json::Value::Null,
+                    error: Some(format!("chunked response didn't decode: {}", e)),
+                },
+            };
+            self.deliver_response(response);
+        }
+        Some(progress)
+    }
+
+    /// Tool calls waiting on a plugin response, across all sessions.
+    pub fn awaiting_responses(&self) -> usize {
+        self.handles()
+            .iter()
+            .map(|h| lock_session(h).responses.len())
+            .sum()
+    }
+
+    /// Update heartbeat for a specific session
+    pub fn heartbeat(&self, session_id: &str) {
+        self.with(session_id, |s| s.last_heartbeat = std::time::Instant::now());
+    }
+
+    /// Check if a session is connected (heartbeat within last 45 seconds)
+    /// Increased from 30s to 45s to handle play mode transitions and long tool execution.
+    /// A session with a request still inside its timeout counts as connected
+    /// regardless of heartbeat age.
+    pub fn is_connected(&self, session_id: &str) -> bool {
+        self.with(session_id, |s| {
+            s.last_heartbeat.elapsed().as_secs() < 45 || s.has_request_in_flight()
+        })
+        .unwrap_or(false)
+    }
+
+    /// Error for an explicitly targeted session that has stopped heartbeating.
+    pub fn unresponsive_error(&self, session_id: &str) -> Option<String> {
+        let silent_for = self.with(session_id, |s| {
+            let silent_for = s.last_heartbeat.elapsed().as_secs();
+            (silent_for >= 45 && !s.has_request_in_flight()).then_some(silent_for)
+        })??;
+        Some(format!(
+            "session_id '{}' is not responding (last heartbeat {}s ago). \
+             Its Studio window may be closed or busy; use list_sessions to pick another.",
+            session_id, silent_for
+        ))
+    }
+
+    /// Wake every session's long poll and WebSocket without queueing
+    /// anything.
+    pub fn wake_all(&self) {
+        for handle in self.handles() {
+            let _ = lock_session(&handle).notify_tx.send(true);
+        }
+    }
+
+    /// Get the notify_rx for a specific session (for long polling)
+    pub fn notify_rx(&self, session_id: &str) -> Option<watch::Receiver<bool>> {
+        self.with(session_id, |s| s.notify_rx.clone())
+    }
+
+    /// Drop bookkeeping for abandoned requests and return the sessions that
+    /// have gone stale: no heartbeat for 120 seconds and nothing in flight.
+    /// Increased from 60s to 120s to survive play mode transitions where
+    /// HTTP polling may be interrupted during Studio state changes.
+    fn expire(&self) -> Vec<String> {
+        let now = std::time::Instant::now();
+        let mut stale = Vec::new();
+        for handle in self.handles() {
+            let mut guard = lock_session(&handle);
+            let session = &mut *guard;
+            session.responses.retain(|id, tx| {
+                if tx.is_closed() {
+                    tracing::debug!("Cleaning up expired channel for request {}", id);
+                    false
+                } else {
+                    true
+                }
+            });
+            let responses = &session.responses;
+            session.chunks.retain(|id| responses.contains_key(id));
+            session.progress.retain(|id, _| responses.contains_key(id));
+            if session.last_heartbeat.elapsed().as_secs() > 120 && !session.has_request_in_flight()
+            {
+                stale.push(session.info.session_id.clone());
+            }
+            session.in_flight.retain(|_, f| f.deadline > now);
+        }
+        stale
+    }
+}
+
+/// Default for --max-queue-depth: requests a session may have queued before
+/// new ones are rejected.
+pub const DEFAULT_MAX_QUEUE_DEPTH: usize = 100;
+
+/// Tool calls remembered for export_history (last 200).
+const MAX_CALL_HISTORY: usize = 200;
+
+/// Argument names whose values never enter the call history. Matched
+/// case-insensitively as substrings, so "apiKey" and "x_api_key" both hit.
+const SENSITIVE_ARG_KEYS: &[&str] = &[
+    "password",
+    "secret",
+    "token",
+    "apikey",
+    "api_key",
+    "cookie",
+    "authorization",
+    "credential",
+];
+
+/// One dispatched plugin call, with sensitive arguments already redacted.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub at_unix_ms: u64,
+    pub tool: String,
+    pub args: serde_json::Value,
+    pub target_session: Option<String>,
+}
+
+/// Copy of `args` with the values of sensitive keys replaced, at any depth.
+pub fn redact_args(args: &serde_json::Value) -> serde_json::Value {
+    match args {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let lower = k.to_ascii_lowercase();
+                    let value = if SENSITIVE_ARG_KEYS.iter().any(|s| lower.contains(s)) {
+                        serde_json::Value::String("[REDACTED]".into())
+                    } else {
+                        redact_args(v)
+                    };
+                    (k.clone(), value)
+                })
+                .collect(),
+        ),
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(redact_args).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+/// How many previously active sessions switch_back remembers.
+const MAX_SESSION_HISTORY: usize = 8;
+
+/// Latest lint result per script from lint_watch, each stamped with a
+/// sequence number so a poll can return only what changed since its cursor.
+#[derive(Debug, Default)]
+pub struct LintWatchLog {
+    pub seq: u64,
+    pub results: HashMap<String, (u64, serde_json::Value)>,
+}
+
+impl LintWatchLog {
+    /// Store `result` as the latest for `script_path`, replacing any older one.
+    pub fn record(&mut self, script_path: &str, result: serde_json::Value) {
+        self.seq += 1;
+        self.results
+            .insert(script_path.to_string(), (self.seq, result));
+    }
+
+    /// Results recorded after `cursor`, oldest first.
+    pub fn since(&self, cursor: u64) -> Vec<serde_json::Value> {
+        let mut changed: Vec<&(u64, serde_json::Value)> = self
+            .results
+            .values()
+            .filter(|(seq, _)| *seq > cursor)
+            .collect();
+        changed.sort_by_key(|(seq, _)| *seq);
+        changed.into_iter().map(|(_, r)| r.clone()).collect()
+    }
+}
+
+/// Tool results larger than this are split into pages of this size (64 KiB).
+pub const RESULT_PAGE_BYTES: usize = 64 * 1024;
+
+/// How long a paginated result stays fetchable with get_result_page.
+const RESULT_PAGE_TTL: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// Most result text held for get_result_page at once (32 MiB). Storing past
+/// it evicts the oldest results first.
+const MAX_RESULT_PAGE_STORE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Oversized tool results held for get_result_page, keyed by continuation
+/// token. Entries expire `RESULT_PAGE_TTL` after they were stored, or
+/// earlier when newer results push the total past
+/// `MAX_RESULT_PAGE_STORE_BYTES`.
+#[derive(Debug, Default)]
+pub struct ResultPages {
+    results: HashMap<String, (std::time::Instant, Vec<String>)>,
+    /// Tokens oldest first; may still name results that already expired
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl ResultPages {
+    /// Split `text` into pages and keep them. Returns the token and the
+    /// number of pages; page 0 is the first.
+    pub fn store(&mut self, text: &str) -> (String, usize) {
+        self.expire();
+        let mut pages = Vec::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let mut end = rest.len().min(RESULT_PAGE_BYTES);
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            let (page, tail) = rest.split_at(end);
+            pages.push(page.to_string());
+            rest = tail;
+        }
+        self.make_room(text.len());
+        let token = Uuid::new_v4().to_string();
+        let count = pages.len();
+        self.bytes += text.len();
+        self.order.push_back(token.clone());
+        self.results
+            .insert(token.clone(), (std::time::Instant::now(), pages));
+        (token, count)
+    }
+
+    /// Page `page` of a stored result and the result's page count, or None
+    /// if the token is unknown or expired.
+    pub fn page(&mut self, token: &str, page: usize) -> Option<(String, usize)> {
+        self.expire();
+        let (_, pages) = self.results.get(token)?;
+        Some((pages.get(page)?.clone(), pages.len()))
+    }
+
+    fn expire(&mut self) {
+        self.results
+            .retain(|_, (stored, _)| stored.elapsed() < RESULT_PAGE_TTL);
+        self.order.retain(|token| self.results.contains_key(token));
+        self.bytes = self
+            .results
+            .values()
+            .map(|(_, pages)| stored_bytes(pages))
+            .sum();
+    }
+
+    /// Evict the oldest results until `incoming` more bytes fit under the cap.
+    /// A single result larger than the cap is still kept, on its own.
+    fn make_room(&mut self, incoming: usize) {
+        while self.bytes + incoming > MAX_RESULT_PAGE_STORE_BYTES {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some((_, pages)) = self.results.remove(&oldest) {
+                self.bytes -= stored_bytes(&pages);
+            }
+        }
+    }
+}
+
+fn stored_bytes(pages: &[String]) -> usize {
+    pages.iter().map(String::len).sum()
+}
+
+/// Chunked responses being reassembled, keyed by request id.
+#[derive(Debug, Default)]
+pub struct ResponseChunks {
+    partial: HashMap<String, PartialResponse>,
+}
+
+#[derive(Debug)]
+struct PartialResponse {
+    parts: Vec<Option<String>>,
+    received: u32,
+    bytes: usize,
+}
+
+/// What adding a chunk did.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkProgress {
+    /// Parts received so far, of `total`
+    Waiting { received: u32, total: u32 },
+    /// Every part is in; the reassembled response text
+    Complete(String),
+}
+
+impl ResponseChunks {
+    /// Store `chunk`. A part sent twice replaces the earlier copy. Errors on
+    /// parts that don't fit the response, which is then dropped.
+    pub fn add(&mut self, chunk: ResponseChunk) -> Result<ChunkProgress, String> {
+        if chunk.total == 0 || chunk.total > MAX_RESPONSE_CHUNKS {
+            self.partial.remove(&chunk.request_id);
+            return Err(format!(
+                "total must be between 1 and {}, got {}",
+                MAX_RESPONSE_CHUNKS, chunk.total
+            ));
+        }
+        let partial = self
+            .partial
+            .entry(chunk.request_id.clone())
+            .or_insert_with(|| PartialResponse {
+                parts: vec![None; chunk.total as usize],
+                received: 0,
+                bytes: 0,
+            });
+        let problem = if partial.parts.len() != chunk.total as usize {
+            Some(format!(
+                "total changed from {} to {}",
+                partial.parts.len(),
+                chunk.total
+            ))
+        } else if chunk.index >= chunk.total {
+            Some(format!(
+                "index {} is out of range for {} parts",
+                chunk.index, chunk.total
+            ))
+        } else if partial.bytes + chunk.data.len() > MAX_CHUNKED_RESPONSE_BYTES {
+            Some(format!(
+                "response exceeds {} MiB",
+                MAX_CHUNKED_RESPONSE_BYTES / (1024 * 1024)
+            ))
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            self.partial.remove(&chunk.request_id);
+            return Err(problem);
+        }
+
+        partial.bytes += chunk.data.len();
+        let slot = &mut partial.parts[chunk.index as usize];
+        if let Some(previous) = slot.replace(chunk.data) {
+            partial.bytes -= previous.len();
+        } else {
+            partial.received += 1;
+        }
+        if partial.received < chunk.total {
+            return Ok(ChunkProgress::Waiting {
+                received: partial.received,
+                total: chunk.total,
+            });
+        }
+        let text = partial.parts.drain(..).flatten().collect();
+        self.partial.remove(&chunk.request_id);
+        Ok(ChunkProgress::Complete(text))
+    }
+
+    /// Drop a partial response, e.g. once its request is answered or gone.
+    pub fn discard(&mut self, request_id: &str) {
+        self.partial.remove(request_id);
+    }
+
+    /// Keep only partial responses `keep` accepts.
+    fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.partial.retain(|id, _| keep(id));
+    }
+}
+
+/// Mutations poll_changes keeps per session.
+const MAX_CHANGES_PER_SESSION: usize = 500;
+
+/// One instance-tree mutation made through a tool call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeEntry {
+    pub seq: u64,
+    pub at_unix_ms: u64,
+    pub tool: String,
+    /// Instance affected, when the call names one. None for changes that
+    /// could touch anything (run_code, undo/redo): drop all cached state.
+    pub path: Option<String>,
+    /// Where a "created" instance was put, when the call names it; `path`
+    /// is the new instance itself once the plugin has reported it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// "created", "deleted", "modified", "source" or "unknown"
+    pub kind: String,
+}
+
+/// The mutations a tool call will make if it succeeds, as (kind, path).
+fn expected_changes(tool: &str, args: &serde_json::Value) -> Vec<(&'static str, Option<String>)> {
+    let path = |key: &str| args[key].as_str().map(|s| s.to_string());
+    match tool {
+        "create_instance" | "import_instance" | "insert_asset" => {
+            vec![("created", path("parentPath"))]
+        }
+        "insert_model" if args["preview"] != true => vec![("created", None)],
+        "test_create" if args["createIn"].is_string() => vec![("created", path("createIn"))],
+        "delete_instance" => vec![("deleted", path("path"))],
+        "set_property" | "set_appearance" => vec![("modified", path("path"))],
+        "mass_set_property" => args["paths"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|p| ("modified", p.as_str().map(|s| s.to_string())))
+            .collect(),
+        "set_script_source" => vec![("source", path("path"))],
+        "script_patch" => vec![("source", path("module_path"))],
+        "undo" | "redo" => vec![("unknown", None)],
+        // Non-exclusive run_code is declared read-only by its caller
+        "run_code" if args["exclusive"] != false => vec![("unknown", None)],
+        _ => Vec::new(),
+    }
+}
+
+/// Per-session log of successful mutating tool calls, for agents sharing a
+/// place to invalidate cached trees. Sequence numbers are shared by all
+/// sessions, and every commit bumps the watch channel so pollers can wait
+/// for the next change instead of spinning.
+#[derive(Debug)]
+pub struct ChangeLog {
+    seq_tx: watch::Sender<u64>,
+    entries: HashMap<String, VecDeque<ChangeEntry>>,
+    /// Changes of in-flight requests, keyed by request id, committed only
+    /// if the plugin reports success.
+    pending: HashMap<String, (String, Vec<ChangeEntry>)>,
+    /// Highest sequence number each session has evicted from its log.
+    evicted: HashMap<String, u64>,
+}
+
+impl Default for ChangeLog {
+    fn default() -> Self {
+        Self {
+            seq_tx: watch::channel(0).0,
+            entries: HashMap::new(),
+            pending: HashMap::new(),
+            evicted: HashMap::new(),
+        }
+    }
+}
+
+impl ChangeLog {
+    /// Remember what request `id` to `session_id` will change.
+    pub fn expect(&mut self, id: &str, session_id: &str, tool: &str, args: &serde_json::Value) {
+        let changes: Vec<ChangeEntry> = expected_changes(tool, args)
+            .into_iter()
+            .map(|(kind, path)| ChangeEntry {
+                seq: 0,
+                at_unix_ms: 0,
+                tool: tool.to_string(),
+                parent: (kind == "created").then(|| path.clone()).flatten(),
+                path,
+                kind: kind.to_string(),
+            })
+            .collect();
+        if !changes.is_empty() {
+            self.pending
+                .insert(id.to_string(), (session_id.to_string(), changes));
+        }
+    }
+
+    /// Drop the expected changes of a request nobody will settle (timed out
+    /// or abandoned by its caller).
+    pub fn forget(&mut self, id: &str) {
+        self.pending.remove(id);
+    }
+
+    /// Commit the changes of the request `response` answers if it succeeded,
+    /// otherwise drop them.
+    pub fn settle(&mut self, response: &PluginResponse) {
+        let Some((session_id, changes)) = self.pending.remove(&response.id) else {
+            return;
+        };
+        if !response.success || response.result["created"] == false {
+            return;
+        }
+        let at_unix_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut seq = *self.seq_tx.borrow();
+        let log = self.entries.entry(session_id.clone()).or_default();
+        for mut change in changes {
+            seq += 1;
+            change.seq = seq;
+            change.at_unix_ms = at_unix_ms;
+            // create_instance reports where the new instance ended up
+            if change.kind == "created" {
+                if let Some(full_name) = response.result["fullName"].as_str() {
+                    change.path = Some(full_name.to_string());
+                }
+            }
+            if log.len() >= MAX_CHANGES_PER_SESSION {
+                if let Some(dropped) = log.pop_front() {
+                    self.evicted.insert(session_id.clone(), dropped.seq);
+                }
+            }
+            log.push_back(change);
+        }
+        self.seq_tx.send_replace(seq);
+    }
+
+    /// Latest sequence number handed out (the cursor for "now").
+    pub fn latest(&self) -> u64 {
+        *self.seq_tx.borrow()
+    }
+
+    /// Receiver that changes whenever a new entry is committed.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.seq_tx.subscribe()
+    }
+
+    /// Entries for `session_id` after `cursor`, oldest first. The flag is
+    /// true if some of them were already evicted, in which case the caller
+    /// missed changes and should drop everything it cached.
+    pub fn since(&self, session_id: &str, cursor: u64) -> (Vec<ChangeEntry>, bool) {
+        let missed = self
+            .evicted
+            .get(session_id)
+            .is_some_and(|&evicted| evicted > cursor);
+        let Some(log) = self.entries.get(session_id) else {
+            return (Vec::new(), missed);
+        };
+        let entries = log.iter().filter(|e| e.seq > cursor).cloned().collect();
+        (entries, missed)
+    }
 }
 
 /// Per-call routing observation (for v0.6 session_id debug). Records every
@@ -71,14 +1135,68 @@ pub struct RoutingObservation {
     pub target_session: Option<String>,
 }
 
+/// An open undo-grouping macro (begin_macro .. end_macro). The plugin holds
+/// the ChangeHistoryService recording; this side only remembers which session
+/// it lives on so it can be closed if routing moves elsewhere.
+#[derive(Debug, Clone)]
+pub struct MacroState {
+    pub name: String,
+    /// Session the recording was opened on. None in proxy mode, where the
+    /// session list lives on the primary.
+    pub session_id: Option<String>,
+    pub started_at: std::time::Instant,
+}
+
+/// What one MCP client has set up for itself. Every streamable-HTTP MCP
+/// session has its own, so chats sharing a server don't clobber each other's
+/// binding, macro or switch history; a stdio server has exactly one.
+#[derive(Debug, Default)]
+pub struct Connection {
+    /// v0.7 session affinity: when an AI client calls set_my_session, this is
+    /// remembered and every subsequent tool call without an explicit
+    /// session_id falls back to it instead of active_session.
+    pub bound_session_id: Option<String>,
+    /// Open begin_macro recording, if any. Only one at a time — nesting is
+    /// rejected by begin_macro.
+    pub active_macro: Option<MacroState>,
+    /// Previously active sessions, most recent last, for switch_back.
+    pub session_history: VecDeque<String>,
+}
+
+impl Connection {
+    /// Forget an open macro whose session has disconnected: the recording
+    /// died with the plugin, so there is nothing left to close.
+    pub fn prune_macro(&mut self, sessions: &Sessions) {
+        let gone = self
+            .active_macro
+            .as_ref()
+            .and_then(|m| m.session_id.as_deref())
+            .is_some_and(|id| !sessions.contains(id));
+        if gone {
+            self.active_macro = None;
+        }
+    }
+}
+
+/// A Connection shared between the MCP handler that owns it and the tool
+/// calls it runs. Never held across an await, and never locked before
+/// AppState.
+#[derive(Debug, Clone, Default)]
+pub struct SharedConnection(Arc<std::sync::Mutex<Connection>>);
+
+impl SharedConnection {
+    pub fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
 /// Shared application state between HTTP server and MCP handler
 pub struct AppState {
-    /// All connected sessions, keyed by session_id
-    pub sessions: HashMap<String, SessionState>,
+    /// All connected sessions, each behind its own lock. Shared with the
+    /// HTTP server, which serves plugin traffic without locking AppState.
+    pub sessions: Arc<Sessions>,
     /// Currently active session ID (where tool calls are routed)
     pub active_session: Option<String>,
-    /// Map of request IDs to response channels (shared across sessions)
-    pub response_channels: HashMap<String, ResponseSender>,
     /// Global notify channel (for backwards compatibility and session registration events)
     pub global_notify_tx: watch::Sender<bool>,
     /// Proxy mode: if true, forward tool calls to primary server via HTTP
@@ -90,29 +1208,160 @@ pub struct AppState {
     /// Last 50 tool dispatches with their target_session value — for v0.6
     /// session_id routing diagnostics, exposed via GET /debug/routing.
     pub routing_log: VecDeque<RoutingObservation>,
-    /// v0.7 session affinity: when an AI client calls set_my_session, this is
-    /// remembered and every subsequent tool call without an explicit
-    /// session_id falls back to it instead of active_session. Each studiolink
-    /// instance has its own bound_session_id, so multi-chat is isolated by
-    /// process boundary.
-    pub bound_session_id: Option<String>,
+    /// Connection state for calls that don't come from an MCP connection
+    /// (the plugin HTTP API, tests). MCP clients each have their own; see
+    /// tools::connection.
+    pub connection: SharedConnection,
+    /// Display names set via rename_session, keyed by client_id so they are
+    /// re-applied when the same Studio window reconnects.
+    pub display_names: HashMap<String, String>,
+    /// Where display_names is persisted. None keeps them in memory only.
+    pub display_names_path: Option<PathBuf>,
+    /// Content-addressed on-disk snapshot store. None = snapshots live only
+    /// in the plugin (proxy instances, tests).
+    pub snapshot_store: Option<crate::snapshots::SnapshotStore>,
+    /// Module paths run_code may `require` (--require-allowlist). None leaves
+    /// require unrestricted.
+    pub require_allowlist: Option<Vec<String>>,
+    /// Named session groups from create_session_group, for broadcast_to_group.
+    /// Members are kept when a session disconnects so the group still works
+    /// once that Studio window reconnects under the same id.
+    pub session_groups: HashMap<String, Vec<String>>,
+    /// Recent plugin calls with redacted args, oldest first (export_history).
+    pub call_history: VecDeque<HistoryEntry>,
+    /// Per-session execution locks that serialize exclusive run_code calls,
+    /// whether they arrive directly or through /proxy/tool_call.
+    pub exec_locks: HashMap<String, Arc<Mutex<()>>>,
+    /// Per-script results accumulated while lint_watch is running.
+    pub lint_watch: LintWatchLog,
+    /// When this server process started (server_diagnostics uptime).
+    pub started_at: std::time::Instant,
+    /// Oversized tool results waiting to be fetched with get_result_page.
+    pub result_pages: ResultPages,
+    /// Queue depth at which new requests for a session are rejected
+    /// (--max-queue-depth).
+    pub max_queue_depth: usize,
+    /// Last datastore_list result per session, with when it was fetched.
+    /// Cleared on session switch; see tools::datastore::DATASTORE_LIST_TTL.
+    pub datastore_lists: HashMap<String, (std::time::Instant, serde_json::Value)>,
+    /// Base URL of this server's HTTP API ("http://127.0.0.1:34872"), set
+    /// once the listener is bound. None in proxy mode and before binding.
+    pub http_base_url: Option<String>,
+    /// Retry policy per tool name; tools without an entry are never retried.
+    /// Seeded from tools::default_retry_policies, adjusted with --retry.
+    pub retry_policies: HashMap<String, crate::tools::RetryPolicy>,
+    /// Retry outcomes per tool since startup (server_diagnostics).
+    pub retry_stats: HashMap<String, crate::tools::RetryCounts>,
+    /// Tokens the plugin HTTP API accepts. None leaves it open (--no-auth,
+    /// proxy instances, tests).
+    /// Behind its own lock so the token check on every plugin request never
+    /// waits on AppState.
+    pub auth: Option<crate::auth::SharedTokens>,
+    /// Bearer token sent to the primary in proxy mode.
+    pub proxy_token: Option<String>,
+    /// --read-only: tools in tools::MUTATING_TOOLS are refused before dispatch.
+    pub read_only: bool,
+    /// --tools-allow/--tools-deny, kept for broadcasts, which name plugin
+    /// tools directly instead of going through the MCP router.
+    pub tool_filter: crate::tools::ToolFilter,
+    /// --datastore-backend opencloud: datastore_* tools go to the Open Cloud
+    /// API instead of the plugin.
+    pub open_cloud: Option<Arc<crate::opencloud::OpenCloudDataStores>>,
+    /// Running sync_watch_start watchers, keyed by canonical directory.
+    pub sync_watches: HashMap<PathBuf, crate::tools::sync::SyncWatch>,
+    /// What the sync watchers did, for sync_watch_poll.
+    pub sync_events: crate::tools::sync::SyncEvents,
 }
 
 impl AppState {
     pub fn new() -> (Arc<Mutex<Self>>, watch::Receiver<bool>) {
         let (global_notify_tx, global_notify_rx) = watch::channel(false);
         let state = Self {
-            sessions: HashMap::new(),
+            sessions: Arc::default(),
             active_session: None,
-            response_channels: HashMap::new(),
             global_notify_tx,
             proxy_mode: false,
             proxy_url: String::new(),
             proxy_client: None,
             routing_log: VecDeque::new(),
-            bound_session_id: None,
+            connection: SharedConnection::default(),
+            display_names: HashMap::new(),
+            display_names_path: None,
+            snapshot_store: None,
+            require_allowlist: None,
+            session_groups: HashMap::new(),
+            call_history: VecDeque::new(),
+            exec_locks: HashMap::new(),
+            lint_watch: LintWatchLog::default(),
+            started_at: std::time::Instant::now(),
+            result_pages: ResultPages::default(),
+            max_queue_depth: DEFAULT_MAX_QUEUE_DEPTH,
+            datastore_lists: HashMap::new(),
+            http_base_url: None,
+            retry_policies: crate::tools::default_retry_policies(),
+            retry_stats: HashMap::new(),
+            auth: None,
+            proxy_token: None,
+            read_only: false,
+            tool_filter: Default::default(),
+            open_cloud: None,
+            sync_watches: HashMap::new(),
+            sync_events: Default::default(),
+        };
+        (Arc::new(Mutex::new(state)), global_notify_rx)
+    }
+
+    /// Default location of the display-name file: ~/.studiolink/session_names.json
+    pub fn default_display_names_path() -> Option<PathBuf> {
+        std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| {
+                PathBuf::from(home)
+                    .join(".studiolink")
+                    .join("session_names.json")
+            })
+    }
+
+    /// Load persisted display names from `path` and keep saving there. A
+    /// missing or unreadable file just starts empty.
+    pub fn load_display_names(&mut self, path: PathBuf) {
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            match serde_json::from_str(&text) {
+                Ok(names) => self.display_names = names,
+                Err(e) => tracing::warn!("Ignoring malformed {}: {}", path.display(), e),
+            }
+        }
+        self.display_names_path = Some(path);
+    }
+
+    fn save_display_names(&self) -> std::io::Result<()> {
+        let Some(path) = &self.display_names_path else {
+            return Ok(());
         };
-        (Arc::new(Mutex::new(state)), global_notify_rx)
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&self.display_names)?)
+    }
+
+    /// Set a session's display name and remember it for its client_id.
+    /// Returns false if the session doesn't exist. A failed save is logged,
+    /// not returned — the rename still applies for this run.
+    pub fn rename_session(&mut self, session_id: &str, display_name: &str) -> bool {
+        let Some(client_id) = self.sessions.with(session_id, |session| {
+            session.info.display_name = Some(display_name.to_string());
+            session.info.client_id.clone()
+        }) else {
+            return false;
+        };
+        if let Some(client_id) = client_id {
+            self.display_names
+                .insert(client_id, display_name.to_string());
+            if let Err(e) = self.save_display_names() {
+                tracing::warn!("Failed to persist session display names: {}", e);
+            }
+        }
+        true
     }
 
     /// Record a tool dispatch with its routing context. Bounded to 50 entries
@@ -133,6 +1382,36 @@ impl AppState {
         });
     }
 
+    /// The execution lock for a session, created on first use.
+    pub fn exec_lock(&mut self, session_id: &str) -> Arc<Mutex<()>> {
+        self.exec_locks
+            .entry(session_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Remember a dispatched call for export_history, redacting secrets first.
+    pub fn record_call(
+        &mut self,
+        tool: &str,
+        args: &serde_json::Value,
+        target_session: Option<&str>,
+    ) {
+        let at_unix_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        if self.call_history.len() >= MAX_CALL_HISTORY {
+            self.call_history.pop_front();
+        }
+        self.call_history.push_back(HistoryEntry {
+            at_unix_ms,
+            tool: tool.to_string(),
+            args: redact_args(args),
+            target_session: target_session.map(|s| s.to_string()),
+        });
+    }
+
     // ═══════════════════════════════════════════
     // SESSION MANAGEMENT
     // ═══════════════════════════════════════════
@@ -150,13 +1429,14 @@ impl AppState {
         if reg.place_id != 0 {
             let duplicates: Vec<String> = self
                 .sessions
-                .iter()
-                .filter(|(id, s)| {
-                    *id != &reg.session_id
-                        && s.info.place_id == reg.place_id
-                        && s.info.place_name == reg.place_name
+                .list()
+                .into_iter()
+                .filter(|info| {
+                    info.session_id != reg.session_id
+                        && info.place_id == reg.place_id
+                        && info.place_name == reg.place_name
                 })
-                .map(|(id, _)| id.clone())
+                .map(|info| info.session_id)
                 .collect();
 
             for dup_id in duplicates {
@@ -165,6 +1445,16 @@ impl AppState {
             }
         }
 
+        // The same session now reports a different place (Studio opened
+        // another file): what was cached for it no longer applies
+        if self
+            .sessions
+            .with(&reg.session_id, |s| s.info.place_id != reg.place_id)
+            .unwrap_or(false)
+        {
+            self.datastore_lists.remove(&reg.session_id);
+        }
+
         let (notify_tx, notify_rx) = watch::channel(false);
         let session_id = reg.session_id.clone();
 
@@ -178,14 +1468,27 @@ impl AppState {
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_secs(),
+                display_name: reg
+                    .client_id
+                    .as_ref()
+                    .and_then(|cid| self.display_names.get(cid).cloned()),
+                client_id: reg.client_id,
             },
             last_heartbeat: std::time::Instant::now(),
             request_queue: VecDeque::new(),
             notify_tx,
             notify_rx,
+            supported_tools: reg.supported_tools.map(|t| t.into_iter().collect()),
+            plugin_version: reg.plugin_version,
+            in_flight: HashMap::new(),
+            websocket: false,
+            responses: HashMap::new(),
+            progress: HashMap::new(),
+            progress_sinks: HashMap::new(),
+            chunks: ResponseChunks::default(),
         };
 
-        self.sessions.insert(session_id.clone(), session);
+        self.sessions.insert(session);
 
         // Auto-activate if no active session, or if current active session is stale/dead
         if self.active_session.is_none() || !self.is_plugin_connected() {
@@ -203,10 +1506,12 @@ impl AppState {
     /// Unregister a session (plugin disconnected)
     pub fn unregister_session(&mut self, session_id: &str) {
         self.sessions.remove(session_id);
+        self.exec_locks.remove(session_id);
+        self.datastore_lists.remove(session_id);
 
         // If the active session was removed, switch to another or None
         if self.active_session.as_deref() == Some(session_id) {
-            self.active_session = self.sessions.keys().next().cloned();
+            self.active_session = self.sessions.ids().into_iter().next();
             if let Some(ref new_active) = self.active_session {
                 tracing::info!("Active session switched to: {}", new_active);
             } else {
@@ -217,9 +1522,21 @@ impl AppState {
         tracing::info!("Session unregistered: {}", session_id);
     }
 
-    /// Switch the active session
-    pub fn switch_session(&mut self, session_id: &str) -> bool {
-        if self.sessions.contains_key(session_id) {
+    /// Switch the active session, remembering the previous one in `conn`'s
+    /// history
+    pub fn switch_session(&mut self, conn: &mut Connection, session_id: &str) -> bool {
+        if self.sessions.contains(session_id) {
+            self.close_stale_macro(conn, session_id);
+            self.datastore_lists.clear();
+            if let Some(previous) = self.active_session.take() {
+                if previous != session_id {
+                    conn.session_history.retain(|id| *id != previous);
+                    conn.session_history.push_back(previous);
+                    if conn.session_history.len() > MAX_SESSION_HISTORY {
+                        conn.session_history.pop_front();
+                    }
+                }
+            }
             self.active_session = Some(session_id.to_string());
             tracing::info!("Switched to session: {}", session_id);
             true
@@ -228,9 +1545,72 @@ impl AppState {
         }
     }
 
-    /// Get info about all connected sessions
-    pub fn list_sessions(&self) -> Vec<SessionInfo> {
-        self.sessions.values().map(|s| s.info.clone()).collect()
+    /// Switch to the most recently active session in `conn`'s history that
+    /// still exists, discarding entries for sessions that have gone away.
+    /// Returns the session switched to, or None when there is nowhere to go
+    /// back to.
+    pub fn switch_back(&mut self, conn: &mut Connection) -> Option<String> {
+        while let Some(previous) = conn.session_history.pop_back() {
+            if self.active_session.as_deref() == Some(previous.as_str()) {
+                continue;
+            }
+            if self.switch_session(conn, &previous) {
+                return Some(previous);
+            }
+        }
+        None
+    }
+
+    /// Close an open macro of `conn` that belongs to a session other than
+    /// `new_target`.
+    /// The end_macro request is queued fire-and-forget on the old session so
+    /// its plugin commits the recording; nobody waits for the response.
+    pub fn close_stale_macro(&self, conn: &mut Connection, new_target: &str) {
+        conn.prune_macro(&self.sessions);
+        let stale = match &conn.active_macro {
+            Some(m) => m.session_id.as_deref().is_some_and(|sid| sid != new_target),
+            None => false,
+        };
+        if !stale {
+            return;
+        }
+        let Some(m) = conn.active_macro.take() else {
+            return;
+        };
+        let old_session = m.session_id.unwrap_or_default();
+        tracing::info!(
+            "Auto-closing macro '{}' on session {} (routing moved to {})",
+            m.name,
+            old_session,
+            new_target
+        );
+        let _ = self.sessions.queue_request(
+            &old_session,
+            "end_macro",
+            serde_json::json!({ "name": m.name }),
+            crate::tools::DEFAULT_TIMEOUT,
+        );
+    }
+
+    /// How an external tool reaches `session_id` through this server: where
+    /// the plugin polls, where to POST tool calls with `target_session`, and
+    /// the Authorization header both need when auth is on. Null until the HTTP listener is bound (and always in proxy mode, whose
+    /// listings come from the primary).
+    pub fn session_endpoint(&self, session_id: &str) -> serde_json::Value {
+        let Some(base) = &self.http_base_url else {
+            return serde_json::Value::Null;
+        };
+        serde_json::json!({
+            "base_url": base,
+            "server_role": "primary",
+            "session_id": session_id,
+            "poll_url": format!("{}/request?session_id={}", base, session_id),
+            "tool_call_url": format!("{}/proxy/tool_call", base),
+            "tool_call_body": { "target_session": session_id },
+            "auth": self.auth.is_some().then_some(
+                "Authorization: Bearer <token> required on both URLs (token in ~/.studiolink/token)",
+            ),
+        })
     }
 
     /// Get the active session ID
@@ -239,11 +1619,9 @@ impl AppState {
     }
 
     /// Get info about the active session
-    pub fn get_active_session_info(&self) -> Option<&SessionInfo> {
-        self.active_session
-            .as_ref()
-            .and_then(|id| self.sessions.get(id))
-            .map(|s| &s.info)
+    pub fn get_active_session_info(&self) -> Option<SessionInfo> {
+        let id = self.active_session.as_ref()?;
+        self.sessions.with(id, |s| s.info.clone())
     }
 
     // ═══════════════════════════════════════════
@@ -252,112 +1630,42 @@ impl AppState {
 
     /// Queue a request to the active session and return a receiver for the response.
     /// Kept for legacy callers (e.g. handle_proxy_tool_call before v0.6 routing); current
-    /// code paths use queue_request_to_session with explicit resolution.
+    /// code paths use Sessions::queue_request with explicit resolution.
     #[allow(dead_code)]
     pub fn queue_request(
-        &mut self,
-        tool: &str,
-        args: serde_json::Value,
-    ) -> Option<(String, ResponseReceiver)> {
-        let session_id = self.active_session.clone()?;
-        self.queue_request_to_session(&session_id, tool, args)
-    }
-
-    /// Queue a request to a specific session
-    pub fn queue_request_to_session(
-        &mut self,
-        session_id: &str,
+        &self,
         tool: &str,
         args: serde_json::Value,
+        timeout: std::time::Duration,
     ) -> Option<(String, ResponseReceiver)> {
-        let session = self.sessions.get_mut(session_id)?;
-
-        let id = Uuid::new_v4().to_string();
-        let request = PluginRequest {
-            id: id.clone(),
-            tool: tool.to_string(),
-            args,
-            target_session: None,
-        };
-
-        let (tx, rx) = mpsc::unbounded_channel();
-        self.response_channels.insert(id.clone(), tx);
-        session.request_queue.push_back(request);
-
-        // Notify this session's plugin
-        let _ = session.notify_tx.send(true);
-
-        Some((id, rx))
-    }
-
-    /// Get the next pending request for a specific session (called by plugin polling)
-    pub fn get_pending_request_for_session(&mut self, session_id: &str) -> Option<PluginRequest> {
-        self.sessions
-            .get_mut(session_id)
-            .and_then(|s| s.request_queue.pop_front())
-    }
-
-    /// Deliver a response from the plugin to the waiting tool handler
-    pub fn deliver_response(&mut self, response: PluginResponse) -> bool {
-        if let Some(tx) = self.response_channels.remove(&response.id) {
-            tx.send(response).is_ok()
-        } else {
-            tracing::warn!("No response channel found for request {}", response.id);
-            false
-        }
-    }
-
-    /// Update heartbeat for a specific session
-    pub fn heartbeat(&mut self, session_id: &str) {
-        if let Some(session) = self.sessions.get_mut(session_id) {
-            session.last_heartbeat = std::time::Instant::now();
-        }
+        let session_id = self.active_session.as_deref()?;
+        self.sessions.queue_request(session_id, tool, args, timeout)
     }
 
-    /// Check if a session is connected (heartbeat within last 45 seconds)
-    /// Increased from 30s to 45s to handle play mode transitions and long tool execution
-    pub fn is_session_connected(&self, session_id: &str) -> bool {
-        self.sessions
-            .get(session_id)
-            .map(|s| s.last_heartbeat.elapsed().as_secs() < 45)
-            .unwrap_or(false)
+    /// Error with a retry hint when a session's queue is at --max-queue-depth,
+    /// so callers back off instead of piling more work on a lagging plugin.
+    pub fn queue_full_error(&self, session_id: &str) -> Option<String> {
+        let depth = self.sessions.queue_depth(session_id);
+        (depth >= self.max_queue_depth).then(|| {
+            format!(
+                "session '{}' has {} requests queued (max {}); the plugin is falling behind. \
+                 Retry in a few seconds.",
+                session_id, depth, self.max_queue_depth
+            )
+        })
     }
 
     /// Check if the active session is connected
     pub fn is_plugin_connected(&self) -> bool {
         self.active_session
             .as_ref()
-            .map(|id| self.is_session_connected(id))
+            .map(|id| self.sessions.is_connected(id))
             .unwrap_or(false)
     }
 
-    /// Get the notify_rx for a specific session (for long polling)
-    pub fn get_session_notify_rx(&self, session_id: &str) -> Option<watch::Receiver<bool>> {
-        self.sessions.get(session_id).map(|s| s.notify_rx.clone())
-    }
-
-    /// Clean up expired response channels
+    /// Clean up expired response channels and disconnected sessions
     pub fn cleanup_expired(&mut self) {
-        self.response_channels.retain(|id, tx| {
-            if tx.is_closed() {
-                tracing::debug!("Cleaning up expired channel for request {}", id);
-                false
-            } else {
-                true
-            }
-        });
-
-        // Clean up disconnected sessions (no heartbeat for 120 seconds)
-        // Increased from 60s to 120s to survive play mode transitions where
-        // HTTP polling may be interrupted during Studio state changes
-        let stale: Vec<String> = self
-            .sessions
-            .iter()
-            .filter(|(_, s)| s.last_heartbeat.elapsed().as_secs() > 120)
-            .map(|(id, _)| id.clone())
-            .collect();
-
-        for id in stale {
+        for id in self.sessions.expire() {
             tracing::info!("Removing stale session: {}", id);
             self.unregister_session(&id);
         }
@@ -368,18 +1676,41 @@ impl AppState {
 mod tests {
     use super::*;
 
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
     fn make_state() -> AppState {
         let (global_notify_tx, _) = watch::channel(false);
         AppState {
-            sessions: HashMap::new(),
+            sessions: Arc::default(),
             active_session: None,
-            response_channels: HashMap::new(),
             global_notify_tx,
             proxy_mode: false,
             proxy_url: String::new(),
             proxy_client: None,
             routing_log: VecDeque::new(),
-            bound_session_id: None,
+            connection: SharedConnection::default(),
+            display_names: HashMap::new(),
+            display_names_path: None,
+            snapshot_store: None,
+            require_allowlist: None,
+            session_groups: HashMap::new(),
+            call_history: VecDeque::new(),
+            exec_locks: HashMap::new(),
+            lint_watch: LintWatchLog::default(),
+            started_at: std::time::Instant::now(),
+            result_pages: ResultPages::default(),
+            max_queue_depth: DEFAULT_MAX_QUEUE_DEPTH,
+            datastore_lists: HashMap::new(),
+            http_base_url: None,
+            retry_policies: crate::tools::default_retry_policies(),
+            retry_stats: HashMap::new(),
+            auth: None,
+            proxy_token: None,
+            read_only: false,
+            tool_filter: Default::default(),
+            open_cloud: None,
+            sync_watches: HashMap::new(),
+            sync_events: Default::default(),
         }
     }
 
@@ -389,6 +1720,9 @@ mod tests {
             place_id,
             place_name: place_name.to_string(),
             game_id: 0,
+            client_id: None,
+            supported_tools: None,
+            plugin_version: None,
         }
     }
 
@@ -399,22 +1733,597 @@ mod tests {
         let mut s = make_state();
         s.register_session(make_reg("a", 0, "Unknown Place"));
         s.register_session(make_reg("b", 0, "Unknown Place"));
-        assert!(s.sessions.contains_key("a"));
-        assert!(s.sessions.contains_key("b"));
+        assert!(s.sessions.contains("a"));
+        assert!(s.sessions.contains("b"));
         assert_eq!(s.sessions.len(), 2);
     }
 
+    #[test]
+    fn switch_back_skips_departed_sessions() {
+        let mut s = make_state();
+        for id in ["a", "b", "c"] {
+            s.register_session(make_reg(id, 0, "Unknown Place"));
+        }
+        let mut conn = Connection::default();
+        assert!(s.switch_session(&mut conn, "a"));
+        assert!(s.switch_session(&mut conn, "b"));
+        assert!(s.switch_session(&mut conn, "c"));
+
+        // Going back toggles between the last two windows
+        assert_eq!(s.switch_back(&mut conn).as_deref(), Some("b"));
+        assert_eq!(s.switch_back(&mut conn).as_deref(), Some("c"));
+
+        s.unregister_session("b");
+        assert!(s.switch_session(&mut conn, "c"));
+        assert_eq!(s.switch_back(&mut conn).as_deref(), Some("a"));
+        assert_eq!(s.switch_back(&mut conn).as_deref(), Some("c"));
+        s.unregister_session("a");
+        assert_eq!(s.switch_back(&mut conn), None);
+
+        // Another connection has its own history
+        assert_eq!(s.switch_back(&mut Connection::default()), None);
+    }
+
+    #[test]
+    fn unregistering_fails_waiting_calls() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 1, "GameA"));
+        s.register_session(make_reg("b", 2, "GameB"));
+        let (_, mut a_rx) = s
+            .sessions
+            .queue_request("a", "get_studio_mode", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        let (_, mut b_rx) = s
+            .sessions
+            .queue_request("b", "get_studio_mode", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+
+        s.unregister_session("a");
+        let response = a_rx.try_recv().unwrap();
+        assert!(!response.success);
+        assert!(response.error.unwrap().contains("disconnected"));
+        assert!(b_rx.try_recv().is_err());
+        assert_eq!(s.sessions.awaiting_responses(), 1);
+    }
+
+    #[test]
+    fn in_flight_request_keeps_session_connected() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 0, "Unknown Place"));
+        s.sessions.with("a", |a| {
+            a.last_heartbeat = std::time::Instant::now() - std::time::Duration::from_secs(200)
+        });
+        assert!(!s.sessions.is_connected("a"));
+
+        let (id, _rx) = s
+            .sessions
+            .queue_request("a", "workspace_analyze", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        assert!(s.sessions.is_connected("a"));
+        s.cleanup_expired();
+        assert!(s.sessions.contains("a"));
+
+        s.sessions.deliver_response(PluginResponse {
+            id,
+            success: true,
+            result: serde_json::json!({}),
+            error: None,
+        });
+        assert!(!s.sessions.is_connected("a"));
+
+        // An abandoned request stops counting once its timeout passes
+        let _lost = s.sessions.queue_request(
+            "a",
+            "workspace_analyze",
+            serde_json::json!({}),
+            std::time::Duration::ZERO,
+        );
+        assert!(!s.sessions.is_connected("a"));
+    }
+
+    #[test]
+    fn requests_are_in_flight_before_the_plugin_can_take_them() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 0, "Unknown Place"));
+        let (id, _rx) = s
+            .sessions
+            .queue_request("a", "workspace_analyze", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        // A poll taking the request at once still leaves it named in flight
+        assert_eq!(s.sessions.next_request("a").unwrap().id, id);
+        let pending = s.sessions.pending_requests(Some("a"));
+        assert_eq!(pending[0].tool, "workspace_analyze");
+        assert_eq!(pending[0].state, "dispatched");
+
+        s.sessions.deliver_response(PluginResponse {
+            id,
+            success: true,
+            result: serde_json::json!({}),
+            error: None,
+        });
+        assert!(s.sessions.pending_requests(None).is_empty());
+        assert!(!s.sessions.with("a", |a| a.has_request_in_flight()).unwrap());
+    }
+
+    #[test]
+    fn cancel_request_dequeues_or_signals_the_plugin() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 0, "Unknown Place"));
+        let (running, mut running_rx) = s
+            .sessions
+            .queue_request("a", "workspace_analyze", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        let (waiting, mut waiting_rx) = s
+            .sessions
+            .queue_request("a", "lint_scripts", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        // The plugin takes the first request
+        assert_eq!(s.sessions.next_request("a").unwrap().id, running);
+
+        let pending = s.sessions.pending_requests(Some("a"));
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].tool, "workspace_analyze");
+        assert_eq!(pending[0].state, "dispatched");
+        assert_eq!(pending[1].state, "queued");
+
+        let cancelled = s.sessions.cancel_request(&waiting).unwrap();
+        assert_eq!(cancelled.state, "queued");
+        assert_eq!(s.sessions.queue_depth("a"), 0);
+        let response = waiting_rx.try_recv().unwrap();
+        assert!(!response.success);
+        assert!(response.error.unwrap().contains("cancelled"));
+
+        let cancelled = s.sessions.cancel_request(&running).unwrap();
+        assert_eq!(cancelled.state, "dispatched");
+        let signal = s.sessions.next_request("a").unwrap();
+        assert_eq!(signal.tool, CANCEL_TOOL);
+        assert_eq!(signal.args["requestId"], running.as_str());
+        assert!(!running_rx.try_recv().unwrap().success);
+
+        assert!(s.sessions.pending_requests(None).is_empty());
+        assert!(s.sessions.cancel_request(&running).is_none());
+    }
+
+    #[test]
+    fn queue_depth_is_reported_and_capped() {
+        let mut s = make_state();
+        s.max_queue_depth = 2;
+        s.register_session(make_reg("a", 0, "Unknown Place"));
+        for _ in 0..2 {
+            s.sessions
+                .queue_request("a", "get_studio_mode", serde_json::json!({}), TIMEOUT);
+        }
+        let err = s.queue_full_error("a").unwrap();
+        assert!(err.contains("2 requests queued (max 2)"), "{}", err);
+        assert!(err.contains("Retry"));
+
+        let next = s.sessions.next_request("a").unwrap();
+        assert_eq!(next.queue_depth, Some(1));
+        assert!(s.queue_full_error("a").is_none());
+        assert_eq!(s.sessions.queue_depth("missing"), 0);
+    }
+
     #[test]
     fn published_place_dedup_still_works() {
         // Regression for a62143c: re-registering same published place evicts the zombie.
         let mut s = make_state();
         s.register_session(make_reg("old", 12345, "MyGame"));
         s.register_session(make_reg("new", 12345, "MyGame"));
-        assert!(!s.sessions.contains_key("old"));
-        assert!(s.sessions.contains_key("new"));
+        assert!(!s.sessions.contains("old"));
+        assert!(s.sessions.contains("new"));
         assert_eq!(s.sessions.len(), 1);
     }
 
+    #[test]
+    fn datastore_lists_dropped_on_switches_and_place_changes() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 1, "GameA"));
+        s.register_session(make_reg("b", 2, "GameB"));
+        let cached = (
+            std::time::Instant::now(),
+            serde_json::json!({ "stores": [] }),
+        );
+        s.datastore_lists.insert("a".into(), cached.clone());
+        s.datastore_lists.insert("b".into(), cached);
+
+        // "b" reconnects with the same id but another place open
+        s.register_session(make_reg("b", 3, "GameC"));
+        assert!(s.datastore_lists.contains_key("a"));
+        assert!(!s.datastore_lists.contains_key("b"));
+
+        assert!(s.switch_session(&mut Connection::default(), "b"));
+        assert!(s.datastore_lists.is_empty());
+    }
+
+    #[test]
+    fn switch_session_closes_stale_macro() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 1, "GameA"));
+        s.register_session(make_reg("b", 2, "GameB"));
+        let mut conn = Connection {
+            active_macro: Some(MacroState {
+                name: "build lobby".to_string(),
+                session_id: Some("a".to_string()),
+                started_at: std::time::Instant::now(),
+            }),
+            ..Default::default()
+        };
+        assert!(s.switch_session(&mut conn, "b"));
+        assert!(conn.active_macro.is_none());
+        let queued = s.sessions.next_request("a").unwrap();
+        assert_eq!(queued.tool, "end_macro");
+        assert_eq!(queued.args["name"], "build lobby");
+    }
+
+    #[test]
+    fn stream_relays_lines_until_closed() {
+        let s = Sessions::default();
+        let (id, mut rx) = s.open_stream();
+        assert_eq!(
+            s.push_stream_lines(&id, "{\"id\":1}\n\n{\"id\":2}\n"),
+            Some(2)
+        );
+        assert_eq!(s.open_streams(), 1);
+        s.close_stream(&id);
+        assert_eq!(rx.try_recv().unwrap(), "{\"id\":1}");
+        assert_eq!(rx.try_recv().unwrap(), "{\"id\":2}");
+        assert!(rx.try_recv().is_err());
+        assert_eq!(s.push_stream_lines(&id, "{}"), None);
+    }
+
+    #[test]
+    fn display_name_survives_reconnect_by_client_id() {
+        let mut s = make_state();
+        let mut reg = make_reg("first", 1, "GameA");
+        reg.client_id = Some("client-1".to_string());
+        s.register_session(reg);
+        assert!(s.rename_session("first", "Combat Test"));
+        s.unregister_session("first");
+
+        let mut reg = make_reg("second", 1, "GameA");
+        reg.client_id = Some("client-1".to_string());
+        s.register_session(reg);
+        let info = s.sessions.with("second", |s| s.info.clone()).unwrap();
+        assert_eq!(info.display_name.as_deref(), Some("Combat Test"));
+        assert_eq!(info.place_name, "GameA");
+        assert!(!s.rename_session("missing", "x"));
+    }
+
+    #[test]
+    fn progress_frames_attach_to_response() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 1, "GameA"));
+        let (id, mut rx) = s
+            .sessions
+            .queue_request("a", "mass_set_property", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        let frame = |current| ProgressFrame {
+            request_id: id.clone(),
+            current,
+            total: Some(2000),
+            message: None,
+        };
+        assert!(s.sessions.record_progress(frame(1000)));
+        assert!(s.sessions.record_progress(frame(2000)));
+        s.sessions.deliver_response(PluginResponse {
+            id: id.clone(),
+            success: true,
+            result: serde_json::json!({ "updated": 2000 }),
+            error: None,
+        });
+        let response = rx.try_recv().unwrap();
+        assert_eq!(response.result["progress"][1]["current"], 2000);
+        assert!(s.sessions.with("a", |a| a.progress.is_empty()).unwrap());
+        // Request no longer in flight
+        assert!(!s.sessions.record_progress(frame(1)));
+    }
+
+    #[test]
+    fn chunked_responses_are_reassembled_in_any_order() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 1, "GameA"));
+        let (id, mut rx) = s
+            .sessions
+            .queue_request("a", "get_file_tree", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        let text = serde_json::to_string(&PluginResponse {
+            id: id.clone(),
+            success: true,
+            result: serde_json::json!({ "services": vec!["Wörkspace"; 50] }),
+            error: None,
+        })
+        .unwrap();
+        let parts: Vec<String> = text
+            .chars()
+            .collect::<Vec<_>>()
+            .chunks(200)
+            .map(|c| c.iter().collect())
+            .collect();
+        let chunk = |index: usize| ResponseChunk {
+            request_id: id.clone(),
+            index: index as u32,
+            total: parts.len() as u32,
+            data: parts[index].clone(),
+        };
+
+        for index in (1..parts.len()).rev() {
+            let progress = s
+                .sessions
+                .deliver_response_chunk(chunk(index))
+                .unwrap()
+                .unwrap();
+            assert!(matches!(progress, ChunkProgress::Waiting { .. }));
+        }
+        // A resent part doesn't count twice
+        assert_eq!(
+            s.sessions.deliver_response_chunk(chunk(1)).unwrap(),
+            Ok(ChunkProgress::Waiting {
+                received: parts.len() as u32 - 1,
+                total: parts.len() as u32
+            })
+        );
+        assert!(rx.try_recv().is_err());
+        let last = s
+            .sessions
+            .deliver_response_chunk(chunk(0))
+            .unwrap()
+            .unwrap();
+        assert!(matches!(last, ChunkProgress::Complete(_)));
+        let response = rx.try_recv().unwrap();
+        assert_eq!(response.result["services"][49], "Wörkspace");
+
+        // Answered: further parts have nowhere to go
+        assert!(s.sessions.deliver_response_chunk(chunk(0)).is_none());
+        let mut bad = chunk(0);
+        bad.index = bad.total;
+        assert!(ResponseChunks::default().add(bad).is_err());
+    }
+
+    #[test]
+    fn undecodable_chunked_response_fails_the_call() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 1, "GameA"));
+        let (id, mut rx) = s
+            .sessions
+            .queue_request("a", "snapshot_take", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        s.sessions.deliver_response_chunk(ResponseChunk {
+            request_id: id.clone(),
+            index: 0,
+            total: 1,
+            data: "{\"id\": ".into(),
+        });
+        let response = rx.try_recv().unwrap();
+        assert!(!response.success);
+        assert!(response.error.unwrap().contains("didn't decode"));
+    }
+
+    #[test]
+    fn progress_frames_reach_the_mcp_sink() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 1, "GameA"));
+        let (id, _rx) = s
+            .sessions
+            .queue_request("a", "workspace_analyze", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        let (tx, mut notifications) = mpsc::unbounded_channel();
+        let token = rmcp::model::ProgressToken(rmcp::model::NumberOrString::Number(7));
+        s.sessions.attach_progress_sink(
+            "a",
+            &id,
+            ProgressSink {
+                token: token.clone(),
+                tx,
+            },
+        );
+
+        assert!(s.sessions.record_progress(ProgressFrame {
+            request_id: id.clone(),
+            current: 2,
+            total: Some(6),
+            message: Some("Analyzing issues".into()),
+        }));
+        let sent = notifications.try_recv().unwrap();
+        assert_eq!(sent.progress_token, token);
+        assert_eq!(sent.progress, 2.0);
+        assert_eq!(sent.total, Some(6.0));
+
+        // Finishing the request drops the sink, closing the channel
+        s.sessions.finish_in_flight(&id);
+        assert!(notifications.try_recv().is_err());
+        assert!(s
+            .sessions
+            .with("a", |a| a.progress_sinks.is_empty())
+            .unwrap());
+    }
+
+    #[test]
+    fn result_pages_split_on_char_boundaries() {
+        let mut pages = ResultPages::default();
+        let text = "é".repeat(RESULT_PAGE_BYTES); // 2 bytes each
+        let (token, count) = pages.store(&text);
+        assert_eq!(count, 2);
+        let (first, total) = pages.page(&token, 0).unwrap();
+        let (second, _) = pages.page(&token, 1).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(first.len(), RESULT_PAGE_BYTES);
+        assert_eq!(first + &second, text);
+        assert!(pages.page(&token, 2).is_none());
+        assert!(pages.page("unknown", 0).is_none());
+    }
+
+    #[test]
+    fn result_pages_evict_oldest_past_the_byte_cap() {
+        let mut pages = ResultPages::default();
+        let quarter = "x".repeat(MAX_RESULT_PAGE_STORE_BYTES / 4);
+        let tokens: Vec<String> = (0..4).map(|_| pages.store(&quarter).0).collect();
+        assert_eq!(pages.bytes, MAX_RESULT_PAGE_STORE_BYTES);
+
+        // One more evicts only the oldest
+        let newest = pages.store(&quarter).0;
+        assert!(pages.page(&tokens[0], 0).is_none());
+        assert!(pages.page(&tokens[1], 0).is_some());
+        assert!(pages.page(&newest, 0).is_some());
+        assert_eq!(pages.bytes, MAX_RESULT_PAGE_STORE_BYTES);
+    }
+
+    #[test]
+    fn change_log_records_only_successful_mutations() {
+        let mut s = make_state();
+        s.register_session(make_reg("a", 1, "GameA"));
+        let respond = |s: &mut AppState, id: String, success: bool, result| {
+            s.sessions.deliver_response(PluginResponse {
+                id,
+                success,
+                result,
+                error: None,
+            });
+        };
+
+        let (id, _rx) = s
+            .sessions
+            .queue_request(
+                "a",
+                "create_instance",
+                serde_json::json!({ "className": "Part", "parentPath": "Workspace" }),
+                TIMEOUT,
+            )
+            .unwrap();
+        respond(
+            &mut s,
+            id,
+            true,
+            serde_json::json!({ "created": true, "fullName": "Workspace.Part" }),
+        );
+        let (id, _rx) = s
+            .sessions
+            .queue_request(
+                "a",
+                "delete_instance",
+                serde_json::json!({ "path": "X" }),
+                TIMEOUT,
+            )
+            .unwrap();
+        respond(&mut s, id, false, serde_json::Value::Null);
+        let (id, _rx) = s
+            .sessions
+            .queue_request("a", "get_file_tree", serde_json::json!({}), TIMEOUT)
+            .unwrap();
+        respond(&mut s, id, true, serde_json::json!({}));
+        // A result naming an instance doesn't move a non-creating change
+        let (id, _rx) = s
+            .sessions
+            .queue_request(
+                "a",
+                "set_property",
+                serde_json::json!({ "path": "Workspace.Part", "property": "Anchored" }),
+                TIMEOUT,
+            )
+            .unwrap();
+        respond(
+            &mut s,
+            id,
+            true,
+            serde_json::json!({ "fullName": "Workspace.Other" }),
+        );
+
+        let log = s.sessions.changes();
+        let (changes, missed) = log.since("a", 0);
+        assert!(!missed);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, "created");
+        assert_eq!(changes[0].path.as_deref(), Some("Workspace.Part"));
+        assert_eq!(changes[0].parent.as_deref(), Some("Workspace"));
+        assert_eq!(changes[1].kind, "modified");
+        assert_eq!(changes[1].path.as_deref(), Some("Workspace.Part"));
+        assert_eq!(changes[1].parent, None);
+        assert_eq!(log.latest(), 2);
+        assert!(log.since("a", 2).0.is_empty());
+        assert!(log.since("b", 0).0.is_empty());
+    }
+
+    #[test]
+    fn change_log_tracks_evictions_per_session() {
+        let mut log = ChangeLog::default();
+        let commit = |log: &mut ChangeLog, id: &str, session: &str| {
+            log.expect(
+                id,
+                session,
+                "delete_instance",
+                &serde_json::json!({ "path": "X" }),
+            );
+            log.settle(&PluginResponse {
+                id: id.to_string(),
+                success: true,
+                result: serde_json::json!({}),
+                error: None,
+            });
+        };
+        // Interleaved sessions: b's first entry has seq 2, but nothing of b's
+        // was ever evicted
+        for n in 0..=MAX_CHANGES_PER_SESSION {
+            commit(&mut log, &format!("a{}", n), "a");
+            if n == 0 {
+                commit(&mut log, "b0", "b");
+            }
+        }
+        let (entries, missed) = log.since("b", 0);
+        assert_eq!((entries.len(), missed), (1, false));
+        assert!(log.since("a", 0).1);
+        let first_kept = log.since("a", 0).0[0].seq;
+        assert!(!log.since("a", first_kept - 1).1);
+
+        // A request that times out leaves nothing behind to settle later
+        log.expect(
+            "late",
+            "b",
+            "delete_instance",
+            &serde_json::json!({ "path": "Y" }),
+        );
+        log.forget("late");
+        assert!(log.pending.is_empty());
+    }
+
+    #[test]
+    fn unsupported_tool_reported_only_when_list_known() {
+        let mut s = make_state();
+        s.register_session(make_reg("legacy", 1, "GameA"));
+        let mut reg = make_reg("new", 2, "GameB");
+        reg.supported_tools = Some(vec!["run_code".to_string()]);
+        reg.plugin_version = Some("0.7.0".to_string());
+        s.register_session(reg);
+
+        let unsupported = |id: &str, tool: &str| {
+            s.sessions
+                .with(id, |session| session.unsupported_tool_error(tool))
+                .unwrap()
+        };
+        assert!(unsupported("legacy", "begin_macro").is_none());
+        assert!(unsupported("new", "run_code").is_none());
+        let msg = unsupported("new", "begin_macro").unwrap();
+        assert!(msg.contains("'begin_macro'") && msg.contains("v0.7.0"));
+    }
+
+    #[test]
+    fn call_history_redacts_sensitive_args() {
+        let mut s = make_state();
+        s.record_call(
+            "datastore_set",
+            &serde_json::json!({
+                "key": "Player_1",
+                "apiKey": "abc",
+                "nested": [{ "authToken": "xyz", "value": 5 }],
+            }),
+            Some("a"),
+        );
+        let entry = &s.call_history[0];
+        assert_eq!(entry.args["key"], "Player_1");
+        assert_eq!(entry.args["apiKey"], "[REDACTED]");
+        assert_eq!(entry.args["nested"][0]["authToken"], "[REDACTED]");
+        assert_eq!(entry.args["nested"][0]["value"], 5);
+        assert_eq!(entry.target_session.as_deref(), Some("a"));
+    }
+
     #[test]
     fn different_published_places_coexist() {
         let mut s = make_state();
@@ -422,4 +2331,30 @@ mod tests {
         s.register_session(make_reg("b", 2, "GameB"));
         assert_eq!(s.sessions.len(), 2);
     }
+
+    #[test]
+    fn session_endpoint_uses_bound_address() {
+        let mut s = make_state();
+        assert!(s.session_endpoint("abc").is_null());
+
+        s.http_base_url = Some("http://127.0.0.1:34872".into());
+        let endpoint = s.session_endpoint("abc");
+        assert_eq!(endpoint["server_role"], "primary");
+        assert_eq!(
+            endpoint["poll_url"],
+            "http://127.0.0.1:34872/request?session_id=abc"
+        );
+        assert_eq!(
+            endpoint["tool_call_url"],
+            "http://127.0.0.1:34872/proxy/tool_call"
+        );
+        assert_eq!(endpoint["tool_call_body"]["target_session"], "abc");
+        assert!(endpoint["auth"].is_null());
+
+        s.auth = Some(Arc::new(std::sync::RwLock::new(
+            crate::auth::AuthTokens::new("t".repeat(32), None),
+        )));
+        let auth = s.session_endpoint("abc")["auth"].clone();
+        assert!(auth.as_str().unwrap().contains("Bearer"));
+    }
 }
@@ -7,16 +7,19 @@ use crate::state::AppState;
 
 /// set_my_session — Bind this MCP connection to a specific Studio session_id.
 ///
-/// After binding, every subsequent tool call on this StudioLink instance falls
+/// After binding, every subsequent tool call from this MCP connection falls
 /// back to the bound session_id when no explicit session_id is passed. This is
-/// per-process (each Claude/Cursor chat spawns its own studiolink instance, so
-/// each chat has its own bound session). Pass null/none to clear the binding
-/// and fall back to active_session.
+/// per connection (each Claude/Cursor chat spawns its own studiolink instance
+/// or opens its own streamable-HTTP session, so each chat has its own bound
+/// session). Pass null/none to clear the binding and fall back to
+/// active_session.
 pub async fn set_my_session(
     state: &Arc<Mutex<AppState>>,
     session_id: Option<String>,
 ) -> Result<serde_json::Value> {
-    let mut s = state.lock().await;
+    let s = state.lock().await;
+    let conn = super::connection(&s);
+    let mut conn = conn.lock();
     match session_id {
         Some(sid) => {
             // Validate against the live sessions map ONLY in direct mode. In
@@ -24,20 +27,21 @@ pub async fn set_my_session(
             // round-trip to verify. Trust the caller — if the id is wrong the
             // first real tool call will surface "session_id 'X' not found on
             // primary StudioLink." via the proxy 404 path.
-            if !s.proxy_mode && !s.sessions.contains_key(&sid) {
+            if !s.proxy_mode && !s.sessions.contains(&sid) {
                 return Err(StudioLinkError::InvalidArguments(format!(
                     "session_id '{}' not found. Use list_sessions to see active sessions.",
                     sid
                 )));
             }
-            s.bound_session_id = Some(sid.clone());
+            s.close_stale_macro(&mut conn, &sid);
+            conn.bound_session_id = Some(sid.clone());
             Ok(json!({
                 "bound_session_id": sid,
                 "note": "Subsequent tool calls without explicit session_id will route here.",
             }))
         }
         None => {
-            let prev = s.bound_session_id.take();
+            let prev = conn.bound_session_id.take();
             Ok(json!({
                 "bound_session_id": null,
                 "previous": prev,
@@ -47,11 +51,12 @@ pub async fn set_my_session(
     }
 }
 
-/// get_my_session — Read the current bound_session_id for this MCP instance.
+/// get_my_session — Read the current bound_session_id for this MCP connection.
 pub async fn get_my_session(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
     let s = state.lock().await;
+    let bound = super::connection(&s).lock().bound_session_id.clone();
     Ok(json!({
-        "bound_session_id": s.bound_session_id,
+        "bound_session_id": bound,
         "active_session": s.active_session,
     }))
 }
@@ -0,0 +1,216 @@
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::{send_to_plugin, DEFAULT_TIMEOUT};
+use crate::error::{Result, StudioLinkError};
+use crate::state::AppState;
+
+/// A named bundle of appearance properties, applied in order.
+pub struct AppearancePreset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// (property, value, valueType) as understood by the plugin's set_property
+    pub properties: &'static [(&'static str, PresetValue, &'static str)],
+}
+
+/// Preset values are static, so they're kept as plain data and turned into
+/// JSON only when a preset is expanded.
+#[derive(Clone, Copy)]
+pub enum PresetValue {
+    Number(f64),
+    Color([f64; 3]),
+    Text(&'static str),
+}
+
+impl PresetValue {
+    fn to_json(self) -> serde_json::Value {
+        match self {
+            PresetValue::Number(n) => json!(n),
+            PresetValue::Color(rgb) => json!(rgb),
+            PresetValue::Text(s) => json!(s),
+        }
+    }
+}
+
+use PresetValue::{Color, Number, Text};
+
+pub const PRESETS: &[AppearancePreset] = &[
+    AppearancePreset {
+        name: "glass",
+        description: "Pale blue, half transparent, slightly reflective glass",
+        properties: &[
+            ("Material", Text("Enum.Material.Glass"), "Enum"),
+            ("Color", Color([0.75, 0.85, 0.95]), "Color3"),
+            ("Transparency", Number(0.5), "number"),
+            ("Reflectance", Number(0.2), "number"),
+        ],
+    },
+    AppearancePreset {
+        name: "neon",
+        description: "Glowing cyan neon",
+        properties: &[
+            ("Material", Text("Enum.Material.Neon"), "Enum"),
+            ("Color", Color([0.2, 0.8, 1.0]), "Color3"),
+            ("Transparency", Number(0.0), "number"),
+            ("Reflectance", Number(0.0), "number"),
+        ],
+    },
+    AppearancePreset {
+        name: "metal",
+        description: "Brushed grey metal",
+        properties: &[
+            ("Material", Text("Enum.Material.Metal"), "Enum"),
+            ("Color", Color([0.64, 0.64, 0.66]), "Color3"),
+            ("Transparency", Number(0.0), "number"),
+            ("Reflectance", Number(0.3), "number"),
+        ],
+    },
+    AppearancePreset {
+        name: "gold",
+        description: "Polished gold",
+        properties: &[
+            ("Material", Text("Enum.Material.Metal"), "Enum"),
+            ("Color", Color([0.94, 0.75, 0.25]), "Color3"),
+            ("Transparency", Number(0.0), "number"),
+            ("Reflectance", Number(0.4), "number"),
+        ],
+    },
+    AppearancePreset {
+        name: "wood",
+        description: "Warm brown wood",
+        properties: &[
+            ("Material", Text("Enum.Material.Wood"), "Enum"),
+            ("Color", Color([0.63, 0.45, 0.29]), "Color3"),
+            ("Transparency", Number(0.0), "number"),
+            ("Reflectance", Number(0.0), "number"),
+        ],
+    },
+    AppearancePreset {
+        name: "plastic",
+        description: "Plain smooth grey plastic (Studio's default look)",
+        properties: &[
+            ("Material", Text("Enum.Material.SmoothPlastic"), "Enum"),
+            ("Color", Color([0.64, 0.64, 0.64]), "Color3"),
+            ("Transparency", Number(0.0), "number"),
+            ("Reflectance", Number(0.0), "number"),
+        ],
+    },
+    AppearancePreset {
+        name: "concrete",
+        description: "Matte grey concrete",
+        properties: &[
+            ("Material", Text("Enum.Material.Concrete"), "Enum"),
+            ("Color", Color([0.5, 0.5, 0.5]), "Color3"),
+            ("Transparency", Number(0.0), "number"),
+            ("Reflectance", Number(0.0), "number"),
+        ],
+    },
+    AppearancePreset {
+        name: "ice",
+        description: "Light blue, slightly see-through ice",
+        properties: &[
+            ("Material", Text("Enum.Material.Ice"), "Enum"),
+            ("Color", Color([0.69, 0.87, 0.96]), "Color3"),
+            ("Transparency", Number(0.2), "number"),
+            ("Reflectance", Number(0.1), "number"),
+        ],
+    },
+    AppearancePreset {
+        name: "forcefield",
+        description: "Animated blue ForceField shimmer",
+        properties: &[
+            ("Material", Text("Enum.Material.ForceField"), "Enum"),
+            ("Color", Color([0.3, 0.6, 1.0]), "Color3"),
+            ("Transparency", Number(0.0), "number"),
+            ("Reflectance", Number(0.0), "number"),
+        ],
+    },
+];
+
+/// Expand a preset name (case-insensitive) into the property sets sent to
+/// the plugin: `[{property, value, valueType}]`.
+pub fn expand_preset(name: &str) -> Result<serde_json::Value> {
+    let preset = PRESETS
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| {
+            let names: Vec<&str> = PRESETS.iter().map(|p| p.name).collect();
+            StudioLinkError::InvalidArguments(format!(
+                "unknown appearance preset '{}'; available: {}",
+                name,
+                names.join(", ")
+            ))
+        })?;
+    Ok(json!(preset
+        .properties
+        .iter()
+        .map(|(property, value, value_type)| json!({
+            "property": property,
+            "value": value.to_json(),
+            "valueType": value_type,
+        }))
+        .collect::<Vec<_>>()))
+}
+
+/// list_appearance_presets — Preset names, descriptions and the properties
+/// each one sets. Served locally; no plugin round trip.
+pub fn list_appearance_presets() -> serde_json::Value {
+    let presets: Vec<serde_json::Value> = PRESETS
+        .iter()
+        .map(|p| {
+            json!({
+                "name": p.name,
+                "description": p.description,
+                "properties": expand_preset(p.name).unwrap_or_default(),
+            })
+        })
+        .collect();
+    json!({ "count": presets.len(), "presets": presets })
+}
+
+/// set_appearance_preset — Apply a preset's properties to an existing
+/// instance as one undo step.
+pub async fn set_appearance_preset(
+    state: &Arc<Mutex<AppState>>,
+    path: &str,
+    preset: &str,
+) -> Result<serde_json::Value> {
+    let appearance = expand_preset(preset)?;
+    send_to_plugin(
+        state,
+        None,
+        "set_appearance",
+        json!({ "path": path, "preset": preset.trim().to_lowercase(), "appearance": appearance }),
+        DEFAULT_TIMEOUT,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_expand_to_typed_property_sets() {
+        let glass = expand_preset("Glass").unwrap();
+        assert_eq!(glass[0]["property"], "Material");
+        assert_eq!(glass[0]["value"], "Enum.Material.Glass");
+        assert_eq!(glass[0]["valueType"], "Enum");
+        assert_eq!(glass[1]["value"], json!([0.75, 0.85, 0.95]));
+
+        let err = expand_preset("chrome").unwrap_err().to_string();
+        assert!(
+            err.contains("unknown appearance preset 'chrome'"),
+            "{}",
+            err
+        );
+        assert!(err.contains("glass, neon, metal"), "{}", err);
+
+        let list = list_appearance_presets();
+        assert_eq!(list["count"], PRESETS.len());
+        for preset in list["presets"].as_array().unwrap() {
+            assert!(!preset["properties"].as_array().unwrap().is_empty());
+        }
+    }
+}
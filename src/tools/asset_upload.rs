@@ -0,0 +1,318 @@
+//! asset_upload: local file → Roblox asset through the Open Cloud Assets API.
+//!
+//! The asset type and content type come from the file extension. Open Cloud
+//! creates assets asynchronously, so the upload returns an operation that is
+//! polled until the asset exists and moderation has decided on it; the result
+//! carries the `rbxassetid://` content ID to pass to set_property.
+
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, StudioLinkError};
+use crate::opencloud::{self, AssetCreator, NewAsset};
+
+/// Default and largest time to wait for the operation and moderation.
+const DEFAULT_WAIT: Duration = Duration::from_secs(120);
+const MAX_WAIT: Duration = Duration::from_secs(600);
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Open Cloud rejects anything larger for every asset type.
+const MAX_FILE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// extension → (Open Cloud asset type, content type). Meshes upload as
+/// Models; Roblox imports the geometry into a MeshPart.
+const FILE_TYPES: &[(&str, &str, &str)] = &[
+    ("png", "Decal", "image/png"),
+    ("jpg", "Decal", "image/jpeg"),
+    ("jpeg", "Decal", "image/jpeg"),
+    ("bmp", "Decal", "image/bmp"),
+    ("tga", "Decal", "image/tga"),
+    ("mp3", "Audio", "audio/mpeg"),
+    ("ogg", "Audio", "audio/ogg"),
+    ("wav", "Audio", "audio/wav"),
+    ("flac", "Audio", "audio/flac"),
+    ("fbx", "Model", "model/fbx"),
+    ("obj", "Model", "model/obj"),
+    ("gltf", "Model", "model/gltf+json"),
+    ("glb", "Model", "model/gltf-binary"),
+    ("rbxm", "Model", "model/x-rbxm"),
+    ("rbxmx", "Model", "model/x-rbxm"),
+    ("mp4", "Video", "video/mp4"),
+    ("mov", "Video", "video/mov"),
+];
+
+pub struct AssetUploadOptions {
+    pub file_path: String,
+    /// Checked against the extension when given
+    pub asset_type: Option<String>,
+    /// Default: the file name without its extension
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub creator: AssetCreator,
+    pub wait_secs: Option<u64>,
+}
+
+/// Upload with the key in `STUDIOLINK_OPENCLOUD_KEY`.
+pub async fn asset_upload(options: AssetUploadOptions) -> Result<Value> {
+    let api_key = opencloud::api_key_from_env().ok_or_else(|| {
+        StudioLinkError::InvalidArguments(format!(
+            "asset_upload needs an Open Cloud API key with the asset:write scope in {}",
+            opencloud::API_KEY_ENV
+        ))
+    })?;
+    upload(opencloud::DEFAULT_BASE_URL, &api_key, options).await
+}
+
+async fn upload(base_url: &str, api_key: &str, options: AssetUploadOptions) -> Result<Value> {
+    let path = Path::new(&options.file_path);
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    let &(_, asset_type, content_type) = FILE_TYPES
+        .iter()
+        .find(|(ext, _, _)| *ext == extension)
+        .ok_or_else(|| {
+            let known: Vec<&str> = FILE_TYPES.iter().map(|(ext, _, _)| *ext).collect();
+            StudioLinkError::InvalidArguments(format!(
+                "can't upload .{} files; supported: {}",
+                extension,
+                known.join(", ")
+            ))
+        })?;
+    if let Some(requested) = &options.asset_type {
+        if !requested.eq_ignore_ascii_case(asset_type) {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                ".{} files upload as {}, not {}",
+                extension, asset_type, requested
+            )));
+        }
+    }
+    let size = std::fs::metadata(path)
+        .map_err(|e| {
+            StudioLinkError::InvalidArguments(format!("can't read {}: {}", path.display(), e))
+        })?
+        .len();
+    if size > MAX_FILE_BYTES {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "{} is {} MB; Open Cloud accepts assets up to {} MB",
+            path.display(),
+            size / (1024 * 1024),
+            MAX_FILE_BYTES / (1024 * 1024)
+        )));
+    }
+    let wait = options
+        .wait_secs
+        .map_or(DEFAULT_WAIT, Duration::from_secs)
+        .min(MAX_WAIT);
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let display_name = options.display_name.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| file_name.clone())
+    });
+    let started = Instant::now();
+    let mut operation = opencloud::create_asset(
+        base_url,
+        api_key,
+        NewAsset {
+            asset_type,
+            display_name: display_name.clone(),
+            description: options.description.unwrap_or_default(),
+            creator: options.creator,
+            file_name,
+            content_type,
+            bytes: std::fs::read(path)?,
+        },
+    )
+    .await?;
+
+    let operation_path = operation["path"].as_str().unwrap_or_default().to_string();
+    while operation["done"].as_bool() != Some(true) {
+        if operation_path.is_empty() {
+            return Err(StudioLinkError::ServerError(format!(
+                "Open Cloud returned no operation to poll: {}",
+                operation
+            )));
+        }
+        if started.elapsed() >= wait {
+            return Ok(json!({
+                "done": false,
+                "operationPath": operation_path,
+                "assetType": asset_type,
+                "displayName": display_name,
+                "waitedMs": started.elapsed().as_millis() as u64,
+                "hint": "Roblox is still processing the upload; the asset will appear in the Creator Dashboard when it finishes",
+            }));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+        operation = opencloud::get_asset_operation(base_url, api_key, &operation_path).await?;
+    }
+    if let Some(error) = operation.get("error").filter(|e| !e.is_null()) {
+        return Err(StudioLinkError::ServerError(format!(
+            "Open Cloud failed to create the asset: {}",
+            error["message"]
+                .as_str()
+                .map_or_else(|| error.to_string(), str::to_string)
+        )));
+    }
+
+    let mut asset = operation["response"].clone();
+    let asset_id = asset["assetId"]
+        .as_str()
+        .map(str::to_string)
+        .or_else(|| asset["assetId"].as_u64().map(|id| id.to_string()))
+        .ok_or_else(|| {
+            StudioLinkError::ServerError(format!(
+                "Open Cloud operation finished without an asset ID: {}",
+                operation
+            ))
+        })?;
+    // The asset exists once the operation is done, but moderation may still
+    // be reviewing it
+    while moderation_state(&asset) == "Reviewing" && started.elapsed() < wait {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        asset = opencloud::get_asset(base_url, api_key, &asset_id).await?;
+    }
+    let moderation = moderation_state(&asset);
+    if moderation == "Rejected" {
+        return Err(StudioLinkError::ServerError(format!(
+            "Roblox moderation rejected asset {} ({})",
+            asset_id, display_name
+        )));
+    }
+
+    Ok(json!({
+        "done": true,
+        "assetId": asset_id,
+        "contentId": format!("rbxassetid://{}", asset_id),
+        "assetType": asset_type,
+        "displayName": display_name,
+        "moderationState": moderation,
+        "waitedMs": started.elapsed().as_millis() as u64,
+    }))
+}
+
+/// "Approved", "Rejected" or "Reviewing", whichever spelling Open Cloud used
+/// ("MODERATION_STATE_APPROVED" and the like). Assets without a moderation
+/// result count as approved.
+fn moderation_state(asset: &Value) -> &'static str {
+    let state = asset["moderationResult"]["moderationState"]
+        .as_str()
+        .unwrap_or("Approved")
+        .to_ascii_lowercase();
+    if state.contains("reject") {
+        "Rejected"
+    } else if state.contains("review") {
+        "Reviewing"
+    } else {
+        "Approved"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    type Uploads = Arc<Mutex<Vec<String>>>;
+
+    async fn start() -> (String, Uploads) {
+        let uploads: Uploads = Arc::default();
+        let router = Router::new()
+            .route(
+                "/assets/v1/assets",
+                post(
+                    |State(uploads): State<Uploads>, headers: HeaderMap, body: String| async move {
+                        let content_type = headers["content-type"].to_str().unwrap().to_string();
+                        uploads
+                            .lock()
+                            .unwrap()
+                            .push(format!("{}\n{}", content_type, body));
+                        Json(json!({ "path": "operations/op-1", "done": false }))
+                    },
+                ),
+            )
+            .route(
+                "/assets/v1/operations/op-1",
+                get(|| async {
+                    Json(json!({
+                        "path": "operations/op-1",
+                        "done": true,
+                        "response": {
+                            "assetId": "555",
+                            "moderationResult": { "moderationState": "MODERATION_STATE_APPROVED" },
+                        },
+                    }))
+                }),
+            )
+            .with_state(uploads.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (format!("http://{}", addr), uploads)
+    }
+
+    fn options(file_path: &Path, asset_type: Option<&str>) -> AssetUploadOptions {
+        AssetUploadOptions {
+            file_path: file_path.display().to_string(),
+            asset_type: asset_type.map(str::to_string),
+            display_name: None,
+            description: None,
+            creator: AssetCreator::Group(42),
+            wait_secs: Some(10),
+        }
+    }
+
+    #[tokio::test]
+    async fn uploads_and_waits_for_the_operation() {
+        let (base, uploads) = start().await;
+        let dir = std::env::temp_dir().join(format!("studiolink-asset-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let icon = dir.join("Coin Icon.png");
+        std::fs::write(&icon, b"PNGDATA").unwrap();
+
+        let result = upload(&base, "key", options(&icon, None)).await.unwrap();
+        assert_eq!(result["contentId"], "rbxassetid://555");
+        assert_eq!(result["assetType"], "Decal");
+        assert_eq!(result["moderationState"], "Approved");
+
+        let upload_body = uploads.lock().unwrap()[0].clone();
+        assert!(upload_body.starts_with("multipart/form-data"));
+        assert!(upload_body.contains(r#""assetType":"Decal""#));
+        assert!(upload_body.contains(r#""groupId":"42""#));
+        assert!(upload_body.contains(r#""displayName":"Coin Icon""#));
+        assert!(upload_body.contains("Content-Type: image/png"));
+        assert!(upload_body.contains("PNGDATA"));
+
+        let err = upload(&base, "key", options(&icon, Some("Audio")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, b"hi").unwrap();
+        assert!(upload(&base, "key", options(&notes, None)).await.is_err());
+        assert_eq!(uploads.lock().unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn moderation_spellings() {
+        let state =
+            |s: &str| moderation_state(&json!({ "moderationResult": { "moderationState": s } }));
+        assert_eq!(state("Reviewing"), "Reviewing");
+        assert_eq!(state("MODERATION_STATE_REJECTED"), "Rejected");
+        assert_eq!(moderation_state(&json!({})), "Approved");
+    }
+}
@@ -1,52 +1,468 @@
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use super::profiler::round2;
 use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
-use crate::error::Result;
+use crate::error::{Result, StudioLinkError};
 use crate::state::AppState;
 
+/// Output captured from print/warn before run_code stops capturing (1 MiB).
+const DEFAULT_MAX_OUTPUT_BYTES: u64 = 1024 * 1024;
+
+/// Upper bound for `max_output_bytes`; anything larger defeats the point of
+/// the cap and risks the response itself blowing up (16 MiB).
+const MAX_OUTPUT_BYTES_LIMIT: u64 = 16 * 1024 * 1024;
+
 /// Tool 1: run_code — Execute Luau code in Studio and return output.
 ///
 /// session_id (Some) routes this single call to a specific session, overriding
 /// active_session for this call only.
+///
+/// `max_output_bytes` caps what the plugin captures from print/warn. Once
+/// exceeded, capturing stops and the result comes back as
+/// `{output, truncated: true, ...}`; with `stop_on_overflow` the script is
+/// also aborted at that point. The cap doesn't shorten the request timeout —
+/// a script that keeps running without printing still runs until it
+/// finishes or the 30s timeout hits.
+///
+/// With `--require-allowlist`, the list is sent along and the plugin rejects
+/// any other `require`. Each rejection leaves a `[REQUIRE BLOCKED]` line in
+/// the output, which is logged here for audit.
+///
+/// `exclusive` (default true) runs the code under the session's execution
+/// lock so concurrent run_code calls don't interleave; read-only snippets can
+/// pass false to skip the queue. A call that had to wait says so in its result.
+///
+/// `capture_budget` makes the result an object with a `stats` entry: how long
+/// the code ran and how many instances were added/removed meanwhile (see
+/// structure_budget for which numbers are exact).
 pub async fn run_code(
     state: &Arc<Mutex<AppState>>,
     session_id: Option<&str>,
     code: &str,
+    cap: OutputCap,
+    exclusive: Option<bool>,
+    preflight: Preflight,
+    capture_budget: Option<bool>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let max_output_bytes = resolve_max_output_bytes(cap.max_bytes)?;
+    let capture_budget = capture_budget.unwrap_or(false);
+    let allowlist = state.lock().await.require_allowlist.clone();
+    let mut args = json!({
+        "command": code,
+        "maxOutputBytes": max_output_bytes,
+        "stopOnOverflow": cap.stop_on_overflow.unwrap_or(false),
+        "exclusive": exclusive.unwrap_or(true),
+        "preflight": preflight != Preflight::Off,
+        "abortOnWarning": preflight == Preflight::RunIfClean,
+    });
+    if capture_budget {
+        args["captureBudget"] = json!(true);
+    }
+    if let Some(allowlist) = &allowlist {
+        args["requireAllowlist"] = json!(allowlist);
+    }
+
+    let result = send_to_plugin(state, session_id, "run_code", args, DEFAULT_TIMEOUT)
+        .await
+        .map(|result| {
+            if capture_budget {
+                structure_budget(result)
+            } else {
+                result
+            }
+        });
+    if allowlist.is_some() {
+        let text = match &result {
+            Ok(serde_json::Value::String(s)) => s.clone(),
+            Ok(other) => other["output"].as_str().unwrap_or_default().to_string(),
+            Err(e) => e.to_string(),
+        };
+        for module in blocked_requires(&text) {
+            tracing::warn!("run_code: require of '{}' blocked by allowlist", module);
+        }
+    }
+    result
+}
+
+/// How much print/warn output run_code captures.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputCap {
+    /// Bytes captured before output is dropped (default 1 MiB).
+    pub max_bytes: Option<u64>,
+    /// Also abort the script once the cap is exceeded.
+    pub stop_on_overflow: Option<bool>,
+}
+
+/// Replace the plugin's raw `budget` with `stats`. `wallMs` is measured
+/// around the call itself; the instance counts come from DataModel-wide
+/// add/remove events, so they also include changes other scripts made in the
+/// same window and never include property writes. Results without a budget
+/// (preflight-only runs, the play-mode server session) get
+/// `stats.available = false`.
+fn structure_budget(result: serde_json::Value) -> serde_json::Value {
+    let mut result = match result {
+        serde_json::Value::String(output) => json!({ "output": output }),
+        serde_json::Value::Object(_) => result,
+        other => json!({ "output": other }),
+    };
+    let stats = match result.as_object_mut().and_then(|obj| obj.remove("budget")) {
+        Some(budget) => json!({
+            "available": true,
+            "wallMs": round2(budget["wallMs"].as_f64().unwrap_or(0.0)),
+            "instancesAdded": budget["instancesAdded"].as_u64().unwrap_or(0),
+            "instancesRemoved": budget["instancesRemoved"].as_u64().unwrap_or(0),
+            "bestEffort": ["instancesAdded", "instancesRemoved"],
+        }),
+        None => json!({
+            "available": false,
+            "reason": "the plugin did not report a budget for this run",
+        }),
+    };
+    result["stats"] = stats;
+    result
+}
+
+/// Static check the plugin runs on run_code input before executing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preflight {
+    /// Run the code as-is.
+    Off,
+    /// Analyze only; the code is never run.
+    ReportOnly,
+    /// Analyze, then run only if there were no errors or warnings.
+    RunIfClean,
+}
+
+impl Preflight {
+    /// From the `preflight` / `abortOnWarning` tool flags.
+    pub fn from_flags(preflight: Option<bool>, abort_on_warning: Option<bool>) -> Result<Self> {
+        match (
+            preflight.unwrap_or(false),
+            abort_on_warning.unwrap_or(false),
+        ) {
+            (false, false) => Ok(Preflight::Off),
+            (true, false) => Ok(Preflight::ReportOnly),
+            (true, true) => Ok(Preflight::RunIfClean),
+            (false, true) => Err(StudioLinkError::InvalidArguments(
+                "abortOnWarning only applies with preflight=true".into(),
+            )),
+        }
+    }
+}
+
+/// Module names from the plugin's `[REQUIRE BLOCKED] <module>` output lines.
+fn blocked_requires(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once("[REQUIRE BLOCKED] "))
+        .map(|(_, module)| module.trim())
+        .collect()
+}
+
+fn resolve_max_output_bytes(requested: Option<u64>) -> Result<u64> {
+    match requested {
+        None => Ok(DEFAULT_MAX_OUTPUT_BYTES),
+        Some(0) => Err(StudioLinkError::InvalidArguments(
+            "maxOutputBytes must be greater than 0".into(),
+        )),
+        Some(n) if n > MAX_OUTPUT_BYTES_LIMIT => Err(StudioLinkError::InvalidArguments(format!(
+            "maxOutputBytes must be at most {} (16 MiB), got {}",
+            MAX_OUTPUT_BYTES_LIMIT, n
+        ))),
+        Some(n) => Ok(n),
+    }
+}
+
+/// Largest `limit` accepted for an insert_model preview.
+const MAX_PREVIEW_RESULTS: u32 = 10;
+
+#[derive(Debug, Default)]
+pub struct InsertModelOptions {
+    /// Creator Store search; the top match is inserted
+    pub query: Option<String>,
+    /// Insert this asset instead of searching
+    pub asset_id: Option<u64>,
+    /// Dotted path to insert under (default Workspace)
+    pub parent: Option<String>,
+    /// Check the model's scripts for backdoors before inserting it
+    pub scan: bool,
+    pub preview: bool,
+    pub limit: Option<u32>,
+}
+
+/// Tool 2: insert_model — Insert a model from the Roblox Creator Store
+///
+/// With `preview`, nothing is inserted: the plugin returns the top `limit`
+/// matches (default 5) and the agent picks one to pass to insert_asset.
+///
+/// With `scan`, the plugin loads the model and holds it outside the place
+/// while its scripts are checked with `security::backdoor_findings`. A clean
+/// model is then inserted; one with findings is discarded and the findings
+/// are returned instead.
+pub async fn insert_model(
+    state: &Arc<Mutex<AppState>>,
+    options: InsertModelOptions,
+) -> Result<serde_json::Value> {
+    let query = match (options.query.as_deref(), options.asset_id) {
+        (Some(query), None) if !query.is_empty() => query.to_string(),
+        (None, Some(id)) if id > 0 => id.to_string(),
+        (None, Some(_)) => {
+            return Err(StudioLinkError::InvalidArguments(
+                "assetId must be a positive asset ID".into(),
+            ))
+        }
+        _ => {
+            return Err(StudioLinkError::InvalidArguments(
+                "pass either query or assetId".into(),
+            ))
+        }
+    };
+    if options.preview {
+        if let Some(limit) = options
+            .limit
+            .filter(|l| *l == 0 || *l > MAX_PREVIEW_RESULTS)
+        {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "limit must be between 1 and {}, got {}",
+                MAX_PREVIEW_RESULTS, limit
+            )));
+        }
+        if options.parent.is_some() || options.scan {
+            return Err(StudioLinkError::InvalidArguments(
+                "preview inserts nothing; parent and scan apply to the insert".into(),
+            ));
+        }
+    }
+    let result = send_to_plugin(
+        state,
+        None,
+        "insert_model",
+        json!({
+            "query": query,
+            "preview": options.preview,
+            "limit": options.limit.unwrap_or(5),
+            "parent": options.parent,
+            "hold": options.scan,
+        }),
+        DEFAULT_TIMEOUT,
+    )
+    .await?;
+    if options.preview {
+        return Ok(structure_preview(&query, &result));
+    }
+    if !options.scan {
+        return Ok(result);
+    }
+
+    let scan = scan_held_model(&result);
+    let clean = scan["findings"].as_array().is_some_and(|f| f.is_empty());
+    let finished = send_to_plugin(
         state,
-        session_id,
-        "run_code",
-        json!({ "command": code }),
+        None,
+        "insert_model_finish",
+        json!({ "pending": result["pending"], "insert": clean }),
         DEFAULT_TIMEOUT,
     )
-    .await
+    .await?;
+    let mut out = json!({
+        "inserted": clean,
+        "modelId": result["modelId"],
+        "modelName": result["modelName"],
+        "scan": scan,
+    });
+    if clean {
+        out["parent"] = finished["parent"].clone();
+        out["insertedObjects"] = finished["insertedObjects"].clone();
+    } else {
+        out["hint"] = json!(
+            "The model was discarded. Review the findings; insert it with scan=false only if they are expected."
+        );
+    }
+    Ok(out)
 }
 
-/// Tool 2: insert_model — Insert a model from the Roblox Creator Store
-pub async fn insert_model(state: &Arc<Mutex<AppState>>, query: &str) -> Result<serde_json::Value> {
+/// Run the backdoor checks over every script of a model held by the plugin.
+fn scan_held_model(held: &serde_json::Value) -> serde_json::Value {
+    let scripts = held["scripts"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let findings: Vec<serde_json::Value> = scripts
+        .iter()
+        .flat_map(|script| {
+            let source = script["source"].as_str().unwrap_or_default();
+            super::security::backdoor_findings(source)
+                .into_iter()
+                .map(move |mut finding| {
+                    finding["script"] = script["path"].clone();
+                    finding["className"] = script["className"].clone();
+                    finding
+                })
+        })
+        .collect();
+    json!({ "scripts": scripts.len(), "findings": findings })
+}
+
+/// Normalize the plugin's preview entries into ranked candidates.
+fn structure_preview(query: &str, raw: &serde_json::Value) -> serde_json::Value {
+    let results: Vec<serde_json::Value> = raw["results"]
+        .as_array()
+        .map(|items| items.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            json!({
+                "rank": i + 1,
+                "assetId": item["assetId"],
+                "name": item["name"],
+                "creator": {
+                    "name": item["creatorName"],
+                    "type": item["creatorType"],
+                },
+                "thumbnail": item["thumbnail"],
+            })
+        })
+        .collect();
+    json!({
+        "query": query,
+        "preview": true,
+        "count": results.len(),
+        "results": results,
+        "hint": "Nothing was inserted. Call insert_asset with the chosen assetId.",
+    })
+}
+
+/// insert_asset — Insert a specific Creator Store asset by ID, typically one
+/// picked from an insert_model preview.
+pub async fn insert_asset(
+    state: &Arc<Mutex<AppState>>,
+    asset_id: u64,
+) -> Result<serde_json::Value> {
+    if asset_id == 0 {
+        return Err(StudioLinkError::InvalidArguments(
+            "assetId must be a positive asset ID".into(),
+        ));
+    }
     send_to_plugin(
         state,
         None,
-        "insert_model",
-        json!({ "query": query }),
+        "insert_asset",
+        json!({ "assetId": asset_id }),
         DEFAULT_TIMEOUT,
     )
     .await
 }
 
+/// One captured console line, as returned with `structured`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ConsoleEntry {
+    /// Monotonic per plugin load; keeps increasing across reads.
+    pub seq: u64,
+    /// Unix milliseconds when the plugin captured the line.
+    pub time: u64,
+    /// OUTPUT, INFO, WARNING or ERROR.
+    pub severity: String,
+    pub message: String,
+    /// With collapse_repeats: how many identical consecutive lines this
+    /// entry stands for (seq/time are the first one's).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeats: Option<u64>,
+}
+
 /// Tool 3: get_console_output — Get Studio console output
-pub async fn get_console_output(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
-    send_to_plugin(
+///
+/// With `collapse_repeats`, runs of identical consecutive lines (e.g. an error
+/// printed every Heartbeat) are folded into one line suffixed with `(xN)`.
+///
+/// With `structured`, the result is `{entries, count, firstSeq, lastSeq}`
+/// with one `ConsoleEntry` per line instead of a text blob; collapsed runs
+/// carry `repeats` there.
+pub async fn get_console_output(
+    state: &Arc<Mutex<AppState>>,
+    collapse_repeats: Option<bool>,
+    structured: Option<bool>,
+) -> Result<serde_json::Value> {
+    let structured = structured.unwrap_or(false);
+    let result = send_to_plugin(
         state,
         None,
         "get_console_output",
-        json!({}),
+        json!({ "structured": structured }),
         DEFAULT_TIMEOUT,
     )
-    .await
+    .await?;
+
+    if structured {
+        return structure_console_entries(&result, collapse_repeats.unwrap_or(false));
+    }
+    if !collapse_repeats.unwrap_or(false) {
+        return Ok(result);
+    }
+    Ok(match result {
+        serde_json::Value::String(text) => {
+            serde_json::Value::String(collapse_repeated_lines(&text))
+        }
+        other => other,
+    })
+}
+
+fn structure_console_entries(
+    result: &serde_json::Value,
+    collapse_repeats: bool,
+) -> Result<serde_json::Value> {
+    // An empty Luau table may arrive as {} rather than []
+    let raw = match &result["entries"] {
+        serde_json::Value::Array(items) => items.clone(),
+        _ => Vec::new(),
+    };
+    let mut entries: Vec<ConsoleEntry> = raw
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| StudioLinkError::PluginError(format!("malformed console entry: {}", e)))?;
+    if collapse_repeats {
+        let mut collapsed: Vec<ConsoleEntry> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match collapsed.last_mut() {
+                Some(prev) if prev.severity == entry.severity && prev.message == entry.message => {
+                    prev.repeats = Some(prev.repeats.unwrap_or(1) + 1);
+                }
+                _ => collapsed.push(entry),
+            }
+        }
+        entries = collapsed;
+    }
+    Ok(json!({
+        "count": entries.len(),
+        "firstSeq": entries.first().map(|e| e.seq),
+        "lastSeq": entries.last().map(|e| e.seq),
+        "entries": entries,
+    }))
+}
+
+/// Fold runs of identical consecutive lines into `line (xN)`.
+fn collapse_repeated_lines(text: &str) -> String {
+    let mut runs: Vec<(&str, usize)> = Vec::new();
+    for line in text.lines() {
+        match runs.last_mut() {
+            Some((prev, count)) if *prev == line => *count += 1,
+            _ => runs.push((line, 1)),
+        }
+    }
+    runs.iter()
+        .map(|(line, count)| {
+            if *count > 1 {
+                format!("{} (x{})", line, count)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 /// Tool 4: start_stop_play — Control play/stop/run_server mode
@@ -64,28 +480,499 @@ pub async fn start_stop_play(
     .await
 }
 
+/// Plugin tools that run caller-supplied code, and so are sent the
+/// `--require-allowlist` list however they are called.
+pub const ALLOWLISTED_TOOLS: &[&str] = &["run_code", "run_script_in_play_mode"];
+
+/// Largest seed magnitude Luau numbers (doubles) hold exactly: 2^53.
+const MAX_RANDOM_SEED: u64 = 1 << 53;
+
 /// Tool 5: run_script_in_play_mode — Run a script in play mode with timeout
+///
+/// With `capture_stats`, the plugin samples frame time, heartbeat step time
+/// and memory while the code runs, and the result gains a `stats` summary.
+///
+/// Every run is seeded: `random_seed`, or one generated here when absent.
+/// The result's `randomSeed` gives the value, whether it was generated, and
+/// whether the plugin applied it, so a failing run can be replayed exactly.
+///
+/// With `--require-allowlist`, the code runs in the same sealed environment
+/// as run_code; blocked requires show up in `errors` and are logged here.
 pub async fn run_script_in_play_mode(
     state: &Arc<Mutex<AppState>>,
     code: &str,
     mode: &str,
     timeout_secs: Option<u64>,
+    capture_stats: Option<bool>,
+    random_seed: Option<i64>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let (seed, generated) = resolve_random_seed(random_seed)?;
+    let allowlist = state.lock().await.require_allowlist.clone();
+    let mut args = json!({
+        "code": code,
+        "mode": mode,
+        "timeout": timeout_secs.unwrap_or(100),
+        "captureStats": capture_stats.unwrap_or(false),
+        "randomSeed": seed,
+    });
+    if let Some(allowlist) = &allowlist {
+        args["requireAllowlist"] = json!(allowlist);
+    }
+    let mut result = send_to_plugin(
         state,
         None,
         "run_script_in_play_mode",
-        json!({
-            "code": code,
-            "mode": mode,
-            "timeout": timeout_secs.unwrap_or(100),
-        }),
+        args,
         EXTENDED_TIMEOUT,
     )
-    .await
+    .await?;
+    if allowlist.is_some() {
+        let errors = result["errors"].as_array().into_iter().flatten();
+        for line in errors.filter_map(|e| e.as_str()) {
+            for module in blocked_requires(line) {
+                tracing::warn!(
+                    "run_script_in_play_mode: require of '{}' blocked by allowlist",
+                    module
+                );
+            }
+        }
+    }
+    if let Some(obj) = result.as_object_mut() {
+        if let Some(samples) = obj.remove("statsSamples") {
+            obj.insert("stats".into(), structure_play_stats(&samples));
+        }
+        // The plugin echoes the seed only when it ran the code with it
+        let applied = obj.remove("randomSeed").is_some_and(|s| !s.is_null());
+        obj.insert(
+            "randomSeed".into(),
+            json!({ "value": seed, "generated": generated, "applied": applied }),
+        );
+    }
+    Ok(result)
+}
+
+/// The seed to run with and whether it was generated. Generated seeds stay
+/// below 2^31 so they're easy to copy back into a call.
+fn resolve_random_seed(requested: Option<i64>) -> Result<(i64, bool)> {
+    match requested {
+        Some(seed) if seed.unsigned_abs() > MAX_RANDOM_SEED => {
+            Err(StudioLinkError::InvalidArguments(format!(
+                "randomSeed must be within ±2^53 ({}) to survive as a Luau number, got {}",
+                MAX_RANDOM_SEED, seed
+            )))
+        }
+        Some(seed) => Ok((seed, false)),
+        None => {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos();
+            Ok((i64::from(nanos % (1 << 31)), true))
+        }
+    }
+}
+
+/// Fewer frames than this (about one second at 60 FPS) are too few to
+/// compare runs, so the summary is marked low confidence.
+const MIN_STATS_FRAMES: usize = 60;
+
+/// Summarize raw play-run samples: frame time avg/p95/max, heartbeat step
+/// time, and memory start/end/peak.
+fn structure_play_stats(samples: &serde_json::Value) -> serde_json::Value {
+    if let Some(reason) = samples["unavailable"].as_str() {
+        return json!({ "unavailable": reason });
+    }
+    let numbers = |key: &str| -> Vec<f64> {
+        samples[key]
+            .as_array()
+            .map(|a| a.iter().filter_map(|v| v.as_f64()).collect())
+            .unwrap_or_default()
+    };
+    let avg = |v: &[f64]| round2(v.iter().sum::<f64>() / v.len() as f64);
+    let max = |v: &[f64]| round2(v.iter().copied().fold(f64::MIN, f64::max));
+
+    let mut frames = numbers("frameMs");
+    frames.sort_by(|a, b| a.total_cmp(b));
+    let heartbeat = numbers("heartbeatMs");
+    let memory = numbers("memoryMb");
+
+    let mut stats = json!({ "frames": frames.len() });
+    if !frames.is_empty() {
+        let p95 = frames[((frames.len() as f64 * 0.95).ceil() as usize).clamp(1, frames.len()) - 1];
+        stats["frameTimeMs"] =
+            json!({ "avg": avg(&frames), "p95": round2(p95), "max": max(&frames) });
+    }
+    if !heartbeat.is_empty() {
+        stats["heartbeatStepMs"] = json!({ "avg": avg(&heartbeat), "max": max(&heartbeat) });
+    }
+    if let (Some(first), Some(last)) = (memory.first(), memory.last()) {
+        stats["memoryMb"] = json!({
+            "start": round2(*first),
+            "end": round2(*last),
+            "peak": max(&memory),
+            "delta": round2(last - first),
+        });
+    }
+    if frames.len() < MIN_STATS_FRAMES {
+        stats["confidence"] = json!("low");
+        stats["note"] = json!(format!(
+            "only {} frames sampled (< {}); run longer for numbers worth comparing",
+            frames.len(),
+            MIN_STATS_FRAMES
+        ));
+    } else {
+        stats["confidence"] = json!("normal");
+    }
+    if samples["truncated"] == true {
+        stats["truncated"] = json!(true);
+    }
+    stats
 }
 
 /// Tool 6: get_studio_mode — Get current Studio mode
 pub async fn get_studio_mode(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
     send_to_plugin(state, None, "get_studio_mode", json!({}), DEFAULT_TIMEOUT).await
 }
+
+/// Modes get_studio_mode reports.
+const STUDIO_MODES: [&str; 3] = ["start_play", "run_server", "stop"];
+
+/// Longest wait_for_studio_mode will wait, and its default.
+const MAX_MODE_WAIT_SECS: f64 = 120.0;
+const DEFAULT_MODE_WAIT_SECS: f64 = 30.0;
+
+/// Pause between get_studio_mode polls, and the most a single poll may take.
+const MODE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+const MODE_POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+fn validate_mode_wait(mode: &str, timeout_secs: Option<f64>) -> Result<std::time::Duration> {
+    if !STUDIO_MODES.contains(&mode) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "mode must be one of {}, got \"{}\"",
+            STUDIO_MODES.join(", "),
+            mode
+        )));
+    }
+    let secs = timeout_secs.unwrap_or(DEFAULT_MODE_WAIT_SECS);
+    if !(secs > 0.0 && secs <= MAX_MODE_WAIT_SECS) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "timeoutSecs must be greater than 0 and at most {}, got {}",
+            MAX_MODE_WAIT_SECS, secs
+        )));
+    }
+    Ok(std::time::Duration::from_secs_f64(secs))
+}
+
+/// wait_for_studio_mode — Poll get_studio_mode until Studio reaches `mode`
+///
+/// Failed polls (the plugin reconnecting or busy while play starts or stops)
+/// are tolerated until the deadline; the last one is quoted if the wait
+/// times out. Returns the mode with `waitedMs` and how many polls it took.
+pub async fn wait_for_studio_mode(
+    state: &Arc<Mutex<AppState>>,
+    mode: &str,
+    timeout_secs: Option<f64>,
+) -> Result<serde_json::Value> {
+    let timeout = validate_mode_wait(mode, timeout_secs)?;
+    let started = tokio::time::Instant::now();
+    let deadline = started + timeout;
+    let mut polls = 0u32;
+    let mut last_seen: Option<String> = None;
+    let mut last_error: Option<String> = None;
+    loop {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        polls += 1;
+        match send_to_plugin(
+            state,
+            None,
+            "get_studio_mode",
+            json!({}),
+            remaining.min(MODE_POLL_TIMEOUT),
+        )
+        .await
+        {
+            Ok(current) if current.as_str() == Some(mode) => {
+                return Ok(json!({
+                    "mode": mode,
+                    "reached": true,
+                    "waitedMs": started.elapsed().as_millis() as u64,
+                    "polls": polls,
+                }));
+            }
+            Ok(current) => {
+                last_seen = Some(current.as_str().map_or(current.to_string(), String::from));
+            }
+            Err(e) => last_error = Some(e.to_string()),
+        }
+        if tokio::time::Instant::now() + MODE_POLL_INTERVAL >= deadline {
+            let mut message = format!(
+                "Studio did not reach mode '{}' within {:.1}s; last reported mode: {}",
+                mode,
+                started.elapsed().as_secs_f64(),
+                last_seen.as_deref().unwrap_or("none")
+            );
+            if let Some(error) = last_error {
+                message.push_str(&format!(" (last poll error: {})", error));
+            }
+            return Err(StudioLinkError::PluginError(message));
+        }
+        tokio::time::sleep(MODE_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_wait_arguments() {
+        assert_eq!(
+            validate_mode_wait("start_play", None).unwrap(),
+            std::time::Duration::from_secs(30)
+        );
+        assert!(validate_mode_wait("stop", Some(0.5)).is_ok());
+        assert!(validate_mode_wait("edit", None).is_err());
+        assert!(validate_mode_wait("stop", Some(0.0)).is_err());
+        assert!(validate_mode_wait("stop", Some(MAX_MODE_WAIT_SECS + 1.0)).is_err());
+    }
+
+    #[tokio::test]
+    async fn mode_wait_times_out_with_last_error() {
+        let state = AppState::new().0;
+        let err = wait_for_studio_mode(&state, "start_play", Some(0.3))
+            .await
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("did not reach mode 'start_play'"),
+            "{}",
+            message
+        );
+        assert!(message.contains("not connected"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn play_mode_scripts_carry_the_require_allowlist() {
+        let (request, result) = crate::tools::test_support::forwarded(
+            |state| async move {
+                state.lock().await.require_allowlist =
+                    Some(vec!["ReplicatedStorage.Shared".into()]);
+                run_script_in_play_mode(&state, "print(1)", "start_play", None, None, Some(7)).await
+            },
+            json!({ "success": true, "errors": ["[REQUIRE BLOCKED] ServerStorage.Secrets"] }),
+        )
+        .await;
+        assert_eq!(
+            request.args["requireAllowlist"],
+            json!(["ReplicatedStorage.Shared"])
+        );
+        assert_eq!(result.unwrap()["randomSeed"]["value"], 7);
+    }
+
+    #[test]
+    fn preflight_flag_combinations() {
+        assert_eq!(Preflight::from_flags(None, None).unwrap(), Preflight::Off);
+        assert_eq!(
+            Preflight::from_flags(Some(true), None).unwrap(),
+            Preflight::ReportOnly
+        );
+        assert_eq!(
+            Preflight::from_flags(Some(true), Some(true)).unwrap(),
+            Preflight::RunIfClean
+        );
+        assert!(matches!(
+            Preflight::from_flags(None, Some(true)),
+            Err(StudioLinkError::InvalidArguments(_))
+        ));
+    }
+
+    #[test]
+    fn console_entries_are_typed_and_collapsible() {
+        let raw = json!({ "entries": [
+            { "seq": 7, "time": 1_700_000_000_000u64, "severity": "ERROR", "message": "boom" },
+            { "seq": 8, "time": 1_700_000_000_016u64, "severity": "ERROR", "message": "boom" },
+            { "seq": 9, "time": 1_700_000_000_020u64, "severity": "OUTPUT", "message": "ok" },
+        ]});
+        let plain = structure_console_entries(&raw, false).unwrap();
+        assert_eq!(plain["count"], 3);
+        assert_eq!(plain["lastSeq"], 9);
+        assert!(plain["entries"][0].get("repeats").is_none());
+
+        let collapsed = structure_console_entries(&raw, true).unwrap();
+        assert_eq!(collapsed["count"], 2);
+        assert_eq!(collapsed["entries"][0]["seq"], 7);
+        assert_eq!(collapsed["entries"][0]["repeats"], 2);
+
+        let empty = structure_console_entries(&json!({ "entries": {} }), false).unwrap();
+        assert_eq!(empty["count"], 0);
+        assert!(empty["firstSeq"].is_null());
+        assert!(structure_console_entries(&json!({ "entries": [{ "seq": "x" }] }), false).is_err());
+    }
+
+    #[test]
+    fn random_seed_is_bounded_or_generated() {
+        assert_eq!(resolve_random_seed(Some(42)).unwrap(), (42, false));
+        assert_eq!(resolve_random_seed(Some(-7)).unwrap(), (-7, false));
+        assert!(resolve_random_seed(Some(1 << 53)).is_ok());
+        assert!(matches!(
+            resolve_random_seed(Some((1 << 53) + 1)),
+            Err(StudioLinkError::InvalidArguments(_))
+        ));
+        let (seed, generated) = resolve_random_seed(None).unwrap();
+        assert!(generated);
+        assert!((0..1 << 31).contains(&seed));
+    }
+
+    #[test]
+    fn budget_becomes_stats() {
+        let result = structure_budget(json!({
+            "output": "[OUTPUT] hi",
+            "budget": { "wallMs": 12.3456, "instancesAdded": 40, "instancesRemoved": 2 },
+        }));
+        assert!(result.get("budget").is_none());
+        assert_eq!(result["output"], "[OUTPUT] hi");
+        assert_eq!(result["stats"]["wallMs"], 12.35);
+        assert_eq!(result["stats"]["instancesAdded"], 40);
+        assert_eq!(result["stats"]["bestEffort"][1], "instancesRemoved");
+
+        let result = structure_budget(json!("[OUTPUT] from server"));
+        assert_eq!(result["output"], "[OUTPUT] from server");
+        assert_eq!(result["stats"]["available"], false);
+    }
+
+    #[test]
+    fn max_output_bytes_defaults_and_bounds() {
+        assert_eq!(
+            resolve_max_output_bytes(None).unwrap(),
+            DEFAULT_MAX_OUTPUT_BYTES
+        );
+        assert_eq!(resolve_max_output_bytes(Some(4096)).unwrap(), 4096);
+        assert!(matches!(
+            resolve_max_output_bytes(Some(0)),
+            Err(StudioLinkError::InvalidArguments(_))
+        ));
+        assert!(matches!(
+            resolve_max_output_bytes(Some(MAX_OUTPUT_BYTES_LIMIT + 1)),
+            Err(StudioLinkError::InvalidArguments(_))
+        ));
+    }
+
+    #[test]
+    fn preview_results_are_ranked() {
+        let raw = json!({ "results": [
+            { "assetId": 111, "name": "Tree", "creatorName": "Roblox", "creatorType": "User", "thumbnail": "rbxthumb://type=Asset&id=111&w=150&h=150" },
+            { "assetId": 222 },
+        ]});
+        let out = structure_preview("tree", &raw);
+        assert_eq!(out["count"], 2);
+        assert_eq!(out["results"][0]["rank"], 1);
+        assert_eq!(out["results"][0]["creator"]["name"], "Roblox");
+        assert_eq!(out["results"][1]["assetId"], 222);
+        assert_eq!(out["results"][1]["name"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_preview_limit_and_asset_id() {
+        let state = AppState::new().0;
+        let options = InsertModelOptions {
+            query: Some("tree".into()),
+            preview: true,
+            limit: Some(0),
+            ..Default::default()
+        };
+        let err = insert_model(&state, options).await.unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+        let options = InsertModelOptions {
+            query: Some("tree".into()),
+            asset_id: Some(123),
+            ..Default::default()
+        };
+        assert!(insert_model(&state, options).await.is_err());
+        let options = InsertModelOptions {
+            asset_id: Some(123),
+            preview: true,
+            scan: true,
+            ..Default::default()
+        };
+        assert!(insert_model(&state, options).await.is_err());
+        let err = insert_asset(&state, 0).await.unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    #[test]
+    fn scan_tags_findings_with_their_script() {
+        let held = json!({
+            "pending": "token",
+            "scripts": [
+                { "path": "Sword.Handle.Damage", "className": "Script", "source": "print(1)" },
+                { "path": "Sword.Loader", "className": "Script", "source": "\nrequire(123456)" },
+            ],
+        });
+        let scan = scan_held_model(&held);
+        assert_eq!(scan["scripts"], 2);
+        assert_eq!(
+            scan["findings"],
+            json!([{
+                "rule": "require_by_id",
+                "line": 2,
+                "snippet": "require(123456)",
+                "script": "Sword.Loader",
+                "className": "Script",
+            }])
+        );
+    }
+
+    #[test]
+    fn finds_blocked_require_lines() {
+        let output = "[OUTPUT] hi\n[REQUIRE BLOCKED] ServerStorage.Secrets\n[OUTPUT] done";
+        assert_eq!(blocked_requires(output), vec!["ServerStorage.Secrets"]);
+        assert!(blocked_requires("[OUTPUT] nothing blocked").is_empty());
+    }
+
+    #[test]
+    fn play_stats_summary_and_low_confidence() {
+        let frames: Vec<f64> = (1..=100)
+            .map(|i| if i == 100 { 50.0 } else { 16.0 })
+            .collect();
+        let stats = structure_play_stats(&json!({
+            "frameMs": frames,
+            "heartbeatMs": [1.0, 2.0, 3.0],
+            "memoryMb": [500.0, 540.5, 520.25],
+        }));
+        assert_eq!(stats["frames"], 100);
+        assert_eq!(stats["frameTimeMs"]["p95"], 16.0);
+        assert_eq!(stats["frameTimeMs"]["max"], 50.0);
+        assert_eq!(stats["frameTimeMs"]["avg"], 16.34);
+        assert_eq!(stats["heartbeatStepMs"]["avg"], 2.0);
+        assert_eq!(stats["memoryMb"]["peak"], 540.5);
+        assert_eq!(stats["memoryMb"]["delta"], 20.25);
+        assert_eq!(stats["confidence"], "normal");
+
+        let short = structure_play_stats(
+            &json!({ "frameMs": [16.0, 17.0], "heartbeatMs": [], "memoryMb": [] }),
+        );
+        assert_eq!(short["confidence"], "low");
+        assert!(short["note"].as_str().unwrap().contains("only 2 frames"));
+        assert!(short.get("memoryMb").is_none());
+
+        let none = structure_play_stats(
+            &json!({ "frameMs": [], "unavailable": "play mode already running" }),
+        );
+        assert_eq!(none["unavailable"], "play mode already running");
+    }
+
+    #[test]
+    fn collapses_consecutive_duplicates() {
+        let text = "[ERROR] boom\n[ERROR] boom\n[ERROR] boom\n[OUTPUT] ok\n[ERROR] boom";
+        assert_eq!(
+            collapse_repeated_lines(text),
+            "[ERROR] boom (x3)\n[OUTPUT] ok\n[ERROR] boom"
+        );
+    }
+
+    #[test]
+    fn collapse_leaves_unique_lines_alone() {
+        assert_eq!(collapse_repeated_lines("a\nb\nc"), "a\nb\nc");
+        assert_eq!(collapse_repeated_lines(""), "");
+    }
+}
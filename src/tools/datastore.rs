@@ -3,70 +3,323 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
-use crate::error::Result;
+use crate::error::{Result, StudioLinkError};
 use crate::state::AppState;
 
+/// The Open Cloud backend, when --datastore-backend opencloud selected it.
+async fn open_cloud(
+    state: &Arc<Mutex<AppState>>,
+) -> Option<Arc<crate::opencloud::OpenCloudDataStores>> {
+    state.lock().await.open_cloud.clone()
+}
+
+/// How long a datastore_list result is served from cache. Store names
+/// rarely change mid-session, and each fetch spends DataStore request budget.
+pub const DATASTORE_LIST_TTL: std::time::Duration = std::time::Duration::from_secs(300);
+
 /// Tool 7: datastore_list — List all DataStore names in the experience
-pub async fn datastore_list(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
-    send_to_plugin(state, None, "datastore_list", json!({}), DEFAULT_TIMEOUT).await
+///
+/// Results are cached per session for `DATASTORE_LIST_TTL`; `refresh` forces
+/// a new fetch. `cached` and `cacheAgeSecs` tell the caller how fresh it is.
+/// The cache is dropped on switch_session and a session's entry after a
+/// datastore_set or datastore_delete through it. The session is resolved before the call and the request pinned to it, so
+/// a switch_session mid-call can't file one place's stores under another.
+/// When the active session has gone quiet the call falls back to normal
+/// routing and its result isn't cached.
+pub async fn datastore_list(
+    state: &Arc<Mutex<AppState>>,
+    refresh: Option<bool>,
+) -> Result<serde_json::Value> {
+    if let Some(cloud) = open_cloud(state).await {
+        return cloud.list_stores().await;
+    }
+    let (session, pinned) = {
+        let s = state.lock().await;
+        let bound = super::connection(&s).lock().bound_session_id.clone();
+        let session = routed_session(&s);
+        let pinned = session
+            .clone()
+            .filter(|id| bound.is_some() || s.proxy_mode || s.sessions.is_connected(id));
+        (session, pinned)
+    };
+    if !refresh.unwrap_or(false) {
+        let s = state.lock().await;
+        if let Some((fetched_at, list)) = session.as_ref().and_then(|id| s.datastore_lists.get(id))
+        {
+            if fetched_at.elapsed() < DATASTORE_LIST_TTL {
+                let mut result = list.clone();
+                result["cached"] = json!(true);
+                result["cacheAgeSecs"] = json!(fetched_at.elapsed().as_secs());
+                return Ok(result);
+            }
+        }
+    }
+
+    let mut result = send_to_plugin(
+        state,
+        pinned.as_deref(),
+        "datastore_list",
+        json!({}),
+        DEFAULT_TIMEOUT,
+    )
+    .await?;
+    if let (Some(id), true) = (pinned, result.is_object()) {
+        state
+            .lock()
+            .await
+            .datastore_lists
+            .insert(id, (std::time::Instant::now(), result.clone()));
+        result["cached"] = json!(false);
+        result["cacheAgeSecs"] = json!(0);
+    }
+    Ok(result)
+}
+
+/// The session a call without an explicit session_id goes to: bound, else
+/// active.
+fn routed_session(s: &AppState) -> Option<String> {
+    super::connection(s)
+        .lock()
+        .bound_session_id
+        .clone()
+        .or_else(|| s.active_session.clone())
+}
+
+/// Forward a write to the plugin, then drop the cached datastore_list of the
+/// session it went to: the write may have created a store.
+async fn write_through(
+    state: &Arc<Mutex<AppState>>,
+    tool: &str,
+    args: serde_json::Value,
+) -> Result<serde_json::Value> {
+    let session = routed_session(&*state.lock().await);
+    let result = send_to_plugin(state, None, tool, args, DEFAULT_TIMEOUT).await?;
+    if let Some(id) = session {
+        state.lock().await.datastore_lists.remove(&id);
+    }
+    Ok(result)
 }
 
 /// Tool 8: datastore_get — Read a specific key from a DataStore
+///
+/// With `decode_json`, a string value holding `HttpService:JSONEncode` output
+/// is returned as the decoded object/array instead of an escaped string.
+/// With `ordered`, reads the OrderedDataStore of that name, whose values are
+/// always integers.
 pub async fn datastore_get(
     state: &Arc<Mutex<AppState>>,
     store_name: &str,
     key: &str,
+    decode_json: Option<bool>,
+    ordered: Option<bool>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
-        state,
-        None,
-        "datastore_get",
-        json!({ "storeName": store_name, "key": key }),
-        DEFAULT_TIMEOUT,
-    )
-    .await
+    let ordered = ordered.unwrap_or(false);
+    if ordered && decode_json.unwrap_or(false) {
+        return Err(StudioLinkError::InvalidArguments(
+            "decodeJson does not apply with ordered=true (ordered values are integers)".into(),
+        ));
+    }
+    let mut result = match open_cloud(state).await {
+        Some(cloud) => cloud.get(store_name, key, ordered).await?,
+        None => {
+            send_to_plugin(
+                state,
+                None,
+                "datastore_get",
+                json!({ "storeName": store_name, "key": key, "ordered": ordered }),
+                DEFAULT_TIMEOUT,
+            )
+            .await?
+        }
+    };
+    if decode_json.unwrap_or(false) {
+        decode_string_value(&mut result);
+    }
+    Ok(result)
+}
+
+/// Most keys datastore_get_many reads in one call. Every key costs one
+/// GetAsync against the server's per-minute budget, so larger sets should
+/// be split across calls.
+const MAX_GET_MANY_KEYS: usize = 50;
+
+/// DataStore keys are limited to 50 characters.
+const MAX_KEY_LENGTH: usize = 50;
+
+/// datastore_get_many — Read a known set of keys in one round trip
+///
+/// The plugin reads the keys one after another, waiting for GetAsync request
+/// budget whenever it runs out instead of letting calls throttle. `results`
+/// maps each key to `{value}` or `{error}`; keys still waiting when the
+/// plugin's pacing deadline passes come back as errors rather than failing
+/// the whole batch.
+pub async fn datastore_get_many(
+    state: &Arc<Mutex<AppState>>,
+    store_name: &str,
+    keys: &[String],
+    decode_json: Option<bool>,
+    ordered: Option<bool>,
+) -> Result<serde_json::Value> {
+    let ordered = ordered.unwrap_or(false);
+    if ordered && decode_json.unwrap_or(false) {
+        return Err(StudioLinkError::InvalidArguments(
+            "decodeJson does not apply with ordered=true (ordered values are integers)".into(),
+        ));
+    }
+    validate_key_batch(keys)?;
+    let mut result = match open_cloud(state).await {
+        Some(cloud) => cloud.get_many(store_name, keys, ordered).await?,
+        None => {
+            send_to_plugin(
+                state,
+                None,
+                "datastore_get_many",
+                json!({ "storeName": store_name, "keys": keys, "ordered": ordered }),
+                EXTENDED_TIMEOUT,
+            )
+            .await?
+        }
+    };
+    if decode_json.unwrap_or(false) {
+        if let Some(results) = result["results"].as_object_mut() {
+            results.values_mut().for_each(decode_string_value);
+        }
+    }
+    Ok(result)
+}
+
+fn validate_key_batch(keys: &[String]) -> Result<()> {
+    if keys.is_empty() || keys.len() > MAX_GET_MANY_KEYS {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "keys must contain between 1 and {} keys, got {}; split larger sets across calls",
+            MAX_GET_MANY_KEYS,
+            keys.len()
+        )));
+    }
+    let mut seen = std::collections::HashSet::new();
+    for key in keys {
+        if key.is_empty() || key.chars().count() > MAX_KEY_LENGTH {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "DataStore keys must be 1-{} characters, got \"{}\"",
+                MAX_KEY_LENGTH, key
+            )));
+        }
+        if !seen.insert(key) {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "key \"{}\" is listed more than once",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Replace `result.value` with its decoded form when it is a string containing
+/// a JSON object or array. Leaves the raw string in place (with a note)
+/// otherwise, so a failed decode never loses data.
+fn decode_string_value(result: &mut serde_json::Value) {
+    let Some(raw) = result.get("value").and_then(|v| v.as_str()) else {
+        return;
+    };
+    match serde_json::from_str::<serde_json::Value>(raw) {
+        Ok(decoded) if decoded.is_object() || decoded.is_array() => {
+            result["value"] = decoded;
+            result["decoded"] = json!(true);
+        }
+        _ => {
+            result["decoded"] = json!(false);
+            result["decodeNote"] =
+                json!("value is a string but not a JSON object/array; returned raw");
+        }
+    }
 }
 
 /// Tool 9: datastore_set — Write a value to a DataStore key
+///
+/// With `ordered`, writes to the OrderedDataStore of that name; the value
+/// must then be a JSON integer, checked here before anything is sent.
 pub async fn datastore_set(
     state: &Arc<Mutex<AppState>>,
     store_name: &str,
     key: &str,
     value: serde_json::Value,
+    ordered: Option<bool>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let ordered = ordered.unwrap_or(false);
+    if ordered {
+        validate_ordered_value(&value)?;
+    }
+    if let Some(cloud) = open_cloud(state).await {
+        return cloud.set(store_name, key, value, ordered).await;
+    }
+    write_through(
         state,
-        None,
         "datastore_set",
-        json!({ "storeName": store_name, "key": key, "value": value }),
-        DEFAULT_TIMEOUT,
+        json!({ "storeName": store_name, "key": key, "value": value, "ordered": ordered }),
     )
     .await
 }
 
+/// OrderedDataStores only store integers.
+fn validate_ordered_value(value: &serde_json::Value) -> Result<()> {
+    if value.is_i64() {
+        return Ok(());
+    }
+    Err(StudioLinkError::InvalidArguments(format!(
+        "OrderedDataStore values must be integers (64-bit signed), got {}",
+        value
+    )))
+}
+
 /// Tool 10: datastore_delete — Delete a key from a DataStore
 pub async fn datastore_delete(
     state: &Arc<Mutex<AppState>>,
     store_name: &str,
     key: &str,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    if let Some(cloud) = open_cloud(state).await {
+        return cloud.delete(store_name, key).await;
+    }
+    write_through(
         state,
-        None,
         "datastore_delete",
         json!({ "storeName": store_name, "key": key }),
-        DEFAULT_TIMEOUT,
     )
     .await
 }
 
+/// Options for scanning an OrderedDataStore with `GetSortedAsync`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OrderedScan {
+    pub ascending: Option<bool>,
+    pub min_value: Option<i64>,
+    pub max_value: Option<i64>,
+}
+
+/// `GetSortedAsync` rejects page sizes above 100.
+const MAX_ORDERED_PAGE_SIZE: u32 = 100;
+
 /// Tool 11: datastore_scan — Scan all keys in a DataStore
+///
+/// With `ordered`, scans the OrderedDataStore of that name instead and
+/// returns `entries` of `{key, value}` sorted by value (descending unless
+/// `ascending`), optionally limited to `[min_value, max_value]`.
 pub async fn datastore_scan(
     state: &Arc<Mutex<AppState>>,
     store_name: &str,
     page_size: Option<u32>,
     max_pages: Option<u32>,
+    ordered: Option<bool>,
+    scan: OrderedScan,
 ) -> Result<serde_json::Value> {
+    let ordered = ordered.unwrap_or(false);
+    validate_ordered_scan(ordered, page_size, &scan)?;
+    if let Some(cloud) = open_cloud(state).await {
+        let (page_size, max_pages) = (page_size.unwrap_or(50), max_pages.unwrap_or(1));
+        return cloud
+            .scan(store_name, page_size, max_pages, ordered, scan)
+            .await;
+    }
     send_to_plugin(
         state,
         None,
@@ -75,8 +328,235 @@ pub async fn datastore_scan(
             "storeName": store_name,
             "pageSize": page_size.unwrap_or(50),
             "maxPages": max_pages.unwrap_or(1),
+            "ordered": ordered,
+            "ascending": scan.ascending.unwrap_or(false),
+            "minValue": scan.min_value,
+            "maxValue": scan.max_value,
         }),
         EXTENDED_TIMEOUT,
     )
     .await
 }
+
+fn validate_ordered_scan(ordered: bool, page_size: Option<u32>, scan: &OrderedScan) -> Result<()> {
+    if !ordered {
+        if scan.ascending.is_some() || scan.min_value.is_some() || scan.max_value.is_some() {
+            return Err(StudioLinkError::InvalidArguments(
+                "ascending, minValue and maxValue only apply with ordered=true".into(),
+            ));
+        }
+        return Ok(());
+    }
+    if let Some(size) = page_size.filter(|s| *s == 0 || *s > MAX_ORDERED_PAGE_SIZE) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "page_size for an ordered scan must be between 1 and {}, got {}",
+            MAX_ORDERED_PAGE_SIZE, size
+        )));
+    }
+    if let (Some(min), Some(max)) = (scan.min_value, scan.max_value) {
+        if min > max {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "minValue ({}) must not be greater than maxValue ({})",
+                min, max
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn datastore_list_served_from_cache_until_refresh() {
+        let (state, _rx) = AppState::new();
+        {
+            let mut s = state.lock().await;
+            s.active_session = Some("a".into());
+            s.datastore_lists.insert(
+                "a".into(),
+                (
+                    std::time::Instant::now() - std::time::Duration::from_secs(42),
+                    json!({ "stores": ["PlayerData"], "count": 1 }),
+                ),
+            );
+        }
+        let cached = datastore_list(&state, None).await.unwrap();
+        assert_eq!(cached["stores"], json!(["PlayerData"]));
+        assert_eq!(cached["cached"], true);
+        assert_eq!(cached["cacheAgeSecs"], 42);
+
+        // refresh goes to the plugin (none connected here)
+        assert!(datastore_list(&state, Some(true)).await.is_err());
+
+        // Expired entries are not served either
+        state.lock().await.datastore_lists.get_mut("a").unwrap().0 =
+            std::time::Instant::now() - DATASTORE_LIST_TTL;
+        assert!(datastore_list(&state, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn datastore_list_caches_under_the_session_that_answered() {
+        let (state, _rx) = AppState::new();
+        {
+            let mut s = state.lock().await;
+            for (id, place_id) in [("a", 1), ("b", 2)] {
+                s.register_session(crate::state::SessionRegistration {
+                    session_id: id.into(),
+                    place_id,
+                    place_name: format!("Place{}", place_id),
+                    game_id: 0,
+                    client_id: None,
+                    supported_tools: None,
+                    plugin_version: None,
+                });
+            }
+            s.active_session = Some("a".into());
+            s.datastore_lists.insert(
+                "b".into(),
+                (std::time::Instant::now(), json!({ "stores": ["B"] })),
+            );
+        }
+        let call = {
+            let state = state.clone();
+            tokio::spawn(async move { datastore_list(&state, None).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        // The active session changes while the call is in flight
+        {
+            let mut s = state.lock().await;
+            let conn = s.connection.clone();
+            s.switch_session(&mut conn.lock(), "b");
+        }
+        {
+            let s = state.lock().await;
+            let request = s.sessions.next_request("a").expect("queued to a");
+            s.sessions.deliver_response(crate::state::PluginResponse {
+                id: request.id,
+                success: true,
+                result: json!({ "stores": ["A"], "count": 1 }),
+                error: None,
+            });
+        }
+        assert_eq!(call.await.unwrap().unwrap()["stores"], json!(["A"]));
+        // The switch dropped what was cached; the answer is filed under the
+        // session that gave it
+        let s = state.lock().await;
+        assert!(s.datastore_lists.contains_key("a"));
+        assert!(!s.datastore_lists.contains_key("b"));
+    }
+
+    #[tokio::test]
+    async fn writes_drop_the_cached_store_list() {
+        let state = crate::tools::test_support::state_with_session("s1");
+        for (tool, store) in [("datastore_set", "New"), ("datastore_delete", "Old")] {
+            state.lock().await.datastore_lists.insert(
+                "s1".into(),
+                (std::time::Instant::now(), json!({ "stores": [] })),
+            );
+            let call = {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if tool == "datastore_set" {
+                        datastore_set(&state, store, "k", json!(1), None).await
+                    } else {
+                        datastore_delete(&state, store, "k").await
+                    }
+                })
+            };
+            let request = loop {
+                if let Some(request) = state.lock().await.sessions.next_request("s1") {
+                    break request;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            };
+            assert_eq!(request.tool, tool);
+            // Still cached while the write is in flight
+            assert!(state.lock().await.datastore_lists.contains_key("s1"));
+            state
+                .lock()
+                .await
+                .sessions
+                .deliver_response(crate::state::PluginResponse {
+                    id: request.id,
+                    success: true,
+                    result: json!({ "success": true }),
+                    error: None,
+                });
+            call.await.unwrap().unwrap();
+            assert!(!state.lock().await.datastore_lists.contains_key("s1"));
+        }
+    }
+
+    #[test]
+    fn ordered_scan_validation() {
+        let bounds = OrderedScan {
+            ascending: Some(true),
+            min_value: Some(100),
+            max_value: Some(10),
+        };
+        assert!(validate_ordered_scan(true, None, &bounds).is_err());
+        assert!(validate_ordered_scan(false, None, &bounds).is_err());
+        assert!(validate_ordered_scan(true, Some(500), &OrderedScan::default()).is_err());
+
+        let ok = OrderedScan {
+            min_value: Some(0),
+            max_value: Some(1000),
+            ..Default::default()
+        };
+        assert!(validate_ordered_scan(true, Some(100), &ok).is_ok());
+        assert!(validate_ordered_scan(false, Some(500), &OrderedScan::default()).is_ok());
+    }
+
+    #[test]
+    fn ordered_values_must_be_integers() {
+        assert!(validate_ordered_value(&json!(1500)).is_ok());
+        assert!(validate_ordered_value(&json!(-3)).is_ok());
+        assert!(validate_ordered_value(&json!(2.5)).is_err());
+        assert!(validate_ordered_value(&json!("100")).is_err());
+        assert!(validate_ordered_value(&json!(u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn key_batches_are_bounded_and_unique() {
+        let keys = |n: usize| (0..n).map(|i| format!("player_{}", i)).collect::<Vec<_>>();
+        assert!(validate_key_batch(&keys(1)).is_ok());
+        assert!(validate_key_batch(&keys(MAX_GET_MANY_KEYS)).is_ok());
+        assert!(validate_key_batch(&[]).is_err());
+        let err = validate_key_batch(&keys(MAX_GET_MANY_KEYS + 1)).unwrap_err();
+        assert!(err.to_string().contains("split larger sets"));
+
+        let dup = vec!["a".to_string(), "b".to_string(), "a".to_string()];
+        assert!(validate_key_batch(&dup)
+            .unwrap_err()
+            .to_string()
+            .contains("more than once"));
+        assert!(validate_key_batch(&["".to_string()]).is_err());
+        assert!(validate_key_batch(&["k".repeat(MAX_KEY_LENGTH + 1)]).is_err());
+    }
+
+    #[test]
+    fn decodes_encoded_blob() {
+        let mut result = json!({ "key": "p1", "value": "{\"coins\":10,\"items\":[1,2]}" });
+        decode_string_value(&mut result);
+        assert_eq!(result["value"]["coins"], 10);
+        assert_eq!(result["decoded"], true);
+    }
+
+    #[test]
+    fn keeps_raw_string_when_not_json() {
+        let mut result = json!({ "key": "p1", "value": "hello" });
+        decode_string_value(&mut result);
+        assert_eq!(result["value"], "hello");
+        assert_eq!(result["decoded"], false);
+        assert!(result.get("decodeNote").is_some());
+    }
+
+    #[test]
+    fn ignores_non_string_values() {
+        let mut result = json!({ "key": "p1", "value": { "coins": 10 } });
+        decode_string_value(&mut result);
+        assert!(result.get("decoded").is_none());
+    }
+}
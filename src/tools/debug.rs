@@ -18,3 +18,100 @@ pub async fn debug_routing(state: &Arc<Mutex<AppState>>) -> Result<serde_json::V
         "note": "target_session=null routed to active_session. target_session=string was an explicit per-call override (multi-chat).",
     }))
 }
+
+/// server_diagnostics — Everything about this server process in one call,
+/// for bug reports: version, mode, uptime, sessions with their queue depths,
+/// proxy target and configured features. Read from AppState only, so it
+/// works with no session connected and never waits on a plugin.
+pub async fn server_diagnostics(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
+    let s = state.lock().await;
+    let pending = s.sessions.pending_requests(None);
+    let mut sessions: Vec<serde_json::Value> = s
+        .sessions
+        .ids()
+        .iter()
+        .filter_map(|id| {
+            s.sessions.with(id, |session| {
+                json!({
+                    "session_id": session.info.session_id,
+                    "place_name": session.info.place_name,
+                    "plugin_version": session.plugin_version,
+                    "queue_depth": session.request_queue.len(),
+                    "last_heartbeat_secs_ago": session.last_heartbeat.elapsed().as_secs(),
+                    "in_flight": session.in_flight.len(),
+                    "pending_requests": pending
+                        .iter()
+                        .filter(|r| r.session_id == session.info.session_id)
+                        .collect::<Vec<_>>(),
+                    "transport": if session.websocket { "websocket" } else { "long_poll" },
+                })
+            })
+        })
+        .collect();
+    sessions.sort_by(|a, b| a["session_id"].as_str().cmp(&b["session_id"].as_str()));
+    let queued: usize = s
+        .sessions
+        .ids()
+        .iter()
+        .map(|id| s.sessions.queue_depth(id))
+        .sum();
+    let retry_attempts: std::collections::BTreeMap<&String, u32> = s
+        .retry_policies
+        .iter()
+        .map(|(tool, policy)| (tool, policy.max_attempts))
+        .collect();
+
+    let (bound_session, active_macro) = {
+        let conn = super::connection(&s);
+        let conn = conn.lock();
+        (
+            conn.bound_session_id.clone(),
+            conn.active_macro.as_ref().map(|m| m.name.clone()),
+        )
+    };
+
+    Ok(json!({
+        "server": "StudioLink",
+        "version": env!("CARGO_PKG_VERSION"),
+        "mode": if s.proxy_mode { "proxy" } else { "primary" },
+        "uptime_secs": s.started_at.elapsed().as_secs(),
+        "proxy_target": s.proxy_mode.then(|| s.proxy_url.clone()),
+        "active_session": s.get_active_session(),
+        "bound_session": bound_session,
+        "connected_sessions": s.sessions.len(),
+        "sessions": sessions,
+        "queued_requests": queued,
+        "awaiting_response": s.sessions.awaiting_responses(),
+        "open_streams": s.sessions.open_streams(),
+        "active_macro": active_macro,
+        "features": {
+            "snapshot_store": s.snapshot_store.is_some(),
+            "persistent_display_names": s.display_names_path.is_some(),
+            "require_allowlist": s.require_allowlist,
+            "read_only": s.read_only,
+            "datastore_backend": if s.open_cloud.is_some() { "opencloud" } else { "plugin" },
+            "max_queue_depth": s.max_queue_depth,
+            "session_groups": s.session_groups.len(),
+        },
+        "retry_attempts": retry_attempts,
+        "retries": s.retry_stats,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn diagnostics_without_sessions() {
+        let (state, _rx) = AppState::new();
+        let diag = server_diagnostics(&state).await.unwrap();
+        assert_eq!(diag["mode"], "primary");
+        assert_eq!(diag["connected_sessions"], 0);
+        assert!(diag["active_session"].is_null());
+        assert!(diag["proxy_target"].is_null());
+        assert_eq!(diag["retry_attempts"]["datastore_get"], 3);
+        assert_eq!(diag["features"]["snapshot_store"], false);
+        assert_eq!(diag["features"]["read_only"], false);
+    }
+}
@@ -2,42 +2,588 @@ use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use super::textdiff::unified_diff;
 use super::{send_to_plugin, EXTENDED_TIMEOUT};
-use crate::error::Result;
+use crate::error::{Result, StudioLinkError};
+use crate::snapshots::{diff_manifests, SnapshotExport, SnapshotStore};
 use crate::state::AppState;
 
+/// Diff lines kept per script before the rest is cut off.
+const MAX_SCRIPT_DIFF_LINES: usize = 400;
+
+/// Diff text returned across all scripts in one compare (256 KiB); scripts
+/// past this only get their line counts.
+const MAX_SCRIPT_DIFFS_BYTES: usize = 256 * 1024;
+
+/// A script with more changed lines than this, of which under a quarter
+/// survived, is summarized as rewritten instead of diffed.
+const REWRITE_MIN_CHANGED_LINES: usize = 40;
+
+/// Node fields compared when describing a changed instance. Mirrors what the
+/// plugin's own compare reports so both paths produce the same shape.
+const COMPARED_FIELDS: &[&str] = &[
+    "ClassName",
+    "Source",
+    "Position",
+    "Size",
+    "Material",
+    "Color",
+    "Transparency",
+    "Anchored",
+    "Attributes",
+    "Tags",
+];
+
+fn snapshot_store(s: &AppState) -> Option<SnapshotStore> {
+    s.snapshot_store.clone()
+}
+
+fn require_store(s: &AppState) -> Result<SnapshotStore> {
+    snapshot_store(s).ok_or_else(|| {
+        StudioLinkError::ServerError("no snapshot store is configured on this server".into())
+    })
+}
+
 /// Tool 15: snapshot_take — Take a snapshot of the current place state
+///
+/// When the server has a snapshot store, the plugin also returns the flattened
+/// node data, which is written content-addressed (see `crate::snapshots`).
+/// The result then carries `storage` stats, including bytes saved by blobs
+/// shared with earlier snapshots.
 pub async fn snapshot_take(
     state: &Arc<Mutex<AppState>>,
     name: Option<&str>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let (store, place_id) = {
+        let s = state.lock().await;
+        (
+            snapshot_store(&s),
+            s.get_active_session_info().map(|i| i.place_id),
+        )
+    };
+
+    let mut result = send_to_plugin(
         state,
         None,
         "snapshot_take",
-        json!({ "name": name.unwrap_or("auto") }),
+        json!({ "name": name.unwrap_or("auto"), "includeData": store.is_some() }),
         EXTENDED_TIMEOUT,
     )
-    .await
+    .await?;
+
+    let Some(store) = store else {
+        return Ok(result);
+    };
+    let Some(nodes) = result
+        .as_object_mut()
+        .and_then(|obj| obj.remove("nodes"))
+        .and_then(|n| n.as_object().cloned())
+    else {
+        // Older plugin that doesn't return node data: live snapshot only
+        return Ok(result);
+    };
+
+    let snapshot_name = result["name"].as_str().unwrap_or("auto").to_string();
+    let timestamp = result["timestamp"].as_u64().unwrap_or(0);
+    let stats = store.save(&snapshot_name, timestamp, place_id, &nodes)?;
+    result["persisted"] = json!(true);
+    result["storage"] = serde_json::to_value(stats)?;
+    Ok(result)
 }
 
 /// Tool 16: snapshot_compare — Compare two snapshots and list differences
+///
+/// If both snapshots are in the store, the diff is computed here from their
+/// manifests (hash comparison; only changed nodes' blobs are read). Otherwise
+/// falls back to the plugin's in-memory snapshots.
+///
+/// With `script_diffs`, each changed script whose Source differs also gets a
+/// unified diff of its stored sources in `scriptDiffs` (stored snapshots
+/// only). Output is bounded per script and overall; see `script_diff`.
 pub async fn snapshot_compare(
     state: &Arc<Mutex<AppState>>,
     snapshot_a: &str,
     snapshot_b: &str,
+    script_diffs: Option<bool>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let script_diffs = script_diffs.unwrap_or(false);
+    let store = snapshot_store(&*state.lock().await);
+    if let Some(store) = store {
+        if let (Some(a), Some(b)) = (
+            store.load_manifest(snapshot_a)?,
+            store.load_manifest(snapshot_b)?,
+        ) {
+            let diff = diff_manifests(&a, &b);
+            let mut changed = Vec::with_capacity(diff.changed.len());
+            let mut scripts = Vec::new();
+            let mut diff_bytes = 0;
+            for path in &diff.changed {
+                let before = store.load_blob(&a.entries[path])?;
+                let after = store.load_blob(&b.entries[path])?;
+                if script_diffs && field_value(&before, "Source") != field_value(&after, "Source") {
+                    scripts.push(script_diff(
+                        path,
+                        (snapshot_a, &before),
+                        (snapshot_b, &after),
+                        &mut diff_bytes,
+                    ));
+                }
+                changed.push(json!({ "path": path, "changes": describe_changes(&before, &after) }));
+            }
+            let mut result = json!({
+                "snapshotA": snapshot_a,
+                "snapshotB": snapshot_b,
+                "added": diff.added,
+                "removed": diff.removed,
+                "changed": changed,
+                "summary": {
+                    "addedCount": diff.added.len(),
+                    "removedCount": diff.removed.len(),
+                    "changedCount": diff.changed.len(),
+                },
+                "comparedBy": "hash",
+            });
+            if script_diffs {
+                result["scriptDiffs"] = json!(scripts);
+            }
+            return Ok(result);
+        }
+    }
+
+    let mut result = send_to_plugin(
         state,
         None,
         "snapshot_compare",
         json!({ "snapshotA": snapshot_a, "snapshotB": snapshot_b }),
         EXTENDED_TIMEOUT,
     )
-    .await
+    .await?;
+    if script_diffs {
+        result["scriptDiffsUnavailable"] = json!(
+            "script diffs are computed from the snapshot store; take both snapshots with a store configured"
+        );
+    }
+    Ok(result)
+}
+
+/// One `scriptDiffs` entry. Carries `added`/`removed` line counts and either
+/// `diff` (unified, cut to MAX_SCRIPT_DIFF_LINES with `truncated`) or a
+/// `summary` when the script was essentially rewritten, the change is too
+/// large to diff, or the compare's MAX_SCRIPT_DIFFS_BYTES budget is spent.
+fn script_diff(
+    path: &str,
+    (name_a, before): (&str, &serde_json::Value),
+    (name_b, after): (&str, &serde_json::Value),
+    diff_bytes: &mut usize,
+) -> serde_json::Value {
+    let source_a = before["Source"].as_str().unwrap_or("");
+    let source_b = after["Source"].as_str().unwrap_or("");
+    let (lines_a, lines_b) = (source_a.lines().count(), source_b.lines().count());
+    let mut entry = json!({ "path": path, "linesBefore": lines_a, "linesAfter": lines_b });
+
+    let from = format!("{}:{}", name_a, path);
+    let to = format!("{}:{}", name_b, path);
+    let Some(diff) = unified_diff(source_a, source_b, &from, &to) else {
+        entry["summary"] = json!(format!(
+            "too many changed lines to diff ({} → {} lines)",
+            lines_a, lines_b
+        ));
+        return entry;
+    };
+    entry["added"] = json!(diff.added);
+    entry["removed"] = json!(diff.removed);
+
+    let kept = lines_a - diff.removed;
+    if diff.added + diff.removed > REWRITE_MIN_CHANGED_LINES && kept * 4 < lines_a.max(lines_b) {
+        entry["summary"] = json!(format!(
+            "rewritten: {} of {} lines kept, +{} -{}",
+            kept, lines_a, diff.added, diff.removed
+        ));
+        return entry;
+    }
+    if *diff_bytes >= MAX_SCRIPT_DIFFS_BYTES {
+        entry["summary"] = json!("diff omitted: output limit for this compare reached");
+        return entry;
+    }
+
+    let total_lines = diff.text.lines().count();
+    let text = if total_lines > MAX_SCRIPT_DIFF_LINES {
+        entry["truncated"] = json!(true);
+        let mut kept: String = diff
+            .text
+            .lines()
+            .take(MAX_SCRIPT_DIFF_LINES)
+            .map(|l| format!("{}\n", l))
+            .collect();
+        kept.push_str(&format!(
+            "... {} more diff lines\n",
+            total_lines - MAX_SCRIPT_DIFF_LINES
+        ));
+        kept
+    } else {
+        diff.text
+    };
+    *diff_bytes += text.len();
+    entry["diff"] = json!(text);
+    entry
+}
+
+/// Human-readable per-field changes between two node bodies.
+fn describe_changes(before: &serde_json::Value, after: &serde_json::Value) -> Vec<String> {
+    COMPARED_FIELDS
+        .iter()
+        .filter(|field| field_value(before, field) != field_value(after, field))
+        .map(|field| match *field {
+            "Source" => "Source changed".to_string(),
+            "Attributes" | "Tags" => format!("{} changed", field),
+            _ => format!(
+                "{}: {} → {}",
+                field,
+                display_field(before.get(*field)),
+                display_field(after.get(*field))
+            ),
+        })
+        .collect()
+}
+
+/// A field's value with explicit null treated the same as absent.
+fn field_value<'a>(node: &'a serde_json::Value, field: &str) -> Option<&'a serde_json::Value> {
+    node.get(field).filter(|v| !v.is_null())
+}
+
+fn display_field(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => "nil".to_string(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
 }
 
 /// Tool 17: snapshot_list — List all saved snapshots
+///
+/// With a snapshot store, returns `{live, persisted}`: the plugin's in-memory
+/// list (null if Studio isn't connected) plus everything stored on disk,
+/// which survives Studio restarts.
 pub async fn snapshot_list(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
-    send_to_plugin(state, None, "snapshot_list", json!({}), EXTENDED_TIMEOUT).await
+    let store = snapshot_store(&*state.lock().await);
+    let live = send_to_plugin(state, None, "snapshot_list", json!({}), EXTENDED_TIMEOUT).await;
+    let Some(store) = store else {
+        return live;
+    };
+
+    let live = match live {
+        Ok(v) => v,
+        Err(StudioLinkError::PluginNotConnected) => serde_json::Value::Null,
+        Err(e) => return Err(e),
+    };
+    let persisted: Vec<serde_json::Value> = store
+        .list()?
+        .iter()
+        .map(|m| {
+            json!({
+                "name": m.name,
+                "timestamp": m.timestamp,
+                "placeId": m.place_id,
+                "nodes": m.entries.len(),
+            })
+        })
+        .collect();
+    Ok(json!({
+        "live": live,
+        "persisted": persisted,
+        "storeDir": store.root().display().to_string(),
+    }))
+}
+
+/// snapshot_export — Write a stored snapshot to `path` as one portable file,
+/// or with `format` "rojo" as a Rojo project in the directory `path`
+///
+/// An existing file at `path` is replaced only with `overwrite`; a Rojo
+/// project always needs a new or empty directory.
+pub async fn snapshot_export(
+    state: &Arc<Mutex<AppState>>,
+    id: &str,
+    path: &str,
+    format: Option<&str>,
+    overwrite: Option<bool>,
+) -> Result<serde_json::Value> {
+    let rojo = match format.unwrap_or("file") {
+        "file" => false,
+        "rojo" => true,
+        other => {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "format must be \"file\" or \"rojo\", got \"{}\"",
+                other
+            )))
+        }
+    };
+    let store = require_store(&*state.lock().await)?;
+    let export = store.export(id)?.ok_or_else(|| {
+        StudioLinkError::InvalidArguments(format!(
+            "snapshot '{}' is not in the store; only snapshots saved to disk can be exported",
+            id
+        ))
+    })?;
+    if rojo {
+        let stats =
+            super::rojo::write_project(&export, std::path::Path::new(path)).map_err(|e| match e
+                .kind()
+            {
+                std::io::ErrorKind::AlreadyExists => StudioLinkError::InvalidArguments(format!(
+                    "{}; export a Rojo project into a new or empty directory",
+                    e
+                )),
+                _ => e.into(),
+            })?;
+        return Ok(json!({
+            "name": export.name,
+            "path": path,
+            "format": "rojo",
+            "project": std::path::Path::new(path).join("default.project.json").display().to_string(),
+            "placeId": export.place_id,
+            "nodes": export.nodes.len(),
+            "written": serde_json::to_value(stats)?,
+        }));
+    }
+    let bytes = serde_json::to_vec(&export)?;
+    let mut options = std::fs::OpenOptions::new();
+    if overwrite.unwrap_or(false) {
+        options.write(true).create(true).truncate(true);
+    } else {
+        options.write(true).create_new(true);
+    }
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => StudioLinkError::InvalidArguments(format!(
+            "{} already exists; pass overwrite=true to replace it",
+            path
+        )),
+        _ => e.into(),
+    })?;
+    std::io::Write::write_all(&mut file, &bytes)?;
+    Ok(json!({
+        "name": export.name,
+        "path": path,
+        "placeId": export.place_id,
+        "nodes": export.nodes.len(),
+        "bytes": bytes.len(),
+    }))
+}
+
+/// snapshot_import — Load an exported snapshot file into the store
+///
+/// The snapshot keeps its name unless `name` is given; an existing snapshot
+/// of that name is never overwritten. An export taken from a different
+/// place than the active session's is refused unless `force`.
+pub async fn snapshot_import(
+    state: &Arc<Mutex<AppState>>,
+    path: &str,
+    name: Option<&str>,
+    force: Option<bool>,
+) -> Result<serde_json::Value> {
+    let (store, active_place) = {
+        let s = state.lock().await;
+        (
+            require_store(&s)?,
+            s.get_active_session_info().map(|i| i.place_id),
+        )
+    };
+    let bytes = std::fs::read(path)
+        .map_err(|e| StudioLinkError::InvalidArguments(format!("cannot read '{}': {}", path, e)))?;
+    let export = SnapshotExport::parse(&bytes)
+        .map_err(|e| StudioLinkError::InvalidArguments(format!("'{}': {}", path, e)))?;
+
+    if !force.unwrap_or(false) {
+        check_import_place(export.place_id, active_place)?;
+    }
+    let name = name.unwrap_or(&export.name);
+    if store.load_manifest(name)?.is_some() {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "a snapshot named '{}' already exists; pass name to import under another name",
+            name
+        )));
+    }
+
+    let stats = store.save(name, export.timestamp, export.place_id, &export.nodes)?;
+    Ok(json!({
+        "name": name,
+        "placeId": export.place_id,
+        "scope": export.scope,
+        "timestamp": export.timestamp,
+        "storage": serde_json::to_value(stats)?,
+    }))
+}
+
+fn check_import_place(exported: Option<u64>, active: Option<u64>) -> Result<()> {
+    let Some(exported) = exported else {
+        return Ok(());
+    };
+    match active {
+        Some(active) if active == exported => Ok(()),
+        Some(active) => Err(StudioLinkError::InvalidArguments(format!(
+            "snapshot is from place {} but the active session is place {}; pass force=true to import anyway",
+            exported, active
+        ))),
+        None => Err(StudioLinkError::InvalidArguments(format!(
+            "snapshot is from place {} and there is no active session to check it against; pass force=true to import anyway",
+            exported
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_support::forwarded;
+
+    fn make_state() -> Arc<Mutex<AppState>> {
+        AppState::new().0
+    }
+
+    #[test]
+    fn describes_field_level_changes() {
+        let before = json!({ "ClassName": "Part", "Size": "4, 1, 2", "Source": null });
+        let after = json!({ "ClassName": "Part", "Size": "8, 1, 2", "Anchored": true });
+        let changes = describe_changes(&before, &after);
+        assert_eq!(
+            changes,
+            vec!["Size: 4, 1, 2 → 8, 1, 2", "Anchored: nil → true"]
+        );
+    }
+
+    #[tokio::test]
+    async fn compare_uses_store_without_plugin() {
+        let state = make_state();
+        let root = std::env::temp_dir().join(format!("studiolink-diff-{}", uuid::Uuid::new_v4()));
+        let store = SnapshotStore::new(&root);
+        let a = json!({ "/W/P": { "ClassName": "Part", "Size": "1, 1, 1" } });
+        let b = json!({ "/W/P": { "ClassName": "Part", "Size": "2, 2, 2" } });
+        store.save("a", 1, None, a.as_object().unwrap()).unwrap();
+        store.save("b", 2, None, b.as_object().unwrap()).unwrap();
+        state.lock().await.snapshot_store = Some(store);
+
+        let result = snapshot_compare(&state, "a", "b", None).await.unwrap();
+        assert!(result.get("scriptDiffs").is_none());
+        assert_eq!(result["comparedBy"], "hash");
+        assert_eq!(result["summary"]["changedCount"], 1);
+        assert_eq!(
+            result["changed"][0]["changes"][0],
+            "Size: 1, 1, 1 → 2, 2, 2"
+        );
+
+        let list = snapshot_list(&state).await.unwrap();
+        assert_eq!(list["live"], serde_json::Value::Null);
+        assert_eq!(list["persisted"].as_array().unwrap().len(), 2);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn compare_returns_bounded_script_diffs() {
+        let state = make_state();
+        let root = std::env::temp_dir().join(format!("studiolink-diff-{}", uuid::Uuid::new_v4()));
+        let store = SnapshotStore::new(&root);
+        let old_big: String = (0..100)
+            .map(|i| format!("local v{} = {}\n", i, i))
+            .collect();
+        let new_big: String = (0..100).map(|i| format!("print({})\n", i)).collect();
+        let a = json!({
+            "/S/Main": { "ClassName": "Script", "Source": "local x = 1\nprint(x)\n" },
+            "/S/Big": { "ClassName": "ModuleScript", "Source": old_big },
+            "/W/P": { "ClassName": "Part", "Size": "1, 1, 1" },
+        });
+        let b = json!({
+            "/S/Main": { "ClassName": "Script", "Source": "local x = 2\nprint(x)\n" },
+            "/S/Big": { "ClassName": "ModuleScript", "Source": new_big },
+            "/W/P": { "ClassName": "Part", "Size": "2, 2, 2" },
+        });
+        store.save("a", 1, None, a.as_object().unwrap()).unwrap();
+        store.save("b", 2, None, b.as_object().unwrap()).unwrap();
+        state.lock().await.snapshot_store = Some(store);
+
+        let result = snapshot_compare(&state, "a", "b", Some(true))
+            .await
+            .unwrap();
+        let diffs = result["scriptDiffs"].as_array().unwrap();
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0]["path"], "/S/Big");
+        assert!(diffs[0]["summary"]
+            .as_str()
+            .unwrap()
+            .starts_with("rewritten"));
+        assert!(diffs[0].get("diff").is_none());
+        assert_eq!(diffs[1]["path"], "/S/Main");
+        assert_eq!(
+            diffs[1]["diff"],
+            "--- a:/S/Main\n+++ b:/S/Main\n@@ -1,2 +1,2 @@\n-local x = 1\n+local x = 2\n print(x)\n"
+        );
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn import_checks_place_and_name() {
+        let state = make_state();
+        let root = std::env::temp_dir().join(format!("studiolink-diff-{}", uuid::Uuid::new_v4()));
+        let store = SnapshotStore::new(&root);
+        let body = json!({ "/W/P": { "ClassName": "Part" } });
+        store
+            .save("remote", 1, Some(99), body.as_object().unwrap())
+            .unwrap();
+        state.lock().await.snapshot_store = Some(store);
+
+        let file = root.join("remote.snapshot.json");
+        let file = file.to_str().unwrap();
+        let exported = snapshot_export(&state, "remote", file, None, None)
+            .await
+            .unwrap();
+        assert_eq!(exported["nodes"], 1);
+        // An existing file is only replaced on request
+        let err = snapshot_export(&state, "remote", file, None, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("overwrite=true"), "{}", err);
+        std::fs::write(file, "stale").unwrap();
+        snapshot_export(&state, "remote", file, None, Some(true))
+            .await
+            .unwrap();
+        assert_ne!(std::fs::read_to_string(file).unwrap(), "stale");
+
+        // No active session to vouch for place 99
+        let err = snapshot_import(&state, file, Some("copy"), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("force=true"));
+        // Name collision is refused even with force
+        let err = snapshot_import(&state, file, None, Some(true))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+
+        let imported = snapshot_import(&state, file, Some("copy"), Some(true))
+            .await
+            .unwrap();
+        assert_eq!(imported["storage"]["blobsShared"], 1);
+        let result = snapshot_compare(&state, "remote", "copy", None)
+            .await
+            .unwrap();
+        assert_eq!(result["summary"]["changedCount"], 0);
+
+        assert!(check_import_place(Some(1), Some(1)).is_ok());
+        assert!(check_import_place(Some(1), Some(2)).is_err());
+        assert!(check_import_place(None, None).is_ok());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn compare_unknown_snapshots_falls_back_to_plugin() {
+        let (request, result) = forwarded(
+            |state| async move { snapshot_compare(&state, "x", "y", Some(true)).await },
+            json!({}),
+        )
+        .await;
+        assert_eq!(request.tool, "snapshot_compare");
+        assert_eq!(request.args, json!({ "snapshotA": "x", "snapshotB": "y" }));
+        assert!(result.unwrap()["scriptDiffsUnavailable"].is_string());
+    }
 }
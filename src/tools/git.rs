@@ -0,0 +1,385 @@
+//! Script history in a local git repository: script_commit snapshots every
+//! script into it, script_history reads back commits and diffs per script.
+//!
+//! The repository's working tree is a sync_export directory, so files mirror
+//! the DataModel and `studiolink-sync.json` maps them to instance paths. Runs
+//! the `git` command-line tool, which must be on PATH.
+
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::sync::{read_manifest, MANIFEST_FILE};
+use crate::error::{Result, StudioLinkError};
+use crate::state::AppState;
+
+/// Commits script_history returns by default, and at most.
+const DEFAULT_HISTORY: usize = 20;
+const MAX_HISTORY: usize = 200;
+
+/// Diff lines kept per commit before the rest is cut off.
+const MAX_DIFF_LINES: usize = 400;
+
+/// Files listed per commit in repository-wide history.
+const MAX_LISTED_FILES: usize = 50;
+
+/// Identity for commits, so history works without any git configuration.
+const COMMITTER: [&str; 4] = [
+    "-c",
+    "user.name=StudioLink",
+    "-c",
+    "user.email=studiolink@localhost",
+];
+
+/// script_commit — Commit the current source of every script.
+///
+/// Nothing is committed when no script changed since the last commit.
+pub async fn script_commit(
+    state: &Arc<Mutex<AppState>>,
+    repo: Option<&str>,
+    message: Option<&str>,
+) -> Result<Value> {
+    let (place_id, place_name) = session_place(state).await;
+    let repo = repo_dir(repo, place_id)?;
+    let foreign = {
+        let repo = repo.clone();
+        blocking(move || Ok(repo.join(".git").exists() && read_manifest(&repo)?.is_none())).await?
+    };
+    if foreign {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "{} is a git repository that script_commit didn't create",
+            repo.display()
+        )));
+    }
+    let repo_str = repo.display().to_string();
+    // The tree is rewritten from Studio each time, so force past local edits
+    let export = super::sync::sync_export(state, &repo_str, None, None, Some(true)).await?;
+    let message = message.map(str::to_string).unwrap_or_else(|| {
+        format!(
+            "Scripts of {} ({} scripts)",
+            place_name.as_deref().unwrap_or("place"),
+            export["files"]
+        )
+    });
+    blocking(move || commit_tree(&repo, &message)).await
+}
+
+/// script_history — Commits that touched the script at `path` (a dotted
+/// instance path or a file in the repository), newest first, with diffs when
+/// `diff`. Without `path`, every commit with the files it changed.
+pub async fn script_history(
+    state: &Arc<Mutex<AppState>>,
+    repo: Option<&str>,
+    path: Option<&str>,
+    limit: Option<usize>,
+    diff: Option<bool>,
+) -> Result<Value> {
+    let (place_id, _) = session_place(state).await;
+    let repo = repo_dir(repo, place_id)?;
+    let path = path.map(str::to_string);
+    let limit = limit.unwrap_or(DEFAULT_HISTORY).clamp(1, MAX_HISTORY);
+    let diff = diff.unwrap_or(false);
+    blocking(move || history(&repo, path.as_deref(), limit, diff)).await
+}
+
+/// Run git and file system work on the blocking pool so a slow repository
+/// doesn't stall the runtime threads serving other tool calls.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| StudioLinkError::ServerError(format!("git task failed: {}", e)))?
+}
+
+async fn session_place(state: &Arc<Mutex<AppState>>) -> (u64, Option<String>) {
+    let s = state.lock().await;
+    s.get_active_session_info()
+        .map_or((0, None), |i| (i.place_id, Some(i.place_name.clone())))
+}
+
+/// `repo` as given, or ~/.studiolink/history/place-<id> for the place.
+fn repo_dir(repo: Option<&str>, place_id: u64) -> Result<PathBuf> {
+    if let Some(repo) = repo {
+        return Ok(PathBuf::from(repo));
+    }
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| {
+            PathBuf::from(home)
+                .join(".studiolink")
+                .join("history")
+                .join(format!("place-{}", place_id))
+        })
+        .ok_or_else(|| {
+            StudioLinkError::InvalidArguments(
+                "no home directory to keep history in; pass repo".into(),
+            )
+        })
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| StudioLinkError::ServerError(format!("can't run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(StudioLinkError::ServerError(format!(
+            "git failed in {}: {}",
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Commit whatever scripts changed in the working tree of `repo`, creating
+/// the repository on first use.
+fn commit_tree(repo: &Path, message: &str) -> Result<Value> {
+    if !repo.join(".git").exists() {
+        git(repo, &["init", "-q"])?;
+    }
+    // The manifest is rewritten on every export; only scripts count as changes
+    let status = git(
+        repo,
+        &[
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--untracked-files=all",
+            "--",
+            ".",
+            &format!(":!{}", MANIFEST_FILE),
+        ],
+    )?;
+    let manifest = read_manifest(repo)?;
+    let changed: Vec<Value> = status
+        .split('\0')
+        .filter(|entry| entry.len() > 3)
+        .map(|entry| {
+            let (code, file) = entry.split_at(3);
+            let change = match code.trim() {
+                "??" | "A" => "added",
+                "D" => "deleted",
+                _ => "modified",
+            };
+            let path = manifest
+                .as_ref()
+                .and_then(|m| m.files.get(file))
+                .map(|s| s.path.clone());
+            json!({ "file": file, "change": change, "path": path })
+        })
+        .collect();
+    if changed.is_empty() {
+        return Ok(json!({
+            "repo": repo.display().to_string(),
+            "committed": false,
+            "reason": "no script changed since the last commit",
+        }));
+    }
+
+    git(repo, &["add", "-A"])?;
+    let mut args = COMMITTER.to_vec();
+    args.extend(["commit", "-q", "-m", message]);
+    git(repo, &args)?;
+    let commit = git(repo, &["rev-parse", "HEAD"])?.trim().to_string();
+    Ok(json!({
+        "repo": repo.display().to_string(),
+        "committed": true,
+        "commit": commit,
+        "message": message,
+        "changed": changed,
+    }))
+}
+
+fn history(repo: &Path, path: Option<&str>, limit: usize, diff: bool) -> Result<Value> {
+    if !repo.join(".git").exists() {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "no script history in {}; run script_commit first",
+            repo.display()
+        )));
+    }
+    let file = match path {
+        Some(path) if path.ends_with(".lua") || path.ends_with(".luau") => Some(path.to_string()),
+        Some(path) => {
+            let manifest = read_manifest(repo)?;
+            let file = manifest
+                .iter()
+                .flat_map(|m| &m.files)
+                .find(|(_, script)| script.path == path)
+                .map(|(file, _)| file.clone())
+                .ok_or_else(|| {
+                    StudioLinkError::InvalidArguments(format!(
+                        "{} is not in the latest commit; pass its file path for scripts deleted since",
+                        path
+                    ))
+                })?;
+            Some(file)
+        }
+        None => None,
+    };
+
+    let limit_arg = format!("-n{}", limit);
+    let mut args = vec!["log", &limit_arg, "--format=%H%x1f%at%x1f%s"];
+    if let Some(file) = &file {
+        args.extend(["--", file.as_str()]);
+    }
+    let log = git(repo, &args)?;
+
+    let mut commits = Vec::new();
+    for line in log.lines().filter(|l| !l.is_empty()) {
+        let mut fields = line.splitn(3, '\u{1f}');
+        let (Some(commit), Some(at), Some(message)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let mut entry = json!({
+            "commit": commit,
+            "timestampUnix": at.parse::<u64>().unwrap_or(0),
+            "message": message,
+        });
+        match &file {
+            Some(file) if diff => {
+                let patch = git(
+                    repo,
+                    &["show", "--format=", "--no-color", commit, "--", file],
+                )?;
+                let lines: Vec<&str> = patch.lines().collect();
+                entry["diff"] = json!(lines
+                    .iter()
+                    .take(MAX_DIFF_LINES)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join("\n"));
+                if lines.len() > MAX_DIFF_LINES {
+                    entry["diffTruncated"] = json!(true);
+                }
+            }
+            Some(_) => {}
+            None => {
+                let files = git(repo, &["show", "--format=", "--name-only", commit])?;
+                let files: Vec<&str> = files
+                    .lines()
+                    .filter(|f| !f.is_empty() && *f != MANIFEST_FILE)
+                    .collect();
+                entry["filesChanged"] = json!(files.len());
+                entry["files"] = json!(files.iter().take(MAX_LISTED_FILES).collect::<Vec<_>>());
+            }
+        }
+        commits.push(entry);
+    }
+    Ok(json!({
+        "repo": repo.display().to_string(),
+        "file": file,
+        "commits": commits,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshots::hash_blob;
+    use crate::tools::sync::{SyncManifest, SyncedScript};
+
+    fn write_export(repo: &Path, scripts: &[(&str, &str, &str)]) {
+        let mut files = std::collections::BTreeMap::new();
+        for (file, path, source) in scripts {
+            let target = repo.join(file);
+            std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+            std::fs::write(&target, source).unwrap();
+            files.insert(
+                file.to_string(),
+                SyncedScript {
+                    path: path.to_string(),
+                    names: path.split('.').map(str::to_string).collect(),
+                    class_name: "ModuleScript".into(),
+                    hash: hash_blob(source.as_bytes()),
+                },
+            );
+        }
+        let manifest = SyncManifest {
+            format: "studiolink-sync".into(),
+            version: 1,
+            place_id: Some(1),
+            exported_at_unix_ms: scripts.len() as u64,
+            root: None,
+            files,
+        };
+        std::fs::write(
+            repo.join(MANIFEST_FILE),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn commits_only_script_changes_and_reads_history_back() {
+        let repo = std::env::temp_dir().join(format!("studiolink-git-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&repo).unwrap();
+        write_export(
+            &repo,
+            &[(
+                "ReplicatedStorage/Util.luau",
+                "ReplicatedStorage.Util",
+                "return 1\n",
+            )],
+        );
+        let first = commit_tree(&repo, "first").unwrap();
+        assert_eq!(first["committed"], true);
+        assert_eq!(first["changed"][0]["change"], "added");
+        assert_eq!(first["changed"][0]["path"], "ReplicatedStorage.Util");
+
+        // Only the manifest differs: nothing to commit
+        write_export(
+            &repo,
+            &[
+                (
+                    "ReplicatedStorage/Util.luau",
+                    "ReplicatedStorage.Util",
+                    "return 1\n",
+                ),
+                (
+                    "ReplicatedStorage/Util.luau",
+                    "ReplicatedStorage.Util",
+                    "return 1\n",
+                ),
+            ],
+        );
+        assert_eq!(commit_tree(&repo, "noop").unwrap()["committed"], false);
+
+        write_export(
+            &repo,
+            &[
+                (
+                    "ReplicatedStorage/Util.luau",
+                    "ReplicatedStorage.Util",
+                    "return 2\n",
+                ),
+                (
+                    "ServerScriptService/Main.server.luau",
+                    "ServerScriptService.Main",
+                    "print(1)\n",
+                ),
+            ],
+        );
+        let second = commit_tree(&repo, "second").unwrap();
+        assert_eq!(second["changed"].as_array().unwrap().len(), 2);
+
+        let util = history(&repo, Some("ReplicatedStorage.Util"), 10, true).unwrap();
+        let commits = util["commits"].as_array().unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0]["message"], "second");
+        let diff = commits[0]["diff"].as_str().unwrap();
+        assert!(diff.contains("-return 1") && diff.contains("+return 2"));
+
+        let all = history(&repo, None, 1, false).unwrap();
+        assert_eq!(all["commits"].as_array().unwrap().len(), 1);
+        assert_eq!(all["commits"][0]["filesChanged"], 2);
+        assert!(history(&repo, Some("Workspace.Missing"), 10, false).is_err());
+        let _ = std::fs::remove_dir_all(&repo);
+    }
+}
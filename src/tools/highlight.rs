@@ -0,0 +1,300 @@
+//! Luau syntax highlighting for numbered source ("   1 | code" lines), for
+//! showing get_script_source results straight to a developer. A small
+//! tokenizer is enough here: it only has to tell keywords, strings,
+//! comments, numbers and common globals apart, not parse.
+
+use crate::error::{Result, StudioLinkError};
+
+/// How returned source is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Render {
+    Plain,
+    /// ANSI colour escapes for terminals
+    Ansi,
+    /// `<pre>` with `<span class="...">` per token, HTML-escaped
+    Html,
+}
+
+impl Render {
+    pub fn parse(render: Option<&str>) -> Result<Self> {
+        match render.unwrap_or("plain") {
+            "plain" => Ok(Render::Plain),
+            "ansi" => Ok(Render::Ansi),
+            "html" => Ok(Render::Html),
+            other => Err(StudioLinkError::InvalidArguments(format!(
+                "render must be \"plain\", \"ansi\" or \"html\", got \"{}\"",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Keyword,
+    Builtin,
+    String,
+    Comment,
+    Number,
+    Plain,
+}
+
+impl Kind {
+    fn ansi(self) -> Option<&'static str> {
+        match self {
+            Kind::Keyword => Some("\x1b[35m"),
+            Kind::Builtin => Some("\x1b[36m"),
+            Kind::String => Some("\x1b[32m"),
+            Kind::Comment => Some("\x1b[90m"),
+            Kind::Number => Some("\x1b[33m"),
+            Kind::Plain => None,
+        }
+    }
+
+    fn class(self) -> Option<&'static str> {
+        match self {
+            Kind::Keyword => Some("kw"),
+            Kind::Builtin => Some("bi"),
+            Kind::String => Some("str"),
+            Kind::Comment => Some("com"),
+            Kind::Number => Some("num"),
+            Kind::Plain => None,
+        }
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "continue", "do", "else", "elseif", "end", "export", "false", "for",
+    "function", "if", "in", "local", "nil", "not", "or", "repeat", "return", "then", "true",
+    "type", "until", "while",
+];
+
+const BUILTINS: &[&str] = &[
+    "game",
+    "workspace",
+    "script",
+    "plugin",
+    "self",
+    "Enum",
+    "Instance",
+    "task",
+    "math",
+    "string",
+    "table",
+    "require",
+    "print",
+    "warn",
+    "error",
+    "pcall",
+    "typeof",
+];
+
+/// Length in bytes of a long bracket opener (`[[`, `[==[`) at the start of
+/// `s`, and its level.
+fn long_bracket(s: &str) -> Option<(usize, usize)> {
+    let rest = s.strip_prefix('[')?;
+    let level = rest.bytes().take_while(|b| *b == b'=').count();
+    (rest.as_bytes().get(level) == Some(&b'[')).then_some((level + 2, level))
+}
+
+/// Split Luau source into (kind, text) tokens covering all of it.
+fn tokenize(src: &str) -> Vec<(Kind, &str)> {
+    let mut tokens = Vec::new();
+    let bytes = src.as_bytes();
+    let mut i = 0;
+    while i < src.len() {
+        let rest = &src[i..];
+        let c = bytes[i];
+        let (kind, len) = if let Some(comment) = rest.strip_prefix("--") {
+            let len = match long_bracket(comment) {
+                Some((open, level)) => {
+                    let close = format!("]{}]", "=".repeat(level));
+                    comment[open..]
+                        .find(&close)
+                        .map_or(rest.len(), |p| 2 + open + p + close.len())
+                }
+                None => rest.find('\n').unwrap_or(rest.len()),
+            };
+            (Kind::Comment, len)
+        } else if let Some((open, level)) = long_bracket(rest) {
+            let close = format!("]{}]", "=".repeat(level));
+            let len = rest[open..]
+                .find(&close)
+                .map_or(rest.len(), |p| open + p + close.len());
+            (Kind::String, len)
+        } else if matches!(c, b'"' | b'\'' | b'`') {
+            let mut j = 1;
+            while j < rest.len() {
+                match rest.as_bytes()[j] {
+                    b'\\' => j += 2,
+                    b'\n' => break,
+                    b if b == c => {
+                        j += 1;
+                        break;
+                    }
+                    _ => j += 1,
+                }
+            }
+            (Kind::String, j.min(rest.len()))
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            let mut j = 0;
+            while j < rest.len() {
+                let b = rest.as_bytes()[j];
+                let exponent_sign = matches!(b, b'+' | b'-')
+                    && j > 0
+                    && matches!(rest.as_bytes()[j - 1], b'e' | b'E')
+                    && !rest.starts_with("0x");
+                if b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || exponent_sign {
+                    j += 1;
+                } else {
+                    break;
+                }
+            }
+            (Kind::Number, j)
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let len = rest
+                .bytes()
+                .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+                .count();
+            let word = &rest[..len];
+            // Field access (`foo.type`, `x:end`) is never a keyword
+            let after_dot = i > 0 && matches!(bytes[i - 1], b'.' | b':');
+            let kind = if after_dot {
+                Kind::Plain
+            } else if KEYWORDS.contains(&word) {
+                Kind::Keyword
+            } else if BUILTINS.contains(&word) {
+                Kind::Builtin
+            } else {
+                Kind::Plain
+            };
+            (kind, len)
+        } else {
+            (Kind::Plain, rest.chars().next().map_or(1, char::len_utf8))
+        };
+        tokens.push((kind, &rest[..len]));
+        i += len;
+    }
+    tokens
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Render numbered source ("   1 | code" per line, as the plugin returns
+/// it). Line numbers are kept in every mode; lines without the " | "
+/// prefix are treated as code.
+pub fn render_numbered_source(numbered: &str, render: Render) -> String {
+    if render == Render::Plain {
+        return numbered.to_string();
+    }
+    let (prefixes, code): (Vec<&str>, Vec<&str>) = numbered
+        .split('\n')
+        .map(|line| match line.find(" | ") {
+            Some(p) if line[..p].trim().chars().all(|c| c.is_ascii_digit()) => {
+                (&line[..p + 3], &line[p + 3..])
+            }
+            _ => ("", line),
+        })
+        .unzip();
+    let code = code.join("\n");
+
+    // Re-split tokens at line breaks so every line can be prefixed and, for
+    // ANSI, styling never runs across a line number.
+    let mut lines: Vec<String> = vec![String::new()];
+    for (kind, text) in tokenize(&code) {
+        for (n, segment) in text.split('\n').enumerate() {
+            if n > 0 {
+                lines.push(String::new());
+            }
+            if segment.is_empty() {
+                continue;
+            }
+            let line = lines.last_mut().expect("lines is never empty");
+            match render {
+                Render::Ansi => match kind.ansi() {
+                    Some(color) => line.push_str(&format!("{}{}\x1b[0m", color, segment)),
+                    None => line.push_str(segment),
+                },
+                _ => match kind.class() {
+                    Some(class) => line.push_str(&format!(
+                        "<span class=\"{}\">{}</span>",
+                        class,
+                        escape_html(segment)
+                    )),
+                    None => line.push_str(&escape_html(segment)),
+                },
+            }
+        }
+    }
+
+    let body: Vec<String> = lines
+        .iter()
+        .zip(prefixes)
+        .map(|(line, prefix)| match (render, prefix.is_empty()) {
+            (_, true) => line.clone(),
+            (Render::Ansi, false) => format!("\x1b[2m{}\x1b[0m{}", prefix, line),
+            (_, false) => format!("<span class=\"ln\">{}</span>{}", escape_html(prefix), line),
+        })
+        .collect();
+    match render {
+        Render::Html => format!("<pre class=\"luau\">{}</pre>", body.join("\n")),
+        _ => body.join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_luau_constructs() {
+        let src =
+            "local s = \"a\\\"b\" -- note\nlocal t = [==[x]]y]==] .. 0x1F + 1e-3\nx.type = nil";
+        let kinds: Vec<(Kind, &str)> = tokenize(src)
+            .into_iter()
+            .filter(|(k, _)| *k != Kind::Plain)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (Kind::Keyword, "local"),
+                (Kind::String, "\"a\\\"b\""),
+                (Kind::Comment, "-- note"),
+                (Kind::Keyword, "local"),
+                (Kind::String, "[==[x]]y]==]"),
+                (Kind::Number, "0x1F"),
+                (Kind::Number, "1e-3"),
+                (Kind::Keyword, "nil"),
+            ]
+        );
+        let rebuilt: String = tokenize(src).iter().map(|(_, t)| *t).collect();
+        assert_eq!(rebuilt, src);
+    }
+
+    #[test]
+    fn renders_keep_line_numbers() {
+        let numbered = "   1 | --[[ a\n   2 | b ]] if x < 1 then\n   3 | end";
+        assert_eq!(render_numbered_source(numbered, Render::Plain), numbered);
+
+        let ansi = render_numbered_source(numbered, Render::Ansi);
+        let lines: Vec<&str> = ansi.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("\x1b[2m   1 | \x1b[0m\x1b[90m--[[ a\x1b[0m"));
+        assert!(lines[1].contains("\x1b[90mb ]]\x1b[0m \x1b[35mif\x1b[0m"));
+        assert!(lines[2].starts_with("\x1b[2m   3 | \x1b[0m"));
+
+        let html = render_numbered_source(numbered, Render::Html);
+        assert!(html.starts_with("<pre class=\"luau\"><span class=\"ln\">   1 | </span>"));
+        assert!(html.contains("x &lt; <span class=\"num\">1</span>"));
+        assert!(html.ends_with("<span class=\"kw\">end</span></pre>"));
+
+        assert!(Render::parse(Some("svg")).is_err());
+        assert_eq!(Render::parse(None).unwrap(), Render::Plain);
+    }
+}
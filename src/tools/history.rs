@@ -3,8 +3,12 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use super::{send_to_plugin, DEFAULT_TIMEOUT};
-use crate::error::Result;
-use crate::state::AppState;
+use crate::error::{Result, StudioLinkError};
+use crate::state::{AppState, HistoryEntry, MacroState};
+
+/// Base URL written into exported curl scripts; overridable at replay time
+/// through the STUDIOLINK_URL environment variable.
+const DEFAULT_REPLAY_URL: &str = "http://127.0.0.1:34872";
 
 /// Tool 48: undo — Undo last action via ChangeHistoryService
 pub async fn undo(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
@@ -15,3 +19,345 @@ pub async fn undo(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
 pub async fn redo(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
     send_to_plugin(state, None, "redo", json!({}), DEFAULT_TIMEOUT).await
 }
+
+/// begin_macro — Open a named ChangeHistoryService recording so every
+/// mutation until end_macro collapses into one undo step.
+///
+/// Only one macro may be open at a time; a second begin_macro is rejected
+/// rather than nested, since Studio recordings don't nest either. The slot
+/// is taken before the plugin is asked, so two concurrent calls can't both
+/// open one, and freed again if the plugin refuses.
+pub async fn begin_macro(state: &Arc<Mutex<AppState>>, name: &str) -> Result<serde_json::Value> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(StudioLinkError::InvalidArguments(
+            "name must be a non-empty string".into(),
+        ));
+    }
+    let (conn, started_at) = {
+        let s = state.lock().await;
+        let conn = super::connection(&s);
+        let mut open = conn.lock();
+        open.prune_macro(&s.sessions);
+        if let Some(open) = &open.active_macro {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "Macro '{}' is already open. Nested macros are not supported — call end_macro first.",
+                open.name
+            )));
+        }
+        let started_at = std::time::Instant::now();
+        open.active_macro = Some(MacroState {
+            name: name.to_string(),
+            session_id: None,
+            started_at,
+        });
+        drop(open);
+        (conn, started_at)
+    };
+    // Only touch the slot while it still holds this call's reservation
+    let ours = |m: &Option<MacroState>| m.as_ref().is_some_and(|m| m.started_at == started_at);
+
+    let result = match send_to_plugin(
+        state,
+        None,
+        "begin_macro",
+        json!({ "name": name }),
+        DEFAULT_TIMEOUT,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            let mut open = conn.lock();
+            if ours(&open.active_macro) {
+                open.active_macro = None;
+            }
+            return Err(e);
+        }
+    };
+
+    // Record the session after the plugin accepted it, using the same routing
+    // send_to_plugin resolved (bound > active; auto-recovery may have updated
+    // active).
+    let s = state.lock().await;
+    let mut open = conn.lock();
+    let session_id = if s.proxy_mode {
+        open.bound_session_id.clone()
+    } else {
+        open.bound_session_id.clone().or(s.active_session.clone())
+    };
+    if ours(&open.active_macro) {
+        if let Some(m) = open.active_macro.as_mut() {
+            m.session_id = session_id.clone();
+        }
+    }
+    Ok(json!({
+        "name": name,
+        "session_id": session_id,
+        "plugin": result,
+    }))
+}
+
+/// end_macro — Commit the open macro's recording as a single undo entry.
+pub async fn end_macro(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
+    let (conn, open) = {
+        let s = state.lock().await;
+        let conn = super::connection(&s);
+        let mut guard = conn.lock();
+        guard.prune_macro(&s.sessions);
+        let open = guard.active_macro.take();
+        drop(guard);
+        (conn, open)
+    };
+    let open = open.ok_or_else(|| {
+        StudioLinkError::InvalidArguments("No macro is open. Call begin_macro first.".into())
+    })?;
+
+    let result = match send_to_plugin(
+        state,
+        open.session_id.as_deref(),
+        "end_macro",
+        json!({ "name": open.name }),
+        DEFAULT_TIMEOUT,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            // Still recording: leave it open so end_macro can be retried
+            let mut guard = conn.lock();
+            if guard.active_macro.is_none() {
+                guard.active_macro = Some(open);
+            }
+            return Err(e);
+        }
+    };
+
+    Ok(json!({
+        "name": open.name,
+        "session_id": open.session_id,
+        "duration_secs": open.started_at.elapsed().as_secs_f64(),
+        "plugin": result,
+    }))
+}
+
+/// export_history — Write this instance's recent plugin calls as a replayable
+/// artifact: a JSON array of `{tool, args}` or a shell script of curl calls
+/// against POST /proxy/tool_call. Args were redacted when recorded.
+pub async fn export_history(
+    state: &Arc<Mutex<AppState>>,
+    path: &str,
+    format: Option<&str>,
+) -> Result<serde_json::Value> {
+    if path.trim().is_empty() {
+        return Err(StudioLinkError::InvalidArguments(
+            "path must be a non-empty file path".into(),
+        ));
+    }
+    let format = format.unwrap_or("json");
+    let entries: Vec<HistoryEntry> = state.lock().await.call_history.iter().cloned().collect();
+    let contents = match format {
+        "json" => serde_json::to_string_pretty(
+            &entries
+                .iter()
+                .map(|e| json!({ "tool": e.tool, "args": e.args }))
+                .collect::<Vec<_>>(),
+        )?,
+        "curl" => render_curl_script(&entries),
+        other => {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "format must be \"json\" or \"curl\", got \"{}\"",
+                other
+            )))
+        }
+    };
+    tokio::fs::write(path, contents).await?;
+    Ok(json!({
+        "path": path,
+        "format": format,
+        "entries": entries.len(),
+    }))
+}
+
+/// One curl call per entry. Session targeting is dropped on purpose: a
+/// replay should hit whatever session is active on the machine running it.
+/// The bearer token comes from STUDIOLINK_TOKEN or the saved token file, and
+/// `curl -f` makes an HTTP error stop the script.
+fn render_curl_script(entries: &[HistoryEntry]) -> String {
+    let mut script = format!(
+        "#!/bin/sh\n# StudioLink tool history ({} calls). Replays against a running StudioLink server.\n\
+         set -e\nURL=\"${{STUDIOLINK_URL:-{}}}\"\n\
+         TOKEN=\"${{STUDIOLINK_TOKEN:-$(cat ~/.studiolink/token 2>/dev/null)}}\"\n\
+         RUN=\"replay-$(date +%s)\"\n\n",
+        entries.len(),
+        DEFAULT_REPLAY_URL
+    );
+    for (i, entry) in entries.iter().enumerate() {
+        let body = json!({ "tool": entry.tool, "args": entry.args })
+            .to_string()
+            .replace('\'', "'\\''");
+        // Splice a per-run request id (expanded by the shell) in front of the
+        // body's own fields
+        let body = format!("{{\"id\":\"'\"$RUN\"'-{}\",{}", i + 1, &body[1..]);
+        script.push_str(&format!(
+            "echo '>> {}'\ncurl -fsS -X POST \"$URL/proxy/tool_call\" -H \"Authorization: Bearer $TOKEN\" -H 'Content-Type: application/json' -d '{}'\necho\n\n",
+            entry.tool, body
+        ));
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_support::forwarded;
+
+    fn make_state() -> Arc<Mutex<AppState>> {
+        AppState::new().0
+    }
+
+    #[test]
+    fn curl_script_quotes_bodies_for_the_shell() {
+        let entries = vec![HistoryEntry {
+            at_unix_ms: 0,
+            tool: "run_code".into(),
+            args: json!({ "command": "print('hi')" }),
+            target_session: None,
+        }];
+        let script = render_curl_script(&entries);
+        assert!(script.starts_with("#!/bin/sh"));
+        assert!(script.contains("$URL/proxy/tool_call"));
+        assert!(script.contains("curl -fsS"));
+        assert!(script.contains("TOKEN=\"${STUDIOLINK_TOKEN:-$(cat ~/.studiolink/token"));
+        assert!(script.contains(r#"-H "Authorization: Bearer $TOKEN""#));
+        assert!(script.contains(r#"{"id":"'"$RUN"'-1","args":{"command":"print('\''hi'\'')"}"#));
+    }
+
+    #[tokio::test]
+    async fn export_writes_json_array() {
+        let state = make_state();
+        state
+            .lock()
+            .await
+            .record_call("get_studio_mode", &json!({}), None);
+        let path =
+            std::env::temp_dir().join(format!("studiolink-history-{}.json", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        let result = export_history(&state, path, None).await.unwrap();
+        assert_eq!(result["entries"], 1);
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(written, json!([{ "tool": "get_studio_mode", "args": {} }]));
+
+        let err = export_history(&state, path, Some("yaml"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn begin_rejects_empty_name() {
+        let state = make_state();
+        let err = begin_macro(&state, "  ").await.unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    #[tokio::test]
+    async fn begin_rejects_nesting() {
+        let state = make_state();
+        state.lock().await.connection.lock().active_macro = Some(MacroState {
+            name: "outer".to_string(),
+            session_id: None,
+            started_at: std::time::Instant::now(),
+        });
+        let err = begin_macro(&state, "inner").await.unwrap_err();
+        match err {
+            StudioLinkError::InvalidArguments(msg) => assert!(msg.contains("outer")),
+            other => panic!("expected InvalidArguments, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn end_without_open_macro_is_rejected() {
+        let state = make_state();
+        let err = end_macro(&state).await.unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    /// Wait for the call running in `handle` to queue a request on "s1",
+    /// then fail it.
+    async fn refuse_next<T: std::fmt::Debug>(
+        state: &Arc<Mutex<AppState>>,
+        handle: &tokio::task::JoinHandle<T>,
+    ) {
+        let request = loop {
+            if let Some(request) = state.lock().await.sessions.next_request("s1") {
+                break request;
+            }
+            assert!(!handle.is_finished(), "call never reached the plugin");
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        state
+            .lock()
+            .await
+            .sessions
+            .deliver_response(crate::state::PluginResponse {
+                id: request.id,
+                success: false,
+                result: serde_json::Value::Null,
+                error: Some("recording failed".into()),
+            });
+    }
+
+    #[tokio::test]
+    async fn begin_reserves_the_macro_until_the_plugin_answers() {
+        let state = crate::tools::test_support::state_with_session("s1");
+        let first = {
+            let state = state.clone();
+            tokio::spawn(async move { begin_macro(&state, "first").await })
+        };
+        while state.lock().await.sessions.queue_depth("s1") == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        // A concurrent begin sees the reservation
+        let err = begin_macro(&state, "second").await.unwrap_err();
+        assert!(err.to_string().contains("first"), "{}", err);
+
+        refuse_next(&state, &first).await;
+        assert!(first.await.unwrap().is_err());
+        assert!(state.lock().await.connection.lock().active_macro.is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_end_keeps_the_macro_open() {
+        let state = crate::tools::test_support::state_with_session("s1");
+        state.lock().await.connection.lock().active_macro = Some(MacroState {
+            name: "feature".to_string(),
+            session_id: Some("s1".to_string()),
+            started_at: std::time::Instant::now(),
+        });
+        let end = {
+            let state = state.clone();
+            tokio::spawn(async move { end_macro(&state).await })
+        };
+        refuse_next(&state, &end).await;
+        assert!(end.await.unwrap().is_err());
+        let s = state.lock().await;
+        let open = s.connection.lock();
+        assert_eq!(open.active_macro.as_ref().unwrap().name, "feature");
+    }
+
+    #[tokio::test]
+    async fn begin_forwards_name_and_records_the_session() {
+        let (request, result) = forwarded(
+            |state| async move { begin_macro(&state, "feature").await },
+            serde_json::json!({}),
+        )
+        .await;
+        assert_eq!(request.tool, "begin_macro");
+        assert_eq!(request.args["name"], "feature");
+        assert_eq!(result.unwrap()["session_id"], "s1");
+    }
+}
@@ -1,50 +1,568 @@
+use base64::Engine;
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 
-use super::{send_to_plugin, DEFAULT_TIMEOUT};
-use crate::error::Result;
+use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
+use crate::error::{Result, StudioLinkError};
 use crate::state::AppState;
 
+/// Valid ranges for the fields of a `PhysicalProperties` value, mirroring the
+/// clamps Roblox applies in `PhysicalProperties.new`.
+const PHYSICAL_PROPERTY_RANGES: &[(&str, f64, f64, bool)] = &[
+    // (field, min, max, required)
+    ("density", 0.0001, 100.0, true),
+    ("friction", 0.0, 2.0, true),
+    ("elasticity", 0.0, 1.0, true),
+    ("frictionWeight", 0.0, 100.0, false),
+    ("elasticityWeight", 0.0, 100.0, false),
+];
+
+/// Check a typed value's shape before dispatch so malformed input fails with
+/// a clear message instead of an opaque plugin-side conversion error.
+fn validate_typed_value(value: &serde_json::Value, value_type: Option<&str>) -> Result<()> {
+    match value_type {
+        Some("PhysicalProperties") => validate_physical_properties(value),
+        Some("Vector2") => validate_vector2(value),
+        Some("Rect") => validate_rect(value),
+        _ => Ok(()),
+    }
+}
+
+/// Validate a typed value and rewrite Color3 values into the one form the
+/// plugin expects.
+fn prepare_typed_value(
+    value: serde_json::Value,
+    value_type: Option<&str>,
+) -> Result<serde_json::Value> {
+    if value_type == Some("Color3") {
+        return normalize_color3(&value);
+    }
+    validate_typed_value(&value, value_type)?;
+    Ok(value)
+}
+
+/// Turn any accepted Color3 form into `{"__type": "Color3", R, G, B}` with
+/// 0-1 components:
+///
+/// - `"#RRGGBB"` hex strings
+/// - `[r, g, b]` arrays, `{r, g, b}` / `{R, G, B}` objects and `"r, g, b"`
+///   strings, either all 0-1 or all 0-255
+///
+/// Components above 1 mean the whole color is on the 0-255 scale, so
+/// `[1, 1, 1]` is white, not near-black.
+fn normalize_color3(value: &serde_json::Value) -> Result<serde_json::Value> {
+    let invalid = || {
+        StudioLinkError::InvalidArguments(format!(
+            "Color3 value must be \"#RRGGBB\", [r, g, b] or {{r, g, b}} with components all 0-1 or all 0-255, got {}",
+            value
+        ))
+    };
+    let components: [f64; 3] = match value {
+        serde_json::Value::String(s) if s.starts_with('#') => {
+            let hex = &s[1..];
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+            [channel(0), channel(2), channel(4)].map(|c| c as f64)
+        }
+        serde_json::Value::String(s) => {
+            let parts: Vec<f64> = s
+                .trim_matches(|c| matches!(c, '[' | ']' | '(' | ')'))
+                .split(',')
+                .map(|p| p.trim().parse::<f64>())
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|_| invalid())?;
+            parts.try_into().map_err(|_| invalid())?
+        }
+        serde_json::Value::Array(_) => number_array(value, 3)
+            .and_then(|v| v.try_into().ok())
+            .ok_or_else(invalid)?,
+        serde_json::Value::Object(obj) => {
+            let field = |lower: &str, upper: &str| {
+                obj.get(lower)
+                    .or_else(|| obj.get(upper))
+                    .and_then(|v| v.as_f64())
+            };
+            match (field("r", "R"), field("g", "G"), field("b", "B")) {
+                (Some(r), Some(g), Some(b)) => [r, g, b],
+                _ => return Err(invalid()),
+            }
+        }
+        _ => return Err(invalid()),
+    };
+    if components
+        .iter()
+        .any(|c| !c.is_finite() || *c < 0.0 || *c > 255.0)
+    {
+        return Err(invalid());
+    }
+    let scale = if components.iter().any(|c| *c > 1.0) {
+        255.0
+    } else {
+        1.0
+    };
+    let [r, g, b] = components.map(|c| c / scale);
+    Ok(json!({ "__type": "Color3", "R": r, "G": g, "B": b }))
+}
+
+/// Property names create_instance treats as Color3 (`Color`, `TextColor3`,
+/// `BackgroundColor3`, ...), since its properties carry no type hints.
+fn is_color_property(name: &str) -> bool {
+    name == "Color" || name.ends_with("Color3")
+}
+
+/// Normalize the Color3-valued entries of a create_instance `properties`
+/// object. Values that are already tagged with a non-Color3 `__type` are
+/// left alone.
+fn normalize_color_properties(properties: &mut serde_json::Value) -> Result<()> {
+    let Some(props) = properties.as_object_mut() else {
+        return Ok(());
+    };
+    for (name, value) in props.iter_mut() {
+        if !is_color_property(name) {
+            continue;
+        }
+        if value["__type"].as_str().is_some_and(|t| t != "Color3") {
+            continue;
+        }
+        *value = normalize_color3(value).map_err(|e| match e {
+            StudioLinkError::InvalidArguments(msg) => {
+                StudioLinkError::InvalidArguments(format!("{}: {}", name, msg))
+            }
+            other => other,
+        })?;
+    }
+    Ok(())
+}
+
+/// `[a, b, ...]` with exactly `len` numbers.
+fn number_array(value: &serde_json::Value, len: usize) -> Option<Vec<f64>> {
+    let items = value.as_array().filter(|items| items.len() == len)?;
+    items.iter().map(|v| v.as_f64()).collect()
+}
+
+/// `Vector2` expects `[x, y]` (e.g. AnchorPoint `[0.5, 0.5]`).
+fn validate_vector2(value: &serde_json::Value) -> Result<()> {
+    if number_array(value, 2).is_none() {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "Vector2 value must be an array of 2 numbers [x, y], got {}",
+            value
+        )));
+    }
+    Ok(())
+}
+
+/// `Rect` expects `{min: [x, y], max: [x, y]}` or `[minX, minY, maxX, maxY]`
+/// (e.g. SliceCenter).
+fn validate_rect(value: &serde_json::Value) -> Result<()> {
+    let valid = number_array(value, 4).is_some()
+        || (number_array(&value["min"], 2).is_some() && number_array(&value["max"], 2).is_some());
+    if !valid {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "Rect value must be {{\"min\": [x, y], \"max\": [x, y]}} or [minX, minY, maxX, maxY], got {}",
+            value
+        )));
+    }
+    Ok(())
+}
+
+/// `PhysicalProperties` expects `{density, friction, elasticity,
+/// frictionWeight?, elasticityWeight?}`. `null` is accepted and turns custom
+/// physical properties off again.
+fn validate_physical_properties(value: &serde_json::Value) -> Result<()> {
+    if value.is_null() {
+        return Ok(());
+    }
+    let obj = value.as_object().ok_or_else(|| {
+        StudioLinkError::InvalidArguments(
+            "PhysicalProperties value must be an object {density, friction, elasticity, frictionWeight?, elasticityWeight?} or null".into(),
+        )
+    })?;
+    for (field, min, max, required) in PHYSICAL_PROPERTY_RANGES {
+        match obj.get(*field) {
+            None if *required => {
+                return Err(StudioLinkError::InvalidArguments(format!(
+                    "PhysicalProperties.{} is required",
+                    field
+                )))
+            }
+            None => {}
+            Some(v) => {
+                let n = v.as_f64().ok_or_else(|| {
+                    StudioLinkError::InvalidArguments(format!(
+                        "PhysicalProperties.{} must be a number",
+                        field
+                    ))
+                })?;
+                if n < *min || n > *max {
+                    return Err(StudioLinkError::InvalidArguments(format!(
+                        "PhysicalProperties.{} must be between {} and {}, got {}",
+                        field, min, max, n
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Tool 38: get_file_tree — Hierarchical instance tree
+///
+/// `sort` "name" or "class" orders every node's children deterministically
+/// (ties keep Studio's order); "default" leaves Studio's order untouched.
+/// The plugin emits an instance it has already listed as a `ref` node
+/// instead of expanding it again; any other repeated `debugId` means the
+/// hierarchy is malformed, so it is logged and listed under
+/// `duplicateDebugIds`.
 pub async fn get_file_tree(
     state: &Arc<Mutex<AppState>>,
     path: Option<&str>,
     depth: Option<u32>,
+    include_tags: Option<bool>,
+    include_attributes: Option<bool>,
+    sort: Option<&str>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let sort = sort.unwrap_or("default");
+    if !matches!(sort, "default" | "name" | "class") {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "sort must be \"default\", \"name\" or \"class\", got \"{}\"",
+            sort
+        )));
+    }
+    let mut tree = send_to_plugin(
         state,
         None,
         "get_file_tree",
-        json!({ "path": path.unwrap_or(""), "depth": depth.unwrap_or(10) }),
+        json!({
+            "path": path.unwrap_or(""),
+            "depth": depth.unwrap_or(10),
+            "includeTags": include_tags.unwrap_or(false),
+            "includeAttributes": include_attributes.unwrap_or(false),
+        }),
         DEFAULT_TIMEOUT,
     )
-    .await
+    .await?;
+    let duplicates = duplicate_node_ids(&tree);
+    if !duplicates.is_empty() {
+        tracing::warn!(
+            "get_file_tree: {} node(s) expanded more than once: {}",
+            duplicates.len(),
+            duplicates.join(", ")
+        );
+        if let Some(obj) = tree.as_object_mut() {
+            obj.insert("duplicateDebugIds".into(), json!(duplicates));
+        }
+    }
+    if sort != "default" {
+        sort_tree(&mut tree, sort == "class");
+    }
+    Ok(tree)
+}
+
+/// debugIds carried by more than one expanded (non-`ref`) node, in the order
+/// they are first repeated.
+fn duplicate_node_ids(tree: &serde_json::Value) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    let mut duplicates = Vec::new();
+    let mut stack: Vec<&serde_json::Value> = match tree["services"].as_array() {
+        Some(services) => services.iter().rev().collect(),
+        None => vec![tree],
+    };
+    while let Some(node) = stack.pop() {
+        if node["ref"].as_bool() == Some(true) {
+            continue;
+        }
+        if let Some(id) = node["debugId"].as_str() {
+            if !seen.insert(id) && !duplicates.iter().any(|d| d == id) {
+                duplicates.push(id.to_string());
+            }
+        }
+        if let Some(children) = node["children"].as_array() {
+            stack.extend(children.iter().rev());
+        }
+    }
+    duplicates
+}
+
+/// Sort children at every level by name (then class), or by class (then
+/// name). The top-level services list keeps its fixed order.
+fn sort_tree(node: &mut serde_json::Value, by_class: bool) {
+    if let Some(services) = node["services"].as_array_mut() {
+        services.iter_mut().for_each(|s| sort_tree(s, by_class));
+        return;
+    }
+    let Some(children) = node.get_mut("children").and_then(|c| c.as_array_mut()) else {
+        return;
+    };
+    let key = |n: &serde_json::Value| {
+        let name = n["name"].as_str().unwrap_or("").to_string();
+        let class = n["className"].as_str().unwrap_or("").to_string();
+        if by_class {
+            (class, name)
+        } else {
+            (name, class)
+        }
+    };
+    children.sort_by_cached_key(key);
+    children.iter_mut().for_each(|c| sort_tree(c, by_class));
+}
+
+/// Running totals over a streamed tree, so a summary can be returned without
+/// ever holding the nodes themselves.
+#[derive(Debug, Default)]
+struct TreeStreamSummary {
+    nodes: u64,
+    max_depth: u64,
+    invalid_lines: u64,
+    classes: HashMap<String, u64>,
+}
+
+impl TreeStreamSummary {
+    fn add(&mut self, line: &str) {
+        let Ok(node) = serde_json::from_str::<serde_json::Value>(line) else {
+            self.invalid_lines += 1;
+            return;
+        };
+        self.nodes += 1;
+        if let Some(depth) = node["depth"].as_u64() {
+            self.max_depth = self.max_depth.max(depth);
+        }
+        if let Some(class) = node["className"].as_str() {
+            *self.classes.entry(class.to_string()).or_default() += 1;
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let mut classes: Vec<(&String, &u64)> = self.classes.iter().collect();
+        classes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let top: Vec<serde_json::Value> = classes
+            .iter()
+            .take(15)
+            .map(|(class, count)| json!({ "className": class, "count": count }))
+            .collect();
+        json!({
+            "nodeCount": self.nodes,
+            "maxDepth": self.max_depth,
+            "distinctClasses": self.classes.len(),
+            "topClasses": top,
+            "invalidLines": self.invalid_lines,
+        })
+    }
+}
+
+/// get_file_tree_stream — Walk a very large tree without building it in memory.
+///
+/// The plugin emits flat node records (`{id, parentId, name, className, depth}`)
+/// in batches to POST /stream/{id}; each NDJSON line is relayed here as it
+/// arrives and either appended to `output_path` or folded into a summary.
+/// **Limitation**: direct mode only — in proxy mode the plugin posts to the
+/// primary, which has no stream open for this call.
+pub async fn get_file_tree_stream(
+    state: &Arc<Mutex<AppState>>,
+    path: Option<&str>,
+    depth: Option<u32>,
+    output_path: Option<&str>,
+) -> Result<serde_json::Value> {
+    if output_path.is_some_and(|p| p.trim().is_empty()) {
+        return Err(StudioLinkError::InvalidArguments(
+            "outputPath must be a non-empty file path".into(),
+        ));
+    }
+    if state.lock().await.proxy_mode {
+        return Err(StudioLinkError::InvalidArguments(
+            "get_file_tree_stream is not available in proxy mode; use get_file_tree".into(),
+        ));
+    }
+
+    let mut file = match output_path {
+        Some(p) => Some(tokio::io::BufWriter::new(tokio::fs::File::create(p).await?)),
+        None => None,
+    };
+
+    let sessions = state.lock().await.sessions.clone();
+    let (stream_id, mut rx) = sessions.open_stream();
+    let consumer = tokio::spawn(async move {
+        let mut summary = TreeStreamSummary::default();
+        while let Some(line) = rx.recv().await {
+            summary.add(&line);
+            if let Some(f) = file.as_mut() {
+                f.write_all(line.as_bytes()).await?;
+                f.write_all(b"\n").await?;
+            }
+        }
+        if let Some(f) = file.as_mut() {
+            f.flush().await?;
+        }
+        Ok::<_, std::io::Error>(summary)
+    });
+
+    let result = send_to_plugin(
+        state,
+        None,
+        "get_file_tree_stream",
+        json!({
+            "path": path.unwrap_or(""),
+            "depth": depth.unwrap_or(1000),
+            "streamId": stream_id,
+        }),
+        EXTENDED_TIMEOUT,
+    )
+    .await;
+
+    // Every chunk POST completes before the plugin responds, so closing here
+    // can't cut off in-flight lines.
+    sessions.close_stream(&stream_id);
+    let summary = consumer
+        .await
+        .map_err(|e| StudioLinkError::ServerError(format!("stream consumer failed: {}", e)))??;
+    let plugin = result?;
+
+    let mut out = summary.to_json();
+    out["batches"] = plugin["batches"].clone();
+    if let Some(p) = output_path {
+        out["outputPath"] = json!(p);
+        out["format"] = json!("ndjson");
+    }
+    Ok(out)
 }
 
+/// Largest serialized subtree the plugin will return.
+pub const MAX_SERIALIZED_BYTES: usize = 10 * 1024 * 1024;
+
+/// Subtrees with more descendants than this get a size warning.
+const LARGE_SUBTREE_DESCENDANTS: u64 = 5_000;
+
 /// Tool 39: get_instance_properties — All properties of an instance
+///
+/// With `serialize`, returns the whole subtree as base64 rbxm instead of a
+/// property summary. With `output_path` the decoded file is written to disk
+/// and the base64 payload is dropped from the result.
+///
+/// `round` rounds every non-integer number in the summary to that many
+/// decimal places. It only affects the returned text, never the place.
+///
+/// `mark_defaults` makes each class property come back as `{value,
+/// isDefault}`, compared by the plugin against a fresh instance of the class.
 pub async fn get_instance_properties(
     state: &Arc<Mutex<AppState>>,
     path: &str,
+    serialize: Option<bool>,
+    output_path: Option<&str>,
+    round: Option<u8>,
+    mark_defaults: Option<bool>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let serialize = serialize.unwrap_or(false);
+    if output_path.is_some() && !serialize {
+        return Err(StudioLinkError::InvalidArguments(
+            "outputPath is only used with serialize=true".into(),
+        ));
+    }
+    if round.is_some() && serialize {
+        return Err(StudioLinkError::InvalidArguments(
+            "round applies to the property summary, not serialize=true".into(),
+        ));
+    }
+    let mark_defaults = mark_defaults.unwrap_or(false);
+    if mark_defaults && serialize {
+        return Err(StudioLinkError::InvalidArguments(
+            "markDefaults applies to the property summary, not serialize=true".into(),
+        ));
+    }
+    let mut args = json!({ "path": path });
+    if mark_defaults {
+        args["markDefaults"] = json!(true);
+    }
+    if serialize {
+        args["serialize"] = json!(true);
+        args["maxBytes"] = json!(MAX_SERIALIZED_BYTES);
+    }
+    let mut result = send_to_plugin(
         state,
         None,
         "get_instance_properties",
-        json!({ "path": path }),
-        DEFAULT_TIMEOUT,
+        args,
+        if serialize {
+            EXTENDED_TIMEOUT
+        } else {
+            DEFAULT_TIMEOUT
+        },
     )
-    .await
+    .await?;
+    if !serialize {
+        if let Some(places) = round {
+            round_numbers(&mut result, places);
+        }
+        return Ok(result);
+    }
+
+    if let Some(count) = result["descendantCount"]
+        .as_u64()
+        .filter(|c| *c > LARGE_SUBTREE_DESCENDANTS)
+    {
+        result["warning"] = json!(format!(
+            "large subtree ({} descendants); consider serializing a smaller part",
+            count
+        ));
+    }
+    if let Some(out) = output_path {
+        let encoded = result["data"]
+            .as_str()
+            .ok_or_else(|| StudioLinkError::PluginError("serialized result has no data".into()))?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| {
+                StudioLinkError::PluginError(format!("invalid base64 from plugin: {}", e))
+            })?;
+        std::fs::write(out, &bytes)?;
+        if let Some(obj) = result.as_object_mut() {
+            obj.remove("data");
+        }
+        result["outputPath"] = json!(out);
+    }
+    Ok(result)
+}
+
+/// Round every fractional number in `value`, recursively, to `places`
+/// decimals. Integers and values too large to scale are left as they are.
+fn round_numbers(value: &mut serde_json::Value, places: u8) {
+    match value {
+        serde_json::Value::Number(n) if n.is_f64() => {
+            let factor = 10f64.powi(i32::from(places));
+            if let Some(rounded) = n
+                .as_f64()
+                .map(|f| (f * factor).round() / factor)
+                .filter(|r| r.is_finite())
+                .and_then(serde_json::Number::from_f64)
+            {
+                *n = rounded;
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|v| round_numbers(v, places)),
+        serde_json::Value::Object(map) => map.values_mut().for_each(|v| round_numbers(v, places)),
+        _ => {}
+    }
 }
 
 /// Tool 40: set_property — Set a single property on an instance
+///
+/// `operation` "add" or "multiply" applies `value` to the property's current
+/// value in the plugin under the same single waypoint, so incremental tweaks
+/// need no read round trip. Only numbers and Vector2/Vector3 qualify.
 pub async fn set_property(
     state: &Arc<Mutex<AppState>>,
     path: &str,
     property: &str,
     value: serde_json::Value,
     value_type: Option<&str>,
+    operation: Option<&str>,
 ) -> Result<serde_json::Value> {
+    let operation = operation.unwrap_or("set");
+    validate_operation(operation, &value, value_type)?;
+    let value = prepare_typed_value(value, value_type)?;
     send_to_plugin(
         state,
         None,
@@ -54,67 +572,906 @@ pub async fn set_property(
             "property": property,
             "value": value,
             "valueType": value_type,
+            "operation": operation,
         }),
         DEFAULT_TIMEOUT,
     )
     .await
 }
 
+/// An arithmetic operation needs a number or a 2/3-number vector as its
+/// operand, and a value type it makes sense for.
+fn validate_operation(
+    operation: &str,
+    value: &serde_json::Value,
+    value_type: Option<&str>,
+) -> Result<()> {
+    match operation {
+        "set" => return Ok(()),
+        "add" | "multiply" => {}
+        other => {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "operation must be \"set\", \"add\" or \"multiply\", got \"{}\"",
+                other
+            )))
+        }
+    }
+    let operand_ok = match value_type {
+        None => {
+            value.is_number()
+                || number_array(value, 2).is_some()
+                || number_array(value, 3).is_some()
+        }
+        Some("number") => value.is_number(),
+        // Vectors can be scaled by a number, but only added to a vector
+        Some("Vector2") => {
+            number_array(value, 2).is_some() || (operation == "multiply" && value.is_number())
+        }
+        Some("Vector3") => {
+            number_array(value, 3).is_some() || (operation == "multiply" && value.is_number())
+        }
+        Some(other) => {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "operation \"{}\" only works on number, Vector2 and Vector3 values, not {}",
+                operation, other
+            )))
+        }
+    };
+    if !operand_ok {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "operation \"{}\" needs a number or an [x, y] / [x, y, z] array, got {}",
+            operation, value
+        )));
+    }
+    Ok(())
+}
+
+/// Placeholders a mass_set_property `valueTemplate` may use: `{index}` is the
+/// 1-based position in `paths`, `{i}` the 0-based one, `{name}` the
+/// instance's current Name.
+const TEMPLATE_PLACEHOLDERS: &[&str] = &["index", "i", "name"];
+
+/// Reject templates with unclosed braces or placeholders the plugin won't
+/// substitute, so a typo doesn't end up literally in every instance.
+fn validate_value_template(template: &str) -> Result<()> {
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let after = &rest[open + 1..];
+        let close = after.find('}').ok_or_else(|| {
+            StudioLinkError::InvalidArguments(format!(
+                "valueTemplate has an unclosed '{{': \"{}\"",
+                template
+            ))
+        })?;
+        let name = &after[..close];
+        if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "valueTemplate placeholder {{{}}} is not supported; use {{index}}, {{i}} or {{name}}",
+                name
+            )));
+        }
+        rest = &after[close + 1..];
+    }
+    Ok(())
+}
+
 /// Tool 41: mass_set_property — Set property across multiple instances
+///
+/// Exactly one of `value` and `value_template` must be given. A template is
+/// expanded by the plugin per target in path order (e.g. `"Part_{index}"`),
+/// converted with `value_type` like a literal, and the computed values are
+/// returned in `values`.
 pub async fn mass_set_property(
     state: &Arc<Mutex<AppState>>,
     paths: Vec<String>,
     property: &str,
-    value: serde_json::Value,
+    value: Option<serde_json::Value>,
+    value_template: Option<&str>,
     value_type: Option<&str>,
+    progress: Option<bool>,
 ) -> Result<serde_json::Value> {
+    let mut args = json!({
+        "paths": paths,
+        "property": property,
+        "valueType": value_type,
+    });
+    match (value, value_template) {
+        (Some(value), None) => {
+            args["value"] = prepare_typed_value(value, value_type)?;
+        }
+        (None, Some(template)) => {
+            validate_value_template(template)?;
+            args["valueTemplate"] = json!(template);
+        }
+        _ => {
+            return Err(StudioLinkError::InvalidArguments(
+                "provide exactly one of value or valueTemplate".into(),
+            ))
+        }
+    }
+    // With progress on, the plugin yields between batches, so the whole run
+    // takes longer; give it the extended timeout.
+    let progress = progress.unwrap_or(false);
+    args["progress"] = json!(progress);
     send_to_plugin(
         state,
         None,
         "mass_set_property",
-        json!({
-            "paths": paths,
-            "property": property,
-            "value": value,
-            "valueType": value_type,
-        }),
-        DEFAULT_TIMEOUT,
+        args,
+        if progress {
+            EXTENDED_TIMEOUT
+        } else {
+            DEFAULT_TIMEOUT
+        },
     )
     .await
 }
 
 /// Tool 42: create_instance — Create a new instance
+///
+/// With `validate` (the default) the plugin checks every property against
+/// the class before parenting the instance. If any fail, nothing is created
+/// and the invalid properties come back as an `InvalidArguments` error.
+/// `include_debug_id` adds the new instance's `debugId` for resolve_debug_id.
+/// `preset` names an appearance preset (see tools::appearance) whose
+/// properties are applied after `properties`. Color properties (`Color`,
+/// `*Color3`) accept the same forms as a Color3 `set_property` value.
+///
+/// The plugin adds `warnings` when the parent is a poor fit for the class
+/// (a LocalScript under Workspace, a Frame outside any ScreenGui). They are
+/// advisory: the instance is still created, and the warnings are logged.
 pub async fn create_instance(
     state: &Arc<Mutex<AppState>>,
     class_name: &str,
     parent_path: Option<&str>,
     properties: Option<serde_json::Value>,
+    validate: Option<bool>,
+    include_debug_id: Option<bool>,
+    preset: Option<&str>,
 ) -> Result<serde_json::Value> {
+    let mut properties = properties;
+    if let Some(props) = properties.as_mut() {
+        normalize_color_properties(props)?;
+    }
+    let mut args = json!({
+        "className": class_name,
+        "parentPath": parent_path.unwrap_or(""),
+        "properties": properties,
+        "validate": validate.unwrap_or(true),
+        "includeDebugId": include_debug_id.unwrap_or(false),
+    });
+    if let Some(preset) = preset {
+        args["appearance"] = super::appearance::expand_preset(preset)?;
+    }
+    let result = send_to_plugin(state, None, "create_instance", args, DEFAULT_TIMEOUT).await?;
+    for warning in result["warnings"].as_array().into_iter().flatten() {
+        tracing::warn!(
+            "create_instance {}: {}",
+            class_name,
+            warning.as_str().unwrap_or_default()
+        );
+    }
+    match invalid_properties_error(class_name, &result) {
+        Some(message) => Err(StudioLinkError::InvalidArguments(message)),
+        None => Ok(result),
+    }
+}
+
+/// The error for a create the plugin refused because of invalid properties.
+fn invalid_properties_error(class_name: &str, result: &serde_json::Value) -> Option<String> {
+    let invalid = result["invalidProperties"].as_array()?;
+    if result["created"] != json!(false) || invalid.is_empty() {
+        return None;
+    }
+    let list: Vec<String> = invalid
+        .iter()
+        .map(|p| {
+            format!(
+                "{} ({})",
+                p["name"].as_str().unwrap_or("?"),
+                p["reason"].as_str().unwrap_or("invalid")
+            )
+        })
+        .collect();
+    Some(format!(
+        "invalid properties for {}: {}; nothing was created",
+        class_name,
+        list.join(", ")
+    ))
+}
+
+/// Tool 43: delete_instance — Delete an instance
+///
+/// Paths that are obviously structural (the DataModel, a service, Terrain)
+/// are rejected here without a round trip. The plugin then checks the
+/// resolved instance itself; `force` only overrides its soft refusals
+/// (e.g. the current camera), never a service.
+pub async fn delete_instance(
+    state: &Arc<Mutex<AppState>>,
+    path: &str,
+    force: Option<bool>,
+) -> Result<serde_json::Value> {
+    if let Some(reason) = undeletable_path_reason(path) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "refusing to delete '{}': {}",
+            path, reason
+        )));
+    }
     send_to_plugin(
         state,
         None,
-        "create_instance",
-        json!({
-            "className": class_name,
-            "parentPath": parent_path.unwrap_or(""),
-            "properties": properties,
-        }),
+        "delete_instance",
+        json!({ "path": path, "force": force.unwrap_or(false) }),
         DEFAULT_TIMEOUT,
     )
     .await
 }
 
-/// Tool 43: delete_instance — Delete an instance
-pub async fn delete_instance(
+/// Why a path can never be deleted, judged from the path alone. Every direct
+/// child of the DataModel is addressed as a service by the plugin's resolver.
+fn undeletable_path_reason(path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = path.split('.').filter(|s| !s.is_empty()).collect();
+    if segments.first() == Some(&"game") {
+        segments.remove(0);
+    }
+    match segments.as_slice() {
+        [] => Some("it is the DataModel (game) itself".into()),
+        [service] => Some(format!("'{}' is a DataModel service", service)),
+        ["Workspace", "Terrain"] => Some("Terrain is a Workspace singleton".into()),
+        _ => None,
+    }
+}
+
+/// Header of every binary model file: `<roblox!` followed by a fixed signature.
+const RBXM_MAGIC: &[u8] = b"<roblox!\x89\xff\x0d\x0a\x1a\x0a";
+
+/// import_instance — Insert the model in an .rbxm/.rbxmx file under
+/// `parent_path`. The file is checked here just enough to reject things
+/// that are clearly not a Roblox model, then forwarded as base64; the plugin
+/// deserializes it under one undo waypoint and reports the inserted roots
+/// along with any asset references that failed to load. With
+/// `include_debug_id`, `debugIds` pairs each inserted root with its DebugId.
+pub async fn import_instance(
     state: &Arc<Mutex<AppState>>,
     path: &str,
+    parent_path: &str,
+    include_debug_id: Option<bool>,
 ) -> Result<serde_json::Value> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| StudioLinkError::InvalidArguments(format!("cannot read '{}': {}", path, e)))?;
+    if bytes.len() > MAX_SERIALIZED_BYTES {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "'{}' is {} bytes, over the {} byte import cap",
+            path,
+            bytes.len(),
+            MAX_SERIALIZED_BYTES
+        )));
+    }
+    let format = model_format(&bytes).ok_or_else(|| {
+        StudioLinkError::InvalidArguments(format!("'{}' is not an rbxm or rbxmx model file", path))
+    })?;
     send_to_plugin(
         state,
         None,
-        "delete_instance",
+        "import_instance",
+        json!({
+            "parentPath": parent_path,
+            "format": format,
+            "data": base64::engine::general_purpose::STANDARD.encode(&bytes),
+            "includeDebugId": include_debug_id.unwrap_or(false),
+        }),
+        EXTENDED_TIMEOUT,
+    )
+    .await
+}
+
+/// "rbxm" for binary models, "rbxmx" for XML ones, None for anything else.
+fn model_format(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(RBXM_MAGIC) {
+        return Some("rbxm");
+    }
+    let text = std::str::from_utf8(bytes).ok()?;
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let text = match text.strip_prefix("<?xml") {
+        Some(rest) => rest.split_once("?>")?.1.trim_start(),
+        None => text,
+    };
+    (text.starts_with("<roblox") && text.trim_end().ends_with("</roblox>")).then_some("rbxmx")
+}
+
+/// get_debug_id — The Studio DebugId of the instance at `path`. Unlike the
+/// path, it survives renames and reparenting for the instance's lifetime.
+pub async fn get_debug_id(state: &Arc<Mutex<AppState>>, path: &str) -> Result<serde_json::Value> {
+    if path.trim().is_empty() {
+        return Err(StudioLinkError::InvalidArguments(
+            "path must be a non-empty dot-separated instance path".into(),
+        ));
+    }
+    send_to_plugin(
+        state,
+        None,
+        "get_debug_id",
         json!({ "path": path }),
         DEFAULT_TIMEOUT,
     )
     .await
 }
+
+/// resolve_debug_id — Current path of the instance with `debug_id`. Fails
+/// with a plugin error once the instance has been destroyed.
+pub async fn resolve_debug_id(
+    state: &Arc<Mutex<AppState>>,
+    debug_id: &str,
+) -> Result<serde_json::Value> {
+    validate_debug_id(debug_id)?;
+    // Walks the whole DataModel on the plugin side
+    send_to_plugin(
+        state,
+        None,
+        "resolve_debug_id",
+        json!({ "debugId": debug_id }),
+        EXTENDED_TIMEOUT,
+    )
+    .await
+}
+
+/// DebugIds look like `<scope>_<number>` (e.g. "0_1234" or "B2E4_56789").
+fn validate_debug_id(debug_id: &str) -> Result<()> {
+    let valid = debug_id.split_once('_').is_some_and(|(scope, number)| {
+        !scope.is_empty()
+            && scope.chars().all(|c| c.is_ascii_alphanumeric())
+            && !number.is_empty()
+            && number.chars().all(|c| c.is_ascii_digit())
+    });
+    if !valid {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "'{}' is not a DebugId; expected the form <scope>_<number> as returned by get_debug_id",
+            debug_id
+        )));
+    }
+    Ok(())
+}
+
+/// get_selection — What's currently selected in Studio
+///
+/// With `summary`, the plugin also reports classes, world bounds and the
+/// properties shared by every selected instance; see `structure_selection_summary`.
+pub async fn get_selection(
+    state: &Arc<Mutex<AppState>>,
+    summary: Option<bool>,
+) -> Result<serde_json::Value> {
+    let summary = summary.unwrap_or(false);
+    let mut result = send_to_plugin(
+        state,
+        None,
+        "get_selection",
+        json!({ "summary": summary }),
+        DEFAULT_TIMEOUT,
+    )
+    .await?;
+    if let Some(raw) = result.get_mut("summary") {
+        *raw = structure_selection_summary(raw);
+    }
+    Ok(result)
+}
+
+/// Turn the plugin's raw selection summary into a stable shape: classes as a
+/// count-sorted list, bounds with derived size and center, and explicit
+/// `mixedClasses` so callers know the common properties are the intersection.
+fn structure_selection_summary(raw: &serde_json::Value) -> serde_json::Value {
+    let mut classes: Vec<(String, u64)> = raw["classes"]
+        .as_object()
+        .map(|m| {
+            m.iter()
+                .map(|(k, v)| (k.clone(), v.as_u64().unwrap_or(0)))
+                .collect()
+        })
+        .unwrap_or_default();
+    classes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let vec3 = |v: &serde_json::Value| -> Option<[f64; 3]> {
+        let a = v.as_array()?;
+        Some([
+            a.first()?.as_f64()?,
+            a.get(1)?.as_f64()?,
+            a.get(2)?.as_f64()?,
+        ])
+    };
+    let bounding_box = match (vec3(&raw["bounds"]["min"]), vec3(&raw["bounds"]["max"])) {
+        (Some(min), Some(max)) => {
+            let size: Vec<f64> = (0..3).map(|i| max[i] - min[i]).collect();
+            let center: Vec<f64> = (0..3).map(|i| (max[i] + min[i]) / 2.0).collect();
+            json!({ "min": min, "max": max, "size": size, "center": center })
+        }
+        _ => serde_json::Value::Null,
+    };
+
+    json!({
+        "classes": classes
+            .iter()
+            .map(|(name, count)| json!({ "className": name, "count": count }))
+            .collect::<Vec<_>>(),
+        "mixedClasses": classes.len() > 1,
+        "boundingBox": bounding_box,
+        "commonProperties": raw.get("commonProperties").cloned().unwrap_or(json!({})),
+        "differingProperties": raw.get("differingProperties").cloned().unwrap_or(json!([])),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_support::forwarded;
+
+    #[test]
+    fn selection_summary_is_structured() {
+        let raw = json!({
+            "classes": { "Part": 2, "Model": 1 },
+            "bounds": { "min": [0.0, 0.0, -2.0], "max": [4.0, 2.0, 2.0] },
+            "commonProperties": { "Anchored": true },
+            "differingProperties": ["Name"],
+        });
+        let out = structure_selection_summary(&raw);
+        assert_eq!(out["classes"][0]["className"], "Part");
+        assert_eq!(out["mixedClasses"], true);
+        assert_eq!(out["boundingBox"]["size"], json!([4.0, 2.0, 4.0]));
+        assert_eq!(out["boundingBox"]["center"], json!([2.0, 1.0, 0.0]));
+        assert_eq!(out["commonProperties"]["Anchored"], true);
+
+        // Nothing 3D selected: no bounds
+        let out = structure_selection_summary(&json!({ "classes": { "Folder": 1 } }));
+        assert_eq!(out["boundingBox"], serde_json::Value::Null);
+        assert_eq!(out["mixedClasses"], false);
+    }
+
+    #[test]
+    fn debug_id_format() {
+        assert!(validate_debug_id("0_1234").is_ok());
+        assert!(validate_debug_id("B2E4_56789").is_ok());
+        assert!(validate_debug_id("").is_err());
+        assert!(validate_debug_id("Workspace.Part").is_err());
+        assert!(validate_debug_id("_123").is_err());
+        assert!(validate_debug_id("0_12a").is_err());
+    }
+
+    #[test]
+    fn services_and_singletons_are_not_deletable() {
+        assert!(undeletable_path_reason("").is_some());
+        assert!(undeletable_path_reason("game").is_some());
+        assert!(undeletable_path_reason("ReplicatedStorage")
+            .unwrap()
+            .contains("DataModel service"));
+        assert!(undeletable_path_reason("game.Workspace").is_some());
+        assert!(undeletable_path_reason("Workspace.Terrain").is_some());
+        assert!(undeletable_path_reason("Workspace.Part").is_none());
+        assert!(undeletable_path_reason("game.ReplicatedStorage.Shared").is_none());
+    }
+
+    #[test]
+    fn tree_stream_summary_counts_classes_and_depth() {
+        let mut summary = TreeStreamSummary::default();
+        summary.add(r#"{"id":1,"className":"Model","depth":0}"#);
+        summary.add(r#"{"id":2,"parentId":1,"className":"Part","depth":1}"#);
+        summary.add(r#"{"id":3,"parentId":1,"className":"Part","depth":1}"#);
+        summary.add("not json");
+        let out = summary.to_json();
+        assert_eq!(out["nodeCount"], 3);
+        assert_eq!(out["maxDepth"], 1);
+        assert_eq!(out["invalidLines"], 1);
+        assert_eq!(out["topClasses"][0]["className"], "Part");
+        assert_eq!(out["topClasses"][0]["count"], 2);
+    }
+
+    #[tokio::test]
+    async fn tree_stream_forwards_its_stream_id_and_closes_it() {
+        let (request, result) = forwarded(
+            |state| async move {
+                let out = get_file_tree_stream(&state, Some("Workspace"), Some(2), None).await;
+                assert_eq!(state.lock().await.sessions.open_streams(), 0);
+                out
+            },
+            json!({ "batches": 0 }),
+        )
+        .await;
+        assert_eq!(request.tool, "get_file_tree_stream");
+        assert_eq!(request.args["path"], "Workspace");
+        assert_eq!(request.args["depth"], 2);
+        assert!(request.args["streamId"].is_string());
+        assert_eq!(result.unwrap()["nodeCount"], 0);
+    }
+
+    fn make_state() -> Arc<Mutex<AppState>> {
+        AppState::new().0
+    }
+
+    #[test]
+    fn color3_forms_normalize_to_unit_range() {
+        let rgb = |v: serde_json::Value| {
+            let c = normalize_color3(&v).unwrap();
+            assert_eq!(c["__type"], "Color3");
+            [
+                c["R"].as_f64().unwrap(),
+                c["G"].as_f64().unwrap(),
+                c["B"].as_f64().unwrap(),
+            ]
+        };
+        assert_eq!(rgb(json!("#FF8000")), [1.0, 128.0 / 255.0, 0.0]);
+        assert_eq!(rgb(json!([255, 0, 51])), [1.0, 0.0, 0.2]);
+        assert_eq!(rgb(json!([1, 0.5, 0])), [1.0, 0.5, 0.0]);
+        assert_eq!(rgb(json!({ "r": 0, "g": 0, "b": 255 })), [0.0, 0.0, 1.0]);
+        assert_eq!(
+            rgb(json!({ "R": 0.25, "G": 0.5, "B": 1 })),
+            [0.25, 0.5, 1.0]
+        );
+        assert_eq!(rgb(json!("255, 255, 0")), [1.0, 1.0, 0.0]);
+
+        for bad in [
+            json!("#FF80"),
+            json!("#GG0000"),
+            json!("red"),
+            json!([1, 2]),
+            json!([300, 0, 0]),
+            json!([-1, 0, 0]),
+            json!({ "r": 1, "g": 1 }),
+            json!(true),
+        ] {
+            let err = prepare_typed_value(bad, Some("Color3")).unwrap_err();
+            assert!(err.to_string().contains("#RRGGBB"));
+        }
+    }
+
+    #[test]
+    fn create_instance_color_properties_are_normalized() {
+        let mut props = json!({
+            "Color": "#00FF00",
+            "TextColor3": [255, 255, 255],
+            "Name": "#notacolor",
+            "Size": [4, 1, 2],
+        });
+        normalize_color_properties(&mut props).unwrap();
+        assert_eq!(props["Color"]["G"], 1.0);
+        assert_eq!(props["TextColor3"]["R"], 1.0);
+        assert_eq!(props["Name"], "#notacolor");
+        assert_eq!(props["Size"], json!([4, 1, 2]));
+
+        let err =
+            normalize_color_properties(&mut json!({ "BackgroundColor3": "blue" })).unwrap_err();
+        assert!(err.to_string().contains("BackgroundColor3: Color3 value"));
+    }
+
+    #[test]
+    fn vector2_and_rect_shapes() {
+        assert!(validate_typed_value(&json!([0.5, 0.5]), Some("Vector2")).is_ok());
+        assert!(validate_typed_value(&json!([0.5]), Some("Vector2")).is_err());
+        assert!(validate_typed_value(&json!({ "X": 1, "Y": 2 }), Some("Vector2")).is_err());
+
+        assert!(validate_typed_value(&json!([10, 10, 20, 20]), Some("Rect")).is_ok());
+        assert!(
+            validate_typed_value(&json!({ "min": [10, 10], "max": [20, 20] }), Some("Rect"))
+                .is_ok()
+        );
+        let err = validate_typed_value(&json!({ "min": [10, 10] }), Some("Rect")).unwrap_err();
+        assert!(err.to_string().contains("[minX, minY, maxX, maxY]"));
+        assert!(validate_typed_value(&json!([1, 2, "3", 4]), Some("Rect")).is_err());
+    }
+
+    #[test]
+    fn recognizes_model_files() {
+        let mut binary = RBXM_MAGIC.to_vec();
+        binary.extend_from_slice(b"\0\0\0\0");
+        assert_eq!(model_format(&binary), Some("rbxm"));
+        assert_eq!(
+            model_format(b"<roblox version=\"4\"><Item class=\"Part\"/></roblox>\n"),
+            Some("rbxmx")
+        );
+        assert_eq!(
+            model_format(b"<?xml version=\"1.0\"?>\n<roblox version=\"4\"></roblox>"),
+            Some("rbxmx")
+        );
+        assert_eq!(model_format(b"<roblox!"), None);
+        assert_eq!(model_format(b"<roblox version=\"4\">truncated"), None);
+        assert_eq!(model_format(b"PK\x03\x04"), None);
+    }
+
+    #[tokio::test]
+    async fn import_rejects_missing_and_garbage_files() {
+        let state = make_state();
+        let err = import_instance(&state, "/nonexistent/model.rbxm", "Workspace", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+
+        let file = std::env::temp_dir().join(format!("studiolink-{}.rbxm", uuid::Uuid::new_v4()));
+        std::fs::write(&file, b"not a model").unwrap();
+        let err = import_instance(&state, file.to_str().unwrap(), "Workspace", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not an rbxm or rbxmx"));
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn invalid_properties_become_an_error() {
+        let refused = json!({
+            "created": false,
+            "invalidProperties": [
+                { "name": "Colr", "reason": "not a property of Part" },
+                { "name": "Anchored", "reason": "cannot assign string" },
+            ],
+        });
+        assert_eq!(
+            invalid_properties_error("Part", &refused).unwrap(),
+            "invalid properties for Part: Colr (not a property of Part), Anchored (cannot assign string); nothing was created"
+        );
+        assert!(invalid_properties_error("Part", &json!({ "created": true })).is_none());
+        // Parent warnings are advisory and never turn a create into an error
+        let warned = json!({
+            "created": true,
+            "warnings": ["LocalScript under Workspace won't run; consider StarterPlayer.StarterPlayerScripts"],
+        });
+        assert!(invalid_properties_error("LocalScript", &warned).is_none());
+    }
+
+    #[tokio::test]
+    async fn output_path_requires_serialize() {
+        let state = make_state();
+        let err = get_instance_properties(
+            &state,
+            "Workspace.Model",
+            None,
+            Some("/tmp/m.rbxm"),
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+
+        let (request, _) = forwarded(
+            |state| async move {
+                get_instance_properties(&state, "Workspace.Model", Some(true), None, None, None)
+                    .await
+            },
+            json!({}),
+        )
+        .await;
+        assert_eq!(request.args["serialize"], true);
+        assert_eq!(request.args["maxBytes"], MAX_SERIALIZED_BYTES);
+
+        let err =
+            get_instance_properties(&state, "Workspace.Model", Some(true), None, Some(3), None)
+                .await
+                .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    #[tokio::test]
+    async fn mark_defaults_only_applies_to_summary() {
+        let state = make_state();
+        let err =
+            get_instance_properties(&state, "Workspace.Part", Some(true), None, None, Some(true))
+                .await
+                .unwrap_err();
+        assert!(err.to_string().contains("markDefaults"), "{}", err);
+
+        let (request, _) = forwarded(
+            |state| async move {
+                get_instance_properties(&state, "Workspace.Part", None, None, None, Some(true))
+                    .await
+            },
+            json!({}),
+        )
+        .await;
+        assert_eq!(request.args["markDefaults"], true);
+        assert!(request.args.get("serialize").is_none());
+    }
+
+    #[test]
+    fn duplicate_node_ids_ignore_ref_nodes() {
+        let tree = json!({ "services": [
+            { "name": "Workspace", "debugId": "1", "children": [
+                { "name": "Model", "debugId": "2", "children": [
+                    { "name": "Workspace", "debugId": "1", "ref": true },
+                ]},
+            ]},
+            { "name": "Lighting", "debugId": "3" },
+        ]});
+        assert!(duplicate_node_ids(&tree).is_empty());
+
+        let looped = json!({ "name": "A", "debugId": "a", "children": [
+            { "name": "B", "debugId": "b", "children": [
+                { "name": "A", "debugId": "a" },
+                { "name": "B", "debugId": "b" },
+                { "name": "A", "debugId": "a" },
+            ]},
+        ]});
+        assert_eq!(duplicate_node_ids(&looped), ["a", "b"]);
+    }
+
+    #[test]
+    fn sort_tree_orders_children_at_every_level() {
+        let mut tree = json!({ "services": [
+            { "name": "Workspace", "className": "Workspace", "children": [
+                { "name": "b", "className": "Part" },
+                { "name": "a", "className": "Model", "children": [
+                    { "name": "z", "className": "Part" },
+                    { "name": "y", "className": "Attachment" },
+                ]},
+                { "name": "a", "className": "Folder" },
+            ]},
+            { "name": "Lighting", "className": "Lighting" },
+        ]});
+        let names = |t: &serde_json::Value, ptr: &str| -> Vec<String> {
+            t.pointer(ptr)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|n| {
+                    format!(
+                        "{}:{}",
+                        n["name"].as_str().unwrap(),
+                        n["className"].as_str().unwrap()
+                    )
+                })
+                .collect()
+        };
+
+        let mut by_class = tree.clone();
+        sort_tree(&mut by_class, true);
+        assert_eq!(
+            names(&by_class, "/services/0/children"),
+            ["a:Folder", "a:Model", "b:Part"]
+        );
+
+        sort_tree(&mut tree, false);
+        assert_eq!(
+            names(&tree, "/services"),
+            ["Workspace:Workspace", "Lighting:Lighting"]
+        );
+        assert_eq!(
+            names(&tree, "/services/0/children"),
+            ["a:Folder", "a:Model", "b:Part"]
+        );
+        assert_eq!(
+            names(&tree, "/services/0/children/1/children"),
+            ["y:Attachment", "z:Part"]
+        );
+    }
+
+    #[test]
+    fn round_numbers_is_recursive_and_skips_integers() {
+        let mut props = json!({
+            "Position": [1.000000238418579, -0.49999988079071045, 12.5],
+            "CFrame": { "position": [0.1234567, 2, 3], "rotation": [[1, 0, -4.371139e-8]] },
+            "Transparency": 0.30000001192092896,
+            "Name": "Part",
+            "ChildCount": 4,
+        });
+        round_numbers(&mut props, 3);
+        assert_eq!(props["Position"], json!([1.0, -0.5, 12.5]));
+        assert_eq!(props["CFrame"]["position"], json!([0.123, 2, 3]));
+        assert_eq!(props["CFrame"]["rotation"][0][2], json!(-0.0));
+        assert_eq!(props["Transparency"], json!(0.3));
+        assert_eq!(props["Name"], "Part");
+        assert_eq!(props["ChildCount"], 4);
+    }
+
+    #[test]
+    fn arithmetic_operations_need_numeric_operands() {
+        assert!(validate_operation("set", &json!("Hello"), Some("string")).is_ok());
+        assert!(validate_operation("add", &json!(0.1), None).is_ok());
+        assert!(validate_operation("add", &json!([0, 5, 0]), Some("Vector3")).is_ok());
+        assert!(validate_operation("multiply", &json!(2), Some("Vector3")).is_ok());
+
+        for (operation, value, value_type) in [
+            ("subtract", json!(1), None),
+            ("add", json!("5"), None),
+            ("add", json!(5), Some("Vector3")),
+            ("add", json!([1, 2]), Some("Vector3")),
+            ("multiply", json!([1, 0, 0]), Some("Color3")),
+        ] {
+            let err = validate_operation(operation, &value, value_type).unwrap_err();
+            assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn physical_properties_rejects_out_of_range() {
+        let state = make_state();
+        let err = set_property(
+            &state,
+            "Workspace.Part",
+            "CustomPhysicalProperties",
+            json!({ "density": 0.7, "friction": 5.0, "elasticity": 0.5 }),
+            Some("PhysicalProperties"),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    #[tokio::test]
+    async fn physical_properties_requires_core_fields() {
+        let state = make_state();
+        let err = set_property(
+            &state,
+            "Workspace.Part",
+            "CustomPhysicalProperties",
+            json!({ "density": 0.7 }),
+            Some("PhysicalProperties"),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    #[tokio::test]
+    async fn physical_properties_valid_reaches_plugin() {
+        for value in [
+            json!({ "density": 0.7, "friction": 0.3, "elasticity": 0.5, "frictionWeight": 1, "elasticityWeight": 1 }),
+            serde_json::Value::Null,
+        ] {
+            let sent = value.clone();
+            let (request, _) = forwarded(
+                |state| async move {
+                    set_property(
+                        &state,
+                        "Workspace.Part",
+                        "CustomPhysicalProperties",
+                        sent,
+                        Some("PhysicalProperties"),
+                        None,
+                    )
+                    .await
+                },
+                json!({}),
+            )
+            .await;
+            assert_eq!(request.args["valueType"], "PhysicalProperties");
+            assert_eq!(request.args["value"], value);
+        }
+    }
+
+    #[tokio::test]
+    async fn mass_set_needs_exactly_one_value_source() {
+        let state = make_state();
+        let paths = vec!["Workspace.A".to_string()];
+        for (value, template) in [(None, None), (Some(json!("x")), Some("Part_{index}"))] {
+            let err = mass_set_property(&state, paths.clone(), "Name", value, template, None, None)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("exactly one"), "{}", err);
+        }
+        let (request, _) = forwarded(
+            |state| async move {
+                mass_set_property(
+                    &state,
+                    paths,
+                    "Name",
+                    None,
+                    Some("Part_{index}"),
+                    None,
+                    None,
+                )
+                .await
+            },
+            json!({}),
+        )
+        .await;
+        assert_eq!(request.args["valueTemplate"], "Part_{index}");
+        assert!(request.args.get("value").is_none());
+    }
+
+    #[test]
+    fn value_templates_only_use_known_placeholders() {
+        assert!(validate_value_template("Part_{index}").is_ok());
+        assert!(validate_value_template("{name}_{i}").is_ok());
+        assert!(validate_value_template("no placeholders").is_ok());
+        let err = validate_value_template("Part_{idx}").unwrap_err();
+        assert!(err.to_string().contains("{idx}"), "{}", err);
+        assert!(validate_value_template("Part_{index").is_err());
+    }
+}
@@ -2,9 +2,9 @@ use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use super::{send_to_plugin, EXTENDED_TIMEOUT};
+use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
 use crate::error::Result;
-use crate::state::AppState;
+use crate::state::{AppState, LintWatchLog};
 
 /// Tool 25: lint_scripts — Analyze all scripts for code quality issues
 /// Checks: deprecated APIs, anti-patterns, naming conventions, unused variables, type annotations
@@ -21,3 +21,112 @@ pub async fn lint_scripts(
     )
     .await
 }
+
+/// Default quiet period after an edit before the plugin re-lints a script.
+const DEFAULT_LINT_DEBOUNCE_MS: u64 = 750;
+
+/// lint_watch_start — Re-lint scripts as they are edited
+///
+/// The plugin watches `Source` changes (optionally only under `path`) and
+/// lints a script once edits to it have paused for `debounce_ms`. Results
+/// are collected with lint_watch_poll. Starting clears earlier results.
+pub async fn lint_watch_start(
+    state: &Arc<Mutex<AppState>>,
+    path: Option<&str>,
+    debounce_ms: Option<u64>,
+) -> Result<serde_json::Value> {
+    let result = send_to_plugin(
+        state,
+        None,
+        "lint_watch_start",
+        json!({
+            "path": path.unwrap_or(""),
+            "debounceMs": debounce_ms.unwrap_or(DEFAULT_LINT_DEBOUNCE_MS),
+        }),
+        DEFAULT_TIMEOUT,
+    )
+    .await?;
+    state.lock().await.lint_watch = LintWatchLog::default();
+    Ok(result)
+}
+
+/// lint_watch_poll — Lint results for scripts changed since `cursor`
+///
+/// Drains the plugin's pending results into the server-side log, then
+/// returns every script whose latest result is newer than `cursor` along
+/// with the cursor to pass next time. Cursor 0 returns everything.
+pub async fn lint_watch_poll(
+    state: &Arc<Mutex<AppState>>,
+    cursor: Option<u64>,
+) -> Result<serde_json::Value> {
+    let drained =
+        send_to_plugin(state, None, "lint_watch_poll", json!({}), DEFAULT_TIMEOUT).await?;
+    let mut s = state.lock().await;
+    record_lint_results(&mut s.lint_watch, &drained);
+    Ok(poll_response(&s.lint_watch, cursor.unwrap_or(0), &drained))
+}
+
+/// lint_watch_stop — Stop watching; returns any results not yet polled
+pub async fn lint_watch_stop(
+    state: &Arc<Mutex<AppState>>,
+    cursor: Option<u64>,
+) -> Result<serde_json::Value> {
+    let drained =
+        send_to_plugin(state, None, "lint_watch_stop", json!({}), DEFAULT_TIMEOUT).await?;
+    let mut s = state.lock().await;
+    record_lint_results(&mut s.lint_watch, &drained);
+    Ok(poll_response(&s.lint_watch, cursor.unwrap_or(0), &drained))
+}
+
+fn record_lint_results(log: &mut LintWatchLog, drained: &serde_json::Value) {
+    for result in drained["results"].as_array().into_iter().flatten() {
+        if let Some(path) = result["scriptPath"].as_str() {
+            log.record(path, result.clone());
+        }
+    }
+}
+
+fn poll_response(
+    log: &LintWatchLog,
+    cursor: u64,
+    drained: &serde_json::Value,
+) -> serde_json::Value {
+    let results = log.since(cursor);
+    json!({
+        "cursor": log.seq,
+        "changedScripts": results.len(),
+        "results": results,
+        "watching": drained["watching"],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(path: &str, issues: usize) -> serde_json::Value {
+        json!({ "scriptPath": path, "issueCount": issues })
+    }
+
+    #[test]
+    fn poll_returns_only_changes_since_cursor() {
+        let mut log = LintWatchLog::default();
+        let first = json!({ "results": [lint("A", 1), lint("B", 0)], "watching": true });
+        record_lint_results(&mut log, &first);
+        let page = poll_response(&log, 0, &first);
+        assert_eq!(page["changedScripts"], 2);
+        let cursor = page["cursor"].as_u64().unwrap();
+
+        // Re-linting A replaces its entry; B is unchanged
+        let second = json!({ "results": [lint("A", 3)], "watching": true });
+        record_lint_results(&mut log, &second);
+        let page = poll_response(&log, cursor, &second);
+        assert_eq!(page["changedScripts"], 1);
+        assert_eq!(page["results"][0]["issueCount"], 3);
+
+        let empty = json!({ "results": [], "watching": true });
+        let page = poll_response(&log, page["cursor"].as_u64().unwrap(), &empty);
+        assert_eq!(page["changedScripts"], 0);
+        assert_eq!(poll_response(&log, 0, &empty)["changedScripts"], 2);
+    }
+}
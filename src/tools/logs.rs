@@ -11,11 +11,17 @@ const VALID_TYPES: &[&str] = &["Output", "Info", "Warning", "Error"];
 /// error_history — Pull entries from LogService:GetLogHistory() with optional
 /// filtering by message type ("Output", "Info", "Warning", "Error") and a
 /// substring pattern. Returns up to `limit` newest matches (default 100).
+///
+/// With `parse_stacks`, each error entry also gets an `error` object of
+/// `{message, frames: [{script, line, function}]}` parsed from its text and
+/// the "Stack Begin"/"Stack End" lines that followed it. Entries whose trace
+/// doesn't parse keep only the raw text and are marked `stackParsed: false`.
 pub async fn error_history(
     state: &Arc<Mutex<AppState>>,
     message_type: Option<String>,
     pattern: Option<String>,
     limit: Option<u32>,
+    parse_stacks: Option<bool>,
 ) -> Result<serde_json::Value> {
     if let Some(t) = &message_type {
         if !VALID_TYPES.contains(&t.as_str()) {
@@ -25,7 +31,8 @@ pub async fn error_history(
             )));
         }
     }
-    send_to_plugin(
+    let parse_stacks = parse_stacks.unwrap_or(false);
+    let mut result = send_to_plugin(
         state,
         None,
         "error_history",
@@ -33,10 +40,102 @@ pub async fn error_history(
             "message_type": message_type,
             "pattern": pattern,
             "limit": limit.unwrap_or(100),
+            "include_stacks": parse_stacks,
         }),
         DEFAULT_TIMEOUT,
     )
-    .await
+    .await?;
+    if parse_stacks {
+        for entry in result["entries"].as_array_mut().into_iter().flatten() {
+            if entry["type"] != "Error" {
+                continue;
+            }
+            let message = entry["message"].as_str().unwrap_or_default();
+            let stack = entry["stack"].as_str().unwrap_or_default();
+            match parse_error(message, stack) {
+                Some(parsed) => {
+                    entry["error"] = parsed;
+                    entry["stackParsed"] = json!(true);
+                }
+                None => entry["stackParsed"] = json!(false),
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Split an error message and its trace lines into `{message, frames}`.
+/// Returns None if any trace line isn't a recognizable frame.
+fn parse_error(message: &str, stack: &str) -> Option<serde_json::Value> {
+    let mut lines = message.lines().chain(stack.lines());
+    let first = lines.next()?.trim();
+    let mut frames = Vec::new();
+
+    // "ServerScriptService.Main:12: attempt to index nil" — the location
+    // prefix is the innermost frame
+    let text = match split_location(first) {
+        Some((script, line, rest)) => {
+            frames.push(json!({ "script": script, "line": line, "function": null }));
+            rest.strip_prefix(':').unwrap_or(rest).trim()
+        }
+        None => first,
+    };
+
+    for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+        if matches!(line, "Stack Begin" | "Stack End" | "stack traceback:") {
+            continue;
+        }
+        let frame = parse_frame(line)?;
+        // LogService repeats the innermost location as the first stack line
+        if frames.len() == 1 && frames[0]["function"].is_null() && same_location(&frames[0], &frame)
+        {
+            frames[0] = frame;
+            continue;
+        }
+        frames.push(frame);
+    }
+    Some(json!({ "message": text, "frames": frames }))
+}
+
+fn same_location(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    a["script"] == b["script"] && a["line"] == b["line"]
+}
+
+/// One trace line, in either LogService form
+/// (`Script 'Path', Line 12 - function name`) or traceback form
+/// (`Path:12 function name`).
+fn parse_frame(line: &str) -> Option<serde_json::Value> {
+    if let Some(rest) = line.strip_prefix("Script '") {
+        let (script, rest) = rest.split_once("', Line ")?;
+        let (number, function) = match rest.split_once(" - ") {
+            Some((n, f)) => (n, Some(f.trim_start_matches("function ").trim())),
+            None => (rest, None),
+        };
+        let line: u64 = number.trim().parse().ok()?;
+        return Some(json!({ "script": script, "line": line, "function": function }));
+    }
+    let (script, line, rest) = split_location(line)?;
+    let function = rest
+        .trim()
+        .strip_prefix("function ")
+        .map(str::trim)
+        .filter(|f| !f.is_empty());
+    Some(json!({ "script": script, "line": line, "function": function }))
+}
+
+/// `Path.To.Script:12<rest>` → (path, 12, rest). The path must not contain
+/// spaces, which keeps ordinary messages with colons from matching.
+fn split_location(text: &str) -> Option<(&str, u64, &str)> {
+    let (script, rest) = text.split_once(':')?;
+    if script.is_empty() || script.contains(char::is_whitespace) {
+        return None;
+    }
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    let line = rest[..digits].parse().ok()?;
+    Some((script, line, &rest[digits..]))
 }
 
 /// crash_dump — Snapshot of recent log activity within `window_secs` (default
@@ -74,10 +173,49 @@ mod tests {
         AppState::new().0
     }
 
+    #[test]
+    fn parses_logservice_stacks() {
+        let parsed = parse_error(
+            "ServerScriptService.Main:12: attempt to index nil with 'Health'",
+            "Stack Begin\nScript 'ServerScriptService.Main', Line 12 - function damage\nScript 'ServerScriptService.Main', Line 30\nStack End",
+        )
+        .unwrap();
+        assert_eq!(parsed["message"], "attempt to index nil with 'Health'");
+        assert_eq!(
+            parsed["frames"],
+            json!([
+                { "script": "ServerScriptService.Main", "line": 12, "function": "damage" },
+                { "script": "ServerScriptService.Main", "line": 30, "function": null },
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_traceback_lines_and_plain_messages() {
+        let parsed = parse_error(
+            "boom\nReplicatedStorage.Util:4 function explode\nReplicatedStorage.Util:9",
+            "",
+        )
+        .unwrap();
+        assert_eq!(parsed["message"], "boom");
+        assert_eq!(parsed["frames"][0]["function"], "explode");
+        assert_eq!(parsed["frames"][1]["line"], 9);
+
+        let plain = parse_error("Infinite yield possible on 'X'", "").unwrap();
+        assert_eq!(plain["frames"], json!([]));
+    }
+
+    #[test]
+    fn malformed_trace_falls_back() {
+        assert!(
+            parse_error("Main:1: oops", "Stack Begin\nsomething odd here\nStack End").is_none()
+        );
+    }
+
     #[tokio::test]
     async fn rejects_unknown_message_type() {
         let state = make_state();
-        let err = error_history(&state, Some("Critical".to_string()), None, None)
+        let err = error_history(&state, Some("Critical".to_string()), None, None, None)
             .await
             .unwrap_err();
         assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
@@ -93,7 +231,9 @@ mod tests {
     #[tokio::test]
     async fn error_history_no_session_returns_plugin_not_connected() {
         let state = make_state();
-        let err = error_history(&state, None, None, None).await.unwrap_err();
+        let err = error_history(&state, None, None, None, None)
+            .await
+            .unwrap_err();
         assert!(matches!(err, StudioLinkError::PluginNotConnected));
     }
 }
@@ -1,6 +1,8 @@
 pub mod affinity;
 pub mod animation;
+pub mod appearance;
 pub mod asset_audit;
+pub mod asset_upload;
 pub mod character;
 pub mod core;
 pub mod datastore;
@@ -8,6 +10,8 @@ pub mod debug;
 pub mod dependencies;
 pub mod diffing;
 pub mod docs;
+pub mod git;
+pub mod highlight;
 pub mod history;
 pub mod input;
 pub mod instance;
@@ -18,14 +22,20 @@ pub mod multi_client;
 pub mod network;
 pub mod profiler;
 pub mod profiler_v2;
+pub mod prompts;
 pub mod publish;
+pub mod resources;
+pub mod rojo;
 pub mod scenario;
 pub mod screenshot;
 pub mod script_patch;
 pub mod scripts;
 pub mod security;
 pub mod session;
+pub mod sourcemap;
+pub mod sync;
 pub mod testing;
+pub mod textdiff;
 pub mod ui;
 pub mod ui_inspector;
 pub mod workspace;
@@ -38,13 +48,370 @@ use tokio::sync::Mutex;
 use crate::error::{Result, StudioLinkError};
 use crate::state::{AppState, PluginRequest};
 
+tokio::task_local! {
+    /// Progress sink of the MCP tool call being handled, set by the MCP layer
+    /// when the client sent a progress token. Requests queued from inside
+    /// that call forward the plugin's progress frames to it.
+    pub static PROGRESS_SINK: crate::state::ProgressSink;
+    /// State of the MCP connection whose call is being handled (its bound
+    /// session, open macro and switch history), set by the MCP layer.
+    pub static CONNECTION: crate::state::SharedConnection;
+}
+
+/// The calling MCP connection's state, or the server's default connection
+/// for calls from outside one (the plugin HTTP API, tests).
+pub fn connection(s: &AppState) -> crate::state::SharedConnection {
+    CONNECTION
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| s.connection.clone())
+}
+
 /// Default timeout for plugin requests (30 seconds)
-const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Extended timeout for long-running operations (120 seconds)
 const EXTENDED_TIMEOUT: Duration = Duration::from_secs(120);
 
-/// Send a tool request to the plugin and wait for the response.
+/// Proxy forwards a request may go through before it is treated as a loop.
+pub const MAX_PROXY_HOPS: u32 = 3;
+
+/// How a tool is retried after a transient plugin error: up to
+/// `max_attempts` calls in total, waiting `base_delay`, then twice that, and
+/// so on (capped at `MAX_RETRY_DELAY`) between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (1 for the first retry).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32 << retry.saturating_sub(1).min(8);
+        (self.base_delay * factor).min(MAX_RETRY_DELAY)
+    }
+}
+
+/// Longest wait between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(8);
+
+/// Tools that may be retried at all: reads with no side effects, so running
+/// one twice is harmless. Anything that changes the place, a DataStore or
+/// play state is absent and can't be given a policy.
+pub const RETRYABLE_TOOLS: &[&str] = &[
+    "datastore_list",
+    "datastore_get",
+    "datastore_get_many",
+    "datastore_scan",
+    "get_file_tree",
+    "get_instance_properties",
+    "get_script_source",
+    "search_objects",
+    "find_by_property",
+    "grep_scripts",
+    "get_studio_mode",
+];
+
+/// Tools refused under --read-only: anything that changes the place, a
+/// DataStore or play state (including driving UI, input or the character in
+/// a running game), runs arbitrary Luau (which could do any of those), or
+/// writes to Roblox through Open Cloud.
+pub const MUTATING_TOOLS: &[&str] = &[
+    "run_code",
+    "run_script_in_play_mode",
+    "microprofiler_capture",
+    "insert_model",
+    "insert_asset",
+    "start_stop_play",
+    "profile_session",
+    "multi_client_test",
+    "test_run",
+    "test_create",
+    "datastore_set",
+    "datastore_delete",
+    "set_property",
+    "mass_set_property",
+    "create_instance",
+    "set_appearance_preset",
+    "delete_instance",
+    "import_instance",
+    "set_script_source",
+    "script_patch",
+    "undo",
+    "redo",
+    "publish_place",
+    "sync_watch_start",
+    "ui_set_text",
+    "ui_click",
+    "input_simulate",
+    "vim_capability_test",
+    "character_moveto",
+    "character_teleport",
+    "character_action",
+    "asset_upload",
+];
+
+/// The plugin tools each entry of MUTATING_TOOLS sends to make its change
+/// (none for tools that only talk to Open Cloud).
+/// broadcast_tool, broadcast_to_group and /proxy/tool_call take raw plugin
+/// tool names, so these are refused there too.
+pub const MUTATING_PLUGIN_TOOLS: &[(&str, &[&str])] = &[
+    ("run_code", &["run_code"]),
+    ("run_script_in_play_mode", &["run_script_in_play_mode"]),
+    ("microprofiler_capture", &["microprofiler_capture"]),
+    ("insert_model", &["insert_model", "insert_model_finish"]),
+    ("insert_asset", &["insert_asset"]),
+    ("start_stop_play", &["start_stop_play"]),
+    ("profile_session", &["start_stop_play", "run_code"]),
+    ("multi_client_test", &["multi_client_test"]),
+    ("test_run", &["test_run"]),
+    ("test_create", &["test_create"]),
+    ("datastore_set", &["datastore_set"]),
+    ("datastore_delete", &["datastore_delete"]),
+    ("set_property", &["set_property"]),
+    ("mass_set_property", &["mass_set_property"]),
+    ("create_instance", &["create_instance"]),
+    ("set_appearance_preset", &["set_appearance"]),
+    ("delete_instance", &["delete_instance"]),
+    ("import_instance", &["import_instance"]),
+    ("set_script_source", &["set_script_source"]),
+    ("script_patch", &["script_patch"]),
+    ("undo", &["undo"]),
+    ("redo", &["redo"]),
+    ("publish_place", &["publish_place"]),
+    ("sync_watch_start", &["set_script_source"]),
+    ("ui_set_text", &["ui_set_text"]),
+    ("ui_click", &["ui_click"]),
+    ("input_simulate", &["input_simulate"]),
+    ("vim_capability_test", &["vim_capability_test"]),
+    ("character_moveto", &["character_moveto"]),
+    ("character_teleport", &["character_teleport"]),
+    ("character_action", &["character_action"]),
+    ("asset_upload", &[]),
+];
+
+/// The refusal for MCP tool `tool` when the server is read-only and the tool
+/// mutates.
+pub fn read_only_refusal(read_only: bool, tool: &str) -> Option<StudioLinkError> {
+    (read_only && MUTATING_TOOLS.contains(&tool)).then(|| StudioLinkError::ReadOnly {
+        tool: tool.to_string(),
+    })
+}
+
+/// The refusal for plugin tool `tool` sent by name (broadcasts, calls
+/// forwarded by a secondary) when the server is read-only.
+pub fn plugin_read_only_refusal(read_only: bool, tool: &str) -> Option<StudioLinkError> {
+    let mutates = MUTATING_PLUGIN_TOOLS
+        .iter()
+        .any(|(_, sent)| sent.contains(&tool));
+    (read_only && mutates).then(|| StudioLinkError::ReadOnly {
+        tool: tool.to_string(),
+    })
+}
+
+/// The refusal for plugin tool `tool` sent by name (broadcasts) when
+/// --tools-allow/--tools-deny removed an MCP tool that sends it. A plugin
+/// tool no MCP tool is known to send is judged by its own name.
+pub fn plugin_filter_refusal(filter: &ToolFilter, tool: &str) -> Option<StudioLinkError> {
+    let mut names: Vec<&str> = MUTATING_PLUGIN_TOOLS
+        .iter()
+        .filter(|(_, sent)| sent.contains(&tool))
+        .map(|(name, _)| *name)
+        .collect();
+    let is_mcp_tool = crate::mcp::StudioLinkMcp::all_tool_names()
+        .iter()
+        .any(|t| t == tool);
+    if is_mcp_tool || names.is_empty() {
+        names.push(tool);
+    }
+    let removed = names.into_iter().find(|name| !filter.permits(name))?;
+    Some(StudioLinkError::InvalidArguments(format!(
+        "{} is not available on this server ({} is removed by --tools-allow/--tools-deny)",
+        tool, removed
+    )))
+}
+
+/// Which tools are registered for MCP clients (--tools-allow/--tools-deny).
+/// Patterns are tool names, or a prefix ending in `*` ("datastore_*").
+/// Deny wins over allow; no allow list means every tool not denied.
+#[derive(Debug, Clone, Default)]
+pub struct ToolFilter {
+    pub allow: Option<Vec<String>>,
+    pub deny: Vec<String>,
+}
+
+/// Tools kept whatever the filter says: without get_result_page, large
+/// results of any other tool couldn't be read past their first page.
+pub const ALWAYS_REGISTERED_TOOLS: &[&str] = &["get_result_page"];
+
+impl ToolFilter {
+    pub fn permits(&self, tool: &str) -> bool {
+        if ALWAYS_REGISTERED_TOOLS.contains(&tool) {
+            return true;
+        }
+        let matches = |patterns: &[String]| patterns.iter().any(|p| pattern_matches(p, tool));
+        !matches(&self.deny) && self.allow.as_deref().is_none_or(matches)
+    }
+
+    /// Patterns that match none of `known`, so typos fail at startup instead
+    /// of silently leaving a tool registered.
+    pub fn unmatched<'a>(&'a self, known: &[&str]) -> Vec<&'a str> {
+        self.allow
+            .iter()
+            .flatten()
+            .chain(&self.deny)
+            .filter(|p| !known.iter().any(|tool| pattern_matches(p, tool)))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+fn pattern_matches(pattern: &str, tool: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => tool.starts_with(prefix),
+        None => pattern == tool,
+    }
+}
+
+/// DataStore reads are throttled by a per-minute request budget, so they are
+/// retried out of the box; other read tools only with --retry.
+const DEFAULT_RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    base_delay: Duration::from_secs(1),
+};
+const DEFAULT_RETRY_TOOLS: &[&str] = &[
+    "datastore_list",
+    "datastore_get",
+    "datastore_get_many",
+    "datastore_scan",
+];
+
+/// Lowercased fragments of plugin errors that are worth another attempt.
+const RETRYABLE_ERROR_PATTERNS: &[&str] = &[
+    "throttl",
+    "request budget",
+    "too many requests",
+    "service unavailable",
+    "http 429",
+    "http 502",
+    "http 503",
+    "try again",
+];
+
+pub fn default_retry_policies() -> std::collections::HashMap<String, RetryPolicy> {
+    DEFAULT_RETRY_TOOLS
+        .iter()
+        .map(|tool| (tool.to_string(), DEFAULT_RETRY_POLICY))
+        .collect()
+}
+
+/// Whether a plugin error message looks transient (throttling, budget).
+pub fn is_retryable_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    RETRYABLE_ERROR_PATTERNS
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Parse a `--retry` entry: `tool=attempts`, where attempts counts the
+/// first call (1 turns retries off for that tool).
+pub fn parse_retry_override(entry: &str) -> std::result::Result<(String, u32), String> {
+    let (tool, attempts) = entry
+        .split_once('=')
+        .ok_or_else(|| format!("expected tool=attempts, got \"{}\"", entry))?;
+    if !RETRYABLE_TOOLS.contains(&tool) {
+        return Err(format!(
+            "\"{}\" can't be retried; only read tools can: {}",
+            tool,
+            RETRYABLE_TOOLS.join(", ")
+        ));
+    }
+    let attempts: u32 = attempts
+        .parse()
+        .ok()
+        .filter(|n| (1..=10).contains(n))
+        .ok_or_else(|| format!("attempts for {} must be 1-10, got \"{}\"", tool, attempts))?;
+    Ok((tool.to_string(), attempts))
+}
+
+/// Retry outcomes for one tool.
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct RetryCounts {
+    /// Extra attempts made after a retryable error.
+    pub retries: u64,
+    /// Calls that failed at first and then succeeded on a retry.
+    pub recovered: u64,
+    /// Calls that still failed after their last attempt.
+    pub exhausted: u64,
+}
+
+/// Send a tool request to the plugin, retrying transient failures when the
+/// tool has a retry policy (see `RETRYABLE_TOOLS`). Only `PluginError`s
+/// whose message matches `RETRYABLE_ERROR_PATTERNS` are retried; timeouts
+/// and disconnects are returned at once. A result that needed retries gets
+/// a `retries` count when it is an object.
+pub async fn send_to_plugin(
+    state: &Arc<Mutex<AppState>>,
+    target_session: Option<&str>,
+    tool: &str,
+    args: Value,
+    timeout: Duration,
+) -> Result<Value> {
+    let policy = state.lock().await.retry_policies.get(tool).copied();
+    let Some(policy) = policy.filter(|p| p.max_attempts > 1) else {
+        return send_once(state, target_session, tool, args, timeout).await;
+    };
+    let mut retries = 0;
+    loop {
+        let outcome = send_once(state, target_session, tool, args.clone(), timeout).await;
+        match outcome {
+            Err(StudioLinkError::PluginError(msg))
+                if retries + 1 < policy.max_attempts && is_retryable_error(&msg) =>
+            {
+                retries += 1;
+                let delay = policy.delay(retries);
+                tracing::warn!(
+                    "{} failed transiently ({}); retry {}/{} in {:?}",
+                    tool,
+                    msg,
+                    retries,
+                    policy.max_attempts - 1,
+                    delay
+                );
+                state
+                    .lock()
+                    .await
+                    .retry_stats
+                    .entry(tool.to_string())
+                    .or_default()
+                    .retries += 1;
+                tokio::time::sleep(delay).await;
+            }
+            outcome => {
+                if retries == 0 {
+                    return outcome;
+                }
+                let mut s = state.lock().await;
+                let counts = s.retry_stats.entry(tool.to_string()).or_default();
+                return match outcome {
+                    Ok(mut result) => {
+                        counts.recovered += 1;
+                        if let Some(obj) = result.as_object_mut() {
+                            obj.insert("retries".into(), retries.into());
+                        }
+                        Ok(result)
+                    }
+                    Err(e) => {
+                        counts.exhausted += 1;
+                        Err(e)
+                    }
+                };
+            }
+        }
+    }
+}
+
+/// Send a tool request to the plugin once and wait for the response.
 ///
 /// `target_session` lets a single call route to a specific session_id,
 /// overriding the global active_session. Pass None to use active_session
@@ -52,7 +419,7 @@ const EXTENDED_TIMEOUT: Duration = Duration::from_secs(120);
 /// instances concurrently without stepping on each other via switch_session.
 ///
 /// In proxy mode, forwards the request to the primary server via HTTP.
-pub async fn send_to_plugin(
+async fn send_once(
     state: &Arc<Mutex<AppState>>,
     target_session: Option<&str>,
     tool: &str,
@@ -69,7 +436,7 @@ pub async fn send_to_plugin(
     // bound_session_id set by set_my_session. Direct param > bound > active.
     let bound = {
         let s = state.lock().await;
-        s.bound_session_id.clone()
+        connection(&s).lock().bound_session_id.clone()
     };
     let target_session: Option<&str> = target_session.or(bound.as_deref());
 
@@ -78,24 +445,30 @@ pub async fn send_to_plugin(
         // its target_session (after affinity resolution).
         let mut s = state.lock().await;
         s.log_routing(tool, target_session);
+        s.record_call(tool, &args, target_session);
     }
 
     if proxy_mode {
-        return send_via_proxy(state, &proxy_url, target_session, tool, args, timeout).await;
+        return send_via_proxy(state, &proxy_url, target_session, tool, args, timeout, 0).await;
     }
 
     // Direct mode: queue request locally
-    let mut rx = {
+    let (resolved_session, sessions) = {
         let mut s = state.lock().await;
 
         let resolved_session: String = match target_session {
             Some(sid) => {
-                if !s.sessions.contains_key(sid) {
+                if !s.sessions.contains(sid) {
                     return Err(StudioLinkError::PluginError(format!(
                         "session_id '{}' not found. Use list_sessions to see active sessions.",
                         sid
                     )));
                 }
+                // An explicit target is never swapped for another session, so
+                // fail now rather than queue to a window that isn't polling.
+                if let Some(msg) = s.sessions.unresponsive_error(sid) {
+                    return Err(StudioLinkError::PluginError(msg));
+                }
                 sid.to_string()
             }
             None => {
@@ -104,9 +477,9 @@ pub async fn send_to_plugin(
                     s.cleanup_expired();
                     let live_session = s
                         .sessions
-                        .iter()
-                        .find(|(_, sess)| sess.last_heartbeat.elapsed().as_secs() < 45)
-                        .map(|(id, _)| id.clone());
+                        .ids()
+                        .into_iter()
+                        .find(|id| s.sessions.is_connected(id));
 
                     if let Some(live_id) = live_session {
                         tracing::info!("Auto-recovered to live session: {}", live_id);
@@ -124,22 +497,61 @@ pub async fn send_to_plugin(
             }
         };
 
-        match s.queue_request_to_session(&resolved_session, tool, args) {
-            Some((_id, rx)) => rx,
+        // Capability check: fail fast with an upgrade hint rather than letting
+        // an older plugin answer "Unknown tool".
+        if let Some(msg) = s
+            .sessions
+            .with(&resolved_session, |sess| sess.unsupported_tool_error(tool))
+            .flatten()
+        {
+            return Err(StudioLinkError::PluginError(msg));
+        }
+        if let Some(msg) = s.queue_full_error(&resolved_session) {
+            return Err(StudioLinkError::PluginError(msg));
+        }
+        (resolved_session, s.sessions.clone())
+    };
+
+    let started = std::time::Instant::now();
+    let exec = acquire_exec_lock(state, &resolved_session, tool, &args, timeout).await?;
+    // Time spent waiting for the lock comes out of the same budget
+    let remaining = timeout.saturating_sub(started.elapsed());
+
+    // When the MCP client asked for progress, have the plugin report it
+    let sink = PROGRESS_SINK.try_with(|sink| sink.clone()).ok();
+    let mut args = args;
+    if let (Some(_), Some(obj)) = (&sink, args.as_object_mut()) {
+        obj.entry("progress").or_insert(Value::Bool(true));
+    }
+
+    let (request_id, mut rx) =
+        match sessions.queue_request(&resolved_session, tool, args, remaining) {
+            Some((id, rx)) => {
+                if let Some(sink) = sink {
+                    sessions.attach_progress_sink(&resolved_session, &id, sink);
+                }
+                (id, rx)
+            }
             None => {
                 return Err(StudioLinkError::PluginError(format!(
                     "Failed to queue request for session {}",
                     resolved_session
                 )))
             }
-        }
-    };
+        };
 
     // Wait for plugin response with timeout
-    match tokio::time::timeout(timeout, rx.recv()).await {
+    let outcome = tokio::time::timeout(remaining, rx.recv()).await;
+    if !matches!(outcome, Ok(Some(_))) {
+        sessions.finish_in_flight(&request_id);
+    }
+    match outcome {
         Ok(Some(response)) => {
             if response.success {
-                Ok(response.result)
+                Ok(match exec.and_then(|(_, waited)| waited) {
+                    Some(waited) => annotate_lock_wait(response.result, waited),
+                    None => response.result,
+                })
             } else {
                 Err(StudioLinkError::PluginError(
                     response
@@ -151,38 +563,94 @@ pub async fn send_to_plugin(
         Ok(None) => Err(StudioLinkError::PluginError(
             "Response channel closed".into(),
         )),
-        Err(_) => Err(StudioLinkError::RequestTimeout(tool.into())),
+        Err(_) => Err(StudioLinkError::RequestTimeout {
+            tool: tool.into(),
+            waited_secs: started.elapsed().as_secs_f64(),
+            timeout_secs: timeout.as_secs(),
+        }),
+    }
+}
+
+/// Exclusive run_code calls (the default; `"exclusive": false` opts out for
+/// read-only code) run one at a time per session so they can't interleave.
+pub fn needs_exec_lock(tool: &str, args: &Value) -> bool {
+    tool == "run_code" && args["exclusive"].as_bool() != Some(false)
+}
+
+/// Take the session's execution lock if this call needs it, held by the
+/// caller across queueing and awaiting the response. Also returns how long
+/// the call waited, if another call was holding the lock. Waiting counts
+/// against `timeout` like the plugin round trip does.
+pub async fn acquire_exec_lock(
+    state: &Arc<Mutex<AppState>>,
+    session_id: &str,
+    tool: &str,
+    args: &Value,
+    timeout: Duration,
+) -> Result<Option<(tokio::sync::OwnedMutexGuard<()>, Option<Duration>)>> {
+    if !needs_exec_lock(tool, args) {
+        return Ok(None);
+    }
+    let lock = state.lock().await.exec_lock(session_id);
+    if let Ok(guard) = lock.clone().try_lock_owned() {
+        return Ok(Some((guard, None)));
+    }
+    let started = std::time::Instant::now();
+    match tokio::time::timeout(timeout, lock.lock_owned()).await {
+        Ok(guard) => Ok(Some((guard, Some(started.elapsed())))),
+        Err(_) => Err(StudioLinkError::RequestTimeout {
+            tool: tool.into(),
+            waited_secs: started.elapsed().as_secs_f64(),
+            timeout_secs: timeout.as_secs(),
+        }),
+    }
+}
+
+/// Tell the caller its run_code queued behind another one: a `lockWaitedMs`
+/// field on object results, a trailing `[LOCK]` line on text output.
+pub fn annotate_lock_wait(result: Value, waited: Duration) -> Value {
+    let ms = waited.as_millis() as u64;
+    match result {
+        Value::Object(mut obj) => {
+            obj.insert("lockWaitedMs".into(), ms.into());
+            Value::Object(obj)
+        }
+        Value::String(text) => Value::String(format!(
+            "{}\n[LOCK] waited {}ms for another run_code on this session",
+            text, ms
+        )),
+        other => other,
     }
 }
 
 /// Forward a tool request to the primary server via HTTP (proxy mode).
 /// Carries `target_session` in the body so the primary can route this single
 /// call to a specific session instead of falling back to its own active.
-async fn send_via_proxy(
+///
+/// `hops` is the number of forwards the call has already been through (0 for
+/// a call that originated here); the forwarded request carries `hops + 1`.
+pub(crate) async fn send_via_proxy(
     state: &Arc<Mutex<AppState>>,
     proxy_url: &str,
     target_session: Option<&str>,
     tool: &str,
     args: Value,
     timeout: Duration,
+    hops: u32,
 ) -> Result<Value> {
     let request = PluginRequest {
         id: uuid::Uuid::new_v4().to_string(),
         tool: tool.to_string(),
         args,
         target_session: target_session.map(|s| s.to_string()),
+        hops: hops + 1,
+        queue_depth: None,
     };
 
-    // Reuse the proxy client from state (avoids recreating per request for connection pooling)
-    let client = {
-        let mut s = state.lock().await;
-        if s.proxy_client.is_none() {
-            s.proxy_client = Some(reqwest::Client::new());
-        }
-        s.proxy_client.clone().unwrap()
-    };
+    let client = proxy_client(state).await;
     let url = format!("{}/proxy/tool_call", proxy_url);
 
+    let started = std::time::Instant::now();
     let response = client
         .post(&url)
         .json(&request)
@@ -191,12 +659,20 @@ async fn send_via_proxy(
         .await
         .map_err(|e| StudioLinkError::PluginError(format!("Proxy request failed: {}", e)))?;
 
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(proxy_unauthorized(state).await);
+    }
+
     if response.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
         return Err(StudioLinkError::PluginNotConnected);
     }
 
     if response.status() == reqwest::StatusCode::GATEWAY_TIMEOUT {
-        return Err(StudioLinkError::RequestTimeout(tool.into()));
+        return Err(StudioLinkError::RequestTimeout {
+            tool: tool.into(),
+            waited_secs: started.elapsed().as_secs_f64(),
+            timeout_secs: timeout.as_secs(),
+        });
     }
 
     if response.status() == reqwest::StatusCode::NOT_FOUND {
@@ -222,6 +698,47 @@ async fn send_via_proxy(
     }
 }
 
+/// Client for calls to the primary in proxy mode, sending the bearer token it
+/// expects. Kept in state so connections are pooled across calls.
+pub(crate) async fn proxy_client(state: &Arc<Mutex<AppState>>) -> reqwest::Client {
+    let mut s = state.lock().await;
+    if let Some(client) = &s.proxy_client {
+        return client.clone();
+    }
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = &s.proxy_token {
+        if let Ok(mut value) = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))
+        {
+            value.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+    }
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap_or_default();
+    s.proxy_client = Some(client.clone());
+    client
+}
+
+/// The primary answered 401: its token was rotated or never matched ours.
+/// Re-read the token file so the next call goes out with whatever is there.
+pub(crate) async fn proxy_unauthorized(state: &Arc<Mutex<AppState>>) -> StudioLinkError {
+    let reloaded =
+        crate::auth::default_token_path().and_then(|path| crate::auth::read_token_file(&path));
+    let mut s = state.lock().await;
+    let changed = reloaded.is_some() && reloaded != s.proxy_token;
+    if changed {
+        s.proxy_token = reloaded;
+        s.proxy_client = None;
+    }
+    StudioLinkError::PluginError(if changed {
+        "The primary StudioLink rejected this instance's token; reloaded ~/.studiolink/token, retry the call.".into()
+    } else {
+        "The primary StudioLink rejected this instance's token. Start this instance with the primary's --token.".into()
+    })
+}
+
 /// Helper to build a tool result string for MCP
 #[allow(dead_code)]
 pub fn tool_result(content: &str) -> Vec<rmcp::model::Content> {
@@ -233,3 +750,406 @@ pub fn tool_result(content: &str) -> Vec<rmcp::model::Content> {
 pub fn tool_error(error: &str) -> Vec<rmcp::model::Content> {
     vec![rmcp::model::Content::text(format!("Error: {}", error))]
 }
+
+/// Helpers for tool tests that need a plugin on the other end.
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use crate::state::{PluginRequest, PluginResponse, SessionRegistration};
+
+    /// A state with one registered (and so active) session, `id`.
+    pub fn state_with_session(id: &str) -> Arc<Mutex<AppState>> {
+        let state = AppState::new().0;
+        let mut s = state.try_lock().unwrap();
+        s.register_session(SessionRegistration {
+            session_id: id.into(),
+            place_id: 1,
+            place_name: "Place".into(),
+            game_id: 0,
+            client_id: None,
+            supported_tools: None,
+            plugin_version: None,
+        });
+        drop(s);
+        state
+    }
+
+    /// Run `call` against a state with a connected session and return the
+    /// request it sent the plugin, along with the call's own result once
+    /// the request is answered successfully with `result`. Panics if the
+    /// call finishes without sending anything.
+    pub async fn forwarded<T, Fut>(
+        call: impl FnOnce(Arc<Mutex<AppState>>) -> Fut,
+        result: Value,
+    ) -> (PluginRequest, Result<T>)
+    where
+        T: Send + 'static,
+        Fut: std::future::Future<Output = Result<T>> + Send + 'static,
+    {
+        let state = state_with_session("s1");
+        let handle = tokio::spawn(call(state.clone()));
+        let request = loop {
+            if let Some(request) = state.lock().await.sessions.next_request("s1") {
+                break request;
+            }
+            if handle.is_finished() {
+                let outcome = handle.await.unwrap();
+                panic!(
+                    "call finished without reaching the plugin: {:?}",
+                    outcome.err()
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        state
+            .lock()
+            .await
+            .sessions
+            .deliver_response(PluginResponse {
+                id: request.id.clone(),
+                success: true,
+                result,
+                error: None,
+            });
+        (request, handle.await.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::state_with_session;
+    use super::*;
+    use crate::state::{PluginResponse, SessionRegistration};
+
+    /// Answer the oldest queued request for `session`, returning its tool args.
+    async fn answer_next(state: &Arc<Mutex<AppState>>, session: &str, result: &str) -> Value {
+        let s = state.lock().await;
+        let request = s.sessions.next_request(session).expect("no queued request");
+        s.sessions.deliver_response(PluginResponse {
+            id: request.id,
+            success: true,
+            result: Value::String(result.into()),
+            error: None,
+        });
+        request.args
+    }
+
+    async fn queued(state: &Arc<Mutex<AppState>>, session: &str) -> usize {
+        state.lock().await.sessions.queue_depth(session)
+    }
+
+    #[test]
+    fn tool_filter_deny_wins_and_prefixes_match() {
+        let filter = ToolFilter {
+            allow: Some(vec!["datastore_*".into(), "get_file_tree".into()]),
+            deny: vec!["datastore_set".into(), "datastore_delete".into()],
+        };
+        assert!(filter.permits("datastore_get"));
+        assert!(filter.permits("get_file_tree"));
+        assert!(!filter.permits("datastore_set"));
+        assert!(!filter.permits("run_code"));
+        assert!(filter.permits("get_result_page"));
+
+        let deny_only = ToolFilter {
+            allow: None,
+            deny: vec!["run_code".into()],
+        };
+        assert!(deny_only.permits("set_property"));
+        assert!(!deny_only.permits("run_code"));
+
+        let typo = ToolFilter {
+            allow: None,
+            deny: vec!["run_cod".into(), "datastore_*".into()],
+        };
+        assert_eq!(
+            typo.unmatched(&["run_code", "datastore_get"]),
+            vec!["run_cod"]
+        );
+    }
+
+    #[test]
+    fn read_only_refuses_only_mutating_tools() {
+        for tool in RETRYABLE_TOOLS {
+            assert!(!MUTATING_TOOLS.contains(tool), "{} is both", tool);
+            assert!(read_only_refusal(true, tool).is_none());
+        }
+        assert!(read_only_refusal(false, "run_code").is_none());
+        let refusal = read_only_refusal(true, "datastore_set").unwrap();
+        assert_eq!(refusal.metadata().unwrap()["kind"], "read_only");
+        assert!(refusal.to_string().starts_with("datastore_set is disabled"));
+        // Driving a running game changes properties and play state too
+        for tool in [
+            "ui_set_text",
+            "ui_click",
+            "input_simulate",
+            // Its probes send real key and mouse events
+            "vim_capability_test",
+            "character_moveto",
+            "character_teleport",
+            "character_action",
+        ] {
+            assert!(read_only_refusal(true, tool).is_some(), "{}", tool);
+            assert!(plugin_read_only_refusal(true, tool).is_some(), "{}", tool);
+        }
+        assert!(read_only_refusal(true, "ui_get_state").is_none());
+    }
+
+    #[test]
+    fn read_only_refuses_open_cloud_writes() {
+        for tool in ["publish_place", "asset_upload"] {
+            let refusal = read_only_refusal(true, tool).unwrap();
+            assert_eq!(refusal.metadata().unwrap()["kind"], "read_only");
+        }
+        assert!(read_only_refusal(false, "asset_upload").is_none());
+    }
+
+    #[test]
+    fn read_only_refuses_every_plugin_tool_a_mutating_tool_sends() {
+        let plugin = include_str!("../../plugin/src/Main.server.luau");
+        for tool in MUTATING_TOOLS {
+            let (_, sent) = MUTATING_PLUGIN_TOOLS
+                .iter()
+                .find(|(name, _)| name == tool)
+                .unwrap_or_else(|| panic!("{} has no MUTATING_PLUGIN_TOOLS entry", tool));
+            for plugin_tool in *sent {
+                assert!(
+                    plugin.contains(&format!("Tools[\"{}\"]", plugin_tool)),
+                    "{} sends {}, which the plugin doesn't register",
+                    tool,
+                    plugin_tool
+                );
+                assert!(plugin_read_only_refusal(true, plugin_tool).is_some());
+                assert!(plugin_read_only_refusal(false, plugin_tool).is_none());
+            }
+        }
+        for (tool, _) in MUTATING_PLUGIN_TOOLS {
+            assert!(MUTATING_TOOLS.contains(tool), "{} isn't mutating", tool);
+        }
+        assert!(plugin_read_only_refusal(true, "get_file_tree").is_none());
+    }
+
+    #[tokio::test]
+    async fn explicit_session_routes_without_touching_active() {
+        let state = state_with_session("a");
+        {
+            let mut s = state.lock().await;
+            s.register_session(SessionRegistration {
+                session_id: "b".into(),
+                place_id: 2,
+                place_name: "Other".into(),
+                game_id: 0,
+                client_id: None,
+                supported_tools: None,
+                plugin_version: None,
+            });
+            s.active_session = Some("a".into());
+        }
+        let call = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let args = serde_json::json!({ "command": "print(1)" });
+                send_to_plugin(&state, Some("b"), "run_code", args, DEFAULT_TIMEOUT).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queued(&state, "a").await, 0);
+        answer_next(&state, "b", "ok").await;
+        assert_eq!(call.await.unwrap().unwrap(), "ok");
+        assert_eq!(state.lock().await.active_session.as_deref(), Some("a"));
+
+        // A session that stopped heartbeating is rejected up front
+        state.lock().await.sessions.with("b", |b| {
+            b.last_heartbeat = std::time::Instant::now() - Duration::from_secs(60)
+        });
+        let err = send_to_plugin(&state, Some("b"), "run_code", Value::Null, DEFAULT_TIMEOUT)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not responding"), "{}", err);
+        assert_eq!(queued(&state, "b").await, 0);
+    }
+
+    #[tokio::test]
+    async fn exclusive_run_code_is_serialized_per_session() {
+        let state = state_with_session("s1");
+        let call = |n: u32| {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let args = serde_json::json!({ "command": format!("print({})", n) });
+                send_to_plugin(&state, Some("s1"), "run_code", args, DEFAULT_TIMEOUT).await
+            })
+        };
+        let first = call(1);
+        let second = call(2);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Only one run_code reaches the plugin while the other holds the lock
+        assert_eq!(queued(&state, "s1").await, 1);
+        answer_next(&state, "s1", "one").await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queued(&state, "s1").await, 1);
+        answer_next(&state, "s1", "two").await;
+
+        let results = [
+            first.await.unwrap().unwrap(),
+            second.await.unwrap().unwrap(),
+        ];
+        let waited = results
+            .iter()
+            .filter(|r| r.as_str().unwrap().contains("[LOCK] waited"))
+            .count();
+        assert_eq!(waited, 1);
+    }
+
+    #[tokio::test]
+    async fn lock_wait_counts_against_the_timeout() {
+        let state = state_with_session("s1");
+        let timeout = Duration::from_millis(400);
+        let call = || {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let started = std::time::Instant::now();
+                let args = serde_json::json!({ "command": "print(1)" });
+                let outcome = send_to_plugin(&state, Some("s1"), "run_code", args, timeout).await;
+                (outcome, started.elapsed())
+            })
+        };
+        let first = call();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let second = call();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        answer_next(&state, "s1", "one").await;
+        assert_eq!(first.await.unwrap().0.unwrap(), "one");
+
+        // The second call waited ~250ms for the lock, so it only has what is
+        // left of its 400ms for the plugin, not another full 400ms
+        let (outcome, elapsed) = second.await.unwrap();
+        assert!(matches!(
+            outcome,
+            Err(StudioLinkError::RequestTimeout { .. })
+        ));
+        assert!(elapsed < Duration::from_millis(600), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn non_exclusive_run_code_skips_the_lock() {
+        let state = state_with_session("s1");
+        let mut handles = Vec::new();
+        for _ in 0..2 {
+            let state = state.clone();
+            handles.push(tokio::spawn(async move {
+                let args = serde_json::json!({ "command": "print(1)", "exclusive": false });
+                send_to_plugin(&state, Some("s1"), "run_code", args, DEFAULT_TIMEOUT).await
+            }));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queued(&state, "s1").await, 2);
+        answer_next(&state, "s1", "a").await;
+        answer_next(&state, "s1", "b").await;
+        for handle in handles {
+            let result = handle.await.unwrap().unwrap();
+            assert!(!result.as_str().unwrap().contains("[LOCK]"));
+        }
+    }
+
+    /// Fail the oldest queued request for `session` with `error`.
+    async fn fail_next(state: &Arc<Mutex<AppState>>, session: &str, error: &str) {
+        let s = state.lock().await;
+        let request = s.sessions.next_request(session).expect("no queued request");
+        s.sessions.deliver_response(PluginResponse {
+            id: request.id,
+            success: false,
+            result: Value::Null,
+            error: Some(error.into()),
+        });
+    }
+
+    #[tokio::test]
+    async fn throttled_reads_are_retried_with_backoff() {
+        let state = state_with_session("s1");
+        state.lock().await.retry_policies.insert(
+            "datastore_get".into(),
+            RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(10),
+            },
+        );
+        let call = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                send_to_plugin(
+                    &state,
+                    Some("s1"),
+                    "datastore_get",
+                    Value::Null,
+                    DEFAULT_TIMEOUT,
+                )
+                .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        fail_next(&state, "s1", "Failed to get key: Request was throttled.").await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        {
+            let s = state.lock().await;
+            let request = s.sessions.next_request("s1").unwrap();
+            s.sessions.deliver_response(PluginResponse {
+                id: request.id,
+                success: true,
+                result: serde_json::json!({ "value": 1 }),
+                error: None,
+            });
+        }
+        let result = call.await.unwrap().unwrap();
+        assert_eq!(result["retries"], 1);
+        let s = state.lock().await;
+        assert_eq!(s.retry_stats["datastore_get"].retries, 1);
+        assert_eq!(s.retry_stats["datastore_get"].recovered, 1);
+    }
+
+    #[tokio::test]
+    async fn non_retryable_errors_and_tools_fail_at_once() {
+        let state = state_with_session("s1");
+        for (tool, error) in [
+            ("datastore_get", "Failed to get key: key name is too long"),
+            ("datastore_set", "Failed to set key: Request was throttled."),
+        ] {
+            let call = {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    send_to_plugin(&state, Some("s1"), tool, Value::Null, DEFAULT_TIMEOUT).await
+                })
+            };
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            fail_next(&state, "s1", error).await;
+            assert!(call.await.unwrap().is_err());
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            assert_eq!(queued(&state, "s1").await, 0);
+        }
+        assert!(state.lock().await.retry_stats.is_empty());
+    }
+
+    #[test]
+    fn retry_configuration() {
+        assert!(is_retryable_error("DataStore request was THROTTLED"));
+        assert!(is_retryable_error("GetAsync request budget exhausted"));
+        assert!(!is_retryable_error("Instance not found: Workspace.Foo"));
+
+        let policy = DEFAULT_RETRY_POLICY;
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(20), MAX_RETRY_DELAY);
+
+        assert_eq!(
+            parse_retry_override("get_file_tree=4"),
+            Ok(("get_file_tree".to_string(), 4))
+        );
+        assert!(parse_retry_override("datastore_set=3")
+            .unwrap_err()
+            .contains("can't be retried"));
+        assert!(parse_retry_override("get_file_tree=0").is_err());
+        assert!(parse_retry_override("get_file_tree").is_err());
+        assert!(DEFAULT_RETRY_TOOLS
+            .iter()
+            .all(|tool| RETRYABLE_TOOLS.contains(tool)));
+    }
+}
@@ -6,6 +6,9 @@ use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
 use crate::error::Result;
 use crate::state::AppState;
 
+/// RemoteEvent calls per second from one player above which they're flagged.
+const PLAYER_SPAM_CALLS_PER_SEC: f64 = 10.0;
+
 /// Tool 29: network_monitor_start — Start monitoring RemoteEvent/Function traffic
 pub async fn network_monitor_start(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
     send_to_plugin(
@@ -19,13 +22,120 @@ pub async fn network_monitor_start(state: &Arc<Mutex<AppState>>) -> Result<serde
 }
 
 /// Tool 30: network_monitor_stop — Stop monitoring and return traffic report
-pub async fn network_monitor_stop(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
-    send_to_plugin(
+///
+/// With `by_player`, RemoteEvent traffic is also broken down by the player
+/// that fired it: `byPlayer` lists each player with their per-remote calls,
+/// ranked busiest first, and flags anyone over `PLAYER_SPAM_CALLS_PER_SEC`.
+/// Only server-side events carry a player, so RemoteFunction traffic and
+/// edit-mode sessions contribute nothing to the breakdown.
+pub async fn network_monitor_stop(
+    state: &Arc<Mutex<AppState>>,
+    by_player: Option<bool>,
+) -> Result<serde_json::Value> {
+    let by_player = by_player.unwrap_or(false);
+    let mut report = send_to_plugin(
         state,
         None,
         "network_monitor_stop",
-        json!({}),
+        json!({ "byPlayer": by_player }),
         EXTENDED_TIMEOUT,
     )
-    .await
+    .await?;
+    if by_player {
+        structure_by_player(&mut report);
+    }
+    Ok(report)
+}
+
+/// Replace the plugin's raw `players` list with the ranked `byPlayer` report.
+fn structure_by_player(report: &mut serde_json::Value) {
+    let duration = report["duration"].as_f64().unwrap_or(0.0);
+    let raw = report
+        .as_object_mut()
+        .and_then(|o| o.remove("players"))
+        .unwrap_or_default();
+
+    let mut players: Vec<serde_json::Value> = raw
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|p| {
+            let mut remotes: Vec<serde_json::Value> =
+                p["remotes"].as_array().cloned().unwrap_or_default();
+            remotes.sort_by_key(|r| std::cmp::Reverse(r["calls"].as_u64().unwrap_or(0)));
+            let calls: u64 = remotes.iter().filter_map(|r| r["calls"].as_u64()).sum();
+            let bytes: u64 = remotes.iter().filter_map(|r| r["bytes"].as_u64()).sum();
+            let rate = if duration > 0.0 {
+                calls as f64 / duration
+            } else {
+                0.0
+            };
+            json!({
+                "player": p["player"],
+                "userId": p["userId"],
+                "totalCalls": calls,
+                "totalBytes": bytes,
+                "callsPerSecond": (rate * 100.0).round() / 100.0,
+                "suspicious": rate > PLAYER_SPAM_CALLS_PER_SEC,
+                "remotes": remotes,
+            })
+        })
+        .collect();
+    players.sort_by_key(|p| std::cmp::Reverse(p["totalCalls"].as_u64().unwrap_or(0)));
+
+    let note = match players.len() {
+        0 => Some("No player fired a RemoteEvent while monitoring (per-player traffic is only seen server-side, e.g. during a play test)"),
+        1 => Some("Only one player fired RemoteEvents, so the breakdown matches the aggregate; start a multi-client test to compare players"),
+        _ => None,
+    };
+    report["byPlayer"] = json!(players);
+    if let Some(note) = note {
+        report["byPlayerNote"] = json!(note);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_players_and_flags_spammers() {
+        let mut report = json!({
+            "duration": 2.0,
+            "totalCalls": 43,
+            "players": [
+                { "player": "Quiet", "userId": 1, "remotes": [
+                    { "remote": "ReplicatedStorage.Chat", "calls": 3, "bytes": 30 },
+                ] },
+                { "player": "Loud", "userId": 2, "remotes": [
+                    { "remote": "ReplicatedStorage.Chat", "calls": 1, "bytes": 10 },
+                    { "remote": "ReplicatedStorage.Fire", "calls": 39, "bytes": 390 },
+                ] },
+            ],
+        });
+        structure_by_player(&mut report);
+        assert!(report.get("players").is_none());
+        let players = report["byPlayer"].as_array().unwrap();
+        assert_eq!(players[0]["player"], "Loud");
+        assert_eq!(players[0]["totalCalls"], 40);
+        assert_eq!(players[0]["callsPerSecond"], 20.0);
+        assert_eq!(players[0]["suspicious"], true);
+        assert_eq!(players[0]["remotes"][0]["remote"], "ReplicatedStorage.Fire");
+        assert_eq!(players[1]["suspicious"], false);
+        assert!(report.get("byPlayerNote").is_none());
+    }
+
+    #[test]
+    fn single_player_gets_a_note() {
+        let mut report = json!({
+            "duration": 1.0,
+            "players": [{ "player": "Solo", "userId": 1, "remotes": [] }],
+        });
+        structure_by_player(&mut report);
+        assert_eq!(report["byPlayer"].as_array().unwrap().len(), 1);
+        assert!(report["byPlayerNote"]
+            .as_str()
+            .unwrap()
+            .contains("one player"));
+    }
 }
@@ -1,10 +1,12 @@
+use serde::Serialize;
 use serde_json::json;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
-use crate::error::Result;
-use crate::state::AppState;
+use crate::error::{Result, StudioLinkError};
+use crate::state::{AppState, SessionInfo};
 
 /// Tool 12: profile_start — Start the ScriptProfiler
 pub async fn profile_start(
@@ -26,7 +28,571 @@ pub async fn profile_stop(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Va
     send_to_plugin(state, None, "profile_stop", json!({}), EXTENDED_TIMEOUT).await
 }
 
+/// Longest profile_session capture, in seconds.
+const MAX_PROFILE_SESSION_SECS: u64 = 300;
+
+/// How long profile_session waits for the Play Server session to register
+/// before running setup code in it.
+const PLAY_SERVER_WAIT: Duration = Duration::from_secs(15);
+
+/// profile_session — Enter play mode, optionally run setup code in the
+/// server, profile for `duration_secs`, then stop the profiler and play mode
+/// and return profile_analyze's result.
+///
+/// Each step is an ordinary tool call. Whatever fails, play mode is stopped
+/// before returning (and the profiler too, if it was started), so a failed
+/// capture never leaves Studio running. The steps run in their own task, so
+/// a client that cancels the call mid-capture still gets that cleanup.
+pub async fn profile_session(
+    state: &Arc<Mutex<AppState>>,
+    code: Option<&str>,
+    mode: Option<&str>,
+    duration_secs: u64,
+    frequency: Option<u32>,
+) -> Result<serde_json::Value> {
+    let state = state.clone();
+    let code = code.map(str::to_string);
+    let mode = mode.map(str::to_string);
+    tokio::spawn(async move {
+        run_profile_session(
+            &state,
+            code.as_deref(),
+            mode.as_deref(),
+            duration_secs,
+            frequency,
+        )
+        .await
+    })
+    .await
+    .map_err(|e| StudioLinkError::PluginError(format!("profile_session task failed: {}", e)))?
+}
+
+async fn run_profile_session(
+    state: &Arc<Mutex<AppState>>,
+    code: Option<&str>,
+    mode: Option<&str>,
+    duration_secs: u64,
+    frequency: Option<u32>,
+) -> Result<serde_json::Value> {
+    let mode = mode.unwrap_or("start_play");
+    validate_profile_session(mode, duration_secs)?;
+
+    let started_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    send_to_plugin(
+        state,
+        None,
+        "start_stop_play",
+        json!({ "mode": mode }),
+        DEFAULT_TIMEOUT,
+    )
+    .await?;
+
+    let mut profiling = false;
+    let captured = capture_in_play(
+        state,
+        code,
+        started_at,
+        duration_secs,
+        frequency,
+        &mut profiling,
+    )
+    .await;
+    if captured.is_err() && profiling {
+        if let Err(e) = profile_stop(state).await {
+            tracing::warn!("profile_session: stopping profiler after failure: {}", e);
+        }
+    }
+    let stopped = send_to_plugin(
+        state,
+        None,
+        "start_stop_play",
+        json!({ "mode": "stop" }),
+        DEFAULT_TIMEOUT,
+    )
+    .await;
+
+    let mut result = captured?;
+    if let Err(e) = stopped {
+        result["stopError"] = json!(format!(
+            "profiling finished but play mode may still be running: {}",
+            e
+        ));
+    }
+    result["session"] = json!({
+        "mode": mode,
+        "durationSecs": duration_secs,
+        "frequency": frequency.unwrap_or(1000),
+        "setupCode": code.is_some(),
+    });
+    Ok(result)
+}
+
+/// The play-mode part of profile_session. `profiling` is set once
+/// profile_start succeeds so the caller knows to stop it on failure.
+async fn capture_in_play(
+    state: &Arc<Mutex<AppState>>,
+    code: Option<&str>,
+    started_at: u64,
+    duration_secs: u64,
+    frequency: Option<u32>,
+    profiling: &mut bool,
+) -> Result<serde_json::Value> {
+    if let Some(code) = code {
+        let server = wait_for_play_server(state, started_at).await?;
+        send_to_plugin(
+            state,
+            Some(&server),
+            "run_code",
+            json!({ "command": code }),
+            DEFAULT_TIMEOUT,
+        )
+        .await?;
+    }
+    profile_start(state, frequency).await?;
+    *profiling = true;
+    tokio::time::sleep(Duration::from_secs(duration_secs)).await;
+    profile_stop(state).await?;
+    *profiling = false;
+    profile_analyze(state).await
+}
+
+fn validate_profile_session(mode: &str, duration_secs: u64) -> Result<()> {
+    if !matches!(mode, "start_play" | "run_server") {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "mode must be 'start_play' or 'run_server', got '{}'",
+            mode
+        )));
+    }
+    if duration_secs == 0 || duration_secs > MAX_PROFILE_SESSION_SECS {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "durationSecs must be 1-{}, got {}",
+            MAX_PROFILE_SESSION_SECS, duration_secs
+        )));
+    }
+    Ok(())
+}
+
+/// The newest session the plugin registered from a play-mode server DataModel
+/// ("Play Server" or "<Place> (Play Server)") at or after `since` (Unix
+/// seconds), so a leftover one from an earlier run isn't picked.
+fn find_play_server(sessions: &[SessionInfo], since: u64) -> Option<String> {
+    sessions
+        .iter()
+        .filter(|s| s.connected_at >= since)
+        .filter(|s| {
+            s.place_name.ends_with("Play Server") || s.place_name.ends_with("(Play Server)")
+        })
+        .max_by_key(|s| s.connected_at)
+        .map(|s| s.session_id.clone())
+}
+
+async fn wait_for_play_server(state: &Arc<Mutex<AppState>>, since: u64) -> Result<String> {
+    let deadline = tokio::time::Instant::now() + PLAY_SERVER_WAIT;
+    loop {
+        if let Some(id) = find_play_server(&state.lock().await.sessions.list(), since) {
+            return Ok(id);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(StudioLinkError::PluginError(format!(
+                "play server session did not register within {}s; setup code was not run",
+                PLAY_SERVER_WAIT.as_secs()
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+/// A CPU hotspot mapped back to the script and line that defines it.
+/// Location fields are None for C functions and code the plugin could not
+/// attribute (anonymous closures, stripped sources).
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Hotspot {
+    pub function: Option<String>,
+    pub script_path: Option<String>,
+    pub line: Option<u32>,
+    pub self_time_ms: Option<f64>,
+    pub suggestion: String,
+}
+
 /// Tool 14: profile_analyze — Analyze profiling data with optimization suggestions
+///
+/// The plugin reports hotspots with a raw `source` string ("Path.To.Script:42",
+/// "[C]", or a bare path); this layer splits it into `scriptPath` + `line` so an
+/// agent can go straight to `get_script_source`.
 pub async fn profile_analyze(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
-    send_to_plugin(state, None, "profile_analyze", json!({}), EXTENDED_TIMEOUT).await
+    let mut result =
+        send_to_plugin(state, None, "profile_analyze", json!({}), EXTENDED_TIMEOUT).await?;
+    if let Some(raw) = result.get("hotspots").and_then(|h| h.as_array()) {
+        let hotspots: Vec<Hotspot> = raw.iter().map(structure_hotspot).collect();
+        result["hotspots"] = json!(hotspots);
+    }
+    Ok(result)
+}
+
+/// Split a plugin source location into (scriptPath, line). C-side functions
+/// ("[C]", "=[C]") and empty sources have no location.
+fn parse_source(source: &str) -> (Option<String>, Option<u32>) {
+    let source = source.trim().trim_start_matches('=');
+    if source.is_empty() || source == "[C]" {
+        return (None, None);
+    }
+    match source.rsplit_once(':') {
+        Some((path, line)) if !path.is_empty() => match line.parse::<u32>() {
+            Ok(n) => (Some(path.to_string()), Some(n)),
+            Err(_) => (Some(source.to_string()), None),
+        },
+        _ => (Some(source.to_string()), None),
+    }
+}
+
+fn structure_hotspot(raw: &serde_json::Value) -> Hotspot {
+    let (script_path, line) = parse_source(raw["source"].as_str().unwrap_or(""));
+    let function = raw["function"]
+        .as_str()
+        .filter(|f| !f.is_empty() && *f != "anonymous")
+        .map(|f| f.to_string());
+    let self_time_ms = raw["selfTimeMs"].as_f64();
+
+    let base = raw["suggestion"].as_str().map(|s| s.to_string()).unwrap_or_else(|| {
+        "High self time — look for repeated work that can be cached or moved out of per-frame callbacks".to_string()
+    });
+    let suggestion = match (&script_path, line) {
+        (Some(path), Some(n)) => format!("{} (see {} line {})", base, path, n),
+        (Some(path), None) => format!("{} (see {})", base, path),
+        _ => format!(
+            "{} (no source location: C function or anonymous code)",
+            base
+        ),
+    };
+
+    Hotspot {
+        function,
+        script_path,
+        line,
+        self_time_ms,
+        suggestion,
+    }
+}
+
+/// profile_memory_start — Start sampling Luau heap and per-category memory
+pub async fn profile_memory_start(
+    state: &Arc<Mutex<AppState>>,
+    interval_ms: Option<u32>,
+) -> Result<serde_json::Value> {
+    send_to_plugin(
+        state,
+        None,
+        "profile_memory_start",
+        json!({ "intervalMs": interval_ms.unwrap_or(100) }),
+        DEFAULT_TIMEOUT,
+    )
+    .await
+}
+
+/// profile_memory_stop — Stop sampling; returns heap totals for the run
+pub async fn profile_memory_stop(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
+    send_to_plugin(
+        state,
+        None,
+        "profile_memory_stop",
+        json!({}),
+        DEFAULT_TIMEOUT,
+    )
+    .await
+}
+
+/// A memory category ranked by how much it allocated during the run.
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocationHotspot {
+    pub category: String,
+    pub allocated_mb: f64,
+    pub net_mb: f64,
+    pub peak_mb: f64,
+    pub suggestion: String,
+}
+
+/// Growth and collection totals for a sampled memory series.
+#[derive(Debug, Default, PartialEq)]
+struct Churn {
+    allocated: f64,
+    reclaimed: f64,
+    collections: u32,
+}
+
+/// Sum the rises and falls of a sampled series. A run of consecutive drops
+/// counts as one collection.
+fn churn(samples: &[f64]) -> Churn {
+    let mut out = Churn::default();
+    let mut falling = false;
+    for pair in samples.windows(2) {
+        let delta = pair[1] - pair[0];
+        if delta >= 0.0 {
+            out.allocated += delta;
+            falling = false;
+        } else {
+            out.reclaimed -= delta;
+            if !falling {
+                out.collections += 1;
+            }
+            falling = true;
+        }
+    }
+    out
+}
+
+pub(super) fn round2(n: f64) -> f64 {
+    (n * 100.0).round() / 100.0
+}
+
+/// profile_memory_analyze — Allocation hotspots and GC pressure
+///
+/// The plugin returns raw samples; this layer ranks memory categories by
+/// bytes allocated (sum of growth between samples) and derives GC pressure
+/// from the Luau heap: allocation rate and collections per minute.
+///
+/// **Limitation**: Roblox has no per-function allocation API for plugins, so
+/// hotspots are per DeveloperMemoryTag category, not per function.
+pub async fn profile_memory_analyze(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
+    let raw = send_to_plugin(
+        state,
+        None,
+        "profile_memory_analyze",
+        json!({}),
+        EXTENDED_TIMEOUT,
+    )
+    .await?;
+    Ok(structure_memory_profile(&raw))
+}
+
+fn number_series(value: &serde_json::Value) -> Vec<f64> {
+    value
+        .as_array()
+        .map(|a| a.iter().filter_map(|v| v.as_f64()).collect())
+        .unwrap_or_default()
+}
+
+fn structure_memory_profile(raw: &serde_json::Value) -> serde_json::Value {
+    let duration = raw["durationSec"].as_f64().unwrap_or(0.0);
+    let heap = number_series(&raw["heapKb"]);
+    let heap_churn = churn(&heap);
+
+    let mut hotspots: Vec<AllocationHotspot> = raw["categoriesMb"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(category, series)| {
+            let series = number_series(series);
+            let c = churn(&series);
+            if c.allocated <= 0.0 {
+                return None;
+            }
+            let net = series.last().copied().unwrap_or(0.0) - series.first().copied().unwrap_or(0.0);
+            let peak = series.iter().copied().fold(0.0, f64::max);
+            let suggestion = if net > c.allocated * 0.5 {
+                "Grows without being released: look for tables, instances or connections that are created and never cleaned up"
+            } else {
+                "High churn: reuse tables/objects instead of allocating them every frame"
+            };
+            Some(AllocationHotspot {
+                category: category.clone(),
+                allocated_mb: round2(c.allocated),
+                net_mb: round2(net),
+                peak_mb: round2(peak),
+                suggestion: suggestion.to_string(),
+            })
+        })
+        .collect();
+    hotspots.sort_by(|a, b| b.allocated_mb.total_cmp(&a.allocated_mb));
+
+    let per_sec = |n: f64| if duration > 0.0 { n / duration } else { 0.0 };
+    let collections_per_min = per_sec(f64::from(heap_churn.collections)) * 60.0;
+    let level = match collections_per_min {
+        r if r >= 30.0 => "high",
+        r if r >= 6.0 => "medium",
+        _ => "low",
+    };
+
+    json!({
+        "durationSec": round2(duration),
+        "samples": heap.len(),
+        "hotspots": hotspots,
+        "gcPressure": {
+            "level": level,
+            "allocatedKb": round2(heap_churn.allocated),
+            "reclaimedKb": round2(heap_churn.reclaimed),
+            "allocationRateKbPerSec": round2(per_sec(heap_churn.allocated)),
+            "collections": heap_churn.collections,
+            "collectionsPerMin": round2(collections_per_min),
+        },
+        "heapKb": {
+            "start": heap.first(),
+            "end": heap.last(),
+            "peak": heap.iter().copied().reduce(f64::max),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{PluginResponse, SessionRegistration};
+
+    /// Answer the next request queued for `session` and return its tool name
+    /// and args.
+    async fn answer_next(
+        state: &Arc<Mutex<AppState>>,
+        session: &str,
+    ) -> (String, serde_json::Value) {
+        let s = state.lock().await;
+        let request = s.sessions.next_request(session).expect("no queued request");
+        s.sessions.deliver_response(PluginResponse {
+            id: request.id,
+            success: true,
+            result: json!({}),
+            error: None,
+        });
+        (request.tool, request.args)
+    }
+
+    #[tokio::test]
+    async fn cancelled_profile_session_still_stops_play_mode() {
+        let state = AppState::new().0;
+        state.lock().await.register_session(SessionRegistration {
+            session_id: "edit".into(),
+            place_id: 1,
+            place_name: "Place".into(),
+            game_id: 0,
+            client_id: None,
+            supported_tools: None,
+            plugin_version: None,
+        });
+        let call = {
+            let state = state.clone();
+            tokio::spawn(async move { profile_session(&state, None, None, 1, None).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(answer_next(&state, "edit").await.0, "start_stop_play");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(answer_next(&state, "edit").await.0, "profile_start");
+
+        // The MCP client gives up while the capture is running
+        call.abort();
+        assert!(call.await.unwrap_err().is_cancelled());
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(answer_next(&state, "edit").await.0, "profile_stop");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(answer_next(&state, "edit").await.0, "profile_analyze");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let (tool, args) = answer_next(&state, "edit").await;
+        assert_eq!(tool, "start_stop_play");
+        assert_eq!(args["mode"], "stop");
+    }
+
+    fn session(id: &str, place_name: &str, connected_at: u64) -> SessionInfo {
+        SessionInfo {
+            session_id: id.into(),
+            place_id: 1,
+            place_name: place_name.into(),
+            game_id: 1,
+            connected_at,
+            client_id: None,
+            display_name: None,
+        }
+    }
+
+    #[test]
+    fn profile_session_finds_fresh_play_server() {
+        let sessions = [
+            session("edit", "Obby", 100),
+            session("old", "Obby (Play Server)", 150),
+            session("new", "Obby (Play Server)", 210),
+        ];
+        assert_eq!(find_play_server(&sessions, 200).as_deref(), Some("new"));
+        assert_eq!(find_play_server(&sessions[..2], 200), None);
+        assert_eq!(
+            find_play_server(&[session("s", "Play Server", 300)], 200).as_deref(),
+            Some("s")
+        );
+
+        assert!(validate_profile_session("start_play", 10).is_ok());
+        assert!(validate_profile_session("stop", 10).is_err());
+        assert!(validate_profile_session("run_server", 0).is_err());
+        assert!(validate_profile_session("run_server", MAX_PROFILE_SESSION_SECS + 1).is_err());
+    }
+
+    #[test]
+    fn churn_counts_each_collection_once() {
+        let c = churn(&[100.0, 150.0, 200.0, 120.0, 80.0, 130.0, 90.0]);
+        assert_eq!(c.allocated, 150.0);
+        assert_eq!(c.reclaimed, 160.0);
+        assert_eq!(c.collections, 2);
+    }
+
+    #[test]
+    fn memory_profile_ranks_categories() {
+        let raw = json!({
+            "durationSec": 10.0,
+            "heapKb": [1000, 3000, 1000, 3000, 1000],
+            "categoriesMb": {
+                "Instances": [10.0, 12.0, 14.0, 16.0],
+                "Sounds": [5.0, 5.0, 5.0],
+                "LuaHeap": [20.0, 40.0, 20.0, 40.0, 20.0],
+            },
+        });
+        let out = structure_memory_profile(&raw);
+        let hotspots = out["hotspots"].as_array().unwrap();
+        assert_eq!(hotspots.len(), 2);
+        assert_eq!(hotspots[0]["category"], "LuaHeap");
+        assert_eq!(hotspots[0]["allocatedMb"], 40.0);
+        assert!(hotspots[0]["suggestion"]
+            .as_str()
+            .unwrap()
+            .contains("churn"));
+        assert_eq!(hotspots[1]["netMb"], 6.0);
+        assert!(hotspots[1]["suggestion"]
+            .as_str()
+            .unwrap()
+            .contains("never cleaned up"));
+
+        assert_eq!(out["gcPressure"]["collections"], 2);
+        assert_eq!(out["gcPressure"]["collectionsPerMin"], 12.0);
+        assert_eq!(out["gcPressure"]["level"], "medium");
+        assert_eq!(out["gcPressure"]["allocationRateKbPerSec"], 400.0);
+    }
+
+    #[test]
+    fn parses_path_and_line() {
+        assert_eq!(
+            parse_source("ServerScriptService.Combat:42"),
+            (Some("ServerScriptService.Combat".to_string()), Some(42))
+        );
+        assert_eq!(
+            parse_source("Workspace.Thing"),
+            (Some("Workspace.Thing".to_string()), None)
+        );
+    }
+
+    #[test]
+    fn c_functions_have_no_location() {
+        assert_eq!(parse_source("[C]"), (None, None));
+        assert_eq!(parse_source("=[C]"), (None, None));
+        assert_eq!(parse_source(""), (None, None));
+    }
+
+    #[test]
+    fn structures_anonymous_hotspot_gracefully() {
+        let h = structure_hotspot(
+            &json!({ "function": "anonymous", "source": "[C]", "selfTimeMs": 3.5 }),
+        );
+        assert_eq!(h.function, None);
+        assert_eq!(h.script_path, None);
+        assert_eq!(h.self_time_ms, Some(3.5));
+        assert!(h.suggestion.contains("no source location"));
+    }
 }
@@ -0,0 +1,229 @@
+//! MCP prompts: StudioLink's recommended multi-tool workflows as templates a
+//! client can offer directly (slash commands and the like). Each prompt
+//! expands to one user message that walks the agent through the tools in
+//! order.
+
+use rmcp::model::{
+    GetPromptResult, JsonObject, Prompt, PromptArgument, PromptMessage, PromptMessageRole,
+};
+
+use crate::error::{Result, StudioLinkError};
+
+struct WorkflowArg {
+    name: &'static str,
+    description: &'static str,
+    /// Substituted when the argument is omitted; None makes it required
+    default: Option<&'static str>,
+}
+
+struct Workflow {
+    name: &'static str,
+    title: &'static str,
+    description: &'static str,
+    arguments: &'static [WorkflowArg],
+    /// Tools the steps call; the prompt is hidden if any is filtered out
+    tools: &'static [&'static str],
+    /// Message text; `{name}` is replaced by the argument of that name
+    template: &'static str,
+}
+
+const WORKFLOWS: &[Workflow] = &[
+    Workflow {
+        name: "audit_security",
+        title: "Audit security",
+        description: "Scan the place for exploitable remotes and client trust issues, confirm each finding in the source and propose fixes",
+        arguments: &[WorkflowArg {
+            name: "focus",
+            description: "What to concentrate on, e.g. \"RemoteEvents that grant currency\" (default: everything)",
+            default: Some("everything the scan finds"),
+        }],
+        tools: &["security_scan", "security_report", "get_script_source", "dependency_map"],
+        template: "Audit this Roblox place for security problems, concentrating on {focus}.\n\
+\n\
+1. Call security_scan. If the project has conventions every handler must follow (a rate limiter, a shared validator), express them as customRules.\n\
+2. Call security_report for risk levels and remediation steps.\n\
+3. For each high or critical finding, read the script with get_script_source and confirm the issue is real: trace what the client controls and what the server trusts.\n\
+4. Call dependency_map to see which modules the affected handlers require, so fixes can go into shared code instead of every caller.\n\
+5. Summarise the confirmed issues by risk, each with its script path and line, why it is exploitable and a proposed fix. Don't change any scripts until the user has agreed to the fixes.",
+    },
+    Workflow {
+        name: "optimize_performance",
+        title: "Optimize performance",
+        description: "Profile a play session, find the costliest scripts and leaks, fix them one at a time and measure again",
+        arguments: &[
+            WorkflowArg {
+                name: "scenario",
+                description: "What the session should exercise, e.g. \"20 NPCs pathfinding\" (default: normal play)",
+                default: Some("normal play"),
+            },
+            WorkflowArg {
+                name: "durationSecs",
+                description: "How long to profile each run, 1-300 seconds (default: 30)",
+                default: Some("30"),
+            },
+        ],
+        tools: &[
+            "profile_session",
+            "memory_scan",
+            "get_script_source",
+            "script_patch",
+            "begin_macro",
+            "end_macro",
+        ],
+        template: "Find and fix the biggest performance costs in this place during {scenario}.\n\
+\n\
+1. Call profile_session with durationSecs={durationSecs}, passing setup code in `code` if the scenario needs NPCs or objects spawned. It returns CPU hotspots mapped to script lines.\n\
+2. Call memory_scan for leaked connections, instances and RunService bindings.\n\
+3. Read each top hotspot with get_script_source and work out why it is expensive: per-frame work that could be event-driven, repeated FindFirstChild or GetDescendants calls, table churn, unbounded loops.\n\
+4. Apply fixes one at a time with script_patch, each inside begin_macro/end_macro so it can be undone in one step.\n\
+5. Run profile_session again with the same settings and report before/after numbers for every hotspot you changed. Revert any change that didn't help.",
+    },
+    Workflow {
+        name: "write_tests",
+        title: "Write tests for a module",
+        description: "Write and run TestEZ tests covering a ModuleScript's public API",
+        arguments: &[WorkflowArg {
+            name: "module",
+            description: "Dotted path of the ModuleScript to test, e.g. ReplicatedStorage.Shared.Inventory",
+            default: None,
+        }],
+        tools: &[
+            "get_script_source",
+            "dependency_map",
+            "test_create",
+            "set_script_source",
+            "test_run",
+            "test_report",
+        ],
+        template: "Write TestEZ tests for the ModuleScript {module}.\n\
+\n\
+1. Read it with get_script_source and list its public API: every function and value the returned table exposes, and the edge cases each one handles.\n\
+2. Call dependency_map to see what it requires, and plan stubs for anything that reaches DataStores, HTTP or other services.\n\
+3. Call test_create with target_path={module} and createIn set to the folder holding the project's other .spec modules (or the module's parent) to create a spec skeleton.\n\
+4. Fill in the spec with set_script_source: one describe block per function, covering normal inputs, edge cases and error paths.\n\
+5. Run it with test_run and read failures with test_report. Fix the tests until they pass; if a failure shows a bug in the module itself, report the bug instead of bending the test around it.",
+    },
+];
+
+const READ_ONLY_NOTE: &str = "This server is in read-only mode and refuses tools that change the place. Wherever a step would edit a script, show the complete new source in your reply for the user to apply instead.";
+
+/// The prompts whose tools are all `available`.
+pub fn list(available: impl Fn(&str) -> bool) -> Vec<Prompt> {
+    WORKFLOWS
+        .iter()
+        .filter(|w| w.tools.iter().all(|t| available(t)))
+        .map(|w| {
+            let arguments = w
+                .arguments
+                .iter()
+                .map(|a| PromptArgument {
+                    name: a.name.into(),
+                    title: None,
+                    description: Some(a.description.into()),
+                    required: Some(a.default.is_none()),
+                })
+                .collect();
+            let mut prompt = Prompt::new(w.name, Some(w.description), Some(arguments));
+            prompt.title = Some(w.title.into());
+            prompt
+        })
+        .collect()
+}
+
+/// Expand prompt `name` with the client's `arguments`.
+pub fn get(
+    name: &str,
+    arguments: Option<&JsonObject>,
+    read_only: bool,
+    available: impl Fn(&str) -> bool,
+) -> Result<GetPromptResult> {
+    let workflow = WORKFLOWS
+        .iter()
+        .find(|w| w.name == name && w.tools.iter().all(|t| available(t)))
+        .ok_or_else(|| StudioLinkError::InvalidArguments(format!("unknown prompt {}", name)))?;
+
+    let mut text = workflow.template.to_string();
+    for arg in workflow.arguments {
+        let given = arguments
+            .and_then(|args| args.get(arg.name))
+            .map(|v| match v.as_str() {
+                Some(s) => s.trim().to_string(),
+                None => v.to_string(),
+            })
+            .filter(|v| !v.is_empty());
+        let value = match (given, arg.default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => default.to_string(),
+            (None, None) => {
+                return Err(StudioLinkError::InvalidArguments(format!(
+                    "prompt {} needs the {} argument",
+                    name, arg.name
+                )))
+            }
+        };
+        text = text.replace(&format!("{{{}}}", arg.name), &value);
+    }
+    let edits = workflow
+        .tools
+        .iter()
+        .any(|t| super::MUTATING_TOOLS.contains(t));
+    if read_only && edits {
+        text = format!("{}\n\n{}", text, READ_ONLY_NOTE);
+    }
+
+    Ok(GetPromptResult {
+        description: Some(workflow.description.into()),
+        messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::PromptMessageContent;
+    use serde_json::json;
+
+    fn text(result: &GetPromptResult) -> &str {
+        match &result.messages[0].content {
+            PromptMessageContent::Text { text } => text,
+            other => panic!("unexpected content {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prompts_fill_arguments_and_follow_server_mode() {
+        let args = json!({"module": "ReplicatedStorage.Inventory"});
+        let result = get("write_tests", args.as_object(), false, |_| true).unwrap();
+        assert!(text(&result).contains("ModuleScript ReplicatedStorage.Inventory."));
+        assert!(text(&result).contains("target_path=ReplicatedStorage.Inventory"));
+        assert!(!text(&result).contains('{'));
+        assert!(get("write_tests", None, false, |_| true).is_err());
+
+        let result = get("optimize_performance", None, true, |_| true).unwrap();
+        assert!(text(&result).contains("durationSecs=30"));
+        assert!(text(&result).ends_with(READ_ONLY_NOTE));
+        let result = get("audit_security", None, true, |_| true).unwrap();
+        assert!(!text(&result).contains(READ_ONLY_NOTE));
+
+        let without_tests = |tool: &str| tool != "test_run";
+        let names: Vec<String> = list(without_tests).into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["audit_security", "optimize_performance"]);
+        assert!(get("write_tests", args.as_object(), false, without_tests).is_err());
+    }
+
+    #[test]
+    fn workflows_only_name_known_tools() {
+        let known = crate::mcp::StudioLinkMcp::all_tool_names();
+        for workflow in WORKFLOWS {
+            for tool in workflow.tools {
+                assert!(known.iter().any(|t| t == tool), "{} is not a tool", tool);
+                assert!(
+                    workflow.template.contains(tool),
+                    "{} unused in {}",
+                    tool,
+                    workflow.name
+                );
+            }
+        }
+    }
+}
@@ -1,11 +1,33 @@
 use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use super::{send_to_plugin, DEFAULT_TIMEOUT};
 use crate::error::{Result, StudioLinkError};
+use crate::opencloud;
 use crate::state::AppState;
 
+/// Open Cloud refuses place files over 100 MB.
+const MAX_PLACE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Options for publishing a local place file through Open Cloud instead of
+/// Studio's dialog. All None/false means "use the dialog".
+#[derive(Debug, Default)]
+pub struct FilePublish {
+    /// .rbxl or .rbxlx to upload
+    pub file_path: Option<String>,
+    /// Defaults to the active session's game id
+    pub universe_id: Option<u64>,
+    /// Defaults to the active session's place id
+    pub place_id: Option<u64>,
+    /// Recorded in the local publish log; Open Cloud has no field for them
+    pub version_notes: Option<String>,
+    /// Check everything without uploading
+    pub dry_run: bool,
+}
+
 /// place_version_history — List published versions of a place.
 ///
 /// **Currently a stub.** Roblox Open Cloud does not yet expose a documented
@@ -25,18 +47,18 @@ pub async fn place_version_history(
     }))
 }
 
-/// publish_place — Open Studio's publish dialog for the active place.
+/// publish_place — Publish the active place, or upload a place file.
 ///
-/// True headless publish requires `RobloxScriptSecurity` which plugins don't
-/// have, so we use `StudioService:PublishAs()` which opens the publish dialog
-/// in Studio for the user to confirm. AI assistants get "dialog opened" — the
-/// user finishes the upload manually.
-///
-/// Open Cloud REST publishing (no dialog) is tracked for a future iteration
-/// once the endpoint contract is verified end-to-end.
+/// With `file_path`, the file is uploaded as a new version through the Open
+/// Cloud place publishing API, using the key in `STUDIOLINK_OPENCLOUD_KEY`.
+/// Plugins can't save the open place to disk, so publishing what Studio has
+/// open without a file goes through `StudioService:PublishAs()`, which opens
+/// the publish dialog for the user to confirm: true headless publish
+/// requires `RobloxScriptSecurity`, which plugins don't have.
 pub async fn publish_place(
     state: &Arc<Mutex<AppState>>,
     version_type: Option<String>,
+    file: FilePublish,
 ) -> Result<serde_json::Value> {
     let vt = version_type.unwrap_or_else(|| "Saved".to_string());
     if vt != "Saved" && vt != "Published" {
@@ -45,14 +67,188 @@ pub async fn publish_place(
             vt
         )));
     }
-    send_to_plugin(
-        state,
-        None,
-        "publish_place",
-        json!({ "versionType": vt }),
-        DEFAULT_TIMEOUT,
+    let Some(file_path) = file.file_path.as_deref() else {
+        if file.universe_id.is_some()
+            || file.place_id.is_some()
+            || file.version_notes.is_some()
+            || file.dry_run
+        {
+            return Err(StudioLinkError::InvalidArguments(
+                "universeId, placeId, versionNotes and dryRun apply to Open Cloud uploads; pass filePath with the .rbxl or .rbxlx to publish".into(),
+            ));
+        }
+        return send_to_plugin(
+            state,
+            None,
+            "publish_place",
+            json!({ "versionType": vt }),
+            DEFAULT_TIMEOUT,
+        )
+        .await;
+    };
+
+    let (session_universe, session_place) = {
+        let s = state.lock().await;
+        s.get_active_session_info()
+            .map_or((0, 0), |info| (info.game_id, info.place_id))
+    };
+    let universe_id = file.universe_id.unwrap_or(session_universe);
+    let place_id = file.place_id.unwrap_or(session_place);
+    let api_key = opencloud::api_key_from_env();
+
+    // Collect every problem so one call (dry run or not) reports them all
+    let mut problems = Vec::new();
+    let place = match read_place_file(Path::new(file_path)) {
+        Ok(place) => Some(place),
+        Err(e) => {
+            problems.push(e.to_string());
+            None
+        }
+    };
+    if universe_id == 0 {
+        problems.push("no universeId given and the active session's place isn't published".into());
+    }
+    if place_id == 0 {
+        problems.push("no placeId given and the active session's place isn't published".into());
+    }
+    if api_key.is_none() {
+        problems.push(format!(
+            "no Open Cloud API key in {} (needs the universe-places:write scope)",
+            opencloud::API_KEY_ENV
+        ));
+    }
+
+    let summary = json!({
+        "filePath": file_path,
+        "bytes": place.as_ref().map(|p| p.bytes.len()),
+        "format": place.as_ref().map(|p| if p.xml { "rbxlx" } else { "rbxl" }),
+        "universeId": universe_id,
+        "placeId": place_id,
+        "versionType": vt,
+        "versionNotes": file.version_notes,
+    });
+    if file.dry_run {
+        let mut result = summary;
+        result["dryRun"] = json!(true);
+        result["ready"] = json!(problems.is_empty());
+        result["problems"] = json!(problems);
+        return Ok(result);
+    }
+    let (Some(place), Some(api_key), true) = (place, api_key, problems.is_empty()) else {
+        return Err(StudioLinkError::InvalidArguments(problems.join("; ")));
+    };
+
+    let version = opencloud::publish_place(
+        opencloud::DEFAULT_BASE_URL,
+        &api_key,
+        universe_id,
+        place_id,
+        place.bytes,
+        place.xml,
+        &vt,
     )
-    .await
+    .await?;
+
+    let mut result = summary;
+    result["published"] = json!(true);
+    result["versionNumber"] = json!(version);
+    if let Some(log) = default_publish_log_path() {
+        let entry = json!({
+            "at_unix_ms": std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            "universeId": universe_id,
+            "placeId": place_id,
+            "versionNumber": version,
+            "versionType": vt,
+            "versionNotes": result["versionNotes"],
+            "filePath": file_path,
+        });
+        match append_log(&log, &entry) {
+            Ok(()) => result["publishLog"] = json!(log.display().to_string()),
+            Err(e) => {
+                tracing::warn!("Could not record publish in {}: {}", log.display(), e);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// A place file read and checked for upload.
+struct PlaceFile {
+    bytes: Vec<u8>,
+    xml: bool,
+}
+
+/// Read a .rbxl or .rbxlx, checking its size and that its contents match
+/// the extension.
+fn read_place_file(path: &Path) -> Result<PlaceFile> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let xml = match extension.as_deref() {
+        Some("rbxl") => false,
+        Some("rbxlx") => true,
+        _ => {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "{} is not a .rbxl or .rbxlx place file",
+                path.display()
+            )))
+        }
+    };
+    let size = std::fs::metadata(path)
+        .map_err(|e| {
+            StudioLinkError::InvalidArguments(format!("can't read {}: {}", path.display(), e))
+        })?
+        .len();
+    if size > MAX_PLACE_BYTES {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "{} is {} MB; Open Cloud accepts place files up to {} MB",
+            path.display(),
+            size / (1024 * 1024),
+            MAX_PLACE_BYTES / (1024 * 1024)
+        )));
+    }
+    let bytes = std::fs::read(path)?;
+    let valid = if xml {
+        // XML places may start with a BOM or an <?xml ?> declaration
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]).into_owned();
+        head.contains("<roblox")
+    } else {
+        bytes.starts_with(b"<roblox!")
+    };
+    if !valid {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "{} doesn't look like a {} place file",
+            path.display(),
+            if xml { "XML" } else { "binary" }
+        )));
+    }
+    Ok(PlaceFile { bytes, xml })
+}
+
+/// Default publish log: ~/.studiolink/publish_log.jsonl
+fn default_publish_log_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| {
+            PathBuf::from(home)
+                .join(".studiolink")
+                .join("publish_log.jsonl")
+        })
+}
+
+fn append_log(path: &Path, entry: &serde_json::Value) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", entry)
 }
 
 #[cfg(test)]
@@ -66,7 +262,7 @@ mod tests {
     #[tokio::test]
     async fn rejects_invalid_version_type() {
         let state = make_state();
-        let err = publish_place(&state, Some("Draft".to_string()))
+        let err = publish_place(&state, Some("Draft".to_string()), FilePublish::default())
             .await
             .unwrap_err();
         assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
@@ -78,7 +274,7 @@ mod tests {
         // registered. Confirms the version_type gate accepts both valid values.
         let state = make_state();
         for vt in ["Saved", "Published"] {
-            let err = publish_place(&state, Some(vt.to_string()))
+            let err = publish_place(&state, Some(vt.to_string()), FilePublish::default())
                 .await
                 .unwrap_err();
             assert!(
@@ -89,4 +285,48 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn dry_run_checks_the_place_file() {
+        let state = make_state();
+        let dir = std::env::temp_dir().join(format!("studiolink-publish-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("Game.rbxl");
+        std::fs::write(&good, b"<roblox!\x89\xff\r\n").unwrap();
+        let fake = dir.join("Fake.rbxl");
+        std::fs::write(&fake, b"<roblox xmlns=").unwrap();
+
+        let dry_run = |path: &Path| FilePublish {
+            file_path: Some(path.display().to_string()),
+            universe_id: Some(7),
+            place_id: Some(99),
+            version_notes: Some("Lobby rework".into()),
+            dry_run: true,
+        };
+        let result = publish_place(&state, None, dry_run(&good)).await.unwrap();
+        assert_eq!(result["format"], "rbxl");
+        assert_eq!(result["bytes"], 12);
+        assert_eq!(result["versionNotes"], "Lobby rework");
+        // Only the API key can be missing, depending on the environment
+        assert!(result["problems"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|p| p.as_str().unwrap().contains(opencloud::API_KEY_ENV)));
+
+        let result = publish_place(&state, None, dry_run(&fake)).await.unwrap();
+        assert_eq!(result["ready"], false);
+        assert!(result["problems"][0]
+            .as_str()
+            .unwrap()
+            .contains("doesn't look like a binary place file"));
+
+        let stray = FilePublish {
+            dry_run: true,
+            ..FilePublish::default()
+        };
+        let err = publish_place(&state, None, stray).await.unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
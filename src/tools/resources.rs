@@ -0,0 +1,406 @@
+//! The place as MCP resources: every script is `roblox://script/<path>` and
+//! every instance `roblox://instance/<path>`, with `<path>` the usual dotted
+//! path (percent-encoded where a name needs it). Listing walks get_file_tree;
+//! reading a script returns its raw source, reading an instance its children
+//! one level deep. Subscriptions are driven by the change log.
+
+use rmcp::model::{Annotated, RawResource, RawResourceTemplate, Resource, ResourceTemplate};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::error::{Result, StudioLinkError};
+use crate::state::{AppState, ChangeEntry};
+
+pub const SCRIPT_PREFIX: &str = "roblox://script/";
+pub const INSTANCE_PREFIX: &str = "roblox://instance/";
+
+const LUAU_MIME: &str = "text/x-luau";
+const JSON_MIME: &str = "application/json";
+
+/// Resources returned per resources/list page.
+pub const RESOURCE_PAGE_SIZE: usize = 500;
+
+/// How deep resources/list looks for scripts.
+const LIST_DEPTH: u32 = 12;
+
+const SCRIPT_CLASSES: [&str; 3] = ["Script", "LocalScript", "ModuleScript"];
+
+/// A resource URI, parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PlaceResource {
+    Script(String),
+    Instance(String),
+}
+
+impl PlaceResource {
+    pub fn parse(uri: &str) -> Result<Self> {
+        let (path, make): (&str, fn(String) -> Self) =
+            if let Some(path) = uri.strip_prefix(SCRIPT_PREFIX) {
+                (path, Self::Script)
+            } else if let Some(path) = uri.strip_prefix(INSTANCE_PREFIX) {
+                (path, Self::Instance)
+            } else {
+                return Err(StudioLinkError::InvalidArguments(format!(
+                    "unknown resource URI {} (expected {}<path> or {}<path>)",
+                    uri, SCRIPT_PREFIX, INSTANCE_PREFIX
+                )));
+            };
+        let path = percent_decode(path).ok_or_else(|| {
+            StudioLinkError::InvalidArguments(format!("malformed resource URI {}", uri))
+        })?;
+        if path.is_empty() {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "resource URI {} names no instance",
+                uri
+            )));
+        }
+        Ok(make(path))
+    }
+
+    pub fn uri(&self) -> String {
+        match self {
+            Self::Script(path) => format!("{}{}", SCRIPT_PREFIX, percent_encode(path)),
+            Self::Instance(path) => format!("{}{}", INSTANCE_PREFIX, percent_encode(path)),
+        }
+    }
+
+    /// Whether a committed change may have altered what reading this
+    /// resource returns. Changes without a path (run_code, undo) touch
+    /// everything; changing or deleting an ancestor may move or remove the
+    /// resource itself. A "created" change affects its parent's listing and
+    /// a resource naming the new instance.
+    pub fn affected_by(&self, change: &ChangeEntry) -> bool {
+        let (path, lists_children) = match self {
+            Self::Script(path) => (path.as_str(), false),
+            // An instance resource lists its children, so changes to a
+            // direct child show up in it too
+            Self::Instance(path) => (path.as_str(), true),
+        };
+        if change.kind == "created" {
+            let parent = change.parent.as_deref().or_else(|| {
+                let created = change.path.as_deref()?;
+                created.rsplit_once('.').map(|(parent, _)| parent)
+            });
+            let Some(parent) = parent else {
+                return true;
+            };
+            return change.path.as_deref() == Some(path) || (lists_children && parent == path);
+        }
+        let Some(changed) = change.path.as_deref() else {
+            return true;
+        };
+        path == changed
+            || under(path, changed).is_some()
+            || (lists_children && under(changed, path).is_some_and(|child| !child.contains('.')))
+    }
+}
+
+/// What follows `outer.` in `inner`, if `inner` lies under `outer`.
+fn under<'a>(inner: &'a str, outer: &str) -> Option<&'a str> {
+    inner
+        .strip_prefix(outer)
+        .and_then(|rest| rest.strip_prefix('.'))
+}
+
+/// Whether a change can add or remove resources, so clients should list
+/// again.
+pub fn changes_list(change: &ChangeEntry) -> bool {
+    matches!(change.kind.as_str(), "created" | "deleted" | "unknown")
+}
+
+/// The resources one MCP connection subscribed to, and the task watching the
+/// change log on its behalf while there are any.
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    pub resources: HashSet<PlaceResource>,
+    pub watcher: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Subscriptions {
+    /// URIs of the subscribed resources `changes` touched, sorted. With
+    /// `missed`, some changes were evicted unseen, so all of them.
+    pub fn updated(&self, changes: &[ChangeEntry], missed: bool) -> Vec<String> {
+        let mut uris: Vec<String> = self
+            .resources
+            .iter()
+            .filter(|r| missed || changes.iter().any(|c| r.affected_by(c)))
+            .map(|r| r.uri())
+            .collect();
+        uris.sort();
+        uris
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        if let Some(watcher) = self.watcher.take() {
+            watcher.abort();
+        }
+    }
+}
+
+/// The URI templates clients can fill in for paths resources/list didn't
+/// reach.
+pub fn templates() -> Vec<ResourceTemplate> {
+    let template = |prefix: &str, name: &str, description: &str, mime: &str| {
+        Annotated::new(
+            RawResourceTemplate {
+                uri_template: format!("{}{{path}}", prefix),
+                name: name.into(),
+                title: None,
+                description: Some(description.into()),
+                mime_type: Some(mime.into()),
+                icons: None,
+            },
+            None,
+        )
+    };
+    vec![
+        template(
+            SCRIPT_PREFIX,
+            "script",
+            "Source of the Script, LocalScript or ModuleScript at a dotted path (e.g. ServerScriptService.Main)",
+            LUAU_MIME,
+        ),
+        template(
+            INSTANCE_PREFIX,
+            "instance",
+            "An instance and its direct children, as JSON, at a dotted path (e.g. Workspace.Map)",
+            JSON_MIME,
+        ),
+    ]
+}
+
+/// Every service as an instance resource, then every script found within
+/// LIST_DEPTH levels, in tree order.
+pub async fn list(state: &Arc<Mutex<AppState>>) -> Result<Vec<Resource>> {
+    let tree =
+        super::instance::get_file_tree(state, None, Some(LIST_DEPTH), None, None, None).await?;
+    Ok(resources_in_tree(&tree))
+}
+
+fn resources_in_tree(tree: &serde_json::Value) -> Vec<Resource> {
+    let services = tree["services"].as_array().cloned().unwrap_or_default();
+    let mut resources: Vec<Resource> = services
+        .iter()
+        .filter_map(|service| service["name"].as_str())
+        .map(|name| {
+            let mut raw = RawResource::new(PlaceResource::Instance(name.into()).uri(), name);
+            raw.mime_type = Some(JSON_MIME.into());
+            Annotated::new(raw, None)
+        })
+        .collect();
+
+    let mut stack: Vec<(String, &serde_json::Value)> = services
+        .iter()
+        .rev()
+        .filter_map(|s| Some((s["name"].as_str()?.to_string(), s)))
+        .collect();
+    while let Some((path, node)) = stack.pop() {
+        if node["ref"].as_bool() == Some(true) {
+            continue;
+        }
+        let class = node["className"].as_str().unwrap_or_default();
+        if SCRIPT_CLASSES.contains(&class) {
+            let mut raw = RawResource::new(PlaceResource::Script(path.clone()).uri(), &path);
+            raw.description = Some(class.into());
+            raw.mime_type = Some(LUAU_MIME.into());
+            resources.push(Annotated::new(raw, None));
+        }
+        if let Some(children) = node["children"].as_array() {
+            for child in children.iter().rev() {
+                if let Some(name) = child["name"].as_str() {
+                    stack.push((format!("{}.{}", path, name), child));
+                }
+            }
+        }
+    }
+    resources
+}
+
+/// What a resource reads as: its text and MIME type.
+pub async fn read(
+    state: &Arc<Mutex<AppState>>,
+    resource: &PlaceResource,
+) -> Result<(String, &'static str)> {
+    match resource {
+        PlaceResource::Script(path) => {
+            let result = super::scripts::get_script_source(state, path, None, None, None).await?;
+            let numbered = result["source"].as_str().unwrap_or_default();
+            Ok((strip_line_numbers(numbered), LUAU_MIME))
+        }
+        PlaceResource::Instance(path) => {
+            let tree = super::instance::get_file_tree(
+                state,
+                Some(path),
+                Some(1),
+                Some(true),
+                Some(true),
+                None,
+            )
+            .await?;
+            Ok((serde_json::to_string_pretty(&tree)?, JSON_MIME))
+        }
+    }
+}
+
+/// Undo the `%4d | ` numbering get_script_source puts on every line.
+pub(super) fn strip_line_numbers(numbered: &str) -> String {
+    numbered
+        .split('\n')
+        .map(|line| match line.split_once(" | ") {
+            Some((number, code)) if number.trim().parse::<u32>().is_ok() => code,
+            _ => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn percent_encode(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+fn percent_decode(encoded: &str) -> Option<String> {
+    let bytes = encoded.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn change(kind: &str, path: Option<&str>) -> ChangeEntry {
+        ChangeEntry {
+            seq: 1,
+            at_unix_ms: 0,
+            tool: "test".into(),
+            path: path.map(|p| p.to_string()),
+            parent: None,
+            kind: kind.into(),
+        }
+    }
+
+    #[test]
+    fn uris_round_trip_and_changes_match() {
+        let script = PlaceResource::Script("ServerScriptService.Main Loop".into());
+        assert_eq!(
+            script.uri(),
+            "roblox://script/ServerScriptService.Main%20Loop"
+        );
+        assert_eq!(PlaceResource::parse(&script.uri()).unwrap(), script);
+        assert!(PlaceResource::parse("roblox://script/").is_err());
+        assert!(PlaceResource::parse("file:///etc/passwd").is_err());
+        assert!(PlaceResource::parse("roblox://instance/Bad%2").is_err());
+
+        let main = PlaceResource::Script("ServerScriptService.Main".into());
+        assert!(main.affected_by(&change("source", Some("ServerScriptService.Main"))));
+        assert!(main.affected_by(&change("deleted", Some("ServerScriptService"))));
+        assert!(main.affected_by(&change("unknown", None)));
+        assert!(!main.affected_by(&change("source", Some("ServerScriptService.MainOld"))));
+
+        let map = PlaceResource::Instance("Workspace.Map".into());
+        assert!(map.affected_by(&change("modified", Some("Workspace.Map.Spawn"))));
+        assert!(!map.affected_by(&change("modified", Some("Workspace.Map.Spawn.Decal"))));
+    }
+
+    /// Created changes as the plugin's response leaves them: `path` is the
+    /// new instance, `parent` where it went.
+    fn settled_creation(args: serde_json::Value, full_name: Option<&str>) -> ChangeEntry {
+        let mut log = crate::state::ChangeLog::default();
+        log.expect("1", "a", "create_instance", &args);
+        let mut result = json!({ "created": true });
+        if let Some(full_name) = full_name {
+            result["fullName"] = json!(full_name);
+        }
+        log.settle(&crate::state::PluginResponse {
+            id: "1".into(),
+            success: true,
+            result,
+            error: None,
+        });
+        log.since("a", 0).0.remove(0)
+    }
+
+    #[test]
+    fn creations_affect_the_parent_and_the_new_instance() {
+        let spawn = settled_creation(
+            json!({ "className": "SpawnLocation", "parentPath": "Workspace.Map" }),
+            Some("Workspace.Map.Spawn"),
+        );
+        assert_eq!(spawn.path.as_deref(), Some("Workspace.Map.Spawn"));
+        let map = PlaceResource::Instance("Workspace.Map".into());
+        assert!(map.affected_by(&spawn));
+        assert!(!PlaceResource::Instance("Workspace".into()).affected_by(&spawn));
+        assert!(!PlaceResource::Instance("Workspace.Map.Spawn.Decal".into()).affected_by(&spawn));
+        assert!(PlaceResource::Instance("Workspace.Map.Spawn".into()).affected_by(&spawn));
+
+        let script = settled_creation(
+            json!({ "className": "Script", "parentPath": "ServerScriptService" }),
+            Some("ServerScriptService.Main"),
+        );
+        assert!(PlaceResource::Script("ServerScriptService.Main".into()).affected_by(&script));
+        assert!(!PlaceResource::Script("ServerScriptService.Other".into()).affected_by(&script));
+
+        // Without fullName the path stays the parent
+        let unnamed = settled_creation(
+            json!({ "className": "Part", "parentPath": "Workspace.Map" }),
+            None,
+        );
+        assert!(map.affected_by(&unnamed));
+        assert!(!PlaceResource::Instance("Workspace".into()).affected_by(&unnamed));
+    }
+
+    #[test]
+    fn listing_walks_services_for_scripts() {
+        let tree = json!({"services": [
+            {"name": "Workspace", "className": "Workspace", "children": [
+                {"name": "Door", "className": "Model", "children": [
+                    {"name": "Open", "className": "Script"}
+                ]}
+            ]},
+            {"name": "ReplicatedStorage", "className": "ReplicatedStorage", "children": [
+                {"name": "Util", "className": "ModuleScript", "debugId": "1"},
+                {"name": "Util", "className": "ModuleScript", "debugId": "1", "ref": true}
+            ]}
+        ]});
+        let uris: Vec<String> = resources_in_tree(&tree)
+            .into_iter()
+            .map(|r| r.raw.uri)
+            .collect();
+        assert_eq!(
+            uris,
+            [
+                "roblox://instance/Workspace",
+                "roblox://instance/ReplicatedStorage",
+                "roblox://script/Workspace.Door.Open",
+                "roblox://script/ReplicatedStorage.Util",
+            ]
+        );
+        assert_eq!(
+            strip_line_numbers("   1 | local a = 1\n   2 | return a | b"),
+            "local a = 1\nreturn a | b"
+        );
+    }
+}
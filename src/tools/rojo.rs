@@ -0,0 +1,312 @@
+//! Stored snapshot → Rojo project on disk, for teams moving Studio state into
+//! filesystem-based source control.
+//!
+//! Scripts become `.server.lua`, `.client.lua` and `.lua` files (`init.*`
+//! inside a directory when the script has children), Folders become
+//! directories, and any other instance becomes a `.model.json` file, or a
+//! directory with `init.meta.json` when it has children. The snapshot only
+//! carries the properties snapshot_take records (BasePart geometry and
+//! appearance, attributes, tags), so other properties keep Rojo's defaults.
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use crate::snapshots::SnapshotExport;
+
+/// What writing a project produced.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RojoStats {
+    pub scripts: usize,
+    pub models: usize,
+    pub directories: usize,
+    /// Instances whose name had to change to be a valid file name, as
+    /// original path → written name
+    pub renamed: BTreeMap<String, String>,
+    /// Attributes left out because Rojo can't express their type from the
+    /// snapshot's JSON
+    pub skipped_attributes: usize,
+}
+
+/// Write `export` as a Rojo project into `dir`, which must not exist or be
+/// empty: a `default.project.json` mapping each service to `src/<Service>`.
+pub fn write_project(export: &SnapshotExport, dir: &Path) -> io::Result<RojoStats> {
+    if dir.exists() && std::fs::read_dir(dir)?.next().is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is not empty", dir.display()),
+        ));
+    }
+
+    let mut children: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for path in export.nodes.keys() {
+        let parent = path.rfind('/').map_or("", |i| &path[..i]);
+        children.entry(parent).or_default().push(path);
+    }
+
+    let mut stats = RojoStats::default();
+    let mut tree = Map::new();
+    tree.insert("$className".into(), json!("DataModel"));
+    let src = dir.join("src");
+    for service in children.get("").cloned().unwrap_or_default() {
+        let name = file_name(service, &mut stats);
+        write_instance(export, &children, service, &src, &name, true, &mut stats)?;
+        tree.insert(
+            node_name(service).to_string(),
+            json!({
+                "$className": export.nodes[service]["ClassName"].as_str().unwrap_or(node_name(service)),
+                "$path": format!("src/{}", name),
+            }),
+        );
+    }
+
+    let project = json!({ "name": export.name, "tree": tree });
+    write_json(&dir.join("default.project.json"), &project)?;
+    Ok(stats)
+}
+
+fn write_instance(
+    export: &SnapshotExport,
+    children: &BTreeMap<&str, Vec<&str>>,
+    path: &str,
+    parent_dir: &Path,
+    name: &str,
+    is_service: bool,
+    stats: &mut RojoStats,
+) -> io::Result<()> {
+    let body = &export.nodes[path];
+    let class = body["ClassName"].as_str().unwrap_or("Folder");
+    let kids = children.get(path).map(Vec::as_slice).unwrap_or_default();
+    let script_suffix = match class {
+        "Script" => Some(".server.lua"),
+        "LocalScript" => Some(".client.lua"),
+        "ModuleScript" => Some(".lua"),
+        _ => None,
+    };
+    let meta = meta_fields(body, stats);
+
+    // Services and Folders are directories whatever they hold; their class
+    // comes from the project file or the directory itself
+    if kids.is_empty() && !is_service && class != "Folder" {
+        match script_suffix {
+            Some(suffix) => {
+                std::fs::create_dir_all(parent_dir)?;
+                let source = body["Source"].as_str().unwrap_or_default();
+                std::fs::write(parent_dir.join(format!("{}{}", name, suffix)), source)?;
+                if !meta.is_empty() {
+                    // Foo.server.lua → Foo.server.meta.json
+                    let stem = suffix.trim_end_matches(".lua");
+                    let meta_path = parent_dir.join(format!("{}{}.meta.json", name, stem));
+                    write_json(&meta_path, &Value::Object(meta))?;
+                }
+                stats.scripts += 1;
+            }
+            None => {
+                let mut model = Map::new();
+                model.insert("className".into(), json!(class));
+                model.extend(meta);
+                write_json(
+                    &parent_dir.join(format!("{}.model.json", name)),
+                    &Value::Object(model),
+                )?;
+                stats.models += 1;
+            }
+        }
+        return Ok(());
+    }
+
+    let own_dir = parent_dir.join(name);
+    std::fs::create_dir_all(&own_dir)?;
+    stats.directories += 1;
+    match script_suffix {
+        Some(suffix) => {
+            let source = body["Source"].as_str().unwrap_or_default();
+            std::fs::write(own_dir.join(format!("init{}", suffix)), source)?;
+            stats.scripts += 1;
+            if !meta.is_empty() {
+                write_json(&own_dir.join("init.meta.json"), &Value::Object(meta))?;
+            }
+        }
+        None if class != "Folder" && !is_service => {
+            let mut init = Map::new();
+            init.insert("className".into(), json!(class));
+            init.extend(meta);
+            write_json(&own_dir.join("init.meta.json"), &Value::Object(init))?;
+        }
+        None if !meta.is_empty() => {
+            write_json(&own_dir.join("init.meta.json"), &Value::Object(meta))?;
+        }
+        None => {}
+    }
+
+    let mut used = std::collections::HashSet::new();
+    for child in kids {
+        let mut child_name = file_name(child, stats);
+        // Two children whose names sanitize alike would overwrite each other
+        while !used.insert(child_name.to_ascii_lowercase()) {
+            child_name.push('_');
+            stats.renamed.insert(child.to_string(), child_name.clone());
+        }
+        write_instance(export, children, child, &own_dir, &child_name, false, stats)?;
+    }
+    Ok(())
+}
+
+/// `properties` and `attributes` for a model or meta file.
+fn meta_fields(body: &Value, stats: &mut RojoStats) -> Map<String, Value> {
+    let mut properties = Map::new();
+    for key in ["Position", "Size"] {
+        if let Some(v) = body[key].as_str().and_then(|s| numbers(s, 3)) {
+            properties.insert(key.into(), json!(v));
+        }
+    }
+    if let Some(v) = body["Color"].as_str().and_then(|s| numbers(s, 3)) {
+        properties.insert("Color".into(), json!(v));
+    }
+    if let Some(material) = body["Material"].as_str() {
+        let material = material.rsplit('.').next().unwrap_or(material);
+        properties.insert("Material".into(), json!(material));
+    }
+    for key in ["Transparency", "Anchored"] {
+        if !body[key].is_null() {
+            properties.insert(key.into(), body[key].clone());
+        }
+    }
+    if let Some(tags) = body["Tags"].as_array().filter(|t| !t.is_empty()) {
+        properties.insert("Tags".into(), json!({ "Tags": tags }));
+    }
+
+    let mut attributes = Map::new();
+    if let Some(attrs) = body["Attributes"].as_object() {
+        for (key, value) in attrs {
+            match value {
+                Value::String(s) => {
+                    attributes.insert(key.clone(), json!({ "String": s }));
+                }
+                Value::Number(n) => {
+                    attributes.insert(key.clone(), json!({ "Float64": n }));
+                }
+                Value::Bool(b) => {
+                    attributes.insert(key.clone(), json!({ "Bool": b }));
+                }
+                _ => stats.skipped_attributes += 1,
+            }
+        }
+    }
+
+    let mut fields = Map::new();
+    if !properties.is_empty() {
+        fields.insert("properties".into(), Value::Object(properties));
+    }
+    if !attributes.is_empty() {
+        fields.insert("attributes".into(), Value::Object(attributes));
+    }
+    fields
+}
+
+/// Parse a `tostring`ed Vector3 or Color3 ("1, 2.5, -3") into its components.
+fn numbers(text: &str, count: usize) -> Option<Vec<f64>> {
+    let parts: Vec<f64> = text
+        .split(',')
+        .map(|p| p.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    (parts.len() == count).then_some(parts)
+}
+
+fn node_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// The instance's name as a file name, recording it in `stats` if it had to
+/// change.
+fn file_name(path: &str, stats: &mut RojoStats) -> String {
+    let name = node_name(path);
+    let safe = safe_file_name(name);
+    if safe != name {
+        stats.renamed.insert(path.to_string(), safe.clone());
+    }
+    safe
+}
+
+/// An instance name with the characters Windows and Unix refuse in file
+/// names replaced by `_`.
+pub(crate) fn safe_file_name(name: &str) -> String {
+    let mut safe: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if safe.trim().is_empty() || safe.ends_with(['.', ' ']) {
+        safe.push('_');
+    }
+    safe
+}
+
+fn write_json(path: &Path, value: &Value) -> io::Result<()> {
+    let mut text = serde_json::to_string_pretty(value)?;
+    text.push('\n');
+    std::fs::write(path, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_becomes_rojo_tree() {
+        let nodes = json!({
+            "/ServerScriptService": { "ClassName": "ServerScriptService" },
+            "/ServerScriptService/Main": { "ClassName": "Script", "Source": "print('hi')" },
+            "/ReplicatedStorage": { "ClassName": "ReplicatedStorage" },
+            "/ReplicatedStorage/Util": { "ClassName": "ModuleScript", "Source": "return {}" },
+            "/ReplicatedStorage/Util/Helper": { "ClassName": "ModuleScript", "Source": "return 1" },
+            "/Workspace": { "ClassName": "Workspace" },
+            "/Workspace/Door: Red": {
+                "ClassName": "Part", "Position": "0, 5, -2", "Size": "4, 1, 2",
+                "Material": "Enum.Material.Wood", "Anchored": true,
+                "Attributes": { "Locked": true, "Spawn": { "X": 1 } }, "Tags": ["Door"],
+            },
+        });
+        let export = SnapshotExport {
+            format: "studiolink-snapshot".into(),
+            version: 1,
+            name: "place".into(),
+            timestamp: 0,
+            place_id: None,
+            scope: "game".into(),
+            nodes: nodes.as_object().unwrap().clone(),
+        };
+        let dir = std::env::temp_dir().join(format!("studiolink-rojo-{}", uuid::Uuid::new_v4()));
+        let stats = write_project(&export, &dir).unwrap();
+        assert_eq!(stats.scripts, 3);
+        assert_eq!(stats.models, 1);
+        assert_eq!(stats.skipped_attributes, 1);
+        assert_eq!(stats.renamed["/Workspace/Door: Red"], "Door_ Red");
+
+        let read = |p: &str| std::fs::read_to_string(dir.join(p)).unwrap();
+        let project: Value = serde_json::from_str(&read("default.project.json")).unwrap();
+        assert_eq!(project["tree"]["Workspace"]["$path"], "src/Workspace");
+        assert_eq!(project["tree"]["Workspace"]["$className"], "Workspace");
+        assert_eq!(
+            read("src/ServerScriptService/Main.server.lua"),
+            "print('hi')"
+        );
+        assert_eq!(read("src/ReplicatedStorage/Util/init.lua"), "return {}");
+        assert_eq!(read("src/ReplicatedStorage/Util/Helper.lua"), "return 1");
+        let door: Value =
+            serde_json::from_str(&read("src/Workspace/Door_ Red.model.json")).unwrap();
+        assert_eq!(door["className"], "Part");
+        assert_eq!(door["properties"]["Position"], json!([0.0, 5.0, -2.0]));
+        assert_eq!(door["properties"]["Material"], "Wood");
+        assert_eq!(door["attributes"]["Locked"], json!({ "Bool": true }));
+
+        assert!(write_project(&export, &dir).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -2,23 +2,100 @@ use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use super::highlight::{render_numbered_source, Render};
+use super::resources::strip_line_numbers;
+use super::textdiff::unified_diff;
 use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
-use crate::error::Result;
+use crate::error::{Result, StudioLinkError};
 use crate::state::AppState;
 
+/// Largest source get_script_source returns in one call (512 KiB). Bigger
+/// scripts have to be read in line ranges.
+const MAX_SCRIPT_SOURCE_BYTES: u64 = 512 * 1024;
+
+/// Diff lines diff_script_source returns before the rest is cut off.
+const MAX_DIFF_LINES: usize = 1000;
+
 /// Tool 44: get_script_source — Get script source with line numbers
+///
+/// `start_line`/`end_line` (1-based, inclusive) fetch only part of the script.
+/// Sources that aren't valid UTF-8, or whose requested part exceeds
+/// `MAX_SCRIPT_SOURCE_BYTES`, fail with an error instead of a mangled result.
+/// `render` "ansi" or "html" syntax-highlights the numbered source.
 pub async fn get_script_source(
     state: &Arc<Mutex<AppState>>,
     path: &str,
+    start_line: Option<u32>,
+    end_line: Option<u32>,
+    render: Option<&str>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let render = Render::parse(render)?;
+    if let (Some(start), Some(end)) = (start_line, end_line) {
+        if start > end {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "startLine ({}) must not be after endLine ({})",
+                start, end
+            )));
+        }
+    }
+    let result = send_to_plugin(
         state,
         None,
         "get_script_source",
-        json!({ "path": path }),
+        json!({
+            "path": path,
+            "startLine": start_line,
+            "endLine": end_line,
+            "maxBytes": MAX_SCRIPT_SOURCE_BYTES,
+        }),
         DEFAULT_TIMEOUT,
     )
-    .await
+    .await?;
+    let mut result = check_script_source(path, result)?;
+    if render != Render::Plain {
+        if let Some(source) = result["source"].as_str() {
+            result["source"] = json!(render_numbered_source(source, render));
+            result["render"] = json!(if render == Render::Ansi {
+                "ansi"
+            } else {
+                "html"
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Turn the plugin's `nonText`/`tooLarge` markers into errors, and re-check
+/// the returned source in case an older plugin sent it unchecked.
+fn check_script_source(path: &str, result: serde_json::Value) -> Result<serde_json::Value> {
+    if result["nonText"].as_bool() == Some(true) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "non-text content: {} is not valid UTF-8 (first bad byte at {})",
+            path, result["invalidUtf8At"]
+        )));
+    }
+
+    let source = result["source"].as_str().unwrap_or_default();
+    let byte_size = result["byteSize"].as_u64().unwrap_or(source.len() as u64);
+    if result["tooLarge"].as_bool() == Some(true) || byte_size > MAX_SCRIPT_SOURCE_BYTES {
+        let hint = match result["lineCount"].as_u64() {
+            Some(lines) => format!(" ({} lines)", lines),
+            None => String::new(),
+        };
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "too large: {} bytes{} exceeds the {} byte limit; fetch it in parts with startLine/endLine",
+            byte_size, hint, MAX_SCRIPT_SOURCE_BYTES
+        )));
+    }
+
+    // Invalid bytes that slipped through JSON encoding show up as U+FFFD or NUL
+    if source.contains(['\u{FFFD}', '\0']) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "non-text content: {} contains binary data",
+            path
+        )));
+    }
+    Ok(result)
 }
 
 /// Tool 45: set_script_source — Set/replace script source
@@ -26,17 +103,69 @@ pub async fn set_script_source(
     state: &Arc<Mutex<AppState>>,
     path: &str,
     source: &str,
+    format: Option<bool>,
 ) -> Result<serde_json::Value> {
     send_to_plugin(
         state,
         None,
         "set_script_source",
-        json!({ "path": path, "source": source }),
+        json!({ "path": path, "source": source, "format": format.unwrap_or(false) }),
         DEFAULT_TIMEOUT,
     )
     .await
 }
 
+/// diff_script_source — Unified diff from the script's source in Studio to
+/// `source`, without changing anything. Shows what set_script_source or
+/// script_patch would do.
+pub async fn diff_script_source(
+    state: &Arc<Mutex<AppState>>,
+    path: &str,
+    source: &str,
+) -> Result<serde_json::Value> {
+    let current = get_script_source(state, path, None, None, None).await?;
+    let studio = strip_line_numbers(current["source"].as_str().unwrap_or_default());
+    source_diff(path, &studio, source)
+}
+
+fn source_diff(path: &str, studio: &str, candidate: &str) -> Result<serde_json::Value> {
+    let (lines_before, lines_after) = (studio.lines().count(), candidate.lines().count());
+    let diff = unified_diff(
+        studio,
+        candidate,
+        &format!("studio:{}", path),
+        &format!("proposed:{}", path),
+    )
+    .ok_or_else(|| {
+        StudioLinkError::InvalidArguments(format!(
+            "too many changed lines to diff ({} → {} lines); the source is effectively rewritten",
+            lines_before, lines_after
+        ))
+    })?;
+
+    let mut result = json!({
+        "path": path,
+        "changed": !diff.text.is_empty(),
+        "added": diff.added,
+        "removed": diff.removed,
+        "linesBefore": lines_before,
+        "linesAfter": lines_after,
+    });
+    let total_lines = diff.text.lines().count();
+    if total_lines > MAX_DIFF_LINES {
+        let kept: Vec<&str> = diff.text.lines().take(MAX_DIFF_LINES).collect();
+        result["diff"] = json!(format!(
+            "{}\n... {} more diff lines\n",
+            kept.join("\n"),
+            total_lines - MAX_DIFF_LINES
+        ));
+        result["truncated"] = json!(true);
+    } else {
+        result["diff"] = json!(diff.text);
+    }
+    Ok(result)
+}
+
 /// Tool 46: grep_scripts — Search all scripts for a pattern
 pub async fn grep_scripts(
     state: &Arc<Mutex<AppState>>,
@@ -53,18 +182,217 @@ pub async fn grep_scripts(
     .await
 }
 
+fn check_scope(scope: Option<&str>) -> Result<&str> {
+    let scope = scope.unwrap_or("place");
+    if !matches!(scope, "place" | "selection") {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "scope must be \"place\" or \"selection\", got \"{}\"",
+            scope
+        )));
+    }
+    Ok(scope)
+}
+
 /// Tool 47: search_objects — Search instances by name or class
+///
+/// `scope` "selection" searches only descendants of what is selected in
+/// Studio; the plugin resolves the selection at call time.
 pub async fn search_objects(
     state: &Arc<Mutex<AppState>>,
     query: &str,
     search_by: Option<&str>,
+    scope: Option<&str>,
 ) -> Result<serde_json::Value> {
+    let scope = check_scope(scope)?;
     send_to_plugin(
         state,
         None,
         "search_objects",
-        json!({ "query": query, "searchBy": search_by.unwrap_or("name") }),
+        json!({ "query": query, "searchBy": search_by.unwrap_or("name"), "scope": scope }),
         EXTENDED_TIMEOUT,
     )
     .await
 }
+
+/// Comparison operators find_by_property accepts.
+const PROPERTY_OPERATORS: [&str; 6] = ["eq", "ne", "gt", "lt", "gte", "lte"];
+
+/// Check that `operator` (and `tolerance`, if given) make sense for
+/// `value`: ordering operators and tolerances only apply to numbers.
+fn check_property_comparison(
+    value: &serde_json::Value,
+    operator: &str,
+    tolerance: Option<f64>,
+) -> Result<()> {
+    if !PROPERTY_OPERATORS.contains(&operator) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "operator must be one of {}, got \"{}\"",
+            PROPERTY_OPERATORS.join(", "),
+            operator
+        )));
+    }
+    let kind = match value {
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Null => "null",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    };
+    if matches!(kind, "array" | "object" | "null") {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "value must be a number, string or boolean, got {}",
+            kind
+        )));
+    }
+    if !matches!(operator, "eq" | "ne") && kind != "number" {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "operator \"{}\" needs a numeric value, got {} {}",
+            operator, kind, value
+        )));
+    }
+    if let Some(tolerance) = tolerance {
+        if kind != "number" || !matches!(operator, "eq" | "ne") {
+            return Err(StudioLinkError::InvalidArguments(
+                "tolerance only applies to eq/ne with a numeric value".into(),
+            ));
+        }
+        if !tolerance.is_finite() || tolerance < 0.0 {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "tolerance must be a non-negative number, got {}",
+                tolerance
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// find_by_property — Find instances whose property compares to a value
+///
+/// `operator` defaults to "eq". Numbers compare numerically (eq/ne within
+/// `tolerance`, default exact); strings and booleans support eq/ne only,
+/// and other property types (enums, vectors) are matched by their string
+/// form. Each match carries the property's actual value.
+pub async fn find_by_property(
+    state: &Arc<Mutex<AppState>>,
+    property: &str,
+    value: serde_json::Value,
+    operator: Option<&str>,
+    tolerance: Option<f64>,
+    class_name: Option<&str>,
+    scope: Option<&str>,
+) -> Result<serde_json::Value> {
+    if property.is_empty() {
+        return Err(StudioLinkError::InvalidArguments(
+            "property must not be empty".into(),
+        ));
+    }
+    let operator = operator.unwrap_or("eq");
+    check_property_comparison(&value, operator, tolerance)?;
+    let scope = check_scope(scope)?;
+    send_to_plugin(
+        state,
+        None,
+        "find_by_property",
+        json!({
+            "property": property,
+            "value": value,
+            "operator": operator,
+            "tolerance": tolerance,
+            "className": class_name,
+            "scope": scope,
+        }),
+        EXTENDED_TIMEOUT,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_support::forwarded;
+
+    #[test]
+    fn rejects_non_text_and_oversized_sources() {
+        let err =
+            check_script_source("S", json!({ "nonText": true, "invalidUtf8At": 7 })).unwrap_err();
+        assert!(err.to_string().contains("non-text content"));
+
+        let err = check_script_source(
+            "S",
+            json!({ "tooLarge": true, "byteSize": 900_000, "lineCount": 20_000 }),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("too large: 900000 bytes (20000 lines)"));
+        assert!(err.to_string().contains("startLine/endLine"));
+
+        let err = check_script_source("S", json!({ "source": "   1 | a\u{FFFD}b" })).unwrap_err();
+        assert!(err.to_string().contains("binary data"));
+
+        let ok = json!({ "source": "   1 | print(1)", "byteSize": 9 });
+        assert_eq!(check_script_source("S", ok.clone()).unwrap(), ok);
+    }
+
+    #[test]
+    fn diffs_studio_source_against_the_candidate() {
+        let studio = "local a = 1\nlocal b = 2\nreturn a + b\n";
+        let result = source_diff(
+            "ReplicatedStorage.Sum",
+            studio,
+            "local a = 1\nlocal b = 3\nreturn a + b\n",
+        )
+        .unwrap();
+        assert_eq!(result["changed"], true);
+        assert_eq!(
+            (result["added"].clone(), result["removed"].clone()),
+            (json!(1), json!(1))
+        );
+        let diff = result["diff"].as_str().unwrap();
+        assert!(diff
+            .starts_with("--- studio:ReplicatedStorage.Sum\n+++ proposed:ReplicatedStorage.Sum\n"));
+        assert!(diff.contains("-local b = 2\n+local b = 3\n"));
+
+        let same = source_diff("ReplicatedStorage.Sum", studio, studio).unwrap();
+        assert_eq!(same["changed"], false);
+        assert_eq!(same["diff"], "");
+    }
+
+    #[tokio::test]
+    async fn search_scope_must_be_place_or_selection() {
+        let state = AppState::new().0;
+        let err = search_objects(&state, "Door", None, Some("folder"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+
+        let (request, _) = forwarded(
+            |state| async move { search_objects(&state, "Door", None, Some("selection")).await },
+            json!([]),
+        )
+        .await;
+        assert_eq!(request.tool, "search_objects");
+        assert_eq!(request.args["scope"], "selection");
+        assert_eq!(request.args["query"], "Door");
+    }
+
+    #[test]
+    fn property_operators_match_value_type() {
+        assert!(check_property_comparison(&json!(10), "gte", None).is_ok());
+        assert!(check_property_comparison(&json!(0.5), "eq", Some(0.01)).is_ok());
+        assert!(check_property_comparison(&json!("Neon"), "ne", None).is_ok());
+        assert!(check_property_comparison(&json!(true), "eq", None).is_ok());
+
+        let err = check_property_comparison(&json!("abc"), "gt", None).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("needs a numeric value, got string"));
+        assert!(check_property_comparison(&json!(true), "lt", None).is_err());
+        assert!(check_property_comparison(&json!(1), "between", None).is_err());
+        assert!(check_property_comparison(&json!([1, 2]), "eq", None).is_err());
+        assert!(check_property_comparison(&json!("a"), "eq", Some(0.1)).is_err());
+        assert!(check_property_comparison(&json!(1), "gt", Some(0.1)).is_err());
+        assert!(check_property_comparison(&json!(1), "eq", Some(-1.0)).is_err());
+    }
+}
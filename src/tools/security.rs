@@ -1,18 +1,227 @@
 use serde_json::json;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use super::{send_to_plugin, EXTENDED_TIMEOUT};
-use crate::error::Result;
+use crate::error::{Result, StudioLinkError};
 use crate::state::AppState;
 
+/// Finding category for RemoteEvent/RemoteFunction handlers whose call chain
+/// reaches `SetAsync`/`UpdateAsync` without validating client input. These
+/// findings carry `line`, `handlerLocation`, `handlerLine` and `chain`.
+pub const UNVALIDATED_PERSISTENCE: &str = "Unvalidated Persistence";
+
+/// Most custom rules accepted per scan; each runs against every script and remote.
+const MAX_CUSTOM_RULES: usize = 20;
+
+/// Longest custom rule source accepted, in bytes.
+const MAX_CUSTOM_RULE_BYTES: usize = 4096;
+
 /// Tool 21: security_scan — Scan the entire place for security vulnerabilities
-/// Checks: RemoteEvent validation, client trust issues, exposed data, rate limiting
-pub async fn security_scan(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
-    send_to_plugin(state, None, "security_scan", json!({}), EXTENDED_TIMEOUT).await
+/// Checks: RemoteEvent validation, client trust issues, exposed data, rate limiting,
+/// client → DataStore write flows
+///
+/// `custom_rules` are Luau predicates (`function(t) ... end`) the plugin runs
+/// against each script and remote; matches are reported as "Custom Rule"
+/// findings tagged with the 1-based `ruleIndex`. Rules are Luau run by the
+/// plugin, so --read-only refuses them like run_code.
+pub async fn security_scan(
+    state: &Arc<Mutex<AppState>>,
+    custom_rules: Option<Vec<String>>,
+) -> Result<serde_json::Value> {
+    let mut args = json!({});
+    if let Some(rules) = custom_rules {
+        if state.lock().await.read_only {
+            return Err(StudioLinkError::ReadOnly {
+                tool: "security_scan with customRules".into(),
+            });
+        }
+        validate_custom_rules(&rules)?;
+        args["customRules"] = json!(rules);
+    }
+    let mut result = send_to_plugin(state, None, "security_scan", args, EXTENDED_TIMEOUT).await?;
+    if let Some(by_category) = count_by_category(&result) {
+        result["byCategory"] = by_category;
+    }
+    Ok(result)
 }
 
 /// Tool 22: security_report — Get a formatted security report with risk levels
 pub async fn security_report(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
     send_to_plugin(state, None, "security_report", json!({}), EXTENDED_TIMEOUT).await
 }
+
+fn validate_custom_rules(rules: &[String]) -> Result<()> {
+    if rules.is_empty() || rules.len() > MAX_CUSTOM_RULES {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "customRules must contain 1-{} rules, got {}",
+            MAX_CUSTOM_RULES,
+            rules.len()
+        )));
+    }
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.trim().is_empty() {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "customRules[{}] is empty",
+                i + 1
+            )));
+        }
+        if rule.len() > MAX_CUSTOM_RULE_BYTES {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "customRules[{}] is {} bytes (max {})",
+                i + 1,
+                rule.len(),
+                MAX_CUSTOM_RULE_BYTES
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Longest line excerpt a backdoor finding carries.
+const MAX_SNIPPET_CHARS: usize = 160;
+
+/// Patterns free models use to smuggle in code: `require` of an asset ID
+/// loads whatever that asset holds at run time, and `getfenv`/`setfenv` are
+/// how obfuscated loaders reach globals without naming them. Each finding is
+/// `{rule, line, snippet}` with a 1-based line. Comments are scanned too,
+/// since telling them apart from strings takes a real lexer.
+pub(crate) fn backdoor_findings(source: &str) -> Vec<serde_json::Value> {
+    let mut findings = Vec::new();
+    for (start, _) in word_matches(source, "require") {
+        let rest = source[start + "require".len()..].trim_start();
+        let is_asset_id = rest
+            .strip_prefix('(')
+            .is_some_and(|arg| arg.trim_start().starts_with(|c: char| c.is_ascii_digit()));
+        if is_asset_id {
+            findings.push(finding(source, start, "require_by_id"));
+        }
+    }
+    for word in ["getfenv", "setfenv"] {
+        for (start, _) in word_matches(source, word) {
+            findings.push(finding(source, start, word));
+        }
+    }
+    findings.sort_by_key(|f| f["line"].as_u64());
+    findings
+}
+
+/// Byte ranges where `word` appears on its own, not inside a longer name.
+fn word_matches<'a>(source: &'a str, word: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    source.match_indices(word).filter_map(move |(start, _)| {
+        let end = start + word.len();
+        let before = source[..start].chars().next_back();
+        let after = source[end..].chars().next();
+        (!before.is_some_and(is_name) && !after.is_some_and(is_name)).then_some((start, end))
+    })
+}
+
+fn finding(source: &str, at: usize, rule: &str) -> serde_json::Value {
+    let line_start = source[..at].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[at..].find('\n').map_or(source.len(), |i| at + i);
+    let snippet: String = source[line_start..line_end]
+        .trim()
+        .chars()
+        .take(MAX_SNIPPET_CHARS)
+        .collect();
+    json!({
+        "rule": rule,
+        "line": source[..at].matches('\n').count() + 1,
+        "snippet": snippet,
+    })
+}
+
+/// Tally `issues[].category` so callers can see at a glance which finding
+/// types fired. The persistence category is always present (0 when clean) so
+/// its absence can't be mistaken for "check didn't run".
+fn count_by_category(result: &serde_json::Value) -> Option<serde_json::Value> {
+    let issues = result.get("issues")?.as_array()?;
+    let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+    counts.insert(UNVALIDATED_PERSISTENCE, 0);
+    for issue in issues {
+        let category = issue["category"].as_str().unwrap_or("Other");
+        *counts.entry(category).or_default() += 1;
+    }
+    Some(json!(counts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_categories_including_persistence() {
+        let result = json!({
+            "issues": [
+                { "category": "Remote Validation" },
+                { "category": UNVALIDATED_PERSISTENCE, "line": 12 },
+                { "category": UNVALIDATED_PERSISTENCE, "line": 40 },
+            ]
+        });
+        let counts = count_by_category(&result).unwrap();
+        assert_eq!(counts["Remote Validation"], 1);
+        assert_eq!(counts[UNVALIDATED_PERSISTENCE], 2);
+    }
+
+    #[test]
+    fn flags_require_by_id_and_fenv_access() {
+        let source = "local Players = game:GetService(\"Players\")\n\
+                      local util = require(script.Util)\n\
+                      require( 4413789712 ).load()\n\
+                      local env = getfenv(2)\n\
+                      local myrequire = require\n\
+                      local x = mysetfenv_helper";
+        let findings = backdoor_findings(source);
+        let rules: Vec<(&str, u64)> = findings
+            .iter()
+            .map(|f| (f["rule"].as_str().unwrap(), f["line"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(rules, vec![("require_by_id", 3), ("getfenv", 4)]);
+        assert_eq!(findings[0]["snippet"], "require( 4413789712 ).load()");
+        assert_eq!(backdoor_findings("return require(\n  0x1F)").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_only_refuses_custom_rules() {
+        let (state, _) = AppState::new();
+        state.lock().await.read_only = true;
+        let rule = "function(t) return true end".to_string();
+        let err = security_scan(&state, Some(vec![rule])).await.unwrap_err();
+        assert!(matches!(err, StudioLinkError::ReadOnly { .. }));
+        // Without rules the scan still reaches the plugin
+        let (request, _) = crate::tools::test_support::forwarded(
+            |state| async move {
+                state.lock().await.read_only = true;
+                security_scan(&state, None).await
+            },
+            json!({ "issues": [] }),
+        )
+        .await;
+        assert_eq!(request.tool, "security_scan");
+    }
+
+    #[test]
+    fn custom_rules_are_bounded() {
+        let rule = "function(t) return t.kind == 'remote' end".to_string();
+        assert!(validate_custom_rules(std::slice::from_ref(&rule)).is_ok());
+        assert!(validate_custom_rules(&[]).is_err());
+        assert!(validate_custom_rules(&vec![rule.clone(); MAX_CUSTOM_RULES + 1]).is_err());
+
+        let err = validate_custom_rules(&[rule.clone(), "  ".into()]).unwrap_err();
+        assert!(
+            err.to_string().contains("customRules[2] is empty"),
+            "{}",
+            err
+        );
+        let err = validate_custom_rules(&["x".repeat(MAX_CUSTOM_RULE_BYTES + 1)]).unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    #[test]
+    fn persistence_category_reported_when_clean() {
+        let counts = count_by_category(&json!({ "issues": [] })).unwrap();
+        assert_eq!(counts[UNVALIDATED_PERSISTENCE], 0);
+    }
+}
@@ -2,6 +2,7 @@ use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use super::{send_to_plugin, DEFAULT_TIMEOUT};
 use crate::error::{Result, StudioLinkError};
 use crate::state::AppState;
 
@@ -13,11 +14,14 @@ pub async fn list_sessions(state: &Arc<Mutex<AppState>>) -> Result<serde_json::V
     };
 
     if proxy_mode {
-        return proxy_get(&proxy_url, "/sessions").await;
+        // Endpoints in the primary's listing already point at the primary
+        let mut result = proxy_get(state, &proxy_url, "/sessions").await?;
+        result["served_by"] = json!("proxy");
+        return Ok(result);
     }
 
     let s = state.lock().await;
-    let sessions = s.list_sessions();
+    let sessions = s.sessions.list();
     let active = s.get_active_session().map(|s| s.to_string());
 
     let session_list: Vec<serde_json::Value> = sessions
@@ -28,7 +32,9 @@ pub async fn list_sessions(state: &Arc<Mutex<AppState>>) -> Result<serde_json::V
                 "place_id": info.place_id,
                 "place_name": info.place_name,
                 "game_id": info.game_id,
+                "display_name": info.display_name,
                 "is_active": active.as_deref() == Some(&info.session_id),
+                "endpoint": s.session_endpoint(&info.session_id),
             })
         })
         .collect();
@@ -37,6 +43,7 @@ pub async fn list_sessions(state: &Arc<Mutex<AppState>>) -> Result<serde_json::V
         "sessions": session_list,
         "active_session": active,
         "count": session_list.len(),
+        "served_by": "primary",
     }))
 }
 
@@ -53,7 +60,7 @@ pub async fn switch_session(
 
     if proxy_mode {
         // Forward switch_session to primary server
-        let client = reqwest::Client::new();
+        let client = super::proxy_client(state).await;
         let url = format!("{}/switch_session", proxy_url);
         let response = client
             .post(&url)
@@ -67,6 +74,9 @@ pub async fn switch_session(
                     e
                 ))
             })?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(super::proxy_unauthorized(state).await);
+        }
 
         return response.json().await.map_err(|e| {
             crate::error::StudioLinkError::PluginError(format!("Proxy response parse error: {}", e))
@@ -74,9 +84,10 @@ pub async fn switch_session(
     }
 
     let mut s = state.lock().await;
+    let conn = super::connection(&s);
 
-    if s.switch_session(session_id) {
-        let info = s.get_active_session_info().cloned();
+    if s.switch_session(&mut conn.lock(), session_id) {
+        let info = s.get_active_session_info();
         Ok(json!({
             "success": true,
             "message": format!("Switched to session: {}", session_id),
@@ -90,15 +101,85 @@ pub async fn switch_session(
     }
 }
 
+/// switch_back — Return to the session that was active before the last switch
+pub async fn switch_back(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
+    let mut s = state.lock().await;
+    if s.proxy_mode {
+        return Err(StudioLinkError::ServerError(
+            "switch_back is not available in proxy mode (sessions are switched on the primary); use switch_session".into(),
+        ));
+    }
+    let conn = super::connection(&s);
+    let switched = s.switch_back(&mut conn.lock());
+    match switched {
+        Some(session_id) => {
+            let info = s.get_active_session_info();
+            Ok(json!({
+                "success": true,
+                "message": format!("Switched back to session: {}", session_id),
+                "session_id": session_id,
+                "place_name": info.map(|i| i.place_name).unwrap_or_default(),
+            }))
+        }
+        None => Ok(json!({
+            "success": false,
+            "message": "No previous session to switch back to (none recorded, or they have all disconnected).",
+        })),
+    }
+}
+
 /// Tool 36: get_active_session — Get information about the currently active session
-pub async fn get_active_session(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
+///
+/// With `enrich`, also asks the plugin for the place icon asset ID, creator
+/// and universe ID. Fields the plugin couldn't resolve are listed under
+/// `enrichErrors`; a failed enrichment never fails the call itself.
+pub async fn get_active_session(
+    state: &Arc<Mutex<AppState>>,
+    enrich: Option<bool>,
+) -> Result<serde_json::Value> {
+    let mut result = active_session_info(state).await?;
+    let connected = result["connected"].as_bool().unwrap_or(false)
+        || result["plugin_connected"].as_bool().unwrap_or(false);
+    if enrich.unwrap_or(false) && connected {
+        let context =
+            send_to_plugin(state, None, "get_place_context", json!({}), DEFAULT_TIMEOUT).await;
+        merge_place_context(&mut result, context);
+    }
+    Ok(result)
+}
+
+/// Fold the plugin's place context into the session info, keeping only the
+/// fields it actually resolved.
+fn merge_place_context(result: &mut serde_json::Value, context: Result<serde_json::Value>) {
+    match context {
+        Ok(ctx) => {
+            for field in ["iconAssetId", "creator", "universeId"] {
+                if let Some(v) = ctx.get(field).filter(|v| !v.is_null()) {
+                    result[field] = v.clone();
+                }
+            }
+            if let Some(errors) = ctx.get("errors").filter(|e| !e.is_null()) {
+                result["enrichErrors"] = errors.clone();
+            }
+        }
+        Err(e) => {
+            result["enrichErrors"] = json!({
+                "iconAssetId": e.to_string(),
+                "creator": e.to_string(),
+                "universeId": e.to_string(),
+            });
+        }
+    }
+}
+
+async fn active_session_info(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
     let (proxy_mode, proxy_url) = {
         let s = state.lock().await;
         (s.proxy_mode, s.proxy_url.clone())
     };
 
     if proxy_mode {
-        return proxy_get(&proxy_url, "/health").await;
+        return proxy_get(state, &proxy_url, "/health").await;
     }
 
     let s = state.lock().await;
@@ -110,6 +191,7 @@ pub async fn get_active_session(state: &Arc<Mutex<AppState>>) -> Result<serde_js
             "place_id": info.place_id,
             "place_name": info.place_name,
             "game_id": info.game_id,
+            "display_name": info.display_name,
         })),
         None => Ok(json!({
             "connected": false,
@@ -118,20 +200,851 @@ pub async fn get_active_session(state: &Arc<Mutex<AppState>>) -> Result<serde_js
     }
 }
 
+/// rename_session — Give a session a display name (e.g. "Combat Test") that
+/// shows in list_sessions/get_active_session. Persisted by client_id, so the
+/// name comes back when the same Studio window reconnects.
+pub async fn rename_session(
+    state: &Arc<Mutex<AppState>>,
+    session_id: &str,
+    display_name: &str,
+) -> Result<serde_json::Value> {
+    let display_name = display_name.trim();
+    if display_name.is_empty() {
+        return Err(StudioLinkError::InvalidArguments(
+            "displayName must be a non-empty string".into(),
+        ));
+    }
+    if display_name.chars().count() > 64 {
+        return Err(StudioLinkError::InvalidArguments(
+            "displayName must be at most 64 characters".into(),
+        ));
+    }
+
+    let (proxy_mode, proxy_url) = {
+        let s = state.lock().await;
+        (s.proxy_mode, s.proxy_url.clone())
+    };
+
+    if proxy_mode {
+        let client = super::proxy_client(state).await;
+        let response = client
+            .post(format!("{}/rename_session", proxy_url))
+            .json(&json!({ "session_id": session_id, "display_name": display_name }))
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await
+            .map_err(|e| {
+                StudioLinkError::PluginError(format!("Proxy rename_session failed: {}", e))
+            })?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(super::proxy_unauthorized(state).await);
+        }
+        return response.json().await.map_err(|e| {
+            StudioLinkError::PluginError(format!("Proxy response parse error: {}", e))
+        });
+    }
+
+    let mut s = state.lock().await;
+    if !s.rename_session(session_id, display_name) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "session_id '{}' not found. Use list_sessions to see active sessions.",
+            session_id
+        )));
+    }
+    let persisted = s
+        .sessions
+        .with(session_id, |session| session.info.client_id.is_some())
+        .unwrap_or(false);
+    Ok(json!({
+        "success": true,
+        "session_id": session_id,
+        "display_name": display_name,
+        "persisted": persisted,
+    }))
+}
+
+/// create_session_group — Name a set of sessions (e.g. the places of one
+/// multi-place experience) so broadcast_to_group can target them together.
+/// Every member must currently be connected. Re-using a name replaces the group.
+pub async fn create_session_group(
+    state: &Arc<Mutex<AppState>>,
+    name: &str,
+    session_ids: &[String],
+) -> Result<serde_json::Value> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(StudioLinkError::InvalidArguments(
+            "name must be a non-empty string".into(),
+        ));
+    }
+    let mut members: Vec<String> = Vec::new();
+    for id in session_ids {
+        if !members.contains(id) {
+            members.push(id.clone());
+        }
+    }
+    if members.is_empty() {
+        return Err(StudioLinkError::InvalidArguments(
+            "sessionIds must contain at least one session".into(),
+        ));
+    }
+
+    let known = connected_session_ids(state).await?;
+    let missing: Vec<&String> = members.iter().filter(|id| !known.contains(id)).collect();
+    if !missing.is_empty() {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "unknown session(s): {}. Use list_sessions to see connected sessions.",
+            missing
+                .iter()
+                .map(|id| id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+
+    let mut s = state.lock().await;
+    let replaced = s
+        .session_groups
+        .insert(name.to_string(), members.clone())
+        .is_some();
+    Ok(json!({
+        "success": true,
+        "group": name,
+        "session_ids": members,
+        "replaced": replaced,
+    }))
+}
+
+/// broadcast_to_group — Run one plugin tool on every session in a group
+/// concurrently. Per-session failures are reported in `results` rather than
+/// failing the whole call.
+pub async fn broadcast_to_group(
+    state: &Arc<Mutex<AppState>>,
+    group: &str,
+    tool: &str,
+    args: Option<serde_json::Value>,
+) -> Result<serde_json::Value> {
+    let args = check_broadcast(state, tool, args).await?;
+    let members = state
+        .lock()
+        .await
+        .session_groups
+        .get(group)
+        .cloned()
+        .ok_or_else(|| {
+            StudioLinkError::InvalidArguments(format!(
+                "session group '{}' not found. Create it with create_session_group.",
+                group
+            ))
+        })?;
+
+    let (results, succeeded) = fan_out(state, &members, tool, &args, DEFAULT_TIMEOUT).await;
+    Ok(json!({
+        "group": group,
+        "tool": tool,
+        "succeeded": succeeded,
+        "failed": members.len() - succeeded,
+        "results": results,
+    }))
+}
+
+/// Longest per-session timeout broadcast_tool accepts.
+pub const MAX_BROADCAST_TIMEOUT_SECS: u64 = 120;
+
+/// broadcast_tool — Run one plugin tool on every connected session (or the
+/// listed ones) concurrently and aggregate the results, e.g. lint_scripts
+/// across all open places. Like broadcast_to_group, one failing session
+/// doesn't fail the call.
+pub async fn broadcast_tool(
+    state: &Arc<Mutex<AppState>>,
+    tool: &str,
+    args: Option<serde_json::Value>,
+    session_ids: Option<Vec<String>>,
+    timeout_secs: Option<u64>,
+) -> Result<serde_json::Value> {
+    let args = check_broadcast(state, tool, args).await?;
+    let timeout_secs = timeout_secs.unwrap_or(DEFAULT_TIMEOUT.as_secs());
+    if !(1..=MAX_BROADCAST_TIMEOUT_SECS).contains(&timeout_secs) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "timeoutSecs must be between 1 and {}, got {}",
+            MAX_BROADCAST_TIMEOUT_SECS, timeout_secs
+        )));
+    }
+
+    let connected = connected_session_ids(state).await?;
+    let targets = match session_ids {
+        Some(ids) => {
+            let missing: Vec<&str> = ids
+                .iter()
+                .filter(|id| !connected.contains(id))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                return Err(StudioLinkError::InvalidArguments(format!(
+                    "unknown session(s): {}. Use list_sessions to see connected sessions.",
+                    missing.join(", ")
+                )));
+            }
+            let mut unique: Vec<String> = Vec::with_capacity(ids.len());
+            for id in ids {
+                if !unique.contains(&id) {
+                    unique.push(id);
+                }
+            }
+            unique
+        }
+        None => {
+            let mut all = connected;
+            all.sort();
+            all
+        }
+    };
+    if targets.is_empty() {
+        return Err(StudioLinkError::PluginNotConnected);
+    }
+
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+    let (results, succeeded) = fan_out(state, &targets, tool, &args, timeout).await;
+    Ok(json!({
+        "tool": tool,
+        "sessions": targets.len(),
+        "succeeded": succeeded,
+        "failed": targets.len() - succeeded,
+        "results": results,
+    }))
+}
+
+/// Shared argument checks for the broadcast tools. Returns the args to send
+/// (an empty object when none were given). The read-only, tool filter and
+/// require allowlist checks are repeated here because the MCP layer only
+/// sees the outer broadcast call.
+async fn check_broadcast(
+    state: &Arc<Mutex<AppState>>,
+    tool: &str,
+    args: Option<serde_json::Value>,
+) -> Result<serde_json::Value> {
+    let mut args = args.unwrap_or_else(|| json!({}));
+    if !args.is_object() {
+        return Err(StudioLinkError::InvalidArguments(
+            "args must be a JSON object".into(),
+        ));
+    }
+    if tool.trim().is_empty() {
+        return Err(StudioLinkError::InvalidArguments(
+            "tool must be a non-empty tool name".into(),
+        ));
+    }
+    let s = state.lock().await;
+    if let Some(refusal) = super::plugin_read_only_refusal(s.read_only, tool)
+        .or_else(|| super::plugin_filter_refusal(&s.tool_filter, tool))
+    {
+        return Err(refusal);
+    }
+    // The allowlist is the server's, not the caller's to pass or leave out
+    if let (Some(allowlist), true) = (
+        &s.require_allowlist,
+        super::core::ALLOWLISTED_TOOLS.contains(&tool),
+    ) {
+        args["requireAllowlist"] = json!(allowlist);
+    }
+    Ok(args)
+}
+
+/// Send `tool` to each session concurrently. Returns one result object per
+/// session, in the order given, and how many succeeded.
+async fn fan_out(
+    state: &Arc<Mutex<AppState>>,
+    sessions: &[String],
+    tool: &str,
+    args: &serde_json::Value,
+    timeout: std::time::Duration,
+) -> (Vec<serde_json::Value>, usize) {
+    let handles: Vec<_> = sessions
+        .iter()
+        .map(|session_id| {
+            let state = state.clone();
+            let session_id = session_id.clone();
+            let tool = tool.to_string();
+            let args = args.clone();
+            tokio::spawn(async move {
+                send_to_plugin(&state, Some(&session_id), &tool, args, timeout).await
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(sessions.len());
+    let mut succeeded = 0;
+    for (session_id, handle) in sessions.iter().zip(handles) {
+        let outcome = handle
+            .await
+            .unwrap_or_else(|e| Err(StudioLinkError::ServerError(e.to_string())));
+        results.push(match outcome {
+            Ok(result) => {
+                succeeded += 1;
+                json!({ "session_id": session_id, "success": true, "result": result })
+            }
+            Err(e) => json!({ "session_id": session_id, "success": false, "error": e.to_string() }),
+        });
+    }
+    (results, succeeded)
+}
+
+/// Ids of the sessions currently connected, asking the primary in proxy mode.
+async fn connected_session_ids(state: &Arc<Mutex<AppState>>) -> Result<Vec<String>> {
+    let (proxy_mode, proxy_url) = {
+        let s = state.lock().await;
+        (s.proxy_mode, s.proxy_url.clone())
+    };
+    if proxy_mode {
+        let listing = proxy_get(state, &proxy_url, "/sessions").await?;
+        return Ok(listing["sessions"]
+            .as_array()
+            .map(|sessions| sessions.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|s| s["session_id"].as_str().map(str::to_string))
+            .collect());
+    }
+    Ok(state.lock().await.sessions.ids())
+}
+
+/// Longest poll_changes may wait for a new change.
+pub const MAX_CHANGE_WAIT_MS: u64 = 30_000;
+
+/// poll_changes — Mutations made on a session since `cursor`, so agents that
+/// share a place can invalidate cached trees. Pass the returned `cursor` back
+/// on the next call (0 the first time). With `wait_ms`, blocks until a change
+/// arrives or the wait runs out. Targets `session_id`, else the bound or
+/// active session; in proxy mode the log lives on the primary.
+pub async fn poll_changes(
+    state: &Arc<Mutex<AppState>>,
+    session_id: Option<&str>,
+    cursor: Option<u64>,
+    wait_ms: Option<u64>,
+) -> Result<serde_json::Value> {
+    let cursor = cursor.unwrap_or(0);
+    let wait_ms = wait_ms.unwrap_or(0);
+    if wait_ms > MAX_CHANGE_WAIT_MS {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "waitMs must be at most {}, got {}",
+            MAX_CHANGE_WAIT_MS, wait_ms
+        )));
+    }
+
+    let (proxy_mode, proxy_url, session_id) = {
+        let s = state.lock().await;
+        let session_id = session_id
+            .map(|id| id.to_string())
+            .or_else(|| super::connection(&s).lock().bound_session_id.clone());
+        (s.proxy_mode, s.proxy_url.clone(), session_id)
+    };
+    if proxy_mode {
+        let mut endpoint = format!("/changes?cursor={}&wait_ms={}", cursor, wait_ms);
+        if let Some(id) = &session_id {
+            endpoint.push_str(&format!("&session_id={}", id));
+        }
+        let timeout = std::time::Duration::from_millis(wait_ms + 5_000);
+        let result = proxy_get_within(state, &proxy_url, &endpoint, timeout).await?;
+        if let Some(error) = result["error"].as_str() {
+            return Err(StudioLinkError::PluginError(error.to_string()));
+        }
+        return Ok(result);
+    }
+    changes_since(state, session_id.as_deref(), cursor, wait_ms).await
+}
+
+/// Local change-log lookup behind poll_changes and GET /changes.
+pub async fn changes_since(
+    state: &Arc<Mutex<AppState>>,
+    session_id: Option<&str>,
+    cursor: u64,
+    wait_ms: u64,
+) -> Result<serde_json::Value> {
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_millis(wait_ms);
+    let mut rx = state.lock().await.sessions.changes().subscribe();
+    loop {
+        {
+            let s = state.lock().await;
+            let session_id = match session_id {
+                Some(id) => id.to_string(),
+                None => s
+                    .get_active_session()
+                    .map(|id| id.to_string())
+                    .ok_or_else(|| {
+                        StudioLinkError::PluginError(
+                            "No active session. Pass session_id or use switch_session.".into(),
+                        )
+                    })?,
+            };
+            let log = s.sessions.changes();
+            let (changes, missed) = log.since(&session_id, cursor);
+            if !changes.is_empty() || tokio::time::Instant::now() >= deadline {
+                return Ok(json!({
+                    "session_id": session_id,
+                    "cursor": log.latest(),
+                    "changes": changes,
+                    "missed": missed,
+                }));
+            }
+        }
+        // Wait for the next commit on any session, then look again
+        if tokio::time::timeout_at(deadline, rx.changed())
+            .await
+            .is_err()
+        {
+            continue;
+        }
+    }
+}
+
+/// cancel_request — Abort pending plugin requests: one by `request_id`, or
+/// every pending call of `tool` (optionally only on `session_id`). A request
+/// the plugin hasn't picked up is dropped from the queue; one it is running
+/// gets a cancel message. With neither id nor tool, only lists what is
+/// pending. In proxy mode the requests live on the primary.
+pub async fn cancel_request(
+    state: &Arc<Mutex<AppState>>,
+    request_id: Option<&str>,
+    tool: Option<&str>,
+    session_id: Option<&str>,
+) -> Result<serde_json::Value> {
+    let (proxy_mode, proxy_url) = {
+        let s = state.lock().await;
+        (s.proxy_mode, s.proxy_url.clone())
+    };
+    if proxy_mode {
+        let client = super::proxy_client(state).await;
+        let response = client
+            .post(format!("{}/cancel", proxy_url))
+            .json(&json!({ "request_id": request_id, "tool": tool, "session_id": session_id }))
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await
+            .map_err(|e| {
+                StudioLinkError::PluginError(format!("Proxy cancel_request failed: {}", e))
+            })?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(super::proxy_unauthorized(state).await);
+        }
+        let result: serde_json::Value = response.json().await.map_err(|e| {
+            StudioLinkError::PluginError(format!("Proxy response parse error: {}", e))
+        })?;
+        if let Some(error) = result["error"].as_str() {
+            return Err(StudioLinkError::InvalidArguments(error.to_string()));
+        }
+        return Ok(result);
+    }
+    cancel_pending(state, request_id, tool, session_id).await
+}
+
+/// Local cancellation behind cancel_request and POST /cancel.
+pub async fn cancel_pending(
+    state: &Arc<Mutex<AppState>>,
+    request_id: Option<&str>,
+    tool: Option<&str>,
+    session_id: Option<&str>,
+) -> Result<serde_json::Value> {
+    let s = state.lock().await;
+    let pending = s.sessions.pending_requests(session_id);
+    let targets: Vec<String> = match (request_id, tool) {
+        (Some(id), _) => {
+            if !pending.iter().any(|r| r.request_id == id) {
+                return Err(StudioLinkError::InvalidArguments(format!(
+                    "no pending request '{}'; it may already have finished. Call cancel_request with no arguments to list pending requests.",
+                    id
+                )));
+            }
+            vec![id.to_string()]
+        }
+        (None, Some(tool)) => pending
+            .iter()
+            .filter(|r| r.tool == tool)
+            .map(|r| r.request_id.clone())
+            .collect(),
+        (None, None) => {
+            return Ok(json!({ "cancelled": [], "count": 0, "pending": pending }));
+        }
+    };
+
+    let cancelled: Vec<crate::state::PendingRequest> = targets
+        .iter()
+        .filter_map(|id| s.sessions.cancel_request(id))
+        .collect();
+    for request in &cancelled {
+        tracing::info!(
+            "Cancelled {} ({}) on session {} [{}]",
+            request.tool,
+            request.request_id,
+            request.session_id,
+            request.state
+        );
+    }
+    Ok(json!({
+        "cancelled": cancelled,
+        "count": cancelled.len(),
+        "pending": s.sessions.pending_requests(session_id),
+    }))
+}
+
 /// Helper: GET request to primary server in proxy mode
-async fn proxy_get(proxy_url: &str, endpoint: &str) -> Result<serde_json::Value> {
-    let client = reqwest::Client::new();
+async fn proxy_get(
+    state: &Arc<Mutex<AppState>>,
+    proxy_url: &str,
+    endpoint: &str,
+) -> Result<serde_json::Value> {
+    proxy_get_within(
+        state,
+        proxy_url,
+        endpoint,
+        std::time::Duration::from_secs(5),
+    )
+    .await
+}
+
+async fn proxy_get_within(
+    state: &Arc<Mutex<AppState>>,
+    proxy_url: &str,
+    endpoint: &str,
+    timeout: std::time::Duration,
+) -> Result<serde_json::Value> {
+    let client = super::proxy_client(state).await;
     let url = format!("{}{}", proxy_url, endpoint);
 
     let response = client
         .get(&url)
-        .timeout(std::time::Duration::from_secs(5))
+        .timeout(timeout)
         .send()
         .await
         .map_err(|e| StudioLinkError::PluginError(format!("Proxy request failed: {}", e)))?;
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(super::proxy_unauthorized(state).await);
+    }
 
     response
         .json()
         .await
         .map_err(|e| StudioLinkError::PluginError(format!("Proxy response parse error: {}", e)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_state() -> Arc<Mutex<AppState>> {
+        AppState::new().0
+    }
+
+    #[test]
+    fn merge_keeps_resolved_fields_and_per_field_errors() {
+        let mut result = json!({ "connected": true, "place_id": 1 });
+        merge_place_context(
+            &mut result,
+            Ok(json!({
+                "universeId": 42,
+                "creator": { "id": 7, "name": "Builder", "type": "User" },
+                "errors": { "iconAssetId": "Place has no icon" },
+            })),
+        );
+        assert_eq!(result["universeId"], 42);
+        assert_eq!(result["creator"]["name"], "Builder");
+        assert!(result.get("iconAssetId").is_none());
+        assert_eq!(result["enrichErrors"]["iconAssetId"], "Place has no icon");
+        assert_eq!(result["place_id"], 1);
+    }
+
+    #[test]
+    fn merge_plugin_failure_keeps_base_info() {
+        let mut result = json!({ "connected": true, "place_id": 1 });
+        merge_place_context(&mut result, Err(StudioLinkError::PluginNotConnected));
+        assert_eq!(result["place_id"], 1);
+        assert!(result["enrichErrors"]["creator"].is_string());
+    }
+
+    #[tokio::test]
+    async fn enrich_without_session_returns_plain_info() {
+        let state = make_state();
+        let result = get_active_session(&state, Some(true)).await.unwrap();
+        assert_eq!(result["connected"], false);
+        assert!(result.get("enrichErrors").is_none());
+    }
+
+    #[tokio::test]
+    async fn rename_rejects_blank_name() {
+        let state = make_state();
+        let err = rename_session(&state, "any", "   ").await.unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    #[tokio::test]
+    async fn group_members_must_be_connected() {
+        let state = make_state();
+        state
+            .lock()
+            .await
+            .register_session(crate::state::SessionRegistration {
+                session_id: "a".into(),
+                place_id: 1,
+                place_name: "Lobby".into(),
+                game_id: 0,
+                client_id: None,
+                supported_tools: None,
+                plugin_version: None,
+            });
+
+        let err = create_session_group(&state, "exp", &["a".into(), "b".into()])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown session(s): b"));
+
+        let ok = create_session_group(&state, "exp", &["a".into(), "a".into()])
+            .await
+            .unwrap();
+        assert_eq!(ok["session_ids"], json!(["a"]));
+        assert_eq!(ok["replaced"], false);
+    }
+
+    #[tokio::test]
+    async fn broadcast_tool_reaches_every_session() {
+        let state = make_state();
+        for id in ["a", "b"] {
+            state
+                .lock()
+                .await
+                .register_session(crate::state::SessionRegistration {
+                    session_id: id.into(),
+                    place_id: 1,
+                    place_name: id.into(),
+                    game_id: 0,
+                    client_id: None,
+                    supported_tools: None,
+                    plugin_version: None,
+                });
+        }
+
+        let call = tokio::spawn({
+            let state = state.clone();
+            async move { broadcast_tool(&state, "lint_scripts", None, None, Some(5)).await }
+        });
+        // Answer "a" and fail "b"
+        for (id, success) in [("a", true), ("b", false)] {
+            let request = loop {
+                let next = state.lock().await.sessions.next_request(id);
+                match next {
+                    Some(request) => break request,
+                    None => tokio::task::yield_now().await,
+                }
+            };
+            assert_eq!(request.tool, "lint_scripts");
+            state
+                .lock()
+                .await
+                .sessions
+                .deliver_response(crate::state::PluginResponse {
+                    id: request.id,
+                    success,
+                    result: json!({ "issues": [] }),
+                    error: (!success).then(|| "boom".to_string()),
+                });
+        }
+
+        let result = call.await.unwrap().unwrap();
+        assert_eq!(result["sessions"], 2);
+        assert_eq!(result["succeeded"], 1);
+        assert_eq!(result["results"][0]["session_id"], "a");
+        assert_eq!(result["results"][1]["success"], false);
+
+        let err = broadcast_tool(&state, "lint_scripts", None, Some(vec!["c".into()]), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown session(s): c"));
+
+        state.lock().await.read_only = true;
+        let err = broadcast_tool(&state, "run_code", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::ReadOnly { .. }));
+        // Plugin names of MCP tools that are named differently are refused too
+        let err = broadcast_tool(&state, "set_appearance", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::ReadOnly { .. }));
+    }
+
+    /// Register sessions `ids` and put them in group "all".
+    async fn state_with_group(ids: &[&str]) -> Arc<Mutex<AppState>> {
+        let state = make_state();
+        let mut s = state.lock().await;
+        for id in ids {
+            s.register_session(crate::state::SessionRegistration {
+                session_id: id.to_string(),
+                place_id: 1,
+                place_name: id.to_string(),
+                game_id: 0,
+                client_id: None,
+                supported_tools: None,
+                plugin_version: None,
+            });
+        }
+        s.session_groups
+            .insert("all".into(), ids.iter().map(|id| id.to_string()).collect());
+        drop(s);
+        state
+    }
+
+    /// The next request queued for `session_id`, answered with an empty result.
+    async fn answer_next(
+        state: &Arc<Mutex<AppState>>,
+        session_id: &str,
+    ) -> crate::state::PluginRequest {
+        let request = loop {
+            let next = state.lock().await.sessions.next_request(session_id);
+            match next {
+                Some(request) => break request,
+                None => tokio::task::yield_now().await,
+            }
+        };
+        state
+            .lock()
+            .await
+            .sessions
+            .deliver_response(crate::state::PluginResponse {
+                id: request.id.clone(),
+                success: true,
+                result: json!(""),
+                error: None,
+            });
+        request
+    }
+
+    #[tokio::test]
+    async fn broadcast_respects_the_tool_filter() {
+        let state = state_with_group(&["a"]).await;
+        state.lock().await.tool_filter = crate::tools::ToolFilter {
+            allow: None,
+            deny: vec!["run_code".into(), "set_appearance_preset".into()],
+        };
+        let err = broadcast_tool(&state, "run_code", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--tools-deny"), "{}", err);
+        // Refused by the MCP tool that sends it, not only by its own name
+        let err = broadcast_tool(&state, "set_appearance", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("set_appearance_preset"), "{}", err);
+
+        state.lock().await.tool_filter = crate::tools::ToolFilter {
+            allow: Some(vec!["lint_scripts".into()]),
+            deny: Vec::new(),
+        };
+        assert!(broadcast_tool(&state, "run_code", None, None, None)
+            .await
+            .is_err());
+        assert!(
+            broadcast_tool(&state, "insert_model_finish", None, None, None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcast_run_code_carries_the_require_allowlist() {
+        let state = state_with_group(&["a"]).await;
+        state.lock().await.require_allowlist = Some(vec!["ReplicatedStorage.Shared".into()]);
+        let call = tokio::spawn({
+            let state = state.clone();
+            async move {
+                let args = json!({ "command": "require(game.ServerStorage.Secrets)" });
+                broadcast_tool(&state, "run_code", Some(args), None, Some(5)).await
+            }
+        });
+        let request = answer_next(&state, "a").await;
+        assert_eq!(
+            request.args["requireAllowlist"],
+            json!(["ReplicatedStorage.Shared"])
+        );
+        assert_eq!(call.await.unwrap().unwrap()["succeeded"], 1);
+
+        // A caller can't replace the server's list with its own
+        let call = tokio::spawn({
+            let state = state.clone();
+            async move {
+                let args = json!({ "command": "", "requireAllowlist": ["ServerStorage"] });
+                broadcast_tool(&state, "run_code", Some(args), None, Some(5)).await
+            }
+        });
+        let request = answer_next(&state, "a").await;
+        assert_eq!(
+            request.args["requireAllowlist"],
+            json!(["ReplicatedStorage.Shared"])
+        );
+        call.await.unwrap().unwrap();
+
+        // Play-mode scripts run caller code too
+        let call = tokio::spawn({
+            let state = state.clone();
+            async move {
+                let args = json!({ "code": "require(game.ServerStorage.Secrets)" });
+                broadcast_tool(&state, "run_script_in_play_mode", Some(args), None, Some(5)).await
+            }
+        });
+        let request = answer_next(&state, "a").await;
+        assert_eq!(
+            request.args["requireAllowlist"],
+            json!(["ReplicatedStorage.Shared"])
+        );
+        call.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn group_broadcast_applies_the_same_checks() {
+        let state = state_with_group(&["a", "b"]).await;
+        state.lock().await.tool_filter = crate::tools::ToolFilter {
+            allow: None,
+            deny: vec!["run_code".into()],
+        };
+        let err = broadcast_to_group(&state, "all", "run_code", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--tools-deny"), "{}", err);
+
+        {
+            let mut s = state.lock().await;
+            s.tool_filter = Default::default();
+            s.require_allowlist = Some(vec!["ReplicatedStorage.Shared".into()]);
+        }
+        let call = tokio::spawn({
+            let state = state.clone();
+            async move {
+                let args = json!({ "command": "require(game.ServerStorage.Secrets)" });
+                broadcast_to_group(&state, "all", "run_code", Some(args)).await
+            }
+        });
+        for id in ["a", "b"] {
+            let request = answer_next(&state, id).await;
+            assert_eq!(
+                request.args["requireAllowlist"],
+                json!(["ReplicatedStorage.Shared"])
+            );
+        }
+        assert_eq!(call.await.unwrap().unwrap()["succeeded"], 2);
+    }
+
+    #[tokio::test]
+    async fn broadcast_to_unknown_group_fails() {
+        let state = make_state();
+        let err = broadcast_to_group(&state, "nope", "run_code", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+
+    #[tokio::test]
+    async fn rename_rejects_unknown_session() {
+        let state = make_state();
+        let err = rename_session(&state, "nope", "Combat Test")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StudioLinkError::InvalidArguments(_)));
+    }
+}
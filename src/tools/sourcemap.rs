@@ -0,0 +1,202 @@
+//! Rojo-style `sourcemap.json` for the live place, so luau-lsp and other
+//! tooling can resolve `require` paths against what Studio actually has.
+//!
+//! The tree comes from get_file_tree. By default it keeps scripts and their
+//! ancestors, as `rojo sourcemap` does; with a sync_export directory, each
+//! exported script also gets the file it was written to in `filePaths`.
+
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::sync::read_manifest;
+use crate::error::{Result, StudioLinkError};
+use crate::state::AppState;
+
+/// get_file_tree depth for the sourcemap; deeper than any sane hierarchy.
+const SOURCEMAP_DEPTH: u32 = 64;
+
+const SCRIPT_CLASSES: [&str; 3] = ["Script", "LocalScript", "ModuleScript"];
+
+/// What went into a sourcemap.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SourcemapStats {
+    instances: usize,
+    scripts: usize,
+    files_mapped: usize,
+}
+
+/// Build the sourcemap. `dir` is a sync_export directory whose files are
+/// mapped in, as paths prefixed with `dir` as given.
+pub async fn generate(
+    state: &Arc<Mutex<AppState>>,
+    dir: Option<&str>,
+    include_non_scripts: bool,
+) -> Result<Value> {
+    let mut files = HashMap::new();
+    if let Some(dir) = dir {
+        let manifest = read_manifest(Path::new(dir))?.ok_or_else(|| {
+            StudioLinkError::InvalidArguments(format!(
+                "{} has no {}; run sync_export first",
+                dir,
+                super::sync::MANIFEST_FILE
+            ))
+        })?;
+        let prefix = dir.trim_end_matches(['/', '\\']);
+        for (file, script) in manifest.files {
+            files.insert(script.names, format!("{}/{}", prefix, file));
+        }
+    }
+    let tree = super::instance::get_file_tree(state, None, Some(SOURCEMAP_DEPTH), None, None, None)
+        .await?;
+    Ok(build(&tree, &files, include_non_scripts))
+}
+
+/// sourcemap_generate — Return the sourcemap, or write it to `output_path`
+/// and return what it holds.
+pub async fn sourcemap_generate(
+    state: &Arc<Mutex<AppState>>,
+    dir: Option<&str>,
+    include_non_scripts: Option<bool>,
+    output_path: Option<&str>,
+) -> Result<Value> {
+    let sourcemap = generate(state, dir, include_non_scripts.unwrap_or(false)).await?;
+    let Some(output_path) = output_path else {
+        return Ok(sourcemap);
+    };
+    let stats = count(&sourcemap);
+    let mut text = serde_json::to_string(&sourcemap)?;
+    text.push('\n');
+    std::fs::write(output_path, text)?;
+    Ok(json!({
+        "path": output_path,
+        "instances": stats.instances,
+        "scripts": stats.scripts,
+        "filesMapped": stats.files_mapped,
+    }))
+}
+
+fn build(tree: &Value, files: &HashMap<Vec<String>, String>, include_non_scripts: bool) -> Value {
+    let mut names = Vec::new();
+    let children: Vec<Value> = tree["services"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|service| node(service, &mut names, files, include_non_scripts))
+        .collect();
+    let mut root = Map::new();
+    root.insert("name".into(), json!("Game"));
+    root.insert("className".into(), json!("DataModel"));
+    if !children.is_empty() {
+        root.insert("children".into(), json!(children));
+    }
+    Value::Object(root)
+}
+
+/// The sourcemap node for `value`, or None if it holds no script and
+/// `include_non_scripts` is off.
+fn node(
+    value: &Value,
+    names: &mut Vec<String>,
+    files: &HashMap<Vec<String>, String>,
+    include_non_scripts: bool,
+) -> Option<Value> {
+    // Instances reached twice are marked ref; the first visit has the subtree
+    if value["ref"].as_bool() == Some(true) {
+        return None;
+    }
+    let name = value["name"].as_str()?;
+    let class = value["className"].as_str().unwrap_or("Instance");
+    names.push(name.to_string());
+    let children: Vec<Value> = value["children"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|child| node(child, names, files, include_non_scripts))
+        .collect();
+    let file = files.get(names.as_slice());
+    names.pop();
+
+    let is_script = SCRIPT_CLASSES.contains(&class);
+    if !is_script && !include_non_scripts && children.is_empty() {
+        return None;
+    }
+    let mut out = Map::new();
+    out.insert("name".into(), json!(name));
+    out.insert("className".into(), json!(class));
+    if let Some(file) = file {
+        out.insert("filePaths".into(), json!([file]));
+    }
+    if !children.is_empty() {
+        out.insert("children".into(), json!(children));
+    }
+    Some(Value::Object(out))
+}
+
+fn count(node: &Value) -> SourcemapStats {
+    let mut stats = SourcemapStats {
+        instances: 1,
+        scripts: usize::from(
+            node["className"]
+                .as_str()
+                .is_some_and(|c| SCRIPT_CLASSES.contains(&c)),
+        ),
+        files_mapped: usize::from(node.get("filePaths").is_some()),
+    };
+    for child in node["children"].as_array().into_iter().flatten() {
+        let child = count(child);
+        stats.instances += child.instances;
+        stats.scripts += child.scripts;
+        stats.files_mapped += child.files_mapped;
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_scripts_and_their_ancestors() {
+        let tree = json!({"services": [
+            {"name": "Workspace", "className": "Workspace", "children": [
+                {"name": "Baseplate", "className": "Part"}
+            ]},
+            {"name": "ReplicatedStorage", "className": "ReplicatedStorage", "children": [
+                {"name": "Shared", "className": "Folder", "children": [
+                    {"name": "Util", "className": "ModuleScript"},
+                    {"name": "Util", "className": "ModuleScript", "ref": true}
+                ]}
+            ]}
+        ]});
+        let files = HashMap::from([(
+            vec!["ReplicatedStorage".into(), "Shared".into(), "Util".into()],
+            "src/ReplicatedStorage/Shared/Util.luau".to_string(),
+        )]);
+
+        let sourcemap = build(&tree, &files, false);
+        assert_eq!(
+            sourcemap,
+            json!({"name": "Game", "className": "DataModel", "children": [
+                {"name": "ReplicatedStorage", "className": "ReplicatedStorage", "children": [
+                    {"name": "Shared", "className": "Folder", "children": [
+                        {"name": "Util", "className": "ModuleScript",
+                         "filePaths": ["src/ReplicatedStorage/Shared/Util.luau"]}
+                    ]}
+                ]}
+            ]})
+        );
+        assert_eq!(
+            count(&sourcemap),
+            SourcemapStats {
+                instances: 4,
+                scripts: 1,
+                files_mapped: 1
+            }
+        );
+
+        assert_eq!(count(&build(&tree, &files, true)).instances, 6);
+    }
+}
@@ -0,0 +1,835 @@
+//! Filesystem sync: the place's scripts as files for external editors.
+//!
+//! sync_export writes every script under a directory that mirrors the
+//! DataModel (`ServerScriptService/Combat/Damage.server.luau`) and records
+//! each file's instance path, class and source hash in `studiolink-sync.json`.
+//! The hashes tell a later export, or sync_status, which files were edited
+//! locally since.
+//!
+//! sync_watch_start closes the loop: a notify watcher on the directory pushes
+//! saved edits back with set_script_source, unless the script also changed in
+//! Studio since it was exported, which is reported as a conflict instead.
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+use super::rojo::safe_file_name;
+use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
+use crate::error::{Result, StudioLinkError};
+use crate::snapshots::hash_blob;
+use crate::state::AppState;
+
+pub const MANIFEST_FILE: &str = "studiolink-sync.json";
+
+const MANIFEST_FORMAT: &str = "studiolink-sync";
+
+/// Written into the manifest; bump when its shape changes.
+const MANIFEST_VERSION: u32 = 1;
+
+/// Local edits listed in an error before the rest are counted.
+const MAX_LISTED_EDITS: usize = 20;
+
+/// Quiet time after a file event before pushing, so an editor's
+/// write-temp-then-rename save settles into one push.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watch events kept for sync_watch_poll; older ones are dropped.
+const MAX_WATCH_EVENTS: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncManifest {
+    pub format: String,
+    pub version: u32,
+    pub place_id: Option<u64>,
+    pub exported_at_unix_ms: u64,
+    /// Dotted path export was limited to; None for the whole place
+    pub root: Option<String>,
+    /// File path relative to the directory, with `/` separators → script
+    pub files: BTreeMap<String, SyncedScript>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncedScript {
+    /// Dotted path, as set_script_source takes it
+    pub path: String,
+    /// Instance names from the service down, for names containing dots
+    pub names: Vec<String>,
+    pub class_name: String,
+    /// SHA-256 of the source as exported
+    pub hash: String,
+}
+
+/// One script as the plugin's sync_export returns it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedScript {
+    path: String,
+    names: Vec<String>,
+    class_name: String,
+    source: String,
+}
+
+/// Read `dir`'s manifest; None if it has none.
+pub fn read_manifest(dir: &Path) -> Result<Option<SyncManifest>> {
+    let path = dir.join(MANIFEST_FILE);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let manifest: SyncManifest = serde_json::from_str(&text)?;
+    if manifest.format != MANIFEST_FORMAT || manifest.version > MANIFEST_VERSION {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "{} is not a sync manifest this server understands",
+            path.display()
+        )));
+    }
+    Ok(Some(manifest))
+}
+
+/// Files listed in `manifest` whose contents no longer match the export,
+/// and those deleted since.
+pub fn local_edits(dir: &Path, manifest: &SyncManifest) -> (Vec<String>, Vec<String>) {
+    let mut modified = Vec::new();
+    let mut missing = Vec::new();
+    for (file, script) in &manifest.files {
+        match std::fs::read(dir.join(file)) {
+            Ok(bytes) if hash_blob(&bytes) != script.hash => modified.push(file.clone()),
+            Ok(_) => {}
+            Err(_) => missing.push(file.clone()),
+        }
+    }
+    (modified, missing)
+}
+
+/// sync_export — Write every script (or those under `root`) into `dir`.
+///
+/// `dir` must be empty, absent, or hold an earlier export; re-exporting over
+/// files edited since is refused unless `force`, so local work isn't lost.
+pub async fn sync_export(
+    state: &Arc<Mutex<AppState>>,
+    dir: &str,
+    root: Option<&str>,
+    extension: Option<&str>,
+    force: Option<bool>,
+) -> Result<serde_json::Value> {
+    let extension = extension.unwrap_or("luau");
+    if extension != "luau" && extension != "lua" {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "extension must be 'luau' or 'lua', got '{}'",
+            extension
+        )));
+    }
+    let dir = Path::new(dir);
+    let previous = check_target(dir, force.unwrap_or(false))?;
+
+    let place_id = {
+        let s = state.lock().await;
+        s.get_active_session_info().map(|i| i.place_id)
+    };
+    let result = send_to_plugin(
+        state,
+        None,
+        "sync_export",
+        json!({ "root": root }),
+        EXTENDED_TIMEOUT,
+    )
+    .await?;
+    let scripts: Vec<ExportedScript> = serde_json::from_value(result["scripts"].clone())?;
+
+    let written = write_scripts(dir, &scripts, extension, previous.as_ref())?;
+    let manifest = SyncManifest {
+        format: MANIFEST_FORMAT.into(),
+        version: MANIFEST_VERSION,
+        place_id,
+        exported_at_unix_ms: unix_ms(),
+        root: root.map(str::to_string),
+        files: written.files,
+    };
+    std::fs::create_dir_all(dir)?;
+    let manifest_path = write_manifest(dir, &manifest)?;
+
+    Ok(json!({
+        "dir": dir.display().to_string(),
+        "manifest": manifest_path.display().to_string(),
+        "files": manifest.files.len(),
+        "bytes": written.bytes,
+        "removed": written.removed,
+        "renamed": written.renamed,
+        "dottedNames": written.dotted_names,
+    }))
+}
+
+fn write_manifest(dir: &Path, manifest: &SyncManifest) -> Result<PathBuf> {
+    let path = dir.join(MANIFEST_FILE);
+    let mut text = serde_json::to_string_pretty(manifest)?;
+    text.push('\n');
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+/// sync_status — Compare an export directory with its manifest: files edited
+/// or deleted locally, and script files the export didn't write.
+pub fn sync_status(dir: &str) -> Result<serde_json::Value> {
+    let dir = Path::new(dir);
+    let manifest = read_manifest(dir)?.ok_or_else(|| {
+        StudioLinkError::InvalidArguments(format!(
+            "{} has no {}; run sync_export first",
+            dir.display(),
+            MANIFEST_FILE
+        ))
+    })?;
+    let (modified, missing) = local_edits(dir, &manifest);
+    let mut untracked = Vec::new();
+    collect_untracked(dir, dir, &manifest, &mut untracked)?;
+    untracked.sort();
+
+    let with_paths = |files: &[String]| -> Vec<serde_json::Value> {
+        files
+            .iter()
+            .map(|f| json!({ "file": f, "path": manifest.files[f].path }))
+            .collect()
+    };
+    Ok(json!({
+        "dir": dir.display().to_string(),
+        "placeId": manifest.place_id,
+        "exportedAtUnixMs": manifest.exported_at_unix_ms,
+        "files": manifest.files.len(),
+        "modified": with_paths(&modified),
+        "missing": with_paths(&missing),
+        "untracked": untracked,
+        "clean": modified.is_empty() && missing.is_empty() && untracked.is_empty(),
+    }))
+}
+
+/// Refuse targets that would lose work; return the earlier manifest, if any.
+fn check_target(dir: &Path, force: bool) -> Result<Option<SyncManifest>> {
+    let previous = read_manifest(dir)?;
+    match &previous {
+        None if dir.exists() && std::fs::read_dir(dir)?.next().is_some() => {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "{} is not empty and holds no earlier export",
+                dir.display()
+            )));
+        }
+        Some(manifest) if !force => {
+            let (modified, _) = local_edits(dir, manifest);
+            if !modified.is_empty() {
+                let mut listed = modified
+                    .iter()
+                    .take(MAX_LISTED_EDITS)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if modified.len() > MAX_LISTED_EDITS {
+                    listed.push_str(&format!(" and {} more", modified.len() - MAX_LISTED_EDITS));
+                }
+                return Err(StudioLinkError::InvalidArguments(format!(
+                    "files edited since the last export would be overwritten: {}. Push them to Studio first or pass force=true",
+                    listed
+                )));
+            }
+        }
+        _ => {}
+    }
+    Ok(previous)
+}
+
+#[derive(Debug, Default)]
+struct Written {
+    files: BTreeMap<String, SyncedScript>,
+    bytes: usize,
+    removed: usize,
+    /// Instance path → file, where the file name differs from the instance's
+    renamed: BTreeMap<String, String>,
+    /// Scripts whose path has a name containing '.', which dotted paths
+    /// can't address
+    dotted_names: Vec<String>,
+}
+
+fn write_scripts(
+    dir: &Path,
+    scripts: &[ExportedScript],
+    extension: &str,
+    previous: Option<&SyncManifest>,
+) -> Result<Written> {
+    let mut written = Written::default();
+    let mut used = HashSet::new();
+    for script in scripts {
+        let Some((name, parents)) = script.names.split_last() else {
+            continue;
+        };
+        let suffix = match script.class_name.as_str() {
+            "Script" => ".server",
+            "LocalScript" => ".client",
+            _ => "",
+        };
+        let mut relative: Vec<String> = parents.iter().map(|n| safe_file_name(n)).collect();
+        let mut stem = safe_file_name(name);
+        let mut file = file_path(&relative, &stem, suffix, extension);
+        // Siblings that sanitize alike, or differ only in case, would share
+        // a file on some systems
+        while !used.insert(file.to_ascii_lowercase()) {
+            stem.push('_');
+            file = file_path(&relative, &stem, suffix, extension);
+        }
+        if stem != *name || relative.iter().zip(parents).any(|(a, b)| a != b) {
+            written.renamed.insert(script.path.clone(), file.clone());
+        }
+        if script.names.iter().any(|n| n.contains('.')) {
+            written.dotted_names.push(script.path.clone());
+        }
+
+        relative.push(format!("{}{}.{}", stem, suffix, extension));
+        let target: PathBuf = relative.iter().fold(dir.to_path_buf(), |p, s| p.join(s));
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&target, &script.source)?;
+        written.bytes += script.source.len();
+        written.files.insert(
+            file,
+            SyncedScript {
+                path: script.path.clone(),
+                names: script.names.clone(),
+                class_name: script.class_name.clone(),
+                hash: hash_blob(script.source.as_bytes()),
+            },
+        );
+    }
+
+    // Scripts deleted or renamed in Studio since the last export
+    for old in previous.into_iter().flat_map(|m| m.files.keys()) {
+        if !written.files.contains_key(old) {
+            let path = dir.join(old);
+            if std::fs::remove_file(&path).is_ok() {
+                written.removed += 1;
+                remove_empty_parents(dir, &path);
+            }
+        }
+    }
+    Ok(written)
+}
+
+fn file_path(dirs: &[String], stem: &str, suffix: &str, extension: &str) -> String {
+    let mut parts = dirs.to_vec();
+    parts.push(format!("{}{}.{}", stem, suffix, extension));
+    parts.join("/")
+}
+
+/// Remove directories left empty between `file` and `dir`.
+fn remove_empty_parents(dir: &Path, file: &Path) {
+    let mut current = file.parent();
+    while let Some(parent) = current.filter(|p| *p != dir && p.starts_with(dir)) {
+        if std::fs::remove_dir(parent).is_err() {
+            break;
+        }
+        current = parent.parent();
+    }
+}
+
+fn collect_untracked(
+    dir: &Path,
+    current: &Path,
+    manifest: &SyncManifest,
+    out: &mut Vec<String>,
+) -> Result<()> {
+    for entry in std::fs::read_dir(current)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_untracked(dir, &path, manifest, out)?;
+            continue;
+        }
+        let is_script = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("lua" | "luau")
+        );
+        let relative = relative_file(dir, &path);
+        if is_script && !manifest.files.contains_key(&relative) {
+            out.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// `path` relative to `dir`, with `/` separators as the manifest keys it.
+pub fn relative_file(dir: &Path, path: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Outcomes of sync watchers, numbered so sync_watch_poll can return only
+/// what happened since its cursor.
+#[derive(Debug, Default)]
+pub struct SyncEvents {
+    seq: u64,
+    events: VecDeque<serde_json::Value>,
+}
+
+impl SyncEvents {
+    pub fn record(&mut self, mut event: serde_json::Value) {
+        self.seq += 1;
+        event["seq"] = json!(self.seq);
+        event["atUnixMs"] = json!(unix_ms());
+        self.events.push_back(event);
+        if self.events.len() > MAX_WATCH_EVENTS {
+            self.events.pop_front();
+        }
+    }
+
+    /// Events after `cursor`, oldest first.
+    pub fn since(&self, cursor: u64) -> Vec<serde_json::Value> {
+        self.events
+            .iter()
+            .filter(|e| e["seq"].as_u64().unwrap_or(0) > cursor)
+            .cloned()
+            .collect()
+    }
+}
+
+/// A running sync watch: the notify watcher and the task acting on what it
+/// sees. Dropping it stops both.
+pub struct SyncWatch {
+    /// Session edits are pushed to, fixed when the watch started
+    pub session_id: Option<String>,
+    _watcher: notify::RecommendedWatcher,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for SyncWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// sync_watch_start — Push local edits in an export directory to Studio as
+/// they are saved.
+///
+/// Edits go to the session active now, even if another becomes active
+/// later. The export must come from that session's place unless `force`.
+pub async fn sync_watch_start(
+    state: &Arc<Mutex<AppState>>,
+    dir: &str,
+    force: Option<bool>,
+) -> Result<serde_json::Value> {
+    let dir = std::fs::canonicalize(dir)
+        .map_err(|e| StudioLinkError::InvalidArguments(format!("can't watch {}: {}", dir, e)))?;
+    let manifest = read_manifest(&dir)?.ok_or_else(|| {
+        StudioLinkError::InvalidArguments(format!(
+            "{} has no {}; run sync_export first",
+            dir.display(),
+            MANIFEST_FILE
+        ))
+    })?;
+    let (session_id, place_id) = {
+        let s = state.lock().await;
+        (
+            s.active_session.clone(),
+            s.get_active_session_info().map(|i| i.place_id),
+        )
+    };
+    if let (Some(exported), Some(active)) = (manifest.place_id, place_id) {
+        if exported != active && !force.unwrap_or(false) {
+            return Err(StudioLinkError::InvalidArguments(format!(
+                "{} was exported from place {} but the active session is place {}; pass force=true to push to it anyway",
+                dir.display(),
+                exported,
+                active
+            )));
+        }
+    }
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })
+    .map_err(|e| StudioLinkError::ServerError(format!("can't start file watcher: {}", e)))?;
+    watcher.watch(&dir, RecursiveMode::Recursive).map_err(|e| {
+        StudioLinkError::ServerError(format!("can't watch {}: {}", dir.display(), e))
+    })?;
+    let task = tokio::spawn(run_watch(
+        state.clone(),
+        dir.clone(),
+        session_id.clone(),
+        rx,
+    ));
+
+    let mut s = state.lock().await;
+    let replaced = s
+        .sync_watches
+        .insert(
+            dir.clone(),
+            SyncWatch {
+                session_id: session_id.clone(),
+                _watcher: watcher,
+                task,
+            },
+        )
+        .is_some();
+    Ok(json!({
+        "dir": dir.display().to_string(),
+        "sessionId": session_id,
+        "files": manifest.files.len(),
+        "restarted": replaced,
+        "cursor": s.sync_events.seq,
+    }))
+}
+
+/// sync_watch_poll — What the running watches did since `cursor`.
+pub async fn sync_watch_poll(
+    state: &Arc<Mutex<AppState>>,
+    cursor: Option<u64>,
+) -> Result<serde_json::Value> {
+    let s = state.lock().await;
+    let mut watching: Vec<(String, Option<String>)> = s
+        .sync_watches
+        .iter()
+        .map(|(dir, watch)| (dir.display().to_string(), watch.session_id.clone()))
+        .collect();
+    watching.sort();
+    let watching: Vec<serde_json::Value> = watching
+        .into_iter()
+        .map(|(dir, session_id)| json!({ "dir": dir, "sessionId": session_id }))
+        .collect();
+    Ok(json!({
+        "events": s.sync_events.since(cursor.unwrap_or(0)),
+        "cursor": s.sync_events.seq,
+        "watching": watching,
+    }))
+}
+
+/// sync_watch_stop — Stop watching `dir`, or every directory.
+pub async fn sync_watch_stop(
+    state: &Arc<Mutex<AppState>>,
+    dir: Option<&str>,
+) -> Result<serde_json::Value> {
+    let mut s = state.lock().await;
+    let stopped: Vec<PathBuf> = match dir {
+        Some(dir) => {
+            let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| PathBuf::from(dir));
+            s.sync_watches
+                .remove(&dir)
+                .map(|_| dir)
+                .into_iter()
+                .collect()
+        }
+        None => s.sync_watches.drain().map(|(dir, _)| dir).collect(),
+    };
+    Ok(json!({
+        "stopped": stopped.iter().map(|d| d.display().to_string()).collect::<Vec<_>>(),
+    }))
+}
+
+async fn run_watch(
+    state: Arc<Mutex<AppState>>,
+    dir: PathBuf,
+    session_id: Option<String>,
+    mut rx: mpsc::UnboundedReceiver<notify::Result<notify::Event>>,
+) {
+    while let Some(event) = rx.recv().await {
+        let mut changed = BTreeSet::new();
+        collect_changed(event, &mut changed);
+        loop {
+            match tokio::time::timeout(WATCH_DEBOUNCE, rx.recv()).await {
+                Ok(Some(event)) => collect_changed(event, &mut changed),
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+        if !changed.is_empty() {
+            let events = push_changes(&state, &dir, session_id.as_deref(), &changed).await;
+            let mut s = state.lock().await;
+            for event in events {
+                s.sync_events.record(event);
+            }
+        }
+    }
+}
+
+fn collect_changed(event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>) {
+    let Ok(event) = event else {
+        return;
+    };
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+        return;
+    }
+    changed.extend(
+        event
+            .paths
+            .into_iter()
+            .filter(|p| matches!(p.extension().and_then(|e| e.to_str()), Some("lua" | "luau"))),
+    );
+}
+
+/// What to do with a tracked file whose contents changed on disk, given the
+/// hashes of the exported, local and current Studio source.
+#[derive(Debug, PartialEq, Eq)]
+enum PushAction {
+    /// Back to what was exported; nothing to do
+    Unchanged,
+    /// Studio still has the exported source: push the edit
+    Push,
+    /// Studio already has the local source: just record it
+    Record,
+    /// Both sides changed
+    Conflict,
+    /// The script no longer exists in Studio
+    Gone,
+}
+
+fn push_action(exported: &str, local: &str, studio: Option<&str>) -> PushAction {
+    match studio {
+        _ if local == exported => PushAction::Unchanged,
+        None => PushAction::Gone,
+        Some(studio) if studio == local => PushAction::Record,
+        Some(studio) if studio == exported => PushAction::Push,
+        Some(_) => PushAction::Conflict,
+    }
+}
+
+/// Push the edited files among `changed` and return an event for each.
+async fn push_changes(
+    state: &Arc<Mutex<AppState>>,
+    dir: &Path,
+    session_id: Option<&str>,
+    changed: &BTreeSet<PathBuf>,
+) -> Vec<serde_json::Value> {
+    let mut events = Vec::new();
+    let mut manifest = match read_manifest(dir) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            return vec![
+                json!({ "outcome": "error", "detail": format!("{} was removed", MANIFEST_FILE) }),
+            ]
+        }
+        Err(e) => return vec![json!({ "outcome": "error", "detail": e.to_string() })],
+    };
+
+    let mut edited = Vec::new();
+    for path in changed {
+        let file = relative_file(dir, path);
+        let Some(script) = manifest.files.get(&file) else {
+            events.push(json!({
+                "file": file,
+                "outcome": "untracked",
+                "detail": "not in the export; create the script in Studio and export again",
+            }));
+            continue;
+        };
+        // Unreadable files are usually mid-save; the next event covers them
+        let Ok(source) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        if hash_blob(source.as_bytes()) != script.hash {
+            edited.push((file, source));
+        }
+    }
+    if edited.is_empty() {
+        return events;
+    }
+
+    let wanted: Vec<&Vec<String>> = edited
+        .iter()
+        .map(|(f, _)| &manifest.files[f].names)
+        .collect();
+    let studio: HashMap<Vec<String>, String> = match send_to_plugin(
+        state,
+        session_id,
+        "sync_export",
+        json!({ "scripts": wanted }),
+        DEFAULT_TIMEOUT,
+    )
+    .await
+    .and_then(|r| {
+        Ok(serde_json::from_value::<Vec<ExportedScript>>(
+            r["scripts"].clone(),
+        )?)
+    }) {
+        Ok(scripts) => scripts.into_iter().map(|s| (s.names, s.source)).collect(),
+        Err(e) => {
+            events.extend(edited.iter().map(
+                |(file, _)| json!({ "file": file, "outcome": "error", "detail": e.to_string() }),
+            ));
+            return events;
+        }
+    };
+
+    let mut manifest_changed = false;
+    for (file, source) in edited {
+        let script = manifest
+            .files
+            .get_mut(&file)
+            .expect("edited files are tracked");
+        let local_hash = hash_blob(source.as_bytes());
+        let studio_hash = studio.get(&script.names).map(|s| hash_blob(s.as_bytes()));
+        let mut event = json!({ "file": file, "path": script.path });
+        match push_action(&script.hash, &local_hash, studio_hash.as_deref()) {
+            PushAction::Unchanged => continue,
+            PushAction::Record => {
+                script.hash = local_hash;
+                manifest_changed = true;
+                event["outcome"] = json!("in_sync");
+            }
+            PushAction::Push => {
+                let pushed = send_to_plugin(
+                    state,
+                    session_id,
+                    "set_script_source",
+                    json!({ "path": script.path, "names": script.names, "source": source }),
+                    DEFAULT_TIMEOUT,
+                )
+                .await;
+                match pushed {
+                    Ok(_) => {
+                        script.hash = local_hash;
+                        manifest_changed = true;
+                        event["outcome"] = json!("pushed");
+                    }
+                    Err(e) => {
+                        event["outcome"] = json!("error");
+                        event["detail"] = json!(e.to_string());
+                    }
+                }
+            }
+            PushAction::Conflict => {
+                event["outcome"] = json!("conflict");
+                event["detail"] = json!("the script changed in Studio since it was exported; the local edit was not pushed. Merge by hand, or sync_export with force=true to take Studio's version");
+            }
+            PushAction::Gone => {
+                event["outcome"] = json!("conflict");
+                event["detail"] = json!("the script no longer exists in Studio");
+            }
+        }
+        events.push(event);
+    }
+    if manifest_changed {
+        if let Err(e) = write_manifest(dir, &manifest) {
+            events.push(json!({ "outcome": "error", "detail": format!("can't update {}: {}", MANIFEST_FILE, e) }));
+        }
+    }
+    events
+}
+
+fn unix_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(names: &[&str], class_name: &str, source: &str) -> ExportedScript {
+        ExportedScript {
+            path: names.join("."),
+            names: names.iter().map(|n| n.to_string()).collect(),
+            class_name: class_name.into(),
+            source: source.into(),
+        }
+    }
+
+    #[test]
+    fn export_mirrors_the_tree_and_tracks_local_edits() {
+        let dir = std::env::temp_dir().join(format!("studiolink-sync-{}", uuid::Uuid::new_v4()));
+        let scripts = vec![
+            script(&["ServerScriptService", "Main"], "Script", "print('hi')"),
+            script(&["ReplicatedStorage", "Util"], "ModuleScript", "return {}"),
+            script(
+                &["ReplicatedStorage", "Util", "Helper"],
+                "ModuleScript",
+                "return 1",
+            ),
+            script(
+                &["StarterPlayer", "StarterPlayerScripts", "v1.2"],
+                "LocalScript",
+                "",
+            ),
+            script(&["ReplicatedStorage", "util"], "ModuleScript", "return 2"),
+        ];
+        assert!(check_target(&dir, false).unwrap().is_none());
+        let written = write_scripts(&dir, &scripts, "luau", None).unwrap();
+        assert_eq!(
+            written.files.keys().collect::<Vec<_>>(),
+            [
+                "ReplicatedStorage/Util.luau",
+                "ReplicatedStorage/Util/Helper.luau",
+                "ReplicatedStorage/util_.luau",
+                "ServerScriptService/Main.server.luau",
+                "StarterPlayer/StarterPlayerScripts/v1.2.client.luau",
+            ]
+        );
+        assert_eq!(
+            written.dotted_names,
+            ["StarterPlayer.StarterPlayerScripts.v1.2"]
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("ReplicatedStorage/Util/Helper.luau")).unwrap(),
+            "return 1"
+        );
+
+        let manifest = SyncManifest {
+            format: MANIFEST_FORMAT.into(),
+            version: MANIFEST_VERSION,
+            place_id: Some(1),
+            exported_at_unix_ms: 0,
+            root: None,
+            files: written.files,
+        };
+        std::fs::write(
+            dir.join(MANIFEST_FILE),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("ServerScriptService/Main.server.luau"),
+            "print('edited')",
+        )
+        .unwrap();
+        std::fs::write(dir.join("ServerScriptService/New.luau"), "").unwrap();
+
+        let status = sync_status(dir.to_str().unwrap()).unwrap();
+        assert_eq!(status["modified"][0]["path"], "ServerScriptService.Main");
+        assert_eq!(status["untracked"], json!(["ServerScriptService/New.luau"]));
+        assert!(check_target(&dir, false).is_err());
+        let previous = check_target(&dir, true).unwrap().unwrap();
+
+        // Re-export without the helper: its file goes, and so does its directory
+        let written = write_scripts(&dir, &scripts[..2], "luau", Some(&previous)).unwrap();
+        assert_eq!(written.removed, 3);
+        assert!(!dir.join("ReplicatedStorage/Util").exists());
+        assert!(!dir.join("StarterPlayer").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn watch_pushes_only_what_studio_still_has_as_exported() {
+        assert_eq!(push_action("a", "a", Some("b")), PushAction::Unchanged);
+        assert_eq!(push_action("a", "b", Some("a")), PushAction::Push);
+        assert_eq!(push_action("a", "b", Some("b")), PushAction::Record);
+        assert_eq!(push_action("a", "b", Some("c")), PushAction::Conflict);
+        assert_eq!(push_action("a", "b", None), PushAction::Gone);
+
+        let mut events = SyncEvents::default();
+        for i in 0..MAX_WATCH_EVENTS + 2 {
+            events.record(json!({ "file": i }));
+        }
+        let recent = events.since(MAX_WATCH_EVENTS as u64);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0]["seq"], MAX_WATCH_EVENTS + 1);
+        assert_eq!(events.since(0).len(), MAX_WATCH_EVENTS);
+    }
+}
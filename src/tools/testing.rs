@@ -3,40 +3,199 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use super::{send_to_plugin, DEFAULT_TIMEOUT, EXTENDED_TIMEOUT};
-use crate::error::Result;
+use crate::error::{Result, StudioLinkError};
 use crate::state::AppState;
 
 /// Tool 18: test_run — Run a TestEZ test suite
+///
+/// With `coverage`, the plugin runs the suite against instrumented copies of
+/// the place's ModuleScripts and reports which executable lines were hit.
+/// That raw data is replaced here by per-module `{module, coveredLines,
+/// totalLines, percent}` entries, and written as lcov when `output_path` is
+/// given. Instrumentation makes the run noticeably slower.
 pub async fn test_run(
     state: &Arc<Mutex<AppState>>,
     path: Option<&str>,
+    coverage: Option<bool>,
+    output_path: Option<&str>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let coverage = coverage.unwrap_or(false);
+    if output_path.is_some() && !coverage {
+        return Err(StudioLinkError::InvalidArguments(
+            "outputPath is only used with coverage=true".into(),
+        ));
+    }
+    let mut result = send_to_plugin(
         state,
         None,
         "test_run",
-        json!({ "path": path.unwrap_or("") }),
+        json!({ "path": path.unwrap_or(""), "coverage": coverage }),
         EXTENDED_TIMEOUT,
     )
-    .await
+    .await?;
+    if !coverage {
+        return Ok(result);
+    }
+
+    let raw = result
+        .get("coverage")
+        .and_then(|c| c.as_array())
+        .cloned()
+        .unwrap_or_default();
+    if let Some(path) = output_path {
+        std::fs::write(path, render_lcov(&raw))?;
+        result["lcovPath"] = json!(path);
+    }
+    result["coverage"] = structure_coverage(&raw);
+    Ok(result)
+}
+
+fn line_numbers(entry: &serde_json::Value, field: &str) -> Vec<u64> {
+    entry[field]
+        .as_array()
+        .map(|lines| lines.iter().filter_map(|l| l.as_u64()).collect())
+        .unwrap_or_default()
+}
+
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    (covered as f64 * 1000.0 / total as f64).round() / 10.0
+}
+
+/// Per-module coverage sorted by module path, plus overall totals.
+fn structure_coverage(raw: &[serde_json::Value]) -> serde_json::Value {
+    let mut modules: Vec<(String, usize, usize)> = raw
+        .iter()
+        .map(|entry| {
+            (
+                entry["module"].as_str().unwrap_or("?").to_string(),
+                line_numbers(entry, "hitLines").len(),
+                line_numbers(entry, "executableLines").len(),
+            )
+        })
+        .collect();
+    modules.sort();
+
+    let covered: usize = modules.iter().map(|m| m.1).sum();
+    let total: usize = modules.iter().map(|m| m.2).sum();
+    json!({
+        "modules": modules
+            .iter()
+            .map(|(module, covered, total)| json!({
+                "module": module,
+                "coveredLines": covered,
+                "totalLines": total,
+                "percent": percent(*covered, *total),
+            }))
+            .collect::<Vec<_>>(),
+        "summary": {
+            "coveredLines": covered,
+            "totalLines": total,
+            "percent": percent(covered, total),
+        },
+    })
+}
+
+/// lcov tracefile with one record per module. `SF:` holds the instance path,
+/// since modules have no file on disk unless the place is synced.
+fn render_lcov(raw: &[serde_json::Value]) -> String {
+    let mut out = String::from("TN:studiolink\n");
+    for entry in raw {
+        let hits = line_numbers(entry, "hitLines");
+        let lines = line_numbers(entry, "executableLines");
+        out.push_str(&format!("SF:{}\n", entry["module"].as_str().unwrap_or("?")));
+        for line in &lines {
+            let count = u8::from(hits.contains(line));
+            out.push_str(&format!("DA:{},{}\n", line, count));
+        }
+        out.push_str(&format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            lines.len(),
+            hits.len()
+        ));
+    }
+    out
 }
 
 /// Tool 19: test_create — Generate a test template for a given script/module
+///
+/// With `create_in`, the plugin also creates the template as a
+/// `<Target>.spec` ModuleScript under that parent in one undo step.
 pub async fn test_create(
     state: &Arc<Mutex<AppState>>,
     target_path: &str,
+    create_in: Option<&str>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
-        state,
-        None,
-        "test_create",
-        json!({ "targetPath": target_path }),
-        DEFAULT_TIMEOUT,
-    )
-    .await
+    let mut args = json!({ "targetPath": target_path });
+    if let Some(parent) = create_in {
+        validate_parent_path(parent)?;
+        args["createIn"] = json!(parent);
+    }
+    send_to_plugin(state, None, "test_create", args, DEFAULT_TIMEOUT).await
+}
+
+/// Reject parent paths that can't name an instance (empty segments such as
+/// "Workspace..Tests" or a trailing dot) before they reach the plugin.
+fn validate_parent_path(path: &str) -> Result<()> {
+    if path.trim().is_empty() || path.split('.').any(|segment| segment.trim().is_empty()) {
+        return Err(StudioLinkError::InvalidArguments(format!(
+            "createIn must be a dot-separated instance path like \"ReplicatedStorage.Tests\", got \"{}\"",
+            path
+        )));
+    }
+    Ok(())
 }
 
 /// Tool 20: test_report — Get detailed test results report
 pub async fn test_report(state: &Arc<Mutex<AppState>>) -> Result<serde_json::Value> {
     send_to_plugin(state, None, "test_report", json!({}), DEFAULT_TIMEOUT).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<serde_json::Value> {
+        vec![
+            json!({ "module": "ReplicatedStorage.Zeta", "executableLines": [1, 2, 4, 5], "hitLines": [1, 2, 4] }),
+            json!({ "module": "ReplicatedStorage.Alpha", "executableLines": [3], "hitLines": [] }),
+        ]
+    }
+
+    #[test]
+    fn structures_coverage_per_module() {
+        let coverage = structure_coverage(&sample());
+        assert_eq!(coverage["modules"][0]["module"], "ReplicatedStorage.Alpha");
+        assert_eq!(coverage["modules"][0]["percent"], 0.0);
+        assert_eq!(coverage["modules"][1]["coveredLines"], 3);
+        assert_eq!(coverage["modules"][1]["totalLines"], 4);
+        assert_eq!(coverage["modules"][1]["percent"], 75.0);
+        assert_eq!(coverage["summary"]["percent"], 60.0);
+    }
+
+    #[test]
+    fn renders_lcov_records() {
+        let lcov = render_lcov(&sample());
+        assert!(lcov.starts_with("TN:studiolink\nSF:ReplicatedStorage.Zeta\nDA:1,1\n"));
+        assert!(lcov.contains("DA:5,0\nLF:4\nLH:3\nend_of_record\n"));
+        assert_eq!(lcov.matches("end_of_record").count(), 2);
+    }
+
+    #[test]
+    fn create_in_must_be_an_instance_path() {
+        assert!(validate_parent_path("ReplicatedStorage.Tests").is_ok());
+        assert!(validate_parent_path("ServerScriptService").is_ok());
+        for bad in ["", "  ", "Workspace..Tests", "ReplicatedStorage.", ".Tests"] {
+            assert!(
+                matches!(
+                    validate_parent_path(bad),
+                    Err(StudioLinkError::InvalidArguments(_))
+                ),
+                "{:?}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Line-based unified diffs for script sources. Common leading and trailing
+//! lines are trimmed first, so the LCS table only covers the region that
+//! actually changed; inputs whose changed region is still too large return
+//! `None` rather than an expensive or partial diff.
+
+/// Upper bound on LCS table cells (changed lines before × after).
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op<'a> {
+    Keep(&'a str),
+    Remove(&'a str),
+    Add(&'a str),
+}
+
+/// A computed diff: `text` holds the `---`/`+++` header and `@@` hunks, and
+/// is empty when the inputs are equal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnifiedDiff {
+    pub text: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Option<Vec<Op<'a>>> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    if (mid_a.len() + 1).saturating_mul(mid_b.len() + 1) > MAX_LCS_CELLS {
+        return None;
+    }
+
+    // lcs[i][j] = LCS length of mid_a[i..] and mid_b[j..]
+    let width = mid_b.len() + 1;
+    let mut lcs = vec![0u32; (mid_a.len() + 1) * width];
+    for i in (0..mid_a.len()).rev() {
+        for j in (0..mid_b.len()).rev() {
+            lcs[i * width + j] = if mid_a[i] == mid_b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops: Vec<Op> = a[..prefix].iter().map(|l| Op::Keep(l)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < mid_a.len() || j < mid_b.len() {
+        if i < mid_a.len() && j < mid_b.len() && mid_a[i] == mid_b[j] {
+            ops.push(Op::Keep(mid_a[i]));
+            i += 1;
+            j += 1;
+        } else if i < mid_a.len()
+            && (j == mid_b.len() || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            // Removals first, so a replaced line reads "-old" then "+new"
+            ops.push(Op::Remove(mid_a[i]));
+            i += 1;
+        } else {
+            ops.push(Op::Add(mid_b[j]));
+            j += 1;
+        }
+    }
+    ops.extend(a[a.len() - suffix..].iter().map(|l| Op::Keep(l)));
+    Some(ops)
+}
+
+/// `start,len` for a hunk header; an empty range names the line before it.
+fn hunk_range(start: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{},0", start.saturating_sub(1))
+    } else {
+        format!("{},{}", start, len)
+    }
+}
+
+/// Diff `before` against `after` line by line, labelling the sides `from`
+/// and `to`. Returns `None` when the changed region is too large to diff.
+pub fn unified_diff(before: &str, after: &str, from: &str, to: &str) -> Option<UnifiedDiff> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    let ops = diff_lines(&a, &b)?;
+
+    let added = ops.iter().filter(|op| matches!(op, Op::Add(_))).count();
+    let removed = ops.iter().filter(|op| matches!(op, Op::Remove(_))).count();
+    if added == 0 && removed == 0 {
+        return Some(UnifiedDiff {
+            text: String::new(),
+            added,
+            removed,
+        });
+    }
+
+    // Group changes whose context windows touch into one hunk each.
+    let changes: Vec<usize> = (0..ops.len())
+        .filter(|&k| !matches!(ops[k], Op::Keep(_)))
+        .collect();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &k in &changes {
+        let start = k.saturating_sub(CONTEXT_LINES);
+        let end = (k + CONTEXT_LINES + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut text = format!("--- {}\n+++ {}\n", from, to);
+    // 1-based line numbers on each side at ops[k]
+    let (mut line_a, mut line_b, mut k) = (1, 1, 0);
+    for (start, end) in hunks {
+        while k < start {
+            match ops[k] {
+                Op::Keep(_) => {
+                    line_a += 1;
+                    line_b += 1;
+                }
+                Op::Remove(_) => line_a += 1,
+                Op::Add(_) => line_b += 1,
+            }
+            k += 1;
+        }
+        let hunk = &ops[start..end];
+        let len_a = hunk.iter().filter(|op| !matches!(op, Op::Add(_))).count();
+        let len_b = hunk
+            .iter()
+            .filter(|op| !matches!(op, Op::Remove(_)))
+            .count();
+        text.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(line_a, len_a),
+            hunk_range(line_b, len_b)
+        ));
+        for op in hunk {
+            let (marker, line) = match op {
+                Op::Keep(l) => (' ', l),
+                Op::Remove(l) => ('-', l),
+                Op::Add(l) => ('+', l),
+            };
+            text.push(marker);
+            text.push_str(line);
+            text.push('\n');
+        }
+        line_a += len_a;
+        line_b += len_b;
+        k = end;
+    }
+    Some(UnifiedDiff {
+        text,
+        added,
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_hunks_with_context() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl";
+        let after = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm";
+        let diff = unified_diff(before, after, "a/S", "b/S").unwrap();
+        assert_eq!((diff.added, diff.removed), (2, 1));
+        assert_eq!(
+            diff.text,
+            "--- a/S\n+++ b/S\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -10,3 +10,4 @@\n j\n k\n l\n+m\n"
+        );
+
+        let same = unified_diff("x\ny", "x\ny", "a", "b").unwrap();
+        assert!(same.text.is_empty());
+
+        let created = unified_diff("", "one\ntwo", "a", "b").unwrap();
+        assert!(created.text.contains("@@ -0,0 +1,2 @@\n+one\n+two\n"));
+    }
+
+    #[test]
+    fn refuses_oversized_changes() {
+        let before: String = (0..3000).map(|i| format!("old {}\n", i)).collect();
+        let after: String = (0..3000).map(|i| format!("new {}\n", i)).collect();
+        assert!(unified_diff(&before, &after, "a", "b").is_none());
+
+        // Just as large, but only the end differs: trimming keeps it cheap
+        let mut edited = before.clone();
+        edited.push_str("tail\n");
+        let diff = unified_diff(&before, &edited, "a", "b").unwrap();
+        assert_eq!((diff.added, diff.removed), (1, 0));
+    }
+}
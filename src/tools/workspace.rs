@@ -1,3 +1,4 @@
+use serde::Serialize;
 use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -6,18 +7,180 @@ use super::{send_to_plugin, EXTENDED_TIMEOUT};
 use crate::error::Result;
 use crate::state::AppState;
 
+/// Bump when a field is added, removed or changes meaning, so stored
+/// fingerprints from different versions aren't diffed blindly.
+pub const FINGERPRINT_VERSION: u32 = 1;
+
+/// Compact numeric summary of a workspace_analyze report, stable enough to
+/// store per commit and diff over time.
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceFingerprint {
+    pub version: u32,
+    pub path: String,
+    pub script_count: u64,
+    pub server_scripts: u64,
+    pub local_scripts: u64,
+    pub module_scripts: u64,
+    pub total_lines: u64,
+    pub avg_lines_per_script: u64,
+    /// Average function length in lines; the analyzer's complexity proxy.
+    pub avg_function_length: u64,
+    pub strict_mode_percent: u64,
+    pub type_annotation_count: u64,
+    pub total_issues: u64,
+    pub security_findings: u64,
+    pub memory_findings: u64,
+    pub deprecated_findings: u64,
+    pub circular_dep_count: u64,
+    pub dead_module_count: u64,
+    pub empty_script_count: u64,
+}
+
+impl WorkspaceFingerprint {
+    /// Extract the fingerprint from a full report. Missing fields count as 0
+    /// so reports from older plugins still produce a comparable fingerprint.
+    pub fn from_report(report: &serde_json::Value) -> Self {
+        let num = |section: &str, field: &str| report[section][field].as_u64().unwrap_or(0);
+        Self {
+            version: FINGERPRINT_VERSION,
+            path: report["path"].as_str().unwrap_or_default().to_string(),
+            script_count: num("statistics", "totalScripts"),
+            server_scripts: num("statistics", "serverScripts"),
+            local_scripts: num("statistics", "localScripts"),
+            module_scripts: num("statistics", "moduleScripts"),
+            total_lines: num("statistics", "totalLines"),
+            avg_lines_per_script: num("statistics", "avgLinesPerScript"),
+            avg_function_length: num("style", "avgFunctionLength"),
+            strict_mode_percent: num("style", "strictModePercent"),
+            type_annotation_count: num("style", "typeAnnotationCount"),
+            total_issues: num("issues", "totalIssues"),
+            security_findings: report["issues"]["categories"]["security"]
+                .as_u64()
+                .unwrap_or(0),
+            memory_findings: report["issues"]["categories"]["memory"]
+                .as_u64()
+                .unwrap_or(0),
+            deprecated_findings: report["issues"]["categories"]["deprecated"]
+                .as_u64()
+                .unwrap_or(0),
+            circular_dep_count: num("dependencies", "circularCount"),
+            dead_module_count: num("dependencies", "deadModuleCount"),
+            empty_script_count: num("statistics", "emptyScriptCount"),
+        }
+    }
+}
+
 /// Tool 37: workspace_analyze — Comprehensive workspace analysis
 /// Analyzes coding style, architecture, statistics, issues, dependencies, and patterns
+///
+/// With `fingerprint`, returns only a `WorkspaceFingerprint` instead of the
+/// full report.
 pub async fn workspace_analyze(
     state: &Arc<Mutex<AppState>>,
     path: Option<&str>,
+    fingerprint: Option<bool>,
 ) -> Result<serde_json::Value> {
-    send_to_plugin(
+    let report = send_to_plugin(
         state,
         None,
         "workspace_analyze",
         json!({ "path": path.unwrap_or("") }),
         EXTENDED_TIMEOUT,
     )
-    .await
+    .await?;
+    // "No scripts found" comes back as {error}; pass it through unchanged
+    if report.get("error").is_some() {
+        return Ok(report);
+    }
+    if !fingerprint.unwrap_or(false) {
+        let mut report = report;
+        report["patterns"]["frameworkGuidance"] = framework_guidance(&report["patterns"]);
+        return Ok(report);
+    }
+    Ok(serde_json::to_value(WorkspaceFingerprint::from_report(
+        &report,
+    ))?)
+}
+
+/// Detected frameworks with the plugin's idiomatic recommendations, grouped
+/// by category (architecture, ui, ecs, state, data). A project that uses none
+/// of the known frameworks is classified "unknown/custom".
+fn framework_guidance(patterns: &serde_json::Value) -> serde_json::Value {
+    let frameworks: Vec<&serde_json::Value> = patterns["frameworks"]
+        .as_array()
+        .map(|f| f.iter().filter(|f| f["name"].is_string()).collect())
+        .unwrap_or_default();
+    if frameworks.is_empty() {
+        return json!({
+            "classification": "unknown/custom",
+            "frameworks": [],
+            "note": "No known framework detected; follow the structure of the existing scripts (see architecture and style)",
+        });
+    }
+    let mut by_category = serde_json::Map::new();
+    for f in &frameworks {
+        let category = f["category"].as_str().unwrap_or("other");
+        let entry = by_category
+            .entry(category.to_string())
+            .or_insert_with(|| json!([]));
+        if let Some(list) = entry.as_array_mut() {
+            list.push(f["name"].clone());
+        }
+    }
+    json!({
+        "classification": "known",
+        "frameworks": frameworks,
+        "byCategory": by_category,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_extracts_metrics_from_report() {
+        let report = json!({
+            "path": "entire workspace",
+            "statistics": { "totalScripts": 12, "moduleScripts": 7, "totalLines": 900 },
+            "style": { "avgFunctionLength": 14, "strictModePercent": 50 },
+            "issues": { "totalIssues": 9, "categories": { "security": 2, "memory": 1 } },
+            "dependencies": { "circularCount": 3, "deadModuleCount": 1 },
+        });
+        let fp = WorkspaceFingerprint::from_report(&report);
+        assert_eq!(fp.version, FINGERPRINT_VERSION);
+        assert_eq!(fp.script_count, 12);
+        assert_eq!(fp.avg_function_length, 14);
+        assert_eq!(fp.security_findings, 2);
+        assert_eq!(fp.circular_dep_count, 3);
+        assert_eq!(fp.deprecated_findings, 0);
+
+        let out = serde_json::to_value(&fp).unwrap();
+        assert_eq!(out["circularDepCount"], 3);
+    }
+
+    #[test]
+    fn framework_guidance_groups_known_and_flags_custom() {
+        let patterns = json!({
+            "frameworks": [
+                { "name": "Knit", "category": "architecture", "recommendations": ["Use Knit.CreateService"] },
+                { "name": "Fusion", "category": "ui", "recommendations": ["Use Value"] },
+            ],
+        });
+        let guidance = framework_guidance(&patterns);
+        assert_eq!(guidance["classification"], "known");
+        assert_eq!(guidance["byCategory"]["ui"], json!(["Fusion"]));
+        assert_eq!(
+            guidance["frameworks"][0]["recommendations"][0],
+            "Use Knit.CreateService"
+        );
+
+        let custom = framework_guidance(&json!({ "libraries": ["Maid"], "frameworks": [] }));
+        assert_eq!(custom["classification"], "unknown/custom");
+        assert_eq!(
+            framework_guidance(&json!({}))["classification"],
+            "unknown/custom"
+        );
+    }
 }